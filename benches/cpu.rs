@@ -0,0 +1,40 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use gboxide::cartridge::Cartridge;
+use gboxide::gameboy::GameBoy;
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+use common::RomBuilder;
+
+// a minimal rom that loops over a mix of loads, alu ops, CB ops and branches
+fn build_rom() -> Vec<u8> {
+    let program = [
+        0x21, 0x00, 0xC0, // LD HL,0xC000
+        0x3E, 0x12,       // LD A,0x12
+        0x80,             // ADD A,B
+        0x04,             // INC B
+        0x22,             // LD (HL+),A
+        0xCB, 0x37,       // SWAP A
+        0xCB, 0x40,       // BIT 0,B
+        0x7C,             // LD A,H
+        0xFE, 0xD0,       // CP 0xD0
+        0x20, 0xF2,       // JR NZ,-14
+        0xC3, 0x50, 0x01, // JP 0x0150
+    ];
+
+    RomBuilder::new(b"CPUBENCH").program(&program).build()
+}
+
+fn run_frames(c: &mut Criterion) {
+    let cartridge = Cartridge::from_rom(build_rom(), &[]).unwrap();
+    let mut gameboy = GameBoy::new(cartridge);
+
+    let mut group = c.benchmark_group("cpu");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("run_to_vblank", |b| b.iter(|| gameboy.run_to_vblank().unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, run_frames);
+criterion_main!(benches);
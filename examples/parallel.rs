@@ -0,0 +1,96 @@
+// runs many independent GameBoys at once, headless, one per thread, the way a reinforcement
+// learning setup steps a batch of environments. each one gets its own copy of the rom and
+// presses random buttons, and reports a byte of memory as its "score" alongside the frame.
+//
+//     cargo run --release --no-default-features --features std --example parallel -- <ROM> [INSTANCES] [FRAMES]
+//
+// a GameBoy shares nothing with any other (no globals, no files, no stdout unless asked
+// with set_serial_sink), so the only thing to arrange is giving each thread its own
+
+use std::env;
+use std::fs;
+use std::process;
+use std::thread;
+use std::time::Instant;
+
+use gboxide::cartridge::Cartridge;
+use gboxide::gameboy::GameBoy;
+use gboxide::gameboy::joypad::Controls;
+
+// where a real training setup would read the score or position from, e.g. from a RAM map
+const SCORE_ADDRESS: u16 = 0xC000;
+// frames each random button press is held for
+const ACTION_FRAMES: u64 = 4;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("usage: {} <ROM> [INSTANCES] [FRAMES]", args[0]);
+        process::exit(1);
+    }
+    let rom = fs::read(&args[1]).unwrap_or_else(|err| {
+        eprintln!("Problem reading rom \"{}\": {}", args[1], err);
+        process::exit(1);
+    });
+    let instances: u64 = args.get(2).map_or(Ok(16), |n| n.parse()).expect("bad instance count");
+    let frames: u64 = args.get(3).map_or(Ok(600), |n| n.parse()).expect("bad frame count");
+
+    let start = Instant::now();
+    let workers: Vec<_> = (0..instances).map(|seed| {
+        let rom = rom.clone();
+        thread::spawn(move || run_episode(rom, seed + 1, frames))
+    }).collect();
+
+    for (instance, worker) in workers.into_iter().enumerate() {
+        match worker.join().unwrap() {
+            Ok((score, frame)) => println!("instance {}: score {}, final frame hash {:016x}",
+                                           instance, score, fnv1a(&frame)),
+            Err(err) => println!("instance {}: {}", instance, err),
+        }
+    }
+
+    let elapsed = start.elapsed().as_secs_f64();
+    println!("{} frames in {:.3}s, {:.0} frames per second across all instances",
+             instances * frames, elapsed, (instances * frames) as f64 / elapsed);
+}
+
+// one environment: random inputs for the given number of frames, then the score and final frame
+fn run_episode(rom: Vec<u8>, seed: u64, frames: u64) -> Result<(u8, Vec<u8>), String> {
+    let cartridge = Cartridge::from_rom(rom, &[]).map_err(|err| err.to_string())?;
+    let mut gameboy = GameBoy::new(cartridge);
+    let mut rng = seed;
+
+    for frame in 0..frames {
+        if frame % ACTION_FRAMES == 0 {
+            gameboy.set_controls(random_controls(&mut rng));
+        }
+        gameboy.run_to_vblank().map_err(|err| err.to_string())?;
+    }
+
+    Ok((gameboy.peek(SCORE_ADDRESS), gameboy.screenshot_rgba()))
+}
+
+// one button (or none) at a time, from a xorshift generator so runs are repeatable per seed
+fn random_controls(rng: &mut u64) -> Controls {
+    *rng ^= *rng << 13;
+    *rng ^= *rng >> 7;
+    *rng ^= *rng << 17;
+
+    let mut controls = Controls::default();
+    match *rng % 9 {
+        0 => controls.left = true,
+        1 => controls.right = true,
+        2 => controls.up = true,
+        3 => controls.down = true,
+        4 => controls.a = true,
+        5 => controls.b = true,
+        6 => controls.start = true,
+        7 => controls.select = true,
+        _ => (),
+    }
+    controls
+}
+
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
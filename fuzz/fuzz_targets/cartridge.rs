@@ -0,0 +1,22 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use gboxide::cartridge::Cartridge;
+
+// loads an arbitrary rom, then pokes every mapper register and
+// reads back the whole cartridge address space
+fuzz_target!(|data: &[u8]| {
+    let mut cartridge = match Cartridge::from_rom(data.to_vec(), &[]) {
+        Ok(cartridge) => cartridge,
+        Err(_) => return,
+    };
+
+    // use the rom's own bytes as the values written, so the fuzzer can steer bank selection
+    let writes = (0x0000..0x8000u16).step_by(0x1000).chain((0xA000..0xC000u16).step_by(0x400));
+    for (addr, &value) in writes.zip(data.iter().cycle()) {
+        cartridge.write(addr, value);
+        for read_addr in (0x0000..0x8000u16).chain(0xA000..0xC000u16).step_by(0x80) {
+            cartridge.read(read_addr);
+        }
+    }
+});
@@ -0,0 +1,19 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use gboxide::gameboy::cpu::CPU;
+use gboxide::gameboy::mmu::MMU;
+
+// runs an arbitrary instruction stream over flat RAM,
+// unrecognized opcodes are fine as long as they come back as errors
+fuzz_target!(|data: &[u8]| {
+    let mut mmu = MMU::with_flat_ram(data);
+    let mut cpu = CPU::new();
+    cpu.registers_mut().pc = 0x0000;
+
+    for _ in 0..1000 {
+        if cpu.step(&mut mmu).is_err() {
+            break;
+        }
+    }
+});
@@ -0,0 +1,8 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use gboxide::cartridge::Header;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Header::from_slice(data);
+});
@@ -0,0 +1,160 @@
+// a local achievements system in the style of RetroAchievements: each achievement is a set of
+// conditions on memory, checked once a frame, and unlocks the first frame they all hold.
+// the rules come from a JSON file like
+//
+// { "achievements": [
+//     { "title": "First blood", "description": "Defeat an enemy",
+//       "conditions": [
+//         { "address": 49664, "compare": "increased" },
+//         { "address": 49666, "size": "word", "compare": "greater", "value": 100 } ] } ] }
+//
+// size is byte (the default) or word (little endian), and compare is one of equal, notequal,
+// less, lessequal, greater, greaterequal, or changed, increased, decreased, which compare
+// against the value on the previous frame and ignore value
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::gameboy::GameBoy;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Size {
+    #[default]
+    Byte,
+    Word,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compare {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Changed,
+    Increased,
+    Decreased,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Condition {
+    pub address: u16,
+    #[serde(default)]
+    pub size: Size,
+    pub compare: Compare,
+    #[serde(default)]
+    pub value: u16,
+}
+
+impl Condition {
+    fn read(&self, gameboy: &mut GameBoy) -> u16 {
+        let low = gameboy.peek(self.address) as u16;
+        match self.size {
+            Size::Byte => low,
+            Size::Word => low | (gameboy.peek(self.address.wrapping_add(1)) as u16) << 8,
+        }
+    }
+
+    // whether the condition holds for the current value, given the last frame's
+    fn holds(&self, current: u16, previous: Option<u16>) -> bool {
+        match self.compare {
+            Compare::Equal => current == self.value,
+            Compare::NotEqual => current != self.value,
+            Compare::Less => current < self.value,
+            Compare::LessEqual => current <= self.value,
+            Compare::Greater => current > self.value,
+            Compare::GreaterEqual => current >= self.value,
+            Compare::Changed | Compare::Increased | Compare::Decreased => match previous {
+                Some(previous) if self.compare == Compare::Changed => current != previous,
+                Some(previous) if self.compare == Compare::Increased => current > previous,
+                Some(previous) => current < previous,
+                // nothing's changed on the first frame we look
+                None => false,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Achievement {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    pub conditions: Vec<Condition>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    achievements: Vec<Achievement>,
+}
+
+pub struct Achievements {
+    achievements: Vec<Achievement>,
+    unlocked: Vec<bool>,
+    // each condition's value on the last frame, for the delta comparisons
+    previous: Vec<Vec<Option<u16>>>,
+}
+
+impl Achievements {
+    pub fn load(path: &Path) -> Result<Achievements, Box<dyn Error>> {
+        Achievements::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(json: &str) -> Result<Achievements, Box<dyn Error>> {
+        let rules: RuleFile = serde_json::from_str(json)?;
+        for achievement in &rules.achievements {
+            if achievement.conditions.is_empty() {
+                return Err(format!("achievement \"{}\" has no conditions", achievement.title).into());
+            }
+        }
+        Ok(Achievements::new(rules.achievements))
+    }
+
+    pub fn new(achievements: Vec<Achievement>) -> Achievements {
+        let unlocked = vec![false; achievements.len()];
+        let previous = achievements.iter().map(|a| vec![None; a.conditions.len()]).collect();
+        Achievements { achievements, unlocked, previous }
+    }
+
+    // call once a frame. on_unlock is called for each achievement whose conditions all hold
+    // for the first time
+    pub fn check<F: FnMut(&Achievement)>(&mut self, gameboy: &mut GameBoy, mut on_unlock: F) {
+        for (i, achievement) in self.achievements.iter().enumerate() {
+            if self.unlocked[i] {
+                continue;
+            }
+
+            // every value is read, even after a condition fails, so the deltas stay current
+            let mut met = true;
+            for (condition, previous) in achievement.conditions.iter().zip(&mut self.previous[i]) {
+                let current = condition.read(gameboy);
+                met &= condition.holds(current, *previous);
+                *previous = Some(current);
+            }
+
+            if met {
+                self.unlocked[i] = true;
+                on_unlock(achievement);
+            }
+        }
+    }
+
+    pub fn achievements(&self) -> &[Achievement] {
+        &self.achievements
+    }
+
+    pub fn unlocked(&self) -> impl Iterator<Item = &Achievement> {
+        self.achievements.iter().zip(&self.unlocked)
+            .filter(|(_, &unlocked)| unlocked)
+            .map(|(achievement, _)| achievement)
+    }
+}
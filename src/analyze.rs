@@ -0,0 +1,147 @@
+// a rough map of what's where in a ROM, for hacking and translating: how full each bank is,
+// where the free space is, what the RST and interrupt vectors do, and any text
+
+use std::fmt;
+
+use crate::gameboy::debugger::BankedAddress;
+use crate::gameboy::disasm::{self, Symbols};
+
+const BANK_SIZE: usize = 0x4000;
+
+// runs of 0xFF shorter than this are more likely data than space left over
+const MIN_FREE_RUN: usize = 16;
+
+const VECTORS: [(u16, &str); 13] = [
+    (0x00, "rst $00"),
+    (0x08, "rst $08"),
+    (0x10, "rst $10"),
+    (0x18, "rst $18"),
+    (0x20, "rst $20"),
+    (0x28, "rst $28"),
+    (0x30, "rst $30"),
+    (0x38, "rst $38"),
+    (0x40, "vblank"),
+    (0x48, "stat"),
+    (0x50, "timer"),
+    (0x58, "serial"),
+    (0x60, "joypad"),
+];
+
+pub struct BankUsage {
+    pub bank: u16,
+    // bytes in runs of at least MIN_FREE_RUN 0xFFs
+    pub free: usize,
+    // where the longest of those runs starts, and how long it is
+    pub largest_free: Option<(BankedAddress, usize)>,
+    pub size: usize,
+}
+
+pub struct Vector {
+    pub addr: u16,
+    pub name: &'static str,
+    // the first instruction there, None if it's unused (0xFF, which would rst $38 forever)
+    pub instruction: Option<String>,
+}
+
+pub struct RomMap {
+    pub size: usize,
+    pub banks: Vec<BankUsage>,
+    pub vectors: Vec<Vector>,
+    // runs of printable ASCII, and where they start
+    pub strings: Vec<(BankedAddress, String)>,
+}
+
+// strings shorter than min_string_length are left out, there's plenty of short runs of
+// printable bytes in code and graphics
+pub fn analyze(rom: &[u8], min_string_length: usize) -> RomMap {
+    let banks = rom.chunks(BANK_SIZE).enumerate()
+        .map(|(bank, bytes)| bank_usage(bank as u16, bytes))
+        .collect();
+
+    let symbols = Symbols::new();
+    let vectors = VECTORS.iter().map(|&(addr, name)| {
+        let bytes = rom.get(addr as usize..).unwrap_or(&[]);
+        let instruction = match bytes.first() {
+            None | Some(0xFF) => None,
+            Some(_) => Some(disasm::decode(bytes, 0, addr, &symbols)
+                .map(|instruction| instruction.text)
+                .unwrap_or_else(|| format!("db ${:02x}", bytes[0]))),
+        };
+        Vector { addr, name, instruction }
+    }).collect();
+
+    let mut strings = Vec::new();
+    let mut start = 0;
+    for (offset, &byte) in rom.iter().enumerate() {
+        let printable = (0x20..=0x7E).contains(&byte);
+        // strings don't carry on across banks, the next bank's mapped somewhere else
+        let bank_start = offset % BANK_SIZE == 0;
+        if (!printable || bank_start) && offset - start >= min_string_length {
+            strings.push((banked_address(start), String::from_utf8_lossy(&rom[start..offset]).into_owned()));
+        }
+        if !printable {
+            start = offset + 1;
+        } else if bank_start {
+            start = offset;
+        }
+    }
+    if rom.len() - start >= min_string_length {
+        strings.push((banked_address(start), String::from_utf8_lossy(&rom[start..]).into_owned()));
+    }
+
+    RomMap { size: rom.len(), banks, vectors, strings }
+}
+
+fn bank_usage(bank: u16, bytes: &[u8]) -> BankUsage {
+    let mut free = 0;
+    let mut largest_free = None;
+    let mut run_start = 0;
+    for offset in 0..=bytes.len() {
+        if bytes.get(offset) == Some(&0xFF) {
+            continue;
+        }
+        let run = offset - run_start;
+        if run >= MIN_FREE_RUN {
+            free += run;
+            if largest_free.is_none_or(|(_, largest)| run > largest) {
+                largest_free = Some((banked_address(bank as usize * BANK_SIZE + run_start), run));
+            }
+        }
+        run_start = offset + 1;
+    }
+    BankUsage { bank, free, largest_free, size: bytes.len() }
+}
+
+// where a byte of the ROM file shows up, bank 0 at 0000-3fff and the rest at 4000-7fff
+fn banked_address(offset: usize) -> BankedAddress {
+    let bank = offset / BANK_SIZE;
+    let addr = offset % BANK_SIZE + if bank == 0 { 0 } else { BANK_SIZE };
+    BankedAddress { bank: bank as u16, addr: addr as u16 }
+}
+
+impl fmt::Display for RomMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} bytes, {} banks", self.size, self.banks.len())?;
+
+        writeln!(f, "\nbanks:")?;
+        for bank in &self.banks {
+            write!(f, "  {:02x}: {:5} bytes used, {:5} free", bank.bank, bank.size - bank.free, bank.free)?;
+            match bank.largest_free {
+                Some((addr, len)) => writeln!(f, " (largest {} bytes at {})", len, addr)?,
+                None => writeln!(f)?,
+            }
+        }
+
+        writeln!(f, "\nvectors:")?;
+        for vector in &self.vectors {
+            let name = format!("{} ({:04x})", vector.name, vector.addr);
+            writeln!(f, "  {:<16} {}", name, vector.instruction.as_deref().unwrap_or("unused"))?;
+        }
+
+        writeln!(f, "\nstrings:")?;
+        for (addr, string) in &self.strings {
+            writeln!(f, "  {} {:?}", addr, string)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,321 @@
+// runs a whole directory of ROMs headless, spread over a few threads, to see which ones get
+// anywhere. each gets a fresh GameBoy that only presses start and a now and then, so this finds
+// the games that crash, hit opcodes that don't exist, use mappers we don't have, or lock up early,
+// not the ones that go wrong later on. how much the screen changes, and when, gives a rough
+// rating for a compatibility report that can be compared between releases
+
+use std::fmt;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::Error;
+use crate::bench::escape_json;
+use crate::cartridge::Cartridge;
+use crate::gameboy::GameBoy;
+use crate::gameboy::fault::{CoreFault, FaultKind};
+use crate::gameboy::joypad::Button;
+use crate::gameboy::serial::SerialDirection;
+
+// what's pressed, spread evenly over the run: start to get past the title screen,
+// a to get through whatever it leads to, then start again in case that was a menu
+const PRESSES: [Button; 3] = [Button::Start, Button::A, Button::Start];
+const PRESS_FRAMES: u32 = 5;
+
+// serial output kept for the report, test ROMs print their results there
+const SERIAL_LENGTH: usize = 256;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    // ran every frame it was asked to
+    Ran,
+    // halted with every interrupt disabled, so it'll never do anything again
+    LockedUp,
+    UnknownOpcode(String),
+    UnsupportedMapper(String),
+    // anything else the core gave up on, the rom's header included
+    Crashed(String),
+    // the core panicked, which is a bug here rather than in the game
+    Panicked(String),
+}
+
+impl Outcome {
+    pub fn is_ok(&self) -> bool {
+        *self == Outcome::Ran
+    }
+
+    // a word to sort and count outcomes by
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Outcome::Ran => "ok",
+            Outcome::LockedUp => "locked up",
+            Outcome::UnknownOpcode(_) => "unknown opcode",
+            Outcome::UnsupportedMapper(_) => "unsupported mapper",
+            Outcome::Crashed(_) => "crashed",
+            Outcome::Panicked(_) => "panicked",
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Ran | Outcome::LockedUp => write!(f, "{}", self.kind()),
+            Outcome::UnknownOpcode(why) | Outcome::UnsupportedMapper(why)
+                | Outcome::Crashed(why) | Outcome::Panicked(why) => write!(f, "{}: {}", self.kind(), why),
+        }
+    }
+}
+
+// a guess at how far a game gets, from how it drew rather than from anyone playing it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rating {
+    // never drew anything
+    Broken,
+    // drew something, but stopped there or crashed
+    Boots,
+    // the screen changed after the first press, so it's taken some input
+    InGame,
+    // and it was still changing at the end of the run without anything going wrong
+    Playable,
+}
+
+impl Rating {
+    pub fn name(self) -> &'static str {
+        match self {
+            Rating::Broken => "broken",
+            Rating::Boots => "boots",
+            Rating::InGame => "in-game",
+            Rating::Playable => "playable",
+        }
+    }
+}
+
+pub struct RomResult {
+    pub rom: PathBuf,
+    pub title: Option<String>,
+    // how far it got
+    pub frames: u64,
+    pub outcome: Outcome,
+    // the first frame with more than one colour on it
+    pub first_drawn: Option<u64>,
+    // frames that differed from the one before, all of them and those after the first press
+    pub changed_frames: u64,
+    pub changed_after_input: u64,
+    pub last_changed: Option<u64>,
+    // the start of what it sent over the serial port
+    pub serial: Vec<u8>,
+}
+
+impl RomResult {
+    // out of frames, how many the run was meant to last
+    pub fn rating(&self, frames: u64) -> Rating {
+        if self.first_drawn.is_none() {
+            Rating::Broken
+        } else if !self.outcome.is_ok() || self.changed_after_input == 0 {
+            Rating::Boots
+        } else if self.last_changed.is_some_and(|frame| frame >= frames - frames / 4) {
+            Rating::Playable
+        } else {
+            Rating::InGame
+        }
+    }
+
+    // the serial output as text, for test ROMs that print "Passed" or "Failed" there
+    pub fn serial_text(&self) -> String {
+        String::from_utf8_lossy(&self.serial).chars()
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .collect::<String>()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+// every .gb and .gbc in directory, not looking in subdirectories, by name
+pub fn roms_in(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut roms: Vec<PathBuf> = directory.read_dir()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let extension = path.extension().and_then(|e| e.to_str()).map(str::to_lowercase);
+            matches!(extension.as_deref(), Some("gb") | Some("gbc"))
+        })
+        .collect();
+    roms.sort();
+    Ok(roms)
+}
+
+// runs each rom for frames frames on threads threads, and gives back how each did in the same order
+pub fn run(roms: &[PathBuf], frames: u64, threads: usize) -> Vec<RomResult> {
+    let next = Mutex::new(roms.iter());
+    let results = Mutex::new(Vec::with_capacity(roms.len()));
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| loop {
+                let rom = match next.lock().unwrap().next() {
+                    Some(rom) => rom,
+                    None => break,
+                };
+                let result = run_rom(rom, frames);
+                results.lock().unwrap().push(result);
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by(|a, b| a.rom.cmp(&b.rom));
+    results
+}
+
+pub fn run_rom(rom: &Path, frames: u64) -> RomResult {
+    let mut result = RomResult {
+        rom: rom.to_path_buf(),
+        title: None,
+        frames: 0,
+        outcome: Outcome::Ran,
+        first_drawn: None,
+        changed_frames: 0,
+        changed_after_input: 0,
+        last_changed: None,
+        serial: Vec::new(),
+    };
+    let cartridge = match Cartridge::new(&rom.to_string_lossy()) {
+        Ok(cartridge) => cartridge,
+        Err(err) => {
+            result.outcome = match err {
+                Error::UnsupportedMapper(_) => Outcome::UnsupportedMapper(err.to_string()),
+                _ => Outcome::Crashed(err.to_string()),
+            };
+            return result;
+        },
+    };
+    result.title = Some(cartridge.header.title.trim().to_string());
+
+    let mut gameboy = GameBoy::new(cartridge);
+    let first_press = frames / (PRESSES.len() as u64 + 1);
+    for (index, &button) in PRESSES.iter().enumerate() {
+        let at = frames * (index as u64 + 1) / (PRESSES.len() as u64 + 1);
+        gameboy.press_after(at as u32, button, PRESS_FRAMES);
+    }
+    let serial = Arc::new(Mutex::new(Vec::new()));
+    let sent = Arc::clone(&serial);
+    gameboy.set_serial_logger(move |exchange| {
+        let mut sent = sent.lock().unwrap();
+        if exchange.direction == SerialDirection::Sent && sent.len() < SERIAL_LENGTH {
+            sent.push(exchange.byte);
+        }
+    });
+
+    let mut last_screen = Vec::new();
+    let ran = panic::catch_unwind(AssertUnwindSafe(|| {
+        for frame in 0..frames {
+            if gameboy.locked_up() {
+                return (frame, Outcome::LockedUp);
+            }
+            if let Err(err) = gameboy.run_to_vblank() {
+                return (frame, fault_outcome(err));
+            }
+
+            let screen = gameboy.screenshot_rgba();
+            if result.first_drawn.is_none() && screen.chunks(4).any(|pixel| pixel != &screen[..4]) {
+                result.first_drawn = Some(frame);
+            }
+            if screen != last_screen {
+                result.changed_frames += 1;
+                if frame > first_press {
+                    result.changed_after_input += 1;
+                }
+                result.last_changed = Some(frame);
+                last_screen = screen;
+            }
+        }
+        (frames, Outcome::Ran)
+    }));
+    let (frames, outcome) = ran.unwrap_or_else(|panic| {
+        let message = panic.downcast_ref::<&str>().map(|message| message.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        (gameboy.frame_count(), Outcome::Panicked(message))
+    });
+    result.frames = frames;
+    result.outcome = outcome;
+    result.serial = serial.lock().map(|serial| serial.clone()).unwrap_or_default();
+    result
+}
+
+// a table of how each rom did, to read or to diff against the last release's.
+// frames is how many each was run for
+pub fn markdown_report(results: &[RomResult], frames: u64) -> String {
+    let mut report = format!("# GBOxide {} compatibility\n\n{}\n\n", env!("CARGO_PKG_VERSION"), summary(results, frames));
+    report += "| ROM | Title | Rating | Frames | Notes |\n|---|---|---|---|---|\n";
+    for result in results {
+        let mut notes = Vec::new();
+        if !result.outcome.is_ok() {
+            notes.push(result.outcome.to_string());
+        }
+        let serial = result.serial_text();
+        if !serial.is_empty() {
+            notes.push(format!("serial: {}", serial));
+        }
+        let cell = |text: &str| text.replace('|', "\\|");
+        report += &format!("| {} | {} | {} | {} | {} |\n",
+                           cell(&result.rom.file_name().unwrap_or_default().to_string_lossy()),
+                           cell(result.title.as_deref().unwrap_or("")),
+                           result.rating(frames).name(),
+                           result.frames,
+                           cell(&notes.join(", ")));
+    }
+    report
+}
+
+// the same as markdown_report, for tools
+pub fn json_report(results: &[RomResult], frames: u64) -> String {
+    let roms: Vec<String> = results.iter().map(|result| {
+        let detail = match &result.outcome {
+            Outcome::Ran | Outcome::LockedUp => String::new(),
+            Outcome::UnknownOpcode(why) | Outcome::UnsupportedMapper(why)
+                | Outcome::Crashed(why) | Outcome::Panicked(why) => why.clone(),
+        };
+        let optional = |frame: Option<u64>| frame.map_or("null".to_string(), |frame| frame.to_string());
+        format!(concat!(
+                    "    {{\"rom\": \"{}\", \"title\": \"{}\", \"rating\": \"{}\", \"outcome\": \"{}\", \"detail\": \"{}\", ",
+                    "\"frames\": {}, \"first_drawn\": {}, \"changed_frames\": {}, \"changed_after_input\": {}, ",
+                    "\"last_changed\": {}, \"serial\": \"{}\"}}"),
+                escape_json(&result.rom.file_name().unwrap_or_default().to_string_lossy()),
+                escape_json(result.title.as_deref().unwrap_or("")),
+                result.rating(frames).name(),
+                result.outcome.kind(),
+                escape_json(&detail),
+                result.frames,
+                optional(result.first_drawn),
+                result.changed_frames,
+                result.changed_after_input,
+                optional(result.last_changed),
+                escape_json(&result.serial_text()))
+    }).collect();
+
+    format!("{{\n  \"version\": \"{}\",\n  \"frames\": {},\n  \"roms\": [\n{}\n  ]\n}}\n",
+            env!("CARGO_PKG_VERSION"), frames, roms.join(",\n"))
+}
+
+// e.g. "12 ROMs, 1800 frames each: 5 playable, 3 in-game, 2 boots, 2 broken"
+pub fn summary(results: &[RomResult], frames: u64) -> String {
+    let counts: Vec<String> = [Rating::Playable, Rating::InGame, Rating::Boots, Rating::Broken].iter()
+        .map(|&rating| (rating, results.iter().filter(|result| result.rating(frames) == rating).count()))
+        .filter(|&(_, count)| count > 0)
+        .map(|(rating, count)| format!("{} {}", count, rating.name()))
+        .collect();
+    format!("{} ROMs, {} frames each: {}", results.len(), frames, counts.join(", "))
+}
+
+fn fault_outcome(err: Error) -> Outcome {
+    match err.fault() {
+        Some(CoreFault { kind: FaultKind::UnknownOpcode(opcode), pc, .. }) => {
+            Outcome::UnknownOpcode(format!("{:#04x} at pc {}", opcode, pc))
+        },
+        _ => Outcome::Crashed(err.to_string()),
+    }
+}
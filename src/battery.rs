@@ -0,0 +1,83 @@
+// battery backed cartridge RAM in the .sav layout other emulators use: the RAM as is,
+// followed by an RTC footer for cartridges with a clock. BGB and newer VBA-M write a 48 byte
+// footer with a 64-bit timestamp, older VBA a 44 byte one with a 32-bit timestamp
+
+use alloc::vec::Vec;
+
+use crate::Error;
+
+const REGISTERS_LEN: usize = 5 * 4;
+const FOOTER_32_LEN: usize = REGISTERS_LEN * 2 + 4;
+const FOOTER_64_LEN: usize = REGISTERS_LEN * 2 + 8;
+
+// the clock's state when the save was written, picked up again by the MBC3's clock.
+// for any other mapper it's only carried through to the next save
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RtcFooter {
+    // seconds, minutes, hours, day counter low, day counter high
+    pub registers: [u32; 5],
+    pub latched: [u32; 5],
+    // unix time the save was written at
+    pub timestamp: u64,
+}
+
+impl RtcFooter {
+    fn parse(bytes: &[u8]) -> RtcFooter {
+        let word = |index: usize| {
+            let mut word = [0; 4];
+            word.copy_from_slice(&bytes[index * 4..index * 4 + 4]);
+            u32::from_le_bytes(word)
+        };
+
+        let mut registers = [0; 5];
+        let mut latched = [0; 5];
+        for i in 0..5 {
+            registers[i] = word(i);
+            latched[i] = word(i + 5);
+        }
+
+        let timestamp = match bytes.len() {
+            FOOTER_64_LEN => word(10) as u64 | (word(11) as u64) << 32,
+            _ => word(10) as u64,
+        };
+
+        RtcFooter { registers, latched, timestamp }
+    }
+
+    // always the 48 byte form, which everything that reads the 44 byte one also accepts
+    fn write(&self, bytes: &mut Vec<u8>) {
+        for word in self.registers.iter().chain(self.latched.iter()) {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.timestamp.to_le_bytes());
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatterySave {
+    pub ram: Vec<u8>,
+    pub rtc: Option<RtcFooter>,
+}
+
+impl BatterySave {
+    // ram_len is the size of the cartridge's RAM, anything after that is taken as an RTC footer
+    pub fn parse(bytes: &[u8], ram_len: usize) -> Result<BatterySave, Error> {
+        let footer_len = bytes.len().checked_sub(ram_len);
+        let rtc = match footer_len {
+            Some(0) => None,
+            Some(FOOTER_32_LEN) | Some(FOOTER_64_LEN) => Some(RtcFooter::parse(&bytes[ram_len..])),
+            _ => return Err(format!("save is {} bytes, expected {} bytes of RAM with an optional {} or {} byte RTC footer",
+                                    bytes.len(), ram_len, FOOTER_32_LEN, FOOTER_64_LEN).into()),
+        };
+
+        Ok(BatterySave { ram: bytes[..ram_len].to_vec(), rtc })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.ram.clone();
+        if let Some(rtc) = &self.rtc {
+            rtc.write(&mut bytes);
+        }
+        bytes
+    }
+}
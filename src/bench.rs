@@ -0,0 +1,93 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::cartridge::Cartridge;
+use crate::gameboy::{self, GameBoy};
+
+pub struct BenchReport {
+    pub rom_title: String,
+    pub frames: u64,
+    pub cycles: u128,
+    pub instructions: u64,
+    pub wall_time: Duration,
+}
+
+impl BenchReport {
+    pub fn emulated_seconds(&self) -> f64 {
+        self.cycles as f64 / gameboy::CYCLES_PER_SECOND as f64
+    }
+
+    // emulated seconds per wallclock second, 1.0 is full speed
+    pub fn speed(&self) -> f64 {
+        self.emulated_seconds() / self.wall_time.as_secs_f64()
+    }
+
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.wall_time.as_secs_f64()
+    }
+
+    pub fn to_json(&self) -> String {
+        format!(
+            concat!(
+                "{{\n",
+                "  \"rom\": \"{}\",\n",
+                "  \"frames\": {},\n",
+                "  \"cycles\": {},\n",
+                "  \"instructions\": {},\n",
+                "  \"wall_seconds\": {:.6},\n",
+                "  \"emulated_seconds\": {:.6},\n",
+                "  \"speed\": {:.3},\n",
+                "  \"instructions_per_second\": {:.0}\n",
+                "}}\n"),
+            escape_json(&self.rom_title),
+            self.frames,
+            self.cycles,
+            self.instructions,
+            self.wall_time.as_secs_f64(),
+            self.emulated_seconds(),
+            self.speed(),
+            self.instructions_per_second())
+    }
+
+    pub fn write_json(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_json())
+            .map_err(|e| format!("couldn't write report {}: {}", path.display(), e))?;
+
+        Ok(())
+    }
+}
+
+// runs the cartridge headless for the given number of frames as fast as possible
+pub fn run(cartridge: Cartridge, frames: u64) -> Result<BenchReport, Box<dyn Error>> {
+    let rom_title = cartridge.header.title.clone();
+    let mut gameboy = GameBoy::new(cartridge);
+
+    let start = Instant::now();
+    for _ in 0..frames {
+        gameboy.run_to_vblank()?;
+    }
+    let wall_time = start.elapsed();
+
+    Ok(BenchReport {
+        rom_title,
+        frames,
+        cycles: gameboy.elapsed_cycles(),
+        instructions: gameboy.elapsed_instructions(),
+        wall_time,
+    })
+}
+
+pub(crate) fn escape_json(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,53 @@
+// a Super GameBoy style border drawn around the screen. there's no SGB support to get one from
+// the game yet, so for now they're cosmetic, loaded from (and saved to) 256x224 PNGs
+
+use std::error::Error;
+use std::path::Path;
+
+use crate::gameboy::dump;
+use crate::gameboy::lcd::{SCREEN_WIDTH, SCREEN_HEIGHT};
+
+pub const BORDER_WIDTH: usize = 256;
+pub const BORDER_HEIGHT: usize = 224;
+
+// where the top left of the game's screen sits within the border
+pub const SCREEN_X: usize = 48;
+pub const SCREEN_Y: usize = 40;
+
+pub struct Border {
+    // RGBA, BORDER_WIDTH x BORDER_HEIGHT
+    pixels: Vec<u8>,
+}
+
+impl Border {
+    pub fn load(path: &Path) -> Result<Border, Box<dyn Error>> {
+        let (width, height, pixels) = dump::load_png(path)?;
+        if width as usize != BORDER_WIDTH || height as usize != BORDER_HEIGHT {
+            return Err(format!("borders are {}x{}, not {}x{}",
+                               BORDER_WIDTH, BORDER_HEIGHT, width, height).into());
+        }
+        if pixels.len() != BORDER_WIDTH * BORDER_HEIGHT * 4 {
+            return Err(format!("{} decoded to {} bytes, not the {} a {}x{} border needs",
+                               path.display(), pixels.len(), BORDER_WIDTH * BORDER_HEIGHT * 4,
+                               BORDER_WIDTH, BORDER_HEIGHT).into());
+        }
+
+        Ok(Border { pixels })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        dump::save_png(path, BORDER_WIDTH as u32, BORDER_HEIGHT as u32, &self.pixels)?;
+        Ok(())
+    }
+
+    // draws the border with an RGBA frame in the middle into out, which is BORDER_WIDTH x BORDER_HEIGHT RGBA
+    pub fn compose(&self, frame: &[u8], out: &mut [u8]) {
+        out.copy_from_slice(&self.pixels);
+
+        let row_length = SCREEN_WIDTH as usize * 4;
+        for (y, row) in frame.chunks(row_length).take(SCREEN_HEIGHT as usize).enumerate() {
+            let start = ((SCREEN_Y + y) * BORDER_WIDTH + SCREEN_X) * 4;
+            out[start..start + row_length].copy_from_slice(row);
+        }
+    }
+}
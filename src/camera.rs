@@ -0,0 +1,117 @@
+#[cfg(feature = "std")]
+use std::error::Error;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+use crate::gameboy::dump;
+
+pub const SENSOR_WIDTH: usize = 128;
+pub const SENSOR_HEIGHT: usize = 112;
+
+// something the Pocket Camera can take pictures with
+pub trait CameraSensor: Send {
+    // a SENSOR_WIDTH x SENSOR_HEIGHT greyscale image, row-major, 0x00 is black and 0xFF is white
+    fn capture(&mut self) -> Vec<u8>;
+}
+
+// no sensor plugged in, the camera just sees a flat grey
+pub struct BlankSensor;
+impl CameraSensor for BlankSensor {
+    fn capture(&mut self) -> Vec<u8> {
+        vec![0x80; SENSOR_WIDTH * SENSOR_HEIGHT]
+    }
+}
+
+// a fixed image loaded from a png file, scaled to the sensor size
+#[cfg(feature = "std")]
+pub struct StaticImage {
+    pixels: Vec<u8>,
+}
+#[cfg(feature = "std")]
+impl CameraSensor for StaticImage {
+    fn capture(&mut self) -> Vec<u8> {
+        self.pixels.clone()
+    }
+}
+#[cfg(feature = "std")]
+impl StaticImage {
+    pub fn new(filename: &str) -> Result<StaticImage, Box<dyn Error>> {
+        let (width, height, rgba) = dump::load_png(Path::new(filename))?;
+        let luma = rgba.chunks(4)
+            .map(|pixel| ((pixel[0] as u32 * 299 + pixel[1] as u32 * 587 + pixel[2] as u32 * 114) / 1000) as u8)
+            .collect::<Vec<u8>>();
+
+        let pixels = scale_to_sensor(&luma, width as usize, height as usize);
+
+        Ok(StaticImage { pixels })
+    }
+}
+
+// frames from a V4L2 webcam, using the luma channel of its YUYV output
+#[cfg(feature = "webcam")]
+pub struct Webcam {
+    camera: rscam::Camera,
+    width: usize,
+    height: usize,
+}
+#[cfg(feature = "webcam")]
+impl CameraSensor for Webcam {
+    fn capture(&mut self) -> Vec<u8> {
+        match self.camera.capture() {
+            Ok(frame) => {
+                let luma = frame.iter().step_by(2).cloned().collect::<Vec<u8>>();
+                scale_to_sensor(&luma, self.width, self.height)
+            },
+            Err(err) => {
+                eprintln!("Webcam capture failed: {}", err);
+                BlankSensor.capture()
+            },
+        }
+    }
+}
+#[cfg(feature = "webcam")]
+impl Webcam {
+    pub fn new(device: &str) -> Result<Webcam, Box<dyn Error>> {
+        let (width, height) = (320, 240);
+        let mut camera = rscam::new(device)?;
+        camera.start(&rscam::Config {
+            interval: (1, 30),
+            resolution: (width as u32, height as u32),
+            format: b"YUYV",
+            ..Default::default()
+        })?;
+
+        Ok(Webcam { camera, width, height })
+    }
+}
+
+// nearest-neighbour scale (and crop to the sensor's aspect ratio) of a greyscale image
+#[cfg(feature = "std")]
+fn scale_to_sensor(luma: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut pixels = vec![0x00; SENSOR_WIDTH * SENSOR_HEIGHT];
+    if width == 0 || height == 0 {
+        return pixels;
+    }
+
+    // pick the largest centred region with the sensor's aspect ratio
+    let (crop_width, crop_height) = if width * SENSOR_HEIGHT > height * SENSOR_WIDTH {
+        (height * SENSOR_WIDTH / SENSOR_HEIGHT, height)
+    } else {
+        (width, width * SENSOR_HEIGHT / SENSOR_WIDTH)
+    };
+    let x_offset = (width - crop_width) / 2;
+    let y_offset = (height - crop_height) / 2;
+
+    for y in 0..SENSOR_HEIGHT {
+        let source_y = y_offset + y * crop_height / SENSOR_HEIGHT;
+        for x in 0..SENSOR_WIDTH {
+            let source_x = x_offset + x * crop_width / SENSOR_WIDTH;
+            pixels[y * SENSOR_WIDTH + x] = luma[source_y * width + source_x];
+        }
+    }
+
+    pixels
+}
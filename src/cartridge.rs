@@ -1,16 +1,27 @@
-use std;
-use std::error::Error;
-use std::fmt;
+use core::fmt;
+use core::num::Wrapping;
+#[cfg(feature = "std")]
 use std::fs::File;
+#[cfg(feature = "std")]
 use std::io::prelude::*;
-use std::io::Cursor;
-use std::num::Wrapping;
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
 use num_traits::FromPrimitive;
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+use crate::battery::{BatterySave, RtcFooter};
+use crate::camera::{CameraSensor, BlankSensor, SENSOR_WIDTH, SENSOR_HEIGHT};
+use crate::clock::{self, ClockSource};
+use crate::motion::{self, Level, MotionSource};
 
 #[allow(non_camel_case_types)]
 #[derive(Clone, Copy, Debug, FromPrimitive)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
 pub enum CartridgeType {
     ROM  = 0x00, ROM_RAM  = 0x08, ROM_RAM_BATTERY  = 0x09,
     MBC1 = 0x01, MBC1_RAM = 0x02, MBC1_RAM_BATTERY = 0x03,
@@ -68,25 +79,62 @@ impl fmt::Display for CartridgeType {
 pub struct Cartridge {
     pub header: Header,
     mbc: Box<dyn MBC>,
+    rumble_callback: Option<Box<dyn FnMut(bool) + Send>>,
+    // from a loaded battery save, kept so it's written back out unchanged if the mapper has no clock
+    rtc: Option<RtcFooter>,
+    // writes to battery backed RAM since power on, enabled or not
+    ram_writes: u64,
 }
 
+// builds a mapper for a cartridge, or declines by returning None.
+// lets downstream users plug in mappers this crate doesn't know about (flash carts, homebrew, etc)
+pub type MBCFactory = dyn Fn(&Header, &[u8]) -> Option<Box<dyn MBC>>;
+
 impl Cartridge {
-    pub fn new(filename: &str) -> Result<Cartridge, Box<dyn Error>> {
+    #[cfg(feature = "std")]
+    pub fn new(filename: &str) -> Result<Cartridge, Error> {
+        Cartridge::with_mappers(filename, &[])
+    }
+
+    // like new, but tries each of the given factories (in order) before the built-in mappers
+    #[cfg(feature = "std")]
+    pub fn with_mappers(filename: &str, mappers: &[&MBCFactory]) -> Result<Cartridge, Error> {
         let mut f = File::open(filename)?;
         let mut rom = Vec::new();
         f.read_to_end(&mut rom)?;
-        let mut header_bytes = [0; 0x50];
-        header_bytes.copy_from_slice(&rom[0x100..0x150]);
-        let header = Header::new(header_bytes)?;
 
-        use CartridgeType as CT;
-        let mbc: Box<dyn MBC> = match header.cartridge_type {
-            CT::ROM | CT::ROM_RAM | CT::ROM_RAM_BATTERY => Box::new(ROM::new(&header, rom)),
-            CT::MBC1 | CT::MBC1_RAM | CT::MBC1_RAM_BATTERY => Box::new(MBC1::new(&header, rom)),
-            _ => panic!("Cartridge type {:?} is not yet implemented", header.cartridge_type),
+        Cartridge::from_rom(rom, mappers)
+    }
+
+    // builds a cartridge from a rom image that's already in memory
+    pub fn from_rom(rom: Vec<u8>, mappers: &[&MBCFactory]) -> Result<Cartridge, Error> {
+        if rom.len() < 0x150 {
+            return Err(format!("rom is too small to contain a header ({} bytes)", rom.len()).into());
+        }
+        let header = Header::from_slice(&rom[0x100..0x150])?;
+
+        let custom_mbc = mappers.iter().find_map(|factory| factory(&header, &rom));
+        let mbc = match custom_mbc {
+            Some(mbc) => mbc,
+            None => Cartridge::builtin_mbc(&header, rom)?,
         };
 
-        Ok(Cartridge { header, mbc })
+        Ok(Cartridge { header, mbc, rumble_callback: None, rtc: None, ram_writes: 0 })
+    }
+
+    fn builtin_mbc(header: &Header, rom: Vec<u8>) -> Result<Box<dyn MBC>, Error> {
+        use CartridgeType as CT;
+        Ok(match header.cartridge_type {
+            CT::ROM | CT::ROM_RAM | CT::ROM_RAM_BATTERY => Box::new(ROM::new(header, rom)),
+            CT::MBC1 | CT::MBC1_RAM | CT::MBC1_RAM_BATTERY => Box::new(MBC1::new(header, rom)),
+            CT::MBC3 | CT::MBC3_RAM | CT::MBC3_RAM_BATTERY => Box::new(MBC3::new(header, rom, false)),
+            CT::MBC3_TIMER_BATTERY | CT::MBC3_TIMER_RAM_BATTERY => Box::new(MBC3::new(header, rom, true)),
+            CT::MBC5 | CT::MBC5_RAM | CT::MBC5_RAM_BATTERY => Box::new(MBC5::new(header, rom, false)),
+            CT::MBC5_RUMBLE | CT::MBC5_RUMBLE_RAM | CT::MBC5_RUMBLE_RAM_BATTERY => Box::new(MBC5::new(header, rom, true)),
+            CT::MBC7_SENSOR_RUMBLE_RAM_BATTERY => Box::new(MBC7::new(rom)),
+            CT::POCKET_CAMERA => Box::new(PocketCamera::new(header, rom)),
+            _ => return Err(Error::UnsupportedMapper(header.cartridge_type)),
+        })
     }
 
     pub fn read(&self, addr: u16) -> u8 {
@@ -94,19 +142,224 @@ impl Cartridge {
     }
 
     pub fn write(&mut self, addr: u16, value: u8) {
+        let prev_rumble = self.mbc.rumble();
         self.mbc.write(addr, value);
+        if (0xA000..=0xBFFF).contains(&addr) && self.has_battery() {
+            self.ram_writes = self.ram_writes.wrapping_add(1);
+        }
+
+        // only notify the frontend when the motor actually changes state
+        let rumble = self.mbc.rumble();
+        if rumble != prev_rumble {
+            if let Some(callback) = self.rumble_callback.as_mut() {
+                callback(rumble);
+            }
+        }
     }
 
     pub fn rom_len(&self) -> usize {
         self.mbc.rom_len()
     }
+
+    pub fn rom_bank(&self) -> u16 {
+        self.mbc.rom_bank()
+    }
+
+    // where in the rom a read from addr comes from, None outside 0x0000-0x7FFF
+    pub fn rom_offset(&self, addr: u16) -> Option<usize> {
+        let offset = match addr {
+            0x0000 ..= 0x3FFF => addr as usize,
+            0x4000 ..= 0x7FFF => self.rom_bank() as usize * 0x4000 + (addr as usize - 0x4000),
+            _ => return None,
+        };
+        match self.rom_len() {
+            0 => None,
+            len => Some(offset % len),
+        }
+    }
+
+    pub fn rumble(&self) -> bool {
+        self.mbc.rumble()
+    }
+
+    pub fn set_rumble_callback(&mut self, callback: Box<dyn FnMut(bool) + Send>) {
+        self.rumble_callback = Some(callback);
+    }
+
+    pub fn set_camera_sensor(&mut self, sensor: Box<dyn CameraSensor>) {
+        self.mbc.set_camera_sensor(sensor);
+    }
+
+    pub fn set_motion_source(&mut self, source: Box<dyn MotionSource>) {
+        self.mbc.set_motion_source(source);
+    }
+
+    pub fn set_clock_source(&mut self, source: Box<dyn ClockSource>) {
+        self.mbc.set_clock_source(source);
+    }
+
+    // whether the cartridge's RAM is kept when the power's off, and so should be saved to disk
+    pub fn has_battery(&self) -> bool {
+        self.header.has_battery()
+    }
+
+    // goes up with every write to the cartridge's battery backed RAM, so a frontend can tell when the game last saved
+    pub fn ram_writes(&self) -> u64 {
+        self.ram_writes
+    }
+
+    // the cartridge's RAM, all banks, as it is with no RTC footer. empty if it has none
+    pub fn ram(&self) -> &[u8] {
+        self.mbc.ram()
+    }
+
+    // overwrites the cartridge's RAM from offset, all banks counted, for editing saves while the game's running
+    pub fn write_ram(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Error> {
+        let ram = self.mbc.ram_mut();
+        let len = ram.len();
+        match ram.get_mut(offset..offset.saturating_add(bytes.len())) {
+            Some(ram) => {
+                ram.copy_from_slice(bytes);
+                Ok(())
+            },
+            None => Err(format!("{} bytes at {:x} doesn't fit in {} bytes of cartridge RAM", bytes.len(), offset, len).into()),
+        }
+    }
+
+    // the cartridge's RAM as a .sav file, see battery.rs for the format
+    pub fn battery_save(&mut self) -> Vec<u8> {
+        let save = BatterySave { ram: self.mbc.ram().to_vec(), rtc: self.mbc.rtc_footer().or(self.rtc) };
+        save.to_bytes()
+    }
+
+    // loads a .sav file from GBOxide, BGB or VBA into the cartridge's RAM
+    pub fn load_battery_save(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let ram = self.mbc.ram_mut();
+        let save = BatterySave::parse(bytes, ram.len())?;
+        ram.copy_from_slice(&save.ram);
+        if let Some(rtc) = &save.rtc {
+            self.mbc.load_rtc_footer(rtc);
+        }
+        self.rtc = save.rtc;
+        Ok(())
+    }
+
+    pub fn save_state(&self) -> MapperState {
+        self.mbc.save_state()
+    }
+
+    // state has to come from a cartridge made from the same rom
+    pub fn load_state(&mut self, state: &MapperState) -> Result<(), Error> {
+        let prev_rumble = self.mbc.rumble();
+        self.mbc.load_state(state)?;
+
+        let rumble = self.mbc.rumble();
+        if rumble != prev_rumble {
+            if let Some(callback) = self.rumble_callback.as_mut() {
+                callback(rumble);
+            }
+        }
+        Ok(())
+    }
+
+    // a cartridge with no rom that only holds a mapper state, what a save state deserializes to
+    #[cfg(feature = "serialize")]
+    fn detached(state: MapperState) -> Cartridge {
+        let header = Header::new([0x00; 0x50]).expect("a blank header is always valid");
+        Cartridge { header, mbc: Box::new(Detached(state)), rumble_callback: None, rtc: None, ram_writes: 0 }
+    }
 }
 
-trait MBC {
+// save states only carry a cartridge's mapper state, the rom and callbacks stay with the running
+// cartridge. use with #[serde(with = "crate::cartridge::state")], then restore with load_state
+#[cfg(feature = "serialize")]
+pub(crate) mod state {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{Cartridge, MapperState};
+
+    pub fn serialize<S: Serializer>(cartridge: &Cartridge, serializer: S) -> Result<S::Ok, S::Error> {
+        cartridge.save_state().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Cartridge, D::Error> {
+        MapperState::deserialize(deserializer).map(Cartridge::detached)
+    }
+}
+
+// the parts of a mapper that change as it runs, for save states. the rom isn't included
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct MapperState {
+    pub ram: Vec<u8>,
+    // bank selections and the like, laid out however the mapper likes
+    pub registers: Vec<u8>,
+}
+
+impl MapperState {
+    // checks a state will fit a mapper with ram_len bytes of ram and registers_len bytes of registers
+    pub fn check(&self, ram_len: usize, registers_len: usize) -> Result<(), Error> {
+        if self.ram.len() != ram_len || self.registers.len() != registers_len {
+            return Err(format!("save state doesn't match the cartridge ({} bytes of ram and {} of registers, expected {} and {})",
+                               self.ram.len(), self.registers.len(), ram_len, registers_len).into());
+        }
+        Ok(())
+    }
+}
+
+// a cartridge's memory bank controller.
+// the mmu passes through reads from 0x0000-0x7FFF and 0xA000-0xBFFF, and writes to the same ranges
+pub trait MBC: Send {
     fn read(&self, addr: u16) -> u8;
     fn write(&mut self, addr: u16, value: u8);
 
     fn rom_len(&self) -> usize;
+
+    // whether the cartridge's rumble motor is currently switched on
+    fn rumble(&self) -> bool { false }
+
+    // only the Pocket Camera has anything to plug a sensor into
+    fn set_camera_sensor(&mut self, _sensor: Box<dyn CameraSensor>) {}
+
+    // for cartridges with an accelerometer, the MBC7's
+    fn set_motion_source(&mut self, _source: Box<dyn MotionSource>) {}
+
+    // for cartridges with a real time clock, the MBC3's
+    fn set_clock_source(&mut self, _source: Box<dyn ClockSource>) {}
+
+    // the clock's state for battery saves, None if there's no clock
+    fn rtc_footer(&mut self) -> Option<RtcFooter> { None }
+    fn load_rtc_footer(&mut self, _footer: &RtcFooter) {}
+
+    // the ROM bank mapped at 0x4000-0x7FFF, for the debugger
+    fn rom_bank(&self) -> u16 { 1 }
+
+    // the cartridge's RAM, all banks, for battery saves. empty if it has none
+    fn ram(&self) -> &[u8] { &[] }
+    fn ram_mut(&mut self) -> &mut [u8] { &mut [] }
+
+    // for save states. mappers that don't override these have nothing to save
+    fn save_state(&self) -> MapperState {
+        MapperState::default()
+    }
+    fn load_state(&mut self, state: &MapperState) -> Result<(), Error> {
+        state.check(0, 0)
+    }
+}
+
+// stands in for a cartridge's mapper between deserializing a save state and loading it
+#[cfg(feature = "serialize")]
+struct Detached(MapperState);
+#[cfg(feature = "serialize")]
+impl MBC for Detached {
+    fn read(&self, _addr: u16) -> u8 { 0xFF }
+    fn write(&mut self, _addr: u16, _value: u8) {}
+
+    fn rom_len(&self) -> usize { 0 }
+
+    fn save_state(&self) -> MapperState {
+        self.0.clone()
+    }
 }
 
 #[allow(non_camel_case_types)]
@@ -117,19 +370,34 @@ struct ROM {
 impl MBC for ROM {
     fn read(&self, addr: u16) -> u8 {
         match addr {
-            0x0000 ..= 0x7FFF => self.rom[addr as usize],
-            0xA000 ..= 0xBFFF => self.ram[addr as usize],
+            // dumps smaller than 32KiB leave the rest of the address space floating
+            0x0000 ..= 0x7FFF => self.rom.get(addr as usize).cloned().unwrap_or(0xFF),
+            0xA000 ..= 0xBFFF => self.ram.get((addr - 0xA000) as usize).cloned().unwrap_or(0xFF),
             _ => 0xFF,
         }
     }
     fn write(&mut self, addr: u16, value: u8) {
         match addr {
-            0xA000 ..= 0xBFFF => self.ram[addr as usize] = value,
+            0xA000 ..= 0xBFFF => if let Some(byte) = self.ram.get_mut((addr - 0xA000) as usize) {
+                *byte = value
+            },
             _ => (),
         }
     }
 
     fn rom_len(&self) -> usize { self.rom.len() }
+
+    fn ram(&self) -> &[u8] { &self.ram }
+    fn ram_mut(&mut self) -> &mut [u8] { &mut self.ram }
+
+    fn save_state(&self) -> MapperState {
+        MapperState { ram: self.ram.clone(), registers: Vec::new() }
+    }
+    fn load_state(&mut self, state: &MapperState) -> Result<(), Error> {
+        state.check(self.ram.len(), 0)?;
+        self.ram.copy_from_slice(&state.ram);
+        Ok(())
+    }
 }
 impl ROM {
     fn new(header: &Header, rom: Vec<u8>) -> ROM {
@@ -150,10 +418,10 @@ struct MBC1 {
 impl MBC for MBC1 {
     fn read(&self, addr: u16) -> u8 {
         match addr {
-            0x0000 ..= 0x3FFF => self.rom[addr as usize],
+            0x0000 ..= 0x3FFF => self.rom.get(addr as usize).cloned().unwrap_or(0xFF),
             0x4000 ..= 0x7FFF => self.read_selected_rom_bank(addr),
             0xA000 ..= 0xBFFF => self.read_selected_ram_bank(addr),
-            _ => unreachable!(), // the mmu should only send us addresses in these ranges
+            _ => 0xFF, // the mmu only sends us addresses in these ranges
         }
     }
 
@@ -168,13 +436,34 @@ impl MBC for MBC1 {
             },
             0x6000 ..= 0x7FFF => self.ram_select_mode = match value & 0x1 { 0x01 => true, _ => false },
             0xA000 ..= 0xBFFF => self.write_selected_ram_bank(addr, value),
-            _ => unreachable!(), // mmu will only pass us addresses in this range
+            _ => (), // the mmu only passes us addresses in this range
         };
     }
 
     fn rom_len(&self) -> usize {
         self.rom.len()
     }
+
+    fn rom_bank(&self) -> u16 { self.rom_bank_selection as u16 }
+    fn ram(&self) -> &[u8] { &self.ram }
+    fn ram_mut(&mut self) -> &mut [u8] { &mut self.ram }
+
+    fn save_state(&self) -> MapperState {
+        MapperState {
+            ram: self.ram.clone(),
+            registers: vec![self.rom_bank_selection, self.ram_bank_selection,
+                            self.ram_enabled as u8, self.ram_select_mode as u8],
+        }
+    }
+    fn load_state(&mut self, state: &MapperState) -> Result<(), Error> {
+        state.check(self.ram.len(), 4)?;
+        self.ram.copy_from_slice(&state.ram);
+        self.rom_bank_selection = state.registers[0];
+        self.ram_bank_selection = state.registers[1];
+        self.ram_enabled = state.registers[2] != 0;
+        self.ram_select_mode = state.registers[3] != 0;
+        Ok(())
+    }
 }
 impl MBC1 {
     fn new(header: &Header, rom: Vec<u8>) -> MBC1 {
@@ -238,8 +527,923 @@ impl MBC1 {
     }
 }
 
+struct MBC3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rom_bank_selection: u8,
+    // 0x00-0x07 picks a ram bank, 0x08-0x0C one of the clock's registers
+    ram_bank_selection: u8,
+    // for the clock's registers too
+    ram_enabled: bool,
+    clock: Option<RealTimeClock>,
+}
+impl MBC for MBC3 {
+    fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000 ..= 0x3FFF => self.rom.get(addr as usize).cloned().unwrap_or(0xFF),
+            0x4000 ..= 0x7FFF => self.read_selected_rom_bank(addr),
+            0xA000 ..= 0xBFFF => self.read_selected_ram_bank(addr),
+            _ => 0xFF, // the mmu only sends us addresses in these ranges
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000 ..= 0x1FFF => self.enable_ram(value),
+            0x2000 ..= 0x3FFF => self.rom_bank_selection = match value & 0x7F { 0x00 => 0x01, bank => bank },
+            0x4000 ..= 0x5FFF => self.ram_bank_selection = value & 0x0F,
+            0x6000 ..= 0x7FFF => if let Some(clock) = &mut self.clock {
+                clock.write_latch(value)
+            },
+            0xA000 ..= 0xBFFF => self.write_selected_ram_bank(addr, value),
+            _ => (), // the mmu only passes us addresses in this range
+        };
+    }
+
+    fn rom_len(&self) -> usize {
+        self.rom.len()
+    }
+
+    fn set_clock_source(&mut self, source: Box<dyn ClockSource>) {
+        if let Some(clock) = &mut self.clock {
+            clock.set_source(source);
+        }
+    }
+
+    fn rtc_footer(&mut self) -> Option<RtcFooter> {
+        self.clock.as_mut().map(RealTimeClock::footer)
+    }
+
+    fn load_rtc_footer(&mut self, footer: &RtcFooter) {
+        if let Some(clock) = &mut self.clock {
+            clock.load_footer(footer);
+        }
+    }
+
+    fn rom_bank(&self) -> u16 { self.rom_bank_selection as u16 }
+    fn ram(&self) -> &[u8] { &self.ram }
+    fn ram_mut(&mut self) -> &mut [u8] { &mut self.ram }
+
+    // the bank registers, then the clock's if there is one
+    fn save_state(&self) -> MapperState {
+        let mut registers = vec![self.rom_bank_selection, self.ram_bank_selection, self.ram_enabled as u8];
+        if let Some(clock) = &self.clock {
+            clock.save_state(&mut registers);
+        }
+        MapperState { ram: self.ram.clone(), registers }
+    }
+    fn load_state(&mut self, state: &MapperState) -> Result<(), Error> {
+        let clock_len = if self.clock.is_some() { RealTimeClock::STATE_LEN } else { 0 };
+        state.check(self.ram.len(), 3 + clock_len)?;
+        self.ram.copy_from_slice(&state.ram);
+        self.rom_bank_selection = state.registers[0];
+        self.ram_bank_selection = state.registers[1];
+        self.ram_enabled = state.registers[2] != 0;
+        if let Some(clock) = &mut self.clock {
+            clock.load_state(&state.registers[3..]);
+        }
+        Ok(())
+    }
+}
+impl MBC3 {
+    fn new(header: &Header, rom: Vec<u8>, has_clock: bool) -> MBC3 {
+        let ram = vec![0x0; header.ram_size as usize];
+        let rom_bank_selection = 0x01;
+        let ram_bank_selection = 0x00;
+        let ram_enabled = false;
+        let clock = if has_clock { Some(RealTimeClock::new(clock::default_source())) } else { None };
+
+        MBC3 { rom, ram, rom_bank_selection, ram_bank_selection, ram_enabled, clock }
+    }
+
+    fn read_selected_rom_bank(&self, addr: u16) -> u8 {
+        let bank_addr = 0x4000 * (self.rom_bank_selection as u32) + (addr as u32 - 0x4000);
+        if (bank_addr as usize) < self.rom.len() {
+            self.rom[bank_addr as usize]
+        } else {
+            0xFF
+        }
+    }
+
+    fn read_selected_ram_bank(&self, addr: u16) -> u8 {
+        if !self.ram_enabled { return 0xFF }
+
+        if let (0x08 ..= 0x0C, Some(clock)) = (self.ram_bank_selection, &self.clock) {
+            return clock.read(self.ram_bank_selection - 0x08);
+        }
+
+        let bank_addr = 0x2000 * (self.ram_bank_selection as u32) + (addr as u32 - 0xA000);
+        if (bank_addr as usize) < self.ram.len() {
+            self.ram[bank_addr as usize]
+        } else {
+            0xFF
+        }
+    }
+
+    fn write_selected_ram_bank(&mut self, addr: u16, value: u8) {
+        if !self.ram_enabled { return }
+
+        if let (0x08 ..= 0x0C, Some(clock)) = (self.ram_bank_selection, &mut self.clock) {
+            clock.write(self.ram_bank_selection - 0x08, value);
+            return;
+        }
+
+        let bank_addr = 0x2000 * (self.ram_bank_selection as u32) + (addr as u32 - 0xA000);
+        if (bank_addr as usize) < self.ram.len() {
+            self.ram[bank_addr as usize] = value
+        }
+    }
+
+    fn enable_ram(&mut self, value: u8) {
+        self.ram_enabled = value & 0x0F == 0x0A;
+    }
+}
+
+// the MBC3's clock: seconds, minutes, hours, and a 9 bit day counter that sets a carry bit when
+// it overflows. it's kept as a count of seconds against the clock source, the registers are
+// only worked out when the game latches them or writes one
+struct RealTimeClock {
+    source: Box<dyn ClockSource>,
+    // when the source read the count as 0, while the clock's running
+    base: i64,
+    // the count it stopped at, while it's halted
+    halted: Option<u64>,
+    day_carry: bool,
+    // seconds, minutes, hours, day counter low, day counter high, as the game last latched them
+    latched: [u8; 5],
+    // the last write to 0x6000-0x7FFF was 0x00, so a 0x01 will latch
+    latch_armed: bool,
+}
+impl RealTimeClock {
+    const DAY: u64 = 24 * 60 * 60;
+    // the day counter wraps at 512
+    const WRAP: u64 = 512 * RealTimeClock::DAY;
+    // bits of the day counter high register
+    const DAY_HIGH: u8 = 0x01;
+    const HALT: u8 = 0x40;
+    const DAY_CARRY: u8 = 0x80;
+    // registers used in each of the 5
+    const MASKS: [u8; 5] = [0x3F, 0x3F, 0x1F, 0xFF, RealTimeClock::DAY_HIGH | RealTimeClock::HALT | RealTimeClock::DAY_CARRY];
+    // base, halted flag and count, day carry, latched registers and latch_armed
+    const STATE_LEN: usize = 8 + 1 + 8 + 1 + 5 + 1;
+
+    fn new(mut source: Box<dyn ClockSource>) -> RealTimeClock {
+        let base = source.now() as i64;
+        RealTimeClock { source, base, halted: None, day_carry: false, latched: [0x00; 5], latch_armed: false }
+    }
+
+    // seconds counted, wrapping the day counter if it's gone past 511
+    fn count(&mut self) -> u64 {
+        let count = match self.halted {
+            Some(count) => count,
+            None => (self.source.now() as i64 - self.base).max(0) as u64,
+        };
+        if count >= RealTimeClock::WRAP {
+            self.set_count(count % RealTimeClock::WRAP);
+            self.day_carry = true;
+        }
+        count % RealTimeClock::WRAP
+    }
+
+    fn set_count(&mut self, count: u64) {
+        match &mut self.halted {
+            Some(halted) => *halted = count,
+            None => self.base = self.source.now() as i64 - count as i64,
+        }
+    }
+
+    // switching sources keeps the clock where it was
+    fn set_source(&mut self, source: Box<dyn ClockSource>) {
+        let count = self.count();
+        self.source = source;
+        self.set_count(count);
+    }
+
+    fn registers(&mut self) -> [u8; 5] {
+        let count = self.count();
+        self.registers_at(count)
+    }
+
+    fn registers_at(&self, count: u64) -> [u8; 5] {
+        let days = count / RealTimeClock::DAY;
+        let mut day_high = (days >> 8) as u8;
+        if self.halted.is_some() {
+            day_high |= RealTimeClock::HALT;
+        }
+        if self.day_carry {
+            day_high |= RealTimeClock::DAY_CARRY;
+        }
+        [(count % 60) as u8, (count / 60 % 60) as u8, (count / 3600 % 24) as u8, days as u8, day_high]
+    }
+
+    // the count the registers make, and whether they halt the clock and set the day carry.
+    // out of range values like 63 seconds are taken as they are
+    fn from_registers(registers: [u8; 5]) -> (u64, bool, bool) {
+        let [seconds, minutes, hours, day_low, day_high] = registers;
+        let days = (day_low as u64) | ((day_high & RealTimeClock::DAY_HIGH) as u64) << 8;
+        let count = seconds as u64 + minutes as u64 * 60 + hours as u64 * 3600 + days * RealTimeClock::DAY;
+        (count, day_high & RealTimeClock::HALT != 0, day_high & RealTimeClock::DAY_CARRY != 0)
+    }
+
+    fn set_registers(&mut self, registers: [u8; 5]) {
+        let (count, halt, day_carry) = RealTimeClock::from_registers(registers);
+        self.halted = if halt { Some(count) } else { None };
+        self.set_count(count);
+        self.day_carry = day_carry;
+    }
+
+    fn read(&self, register: u8) -> u8 {
+        self.latched[register as usize]
+    }
+
+    fn write(&mut self, register: u8, value: u8) {
+        let mut registers = self.registers();
+        registers[register as usize] = value & RealTimeClock::MASKS[register as usize];
+        self.set_registers(registers);
+    }
+
+    // writing 0x00 then 0x01 copies the running registers to where the game can read them
+    fn write_latch(&mut self, value: u8) {
+        if self.latch_armed && value == 0x01 {
+            self.latched = self.registers();
+        }
+        self.latch_armed = value == 0x00;
+    }
+
+    // the live registers, stamped with the real time rather than the source's, so a save written
+    // under a sped up clock keeps the time it gained when it's loaded under the real one
+    fn footer(&mut self) -> RtcFooter {
+        let mut registers = [0; 5];
+        for (word, &register) in registers.iter_mut().zip(self.registers().iter()) {
+            *word = register as u32;
+        }
+        let mut latched = [0; 5];
+        for (word, &register) in latched.iter_mut().zip(self.latched.iter()) {
+            *word = register as u32;
+        }
+        RtcFooter { registers, latched, timestamp: self.source.wall_clock() }
+    }
+
+    // picks up from a battery save, counting the real time since it was written
+    fn load_footer(&mut self, footer: &RtcFooter) {
+        let mut registers = [0; 5];
+        for (register, &word) in registers.iter_mut().zip(footer.registers.iter()) {
+            *register = word as u8;
+        }
+        let (count, halt, day_carry) = RealTimeClock::from_registers(registers);
+        let since_saved = self.source.wall_clock().saturating_sub(footer.timestamp);
+        self.halted = if halt { Some(count) } else { None };
+        self.set_count(if halt { count } else { count + since_saved });
+        self.day_carry = day_carry;
+        for (latched, &word) in self.latched.iter_mut().zip(footer.latched.iter()) {
+            *latched = word as u8;
+        }
+    }
+
+    fn save_state(&self, registers: &mut Vec<u8>) {
+        registers.extend_from_slice(&self.base.to_le_bytes());
+        registers.push(self.halted.is_some() as u8);
+        registers.extend_from_slice(&self.halted.unwrap_or(0).to_le_bytes());
+        registers.push(self.day_carry as u8);
+        registers.extend_from_slice(&self.latched);
+        registers.push(self.latch_armed as u8);
+    }
+
+    // registers has to be STATE_LEN long
+    fn load_state(&mut self, registers: &[u8]) {
+        let mut word = [0; 8];
+        word.copy_from_slice(&registers[0..8]);
+        self.base = i64::from_le_bytes(word);
+        word.copy_from_slice(&registers[9..17]);
+        self.halted = if registers[8] != 0 { Some(u64::from_le_bytes(word)) } else { None };
+        self.day_carry = registers[17] != 0;
+        self.latched.copy_from_slice(&registers[18..23]);
+        self.latch_armed = registers[23] != 0;
+    }
+}
+
+struct MBC5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rom_bank_selection: u16,
+    ram_bank_selection: u8,
+    ram_enabled: bool,
+    has_rumble: bool,
+    rumble: bool,
+}
+impl MBC for MBC5 {
+    fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000 ..= 0x3FFF => self.rom.get(addr as usize).cloned().unwrap_or(0xFF),
+            0x4000 ..= 0x7FFF => self.read_selected_rom_bank(addr),
+            0xA000 ..= 0xBFFF => self.read_selected_ram_bank(addr),
+            _ => 0xFF, // the mmu only sends us addresses in these ranges
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000 ..= 0x1FFF => self.enable_ram(value),
+            0x2000 ..= 0x2FFF => self.select_rom_bank_lower_bits(value),
+            0x3000 ..= 0x3FFF => self.select_rom_bank_upper_bit(value),
+            0x4000 ..= 0x5FFF => self.select_ram_bank(value),
+            0x6000 ..= 0x7FFF => (),
+            0xA000 ..= 0xBFFF => self.write_selected_ram_bank(addr, value),
+            _ => (), // the mmu only passes us addresses in this range
+        };
+    }
+
+    fn rom_len(&self) -> usize {
+        self.rom.len()
+    }
+
+    fn rumble(&self) -> bool {
+        self.rumble
+    }
+
+    fn rom_bank(&self) -> u16 { self.rom_bank_selection }
+    fn ram(&self) -> &[u8] { &self.ram }
+    fn ram_mut(&mut self) -> &mut [u8] { &mut self.ram }
+
+    fn save_state(&self) -> MapperState {
+        let [bank_low, bank_high] = self.rom_bank_selection.to_le_bytes();
+        MapperState {
+            ram: self.ram.clone(),
+            registers: vec![bank_low, bank_high, self.ram_bank_selection,
+                            self.ram_enabled as u8, self.rumble as u8],
+        }
+    }
+    fn load_state(&mut self, state: &MapperState) -> Result<(), Error> {
+        state.check(self.ram.len(), 5)?;
+        self.ram.copy_from_slice(&state.ram);
+        self.rom_bank_selection = u16::from_le_bytes([state.registers[0], state.registers[1]]);
+        self.ram_bank_selection = state.registers[2];
+        self.ram_enabled = state.registers[3] != 0;
+        self.rumble = state.registers[4] != 0;
+        Ok(())
+    }
+}
+impl MBC5 {
+    fn new(header: &Header, rom: Vec<u8>, has_rumble: bool) -> MBC5 {
+        let ram = vec![0x0; header.ram_size as usize];
+        let rom_bank_selection = 0x01;
+        let ram_bank_selection = 0x00;
+        let ram_enabled = false;
+        let rumble = false;
+
+        MBC5 { rom, ram, rom_bank_selection, ram_bank_selection, ram_enabled, has_rumble, rumble }
+    }
+
+    fn read_selected_rom_bank(&self, addr: u16) -> u8 {
+        let bank_addr = 0x4000 * (self.rom_bank_selection as u32) + (addr as u32 - 0x4000);
+        if (bank_addr as usize) < self.rom.len() {
+            self.rom[bank_addr as usize]
+        } else {
+            0xFF
+        }
+    }
+
+    fn read_selected_ram_bank(&self, addr: u16) -> u8 {
+        if !self.ram_enabled { return 0xFF }
+
+        let bank_addr = 0x2000 * (self.ram_bank_selection as u32) + (addr as u32 - 0xA000);
+        if (bank_addr as usize) < self.ram.len() {
+            self.ram[bank_addr as usize]
+        } else {
+            0xFF
+        }
+    }
+
+    fn write_selected_ram_bank(&mut self, addr: u16, value: u8) {
+        if !self.ram_enabled { return }
+
+        let bank_addr = 0x2000 * (self.ram_bank_selection as u32) + (addr as u32 - 0xA000);
+        if (bank_addr as usize) < self.ram.len() {
+            self.ram[bank_addr as usize] = value
+        }
+    }
+
+    fn enable_ram(&mut self, value: u8) {
+        self.ram_enabled = match value & 0x0F {
+            0x0A => true,
+            _ => false
+        }
+    }
+
+    fn select_ram_bank(&mut self, value: u8) {
+        if self.has_rumble {
+            // on rumble carts bit 3 drives the motor instead of selecting a ram bank
+            self.rumble = value & 0b1000 != 0;
+            self.ram_bank_selection = value & 0b0111;
+        } else {
+            self.ram_bank_selection = value & 0b1111;
+        }
+    }
+
+    fn select_rom_bank_lower_bits(&mut self, value: u8) {
+        // unlike MBC1, bank 0 can be mapped into 0x4000-0x7FFF
+        self.rom_bank_selection &= 0x100;
+        self.rom_bank_selection |= value as u16;
+    }
+
+    fn select_rom_bank_upper_bit(&mut self, value: u8) {
+        self.rom_bank_selection &= 0x0FF;
+        self.rom_bank_selection |= ((value & 0b1) as u16) << 8;
+    }
+}
+
+struct MBC7 {
+    rom: Vec<u8>,
+    rom_bank_selection: u8,
+    // the registers at 0xA000-0xAFFF need both of these
+    ram_enabled: bool,
+    registers_enabled: bool,
+    // the accelerometer's x and y as last latched
+    latched: (u16, u16),
+    // a latch only happens once the last one's been erased
+    latch_erased: bool,
+    motion: Box<dyn MotionSource>,
+    eeprom: Eeprom,
+}
+impl MBC for MBC7 {
+    fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000 ..= 0x3FFF => self.rom.get(addr as usize).cloned().unwrap_or(0xFF),
+            0x4000 ..= 0x7FFF => self.read_selected_rom_bank(addr),
+            0xA000 ..= 0xAFFF => self.read_register(addr),
+            _ => 0xFF, // and 0xB000-0xBFFF has nothing on it
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000 ..= 0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000 ..= 0x3FFF => self.rom_bank_selection = value,
+            0x4000 ..= 0x5FFF => self.registers_enabled = value == 0x40,
+            0xA000 ..= 0xAFFF => self.write_register(addr, value),
+            _ => (), // the mmu only passes us addresses in this range
+        };
+    }
+
+    fn rom_len(&self) -> usize {
+        self.rom.len()
+    }
+
+    fn set_motion_source(&mut self, source: Box<dyn MotionSource>) {
+        self.motion = source;
+    }
+
+    fn rom_bank(&self) -> u16 { self.rom_bank_selection as u16 }
+    // the EEPROM stands in for RAM in battery saves
+    fn ram(&self) -> &[u8] { &self.eeprom.data }
+    fn ram_mut(&mut self) -> &mut [u8] { &mut self.eeprom.data }
+
+    // the bank and latch registers, then the EEPROM's
+    fn save_state(&self) -> MapperState {
+        let [x_low, x_high] = self.latched.0.to_le_bytes();
+        let [y_low, y_high] = self.latched.1.to_le_bytes();
+        let mut registers = vec![self.rom_bank_selection, self.ram_enabled as u8, self.registers_enabled as u8,
+                                 self.latch_erased as u8, x_low, x_high, y_low, y_high];
+        self.eeprom.save_state(&mut registers);
+        MapperState { ram: self.eeprom.data.clone(), registers }
+    }
+    fn load_state(&mut self, state: &MapperState) -> Result<(), Error> {
+        state.check(self.eeprom.data.len(), 8 + Eeprom::STATE_LEN)?;
+        self.eeprom.data.copy_from_slice(&state.ram);
+        self.rom_bank_selection = state.registers[0];
+        self.ram_enabled = state.registers[1] != 0;
+        self.registers_enabled = state.registers[2] != 0;
+        self.latch_erased = state.registers[3] != 0;
+        self.latched = (u16::from_le_bytes([state.registers[4], state.registers[5]]),
+                        u16::from_le_bytes([state.registers[6], state.registers[7]]));
+        self.eeprom.load_state(&state.registers[8..]);
+        Ok(())
+    }
+}
+impl MBC7 {
+    // what the x and y registers read after an erase, until the next latch
+    const ERASED: u16 = 0x8000;
+
+    fn new(rom: Vec<u8>) -> MBC7 {
+        MBC7 {
+            rom,
+            rom_bank_selection: 0x01,
+            ram_enabled: false,
+            registers_enabled: false,
+            latched: (MBC7::ERASED, MBC7::ERASED),
+            latch_erased: false,
+            motion: Box::new(Level),
+            eeprom: Eeprom::new(),
+        }
+    }
+
+    fn read_selected_rom_bank(&self, addr: u16) -> u8 {
+        let bank_addr = 0x4000 * (self.rom_bank_selection as u32) + (addr as u32 - 0x4000);
+        if (bank_addr as usize) < self.rom.len() {
+            self.rom[bank_addr as usize]
+        } else {
+            0xFF
+        }
+    }
+
+    // the register's picked by bits 4-7 of the address, the rest are ignored
+    fn read_register(&self, addr: u16) -> u8 {
+        if !self.ram_enabled || !self.registers_enabled { return 0xFF }
+
+        let [x_low, x_high] = self.latched.0.to_le_bytes();
+        let [y_low, y_high] = self.latched.1.to_le_bytes();
+        match (addr >> 4) & 0x0F {
+            0x2 => x_low,
+            0x3 => x_high,
+            0x4 => y_low,
+            0x5 => y_high,
+            0x6 => 0x00,
+            0x8 => self.eeprom.read(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        if !self.ram_enabled || !self.registers_enabled { return }
+
+        match ((addr >> 4) & 0x0F, value) {
+            (0x0, 0x55) => {
+                self.latched = (MBC7::ERASED, MBC7::ERASED);
+                self.latch_erased = true;
+            },
+            (0x1, 0xAA) if self.latch_erased => {
+                self.latched = motion::mbc7_reading(self.motion.tilt());
+                self.latch_erased = false;
+            },
+            (0x8, _) => self.eeprom.write(value),
+            _ => (),
+        }
+    }
+}
+
+// the MBC7's 93LC56 EEPROM, 128 16 bit words behind a serial line the game drives bit by bit
+// through Ax8x: chip select in bit 7, clock in bit 6, data in at bit 1 and data out at bit 0.
+// commands are a start bit, a 2 bit opcode and an 8 bit address clocked in on the rising edge,
+// then 16 bits of data for writes, or 16 clocked out (after a dummy 0) for reads
+struct Eeprom {
+    // little endian words, the layout other emulators save
+    data: Vec<u8>,
+    select: bool,
+    clock: bool,
+    data_in: bool,
+    data_out: bool,
+    // writes and erases are ignored until EWEN enables them
+    write_enabled: bool,
+    // bits clocked in since the command started, from the start bit on
+    command: u32,
+    command_bits: u8,
+    // the word being read and how many of its bits have gone out, while reading
+    reading: Option<(u8, u8)>,
+}
+impl Eeprom {
+    const WORDS: usize = 128;
+    // start bit, opcode and address
+    const HEADER_BITS: u8 = 1 + 2 + 8;
+    const DATA_BITS: u8 = 16;
+    // select, clock, data in and out, write enabled, the command and its length, then reading
+    const STATE_LEN: usize = 5 + 4 + 1 + 3;
+
+    fn new() -> Eeprom {
+        Eeprom {
+            data: vec![0xFF; Eeprom::WORDS * 2],
+            select: false,
+            clock: false,
+            data_in: false,
+            data_out: true,
+            write_enabled: false,
+            command: 0,
+            command_bits: 0,
+            reading: None,
+        }
+    }
+
+    fn read(&self) -> u8 {
+        (self.select as u8) << 7 | (self.clock as u8) << 6 | (self.data_in as u8) << 1 | self.data_out as u8
+    }
+
+    fn write(&mut self, value: u8) {
+        let select = value & 0x80 != 0;
+        let clock = value & 0x40 != 0;
+        self.data_in = value & 0x02 != 0;
+
+        if !select {
+            // dropping chip select ends whatever command was going
+            self.command = 0;
+            self.command_bits = 0;
+            self.reading = None;
+        } else if clock && !self.clock {
+            self.clock_rising();
+        }
+        self.select = select;
+        self.clock = clock;
+    }
+
+    fn word(&self, addr: u8) -> u16 {
+        let offset = addr as usize % Eeprom::WORDS * 2;
+        u16::from_le_bytes([self.data[offset], self.data[offset + 1]])
+    }
+
+    fn set_word(&mut self, addr: u8, word: u16) {
+        if !self.write_enabled { return }
+        let offset = addr as usize % Eeprom::WORDS * 2;
+        self.data[offset..offset + 2].copy_from_slice(&word.to_le_bytes());
+    }
+
+    fn clock_rising(&mut self) {
+        if let Some((addr, bit)) = self.reading {
+            // reads carry on into the next word for as long as the clock keeps going
+            self.data_out = self.word(addr) >> (15 - bit) & 1 != 0;
+            self.reading = if bit == 15 { Some((addr.wrapping_add(1), 0)) } else { Some((addr, bit + 1)) };
+            return;
+        }
+
+        // zeros before the start bit don't count
+        if self.command_bits == 0 && !self.data_in {
+            return;
+        }
+        self.command = self.command << 1 | self.data_in as u32;
+        self.command_bits += 1;
+
+        let with_data = Eeprom::HEADER_BITS + Eeprom::DATA_BITS;
+        let (opcode, addr) = match self.command_bits {
+            Eeprom::HEADER_BITS => ((self.command >> 8) & 0b11, self.command as u8),
+            bits if bits == with_data => ((self.command >> 24) & 0b11, (self.command >> 16) as u8),
+            _ => return,
+        };
+        let data = self.command as u16;
+        let has_data = self.command_bits == with_data;
+
+        match (opcode, addr >> 6, has_data) {
+            // READ, with the dummy 0 out first
+            (0b10, _, false) => {
+                self.reading = Some((addr, 0));
+                self.data_out = false;
+                return;
+            },
+            // ERASE
+            (0b11, _, false) => self.set_word(addr, 0xFFFF),
+            // WRITE
+            (0b01, _, true) => self.set_word(addr, data),
+            (0b01, _, false) => return,
+            // the rest are picked by the top two address bits: EWDS, WRAL, ERAL and EWEN
+            (0b00, 0b00, false) => self.write_enabled = false,
+            (0b00, 0b01, true) => for addr in 0..Eeprom::WORDS as u8 {
+                self.set_word(addr, data);
+            },
+            (0b00, 0b01, false) => return,
+            (0b00, 0b10, false) => for addr in 0..Eeprom::WORDS as u8 {
+                self.set_word(addr, 0xFFFF);
+            },
+            (0b00, 0b11, false) => self.write_enabled = true,
+            _ => (),
+        }
+
+        // done, and writes finish instantly so it's straight back to ready
+        self.data_out = true;
+        self.command = 0;
+        self.command_bits = 0;
+    }
+
+    fn save_state(&self, registers: &mut Vec<u8>) {
+        registers.extend_from_slice(&[self.select as u8, self.clock as u8, self.data_in as u8,
+                                      self.data_out as u8, self.write_enabled as u8]);
+        registers.extend_from_slice(&self.command.to_le_bytes());
+        registers.push(self.command_bits);
+        let (addr, bit) = self.reading.unwrap_or((0, 0));
+        registers.extend_from_slice(&[self.reading.is_some() as u8, addr, bit]);
+    }
+
+    // registers has to be STATE_LEN long
+    fn load_state(&mut self, registers: &[u8]) {
+        self.select = registers[0] != 0;
+        self.clock = registers[1] != 0;
+        self.data_in = registers[2] != 0;
+        self.data_out = registers[3] != 0;
+        self.write_enabled = registers[4] != 0;
+        self.command = u32::from_le_bytes([registers[5], registers[6], registers[7], registers[8]]);
+        self.command_bits = registers[9];
+        self.reading = if registers[10] != 0 { Some((registers[11], registers[12])) } else { None };
+    }
+}
+
+struct PocketCamera {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rom_bank_selection: u8,
+    ram_bank_selection: u8,
+    ram_enabled: bool,
+    registers_mapped: bool,
+    // 0xA000-0xA035 when mapped: control, exposure/gain settings, then the 4x4 dither matrix
+    registers: [u8; 0x36],
+    sensor: Box<dyn CameraSensor>,
+}
+impl MBC for PocketCamera {
+    fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000 ..= 0x3FFF => self.rom.get(addr as usize).cloned().unwrap_or(0xFF),
+            0x4000 ..= 0x7FFF => self.read_selected_rom_bank(addr),
+            0xA000 ..= 0xBFFF => if self.registers_mapped {
+                self.read_register(addr)
+            } else {
+                self.read_selected_ram_bank(addr)
+            },
+            _ => 0xFF, // the mmu only sends us addresses in these ranges
+        }
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000 ..= 0x1FFF => self.enable_ram(value),
+            0x2000 ..= 0x3FFF => self.rom_bank_selection = value & 0x3F,
+            0x4000 ..= 0x5FFF => self.select_ram_bank(value),
+            0x6000 ..= 0x7FFF => (),
+            0xA000 ..= 0xBFFF => if self.registers_mapped {
+                self.write_register(addr, value)
+            } else {
+                self.write_selected_ram_bank(addr, value)
+            },
+            _ => (), // the mmu only passes us addresses in this range
+        };
+    }
+
+    fn rom_len(&self) -> usize {
+        self.rom.len()
+    }
+
+    fn set_camera_sensor(&mut self, sensor: Box<dyn CameraSensor>) {
+        self.sensor = sensor;
+    }
+
+    fn rom_bank(&self) -> u16 { self.rom_bank_selection as u16 }
+    fn ram(&self) -> &[u8] { &self.ram }
+    fn ram_mut(&mut self) -> &mut [u8] { &mut self.ram }
+
+    // the bank registers, then the camera's own
+    fn save_state(&self) -> MapperState {
+        let mut registers = vec![self.rom_bank_selection, self.ram_bank_selection,
+                                 self.ram_enabled as u8, self.registers_mapped as u8];
+        registers.extend_from_slice(&self.registers);
+        MapperState { ram: self.ram.clone(), registers }
+    }
+    fn load_state(&mut self, state: &MapperState) -> Result<(), Error> {
+        state.check(self.ram.len(), 4 + self.registers.len())?;
+        self.ram.copy_from_slice(&state.ram);
+        self.rom_bank_selection = state.registers[0];
+        self.ram_bank_selection = state.registers[1];
+        self.ram_enabled = state.registers[2] != 0;
+        self.registers_mapped = state.registers[3] != 0;
+        self.registers.copy_from_slice(&state.registers[4..]);
+        Ok(())
+    }
+}
+impl PocketCamera {
+    // captured images are written to ram bank 0 from here, as 16x14 tiles of 2bpp data
+    const IMAGE_ADDR: usize = 0x0100;
+
+    fn new(header: &Header, rom: Vec<u8>) -> PocketCamera {
+        let ram = vec![0x0; header.ram_size as usize];
+        let rom_bank_selection = 0x01;
+        let ram_bank_selection = 0x00;
+        let ram_enabled = false;
+        let registers_mapped = false;
+        let registers = [0x00; 0x36];
+        let sensor = Box::new(BlankSensor);
+
+        PocketCamera {
+            rom, ram, rom_bank_selection, ram_bank_selection, ram_enabled,
+            registers_mapped, registers, sensor,
+        }
+    }
+
+    fn read_selected_rom_bank(&self, addr: u16) -> u8 {
+        let bank_addr = 0x4000 * (self.rom_bank_selection as u32) + (addr as u32 - 0x4000);
+        if (bank_addr as usize) < self.rom.len() {
+            self.rom[bank_addr as usize]
+        } else {
+            0xFF
+        }
+    }
+
+    fn read_selected_ram_bank(&self, addr: u16) -> u8 {
+        // the camera's ram can be read even when it isn't write-enabled
+        let bank_addr = 0x2000 * (self.ram_bank_selection as u32) + (addr as u32 - 0xA000);
+        if (bank_addr as usize) < self.ram.len() {
+            self.ram[bank_addr as usize]
+        } else {
+            0xFF
+        }
+    }
+
+    fn write_selected_ram_bank(&mut self, addr: u16, value: u8) {
+        if !self.ram_enabled { return }
+
+        let bank_addr = 0x2000 * (self.ram_bank_selection as u32) + (addr as u32 - 0xA000);
+        if (bank_addr as usize) < self.ram.len() {
+            self.ram[bank_addr as usize] = value
+        }
+    }
+
+    fn enable_ram(&mut self, value: u8) {
+        self.ram_enabled = match value & 0x0F {
+            0x0A => true,
+            _ => false
+        }
+    }
+
+    fn select_ram_bank(&mut self, value: u8) {
+        // bit 4 swaps the ram window out for the camera's registers
+        self.registers_mapped = value & 0x10 != 0;
+        self.ram_bank_selection = value & 0x0F;
+    }
+
+    fn read_register(&self, addr: u16) -> u8 {
+        // only the control register is readable, the rest are write-only
+        match (addr - 0xA000) & 0x7F {
+            0x00 => self.registers[0x00],
+            _ => 0x00,
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, value: u8) {
+        let register = ((addr - 0xA000) & 0x7F) as usize;
+        if register >= self.registers.len() {
+            return;
+        }
+
+        self.registers[register] = value;
+        if register == 0x00 && value & 0x01 != 0 {
+            // the capture happens instantly, so the busy bit is already clear
+            // by the time the camera software polls for it
+            self.capture();
+            self.registers[0x00] &= !0x01;
+        }
+    }
+
+    fn capture(&mut self) {
+        let image = self.sensor.capture();
+
+        // exposure time is a big-endian 16-bit value, 0x0800 is treated as a neutral exposure
+        let exposure = ((self.registers[0x02] as u32) << 8) | self.registers[0x03] as u32;
+        let invert = self.registers[0x04] & 0x08 != 0;
+        let dither_matrix = &self.registers[0x06..0x36];
+
+        let image_end = PocketCamera::IMAGE_ADDR + SENSOR_WIDTH * SENSOR_HEIGHT / 4;
+        if self.ram.len() < image_end {
+            return;
+        }
+        for byte in &mut self.ram[PocketCamera::IMAGE_ADDR..image_end] {
+            *byte = 0x00;
+        }
+
+        for y in 0..SENSOR_HEIGHT {
+            for x in 0..SENSOR_WIDTH {
+                let pixel = image.get(y * SENSOR_WIDTH + x).cloned().unwrap_or(0x00) as u32;
+                let mut value = core::cmp::min(pixel * exposure / 0x0800, 0xFF) as u8;
+                if invert {
+                    value = !value;
+                }
+
+                // each matrix cell has three ascending thresholds separating the four shades
+                let matrix_offset = ((y % 4) * 4 + (x % 4)) * 3;
+                let thresholds = &dither_matrix[matrix_offset..matrix_offset + 3];
+                let shade = if value < thresholds[0] {
+                    0b11
+                } else if value < thresholds[1] {
+                    0b10
+                } else if value < thresholds[2] {
+                    0b01
+                } else {
+                    0b00
+                };
+
+                let tile = (y / 8) * (SENSOR_WIDTH / 8) + (x / 8);
+                let tile_addr = PocketCamera::IMAGE_ADDR + tile * 16 + (y % 8) * 2;
+                let pixel_bit = 7 - (x % 8);
+                self.ram[tile_addr] |= (shade & 0b01) << pixel_bit;
+                self.ram[tile_addr + 1] |= ((shade & 0b10) >> 1) << pixel_bit;
+            }
+        }
+    }
+}
+
+// the logo every cartridge has to carry for the boot ROM to run it
+pub const NINTENDO_LOGO: [u8; 0x30] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83,
+    0x00, 0x0C, 0x00, 0x0D, 0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E,
+    0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99, 0xBB, 0xBB, 0x67, 0x63,
+    0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+// serializes as is for tools that want the parsed header, e.g. launchers building game lists
+#[cfg_attr(feature = "serialize", derive(Serialize))]
 pub struct Header {
     pub raw_entry_point: [u8; 0x4],         // 0x100-0x103
+    #[cfg_attr(feature = "serialize", serde(with = "crate::utils::big_array"))]
     pub raw_nintendo_logo: [u8; 0x30],      // 0x104-0x133
     pub raw_title: [u8; 0x10],              // 0x134-0x143
     pub raw_manufacturer_code: [u8; 0x4],   // 0x13f-0x142
@@ -273,7 +1477,17 @@ pub struct Header {
 }
 
 impl Header {
-    pub fn new(header_bytes: [u8; 0x50]) -> Result<Header, Box<dyn Error>> {
+    // parses the header from the start of bytes, which may be any length
+    pub fn from_slice(bytes: &[u8]) -> Result<Header, Error> {
+        if bytes.len() < 0x50 {
+            return Err(format!("header is too short ({} bytes)", bytes.len()).into());
+        }
+        let mut header_bytes = [0; 0x50];
+        header_bytes.copy_from_slice(&bytes[..0x50]);
+        Header::new(header_bytes)
+    }
+
+    pub fn new(header_bytes: [u8; 0x50]) -> Result<Header, Error> {
         let mut raw_entry_point = [0u8; 0x4];
         raw_entry_point.copy_from_slice(&header_bytes[0x0..0x4]);
         let mut raw_nintendo_logo = [0u8; 0x30];
@@ -301,11 +1515,11 @@ impl Header {
             _ => false
         };
         let title = match cgb_flag {
-            true => crate::utils::string::str_from_u8_null_utf8(&raw_title[..11])?.to_string(),
-            false => crate::utils::string::str_from_u8_null_utf8(&raw_title)?.to_string()
+            true => crate::utils::string::str_from_u8_null_utf8(&raw_title[..11]).map_err(Header::invalid_string)?.to_string(),
+            false => crate::utils::string::str_from_u8_null_utf8(&raw_title).map_err(Header::invalid_string)?.to_string()
         };
         let manufacturer_code = match cgb_flag {
-            true => crate::utils::string::str_from_u8_null_utf8(&raw_manufacturer_code) ?.to_string(),
+            true => crate::utils::string::str_from_u8_null_utf8(&raw_manufacturer_code).map_err(Header::invalid_string)?.to_string(),
             false => String::new()
         };
 
@@ -317,7 +1531,7 @@ impl Header {
         };
         let licensee_code = match sgb_flag {
             true => {
-                let l_c = crate::utils::string::str_from_u8_null_utf8(&raw_new_licensee_code)?;
+                let l_c = crate::utils::string::str_from_u8_null_utf8(&raw_new_licensee_code).map_err(Header::invalid_string)?;
                 Header::lookup_new_licensee_code(&l_c)?.to_string()
             },
             false => Header::lookup_old_licensee_code(&raw_old_licensee_code)?.to_string(),
@@ -347,7 +1561,7 @@ impl Header {
         };
         let version_number = raw_mask_rom_version_number;
         let header_checksum = raw_header_checksum;
-        let global_checksum = Cursor::new(raw_global_checksum).read_u16::<LittleEndian>()?;
+        let global_checksum = u16::from_le_bytes(raw_global_checksum);
 
         let calculated_header_checksum = Header::calculate_header_checksum(&header_bytes[0x34..0x4d]);
 
@@ -384,6 +1598,45 @@ impl Header {
         })
     }
 
+    pub fn has_battery(&self) -> bool {
+        use CartridgeType::*;
+        match self.cartridge_type {
+            ROM_RAM_BATTERY | MBC1_RAM_BATTERY | MBC2_BATTERY | MMM01_RAM_BATTERY |
+            MBC3_RAM_BATTERY | MBC3_TIMER_BATTERY | MBC3_TIMER_RAM_BATTERY |
+            MBC5_RAM_BATTERY | MBC5_RUMBLE_RAM_BATTERY | MBC7_SENSOR_RUMBLE_RAM_BATTERY |
+            POCKET_CAMERA | HuC3 | HuC1_RAM_BATTERY => true,
+            _ => false,
+        }
+    }
+
+    // whether the cartridge has a real time clock, emulated or not
+    pub fn has_rtc(&self) -> bool {
+        use CartridgeType::*;
+        matches!(self.cartridge_type, MBC3_TIMER_BATTERY | MBC3_TIMER_RAM_BATTERY | HuC3)
+    }
+
+    // the boot ROM locks up unless both of these pass
+    pub fn logo_valid(&self) -> bool {
+        self.raw_nintendo_logo[..] == NINTENDO_LOGO[..]
+    }
+
+    pub fn header_checksum_valid(&self) -> bool {
+        self.header_checksum == self.calculated_header_checksum
+    }
+
+    // where the 4 bytes at 0x100 hand off to. nearly every cartridge has "nop; jp a16" here,
+    // but some leave out the nop or use jr. None if it's anything else, like code that runs in place
+    pub fn entry_target(&self) -> Option<u16> {
+        let entry = &self.raw_entry_point;
+        let start = entry.iter().position(|&byte| byte != 0x00)?;
+        let addr = 0x100 + start as u16;
+        match entry[start..] {
+            [0xC3, low, high, ..] => Some(u16::from_le_bytes([low, high])),
+            [0x18, offset, ..] => Some((addr + 2).wrapping_add(offset as i8 as u16)),
+            _ => None,
+        }
+    }
+
     fn calculate_header_checksum(checksum_slice: &[u8]) -> u8 {
         //if checksum_slice.len() != 0x4c-0x34 + 1 {
         //    return Err(format!("header slice wrong length for checksum {}", checksum_slice.len()).into());
@@ -397,7 +1650,11 @@ impl Header {
         checksum.0
     }
 
-    fn lookup_new_licensee_code(licensee_code: &str) -> Result<&str, Box<dyn Error>> {
+    fn invalid_string(err: core::str::Utf8Error) -> Error {
+        format!("invalid header string: {}", err).into()
+    }
+
+    fn lookup_new_licensee_code(licensee_code: &str) -> Result<&str, Error> {
         match licensee_code {
             "00" => Ok("none"),
             "01" => Ok("Nintendo R&D1"),
@@ -465,7 +1722,7 @@ impl Header {
         }
     }
 
-    fn lookup_old_licensee_code<'a>(licensee_code: &'a u8) -> Result<&'a str, Box<dyn Error>> {
+    fn lookup_old_licensee_code<'a>(licensee_code: &'a u8) -> Result<&'a str, Error> {
         match licensee_code {
             &0x00 => Ok("none"),
             &0x01 => Ok("Nintendo"),
@@ -620,8 +1877,8 @@ impl Header {
     }
 }
 
-impl std::fmt::Debug for Header {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl fmt::Debug for Header {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, r#"
 title: {:?}
 manufacturer_code: {:?}
@@ -635,7 +1892,8 @@ japanese: {:?}
 version_number: {:?}
 header_checksum: {:?}
 calculated_checksum: {:?}
-global_checksum: {:?}"#,
+global_checksum: {:?}
+entry_target: {:x?}"#,
 self.title,
 self.manufacturer_code,
 self.cgb_flag,
@@ -648,6 +1906,7 @@ self.japanese,
 self.version_number,
 self.header_checksum,
 self.calculated_header_checksum,
-self.global_checksum)
+self.global_checksum,
+self.entry_target())
     }
 }
@@ -0,0 +1,78 @@
+// named cheats from libretro's .cht files, to switch on and off rather than type in. a game's
+// file is found in a directory by its CRC32, e.g. 46df91ad.cht, or by its No-Intro name as
+// libretro-database names them, e.g. "Tetris (World) (Rev A).cht"
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::gameboy::cheat::CheatCode;
+use crate::romdb::crc32;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cheat {
+    pub name: String,
+    pub codes: Vec<CheatCode>,
+    pub enabled: bool,
+}
+
+// the codes of every cheat that's switched on
+pub fn enabled_codes(cheats: &[Cheat]) -> Vec<CheatCode> {
+    cheats.iter().filter(|cheat| cheat.enabled).flat_map(|cheat| cheat.codes.iter().copied()).collect()
+}
+
+// the rom's .cht file in directory, if there is one. name is the game's No-Intro name, if it's known
+pub fn find(directory: &Path, rom: &[u8], name: Option<&str>) -> Option<PathBuf> {
+    let by_crc = directory.join(format!("{:08x}.cht", crc32(rom)));
+    let by_name = name.map(|name| directory.join(format!("{}.cht", name)));
+    Some(by_crc).into_iter().chain(by_name).find(|path| path.is_file())
+}
+
+pub fn load(path: &Path) -> Result<Vec<Cheat>, Box<dyn Error>> {
+    parse(&fs::read_to_string(path)?)
+}
+
+// cheatN_desc, cheatN_code and cheatN_enable for N up to cheats, one key = value to a line.
+// a cheat's codes are joined with +
+pub fn parse(cht: &str) -> Result<Vec<Cheat>, Box<dyn Error>> {
+    let mut count = None;
+    let mut cheats: Vec<(Option<String>, Option<String>, bool)> = Vec::new();
+
+    for line in cht.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        let (key, value) = line.split_once('=').ok_or_else(|| format!("expected key = value, got {}", line))?;
+        let key = key.trim();
+        let value = value.trim().trim_matches('"');
+
+        if key == "cheats" {
+            count = Some(value.parse::<usize>().map_err(|e| format!("bad cheat count {}: {}", value, e))?);
+            continue;
+        }
+        let (index, field) = match key.strip_prefix("cheat").and_then(|rest| rest.split_once('_')) {
+            Some((index, field)) => match index.parse::<usize>() {
+                Ok(index) => (index, field),
+                Err(_) => continue,
+            },
+            None => continue,
+        };
+        if cheats.len() <= index {
+            cheats.resize(index + 1, (None, None, false));
+        }
+        match field {
+            "desc" => cheats[index].0 = Some(value.to_string()),
+            "code" => cheats[index].1 = Some(value.to_string()),
+            "enable" => cheats[index].2 = value == "true",
+            _ => (),
+        }
+    }
+
+    let count = count.ok_or("no cheats = N line in .cht file")?;
+    cheats.resize(count, (None, None, false));
+    cheats.into_iter().enumerate().map(|(index, (name, code, enabled))| {
+        let name = name.unwrap_or_else(|| format!("Cheat {}", index + 1));
+        let code = code.ok_or_else(|| format!("{} has no code", name))?;
+        let codes = code.split('+').map(str::trim).filter(|code| !code.is_empty())
+            .map(|code| code.parse::<CheatCode>().map_err(|e| format!("{}: {}", name, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Cheat { name, codes, enabled })
+    }).collect()
+}
@@ -0,0 +1,101 @@
+// the time a cartridge's real time clock reads, like the MBC3's in Pokémon Gold and Silver.
+// swapping the source makes tests deterministic, and lets players skip ahead to a game's daily events
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+pub trait ClockSource: Send {
+    // seconds since the unix epoch
+    fn now(&mut self) -> u64;
+
+    // the actual time, for stamping battery saves. only differs from now for sources that
+    // run fast or slow
+    fn wall_clock(&mut self) -> u64 {
+        self.now()
+    }
+}
+
+// the host's clock, what a cartridge gets if nothing else is plugged in
+#[cfg(feature = "std")]
+pub struct SystemClock;
+#[cfg(feature = "std")]
+impl ClockSource for SystemClock {
+    fn now(&mut self) -> u64 {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs())
+    }
+}
+
+// what a cartridge's clock reads until something else is plugged in. without std there's no
+// host clock to ask, so it stays stopped
+#[cfg(feature = "std")]
+pub(crate) fn default_source() -> Box<dyn ClockSource> {
+    Box::new(SystemClock)
+}
+#[cfg(not(feature = "std"))]
+pub(crate) fn default_source() -> Box<dyn ClockSource> {
+    Box::new(FixedClock(0))
+}
+
+// stopped at one moment
+pub struct FixedClock(pub u64);
+impl ClockSource for FixedClock {
+    fn now(&mut self) -> u64 {
+        self.0
+    }
+}
+
+// starts at start and runs scale times as fast as source, 24.0 for a day an hour
+pub struct ScaledClock<C: ClockSource> {
+    source: C,
+    start: u64,
+    // what source read when this was made
+    from: u64,
+    scale: f64,
+}
+
+impl<C: ClockSource> ScaledClock<C> {
+    pub fn new(mut source: C, start: u64, scale: f64) -> ScaledClock<C> {
+        let from = source.now();
+        ScaledClock { source, start, from, scale }
+    }
+}
+
+impl<C: ClockSource> ClockSource for ScaledClock<C> {
+    fn now(&mut self) -> u64 {
+        let elapsed = self.source.now().saturating_sub(self.from);
+        self.start + (elapsed as f64 * self.scale) as u64
+    }
+
+    fn wall_clock(&mut self) -> u64 {
+        self.source.wall_clock()
+    }
+}
+
+// only moves when it's told to. clones share the time, so one can stay behind to advance
+// the one given to the GameBoy
+#[derive(Clone, Default)]
+pub struct ManualClock {
+    now: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    pub fn new(now: u64) -> ManualClock {
+        ManualClock { now: Arc::new(AtomicU64::new(now)) }
+    }
+
+    pub fn set(&self, now: u64) {
+        self.now.store(now, Ordering::Relaxed);
+    }
+
+    pub fn advance(&self, seconds: u64) {
+        self.now.fetch_add(seconds, Ordering::Relaxed);
+    }
+}
+
+impl ClockSource for ManualClock {
+    fn now(&mut self) -> u64 {
+        self.now.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,221 @@
+// finds the first point at which two runs of a rom disagree, for debugging accuracy changes.
+// either two cores run in lockstep, or one core is checked against a reference trace log
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::io::BufRead;
+
+use crate::gameboy::GameBoy;
+use crate::gameboy::debugger::BankedAddress;
+use crate::gameboy::registers::Registers;
+
+// lines of each side's history shown with a divergence, to see what led up to it
+const CONTEXT_LINES: usize = 8;
+
+pub struct Divergence {
+    // instructions run before the divergence (the reference log line number, for logs)
+    pub instruction: u64,
+    pub cycle: u128,
+    // where the instruction that diverged was (or for lockstep, where the left core is after it)
+    pub pc: BankedAddress,
+    pub differences: Vec<String>,
+    // what led up to it: the reference log's lines before it, and the instructions we ran
+    pub context: Vec<String>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "diverged after {} instructions, at cycle {}, pc {}", self.instruction, self.cycle, self.pc)?;
+        for difference in self.differences.iter() {
+            writeln!(f, "  {}", difference)?;
+        }
+        for line in self.context.iter() {
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+fn register_differences(left: &Registers, right: &Registers) -> Vec<String> {
+    let pairs = [
+        ("A", left.a as u16, right.a as u16), ("F", left.f.bits() as u16, right.f.bits() as u16),
+        ("B", left.b as u16, right.b as u16), ("C", left.c as u16, right.c as u16),
+        ("D", left.d as u16, right.d as u16), ("E", left.e as u16, right.e as u16),
+        ("H", left.h as u16, right.h as u16), ("L", left.l as u16, right.l as u16),
+        ("SP", left.sp, right.sp), ("PC", left.pc, right.pc),
+    ];
+
+    pairs.iter()
+        .filter(|&&(_, l, r)| l != r)
+        .map(|&(name, l, r)| format!("{}: {:#06x} vs {:#06x}", name, l, r))
+        .collect()
+}
+
+// steps both cores until max_steps have run, comparing registers and cycle counts after every step,
+// and the whole address space every memory_interval steps (0 to never compare memory)
+pub fn lockstep(left: &mut GameBoy, right: &mut GameBoy, max_steps: u64, memory_interval: u64)
+        -> Result<Option<Divergence>, Box<dyn Error>> {
+    for step in 1..=max_steps {
+        left.step()?;
+        right.step()?;
+
+        let mut differences = register_differences(&left.registers(), &right.registers());
+        if left.elapsed_cycles() != right.elapsed_cycles() {
+            differences.push(format!("cycles: {} vs {}", left.elapsed_cycles(), right.elapsed_cycles()));
+        }
+        if memory_interval != 0 && step % memory_interval == 0 {
+            for addr in 0x0000..=0xFFFF {
+                let (l, r) = (left.peek(addr), right.peek(addr));
+                if l != r {
+                    differences.push(format!("[{:#06x}]: {:#04x} vs {:#04x}", addr, l, r));
+                }
+            }
+        }
+
+        if !differences.is_empty() {
+            let mut context = recent_instructions(left, "left core's instructions");
+            context.extend(recent_instructions(right, "right core's instructions"));
+            return Ok(Some(Divergence {
+                instruction: left.elapsed_instructions(),
+                cycle: left.elapsed_cycles(),
+                pc: left.pc(),
+                differences,
+                context,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+// checks the state before every instruction against a reference log in the gameboy-doctor format:
+// `A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02`
+// plus any memory the log's emulator was told to watch, as `[C000]:12` fields.
+// (differences are reported as ours vs the log's)
+pub fn against_log<R: BufRead>(gameboy: &mut GameBoy, log: R) -> Result<Option<Divergence>, Box<dyn Error>> {
+    let mut previous_lines = VecDeque::with_capacity(CONTEXT_LINES);
+
+    for (line_number, line) in log.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let watched = line.split_whitespace()
+            .filter_map(|field| memory_field(field))
+            .map(|(addr, _)| u16::from_str_radix(addr, 16)
+                .map_err(|e| format!("line {}: bad address {}: {}", line_number + 1, addr, e)))
+            .collect::<Result<Vec<u16>, String>>()?;
+
+        // interrupt dispatch and halted cycles aren't logged, so step until an instruction runs,
+        // keeping the state from just before it
+        let (registers, pc, cycle, pc_memory, memory) = loop {
+            let instructions = gameboy.elapsed_instructions();
+            let registers = gameboy.registers();
+            let pc = gameboy.pc();
+            let cycle = gameboy.elapsed_cycles();
+            let mut pc_memory = [0x00; 4];
+            for (offset, byte) in pc_memory.iter_mut().enumerate() {
+                *byte = gameboy.peek(registers.pc.wrapping_add(offset as u16));
+            }
+            let memory: Vec<u8> = watched.iter().map(|&addr| gameboy.peek(addr)).collect();
+
+            gameboy.step()?;
+            if gameboy.elapsed_instructions() != instructions {
+                break (registers, pc, cycle, pc_memory, memory);
+            }
+        };
+
+        let mut differences = Vec::new();
+        let mut memory = watched.iter().zip(memory);
+        for field in line.split_whitespace() {
+            if let Some((_, value)) = memory_field(field) {
+                let (&addr, actual) = memory.next().expect("a byte was read for every memory field");
+                let expected = u8::from_str_radix(value, 16)
+                    .map_err(|e| format!("line {}: bad value for [{:04X}]: {}", line_number + 1, addr, e))?;
+                if actual != expected {
+                    differences.push(format!("[{:#06x}]: {:#04x} vs {:#04x}", addr, actual, expected));
+                }
+                continue;
+            }
+
+            let mut parts = field.splitn(2, ':');
+            let (name, value) = match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => (name, value),
+                _ => return Err(format!("line {}: malformed field {}", line_number + 1, field).into()),
+            };
+
+            if name == "PCMEM" {
+                for (offset, byte) in value.split(',').enumerate().take(pc_memory.len()) {
+                    let expected = u8::from_str_radix(byte, 16)
+                        .map_err(|e| format!("line {}: bad PCMEM byte {}: {}", line_number + 1, byte, e))?;
+                    let addr = registers.pc.wrapping_add(offset as u16);
+                    let actual = pc_memory[offset];
+                    if actual != expected {
+                        differences.push(format!("[{:#06x}]: {:#04x} vs {:#04x}", addr, actual, expected));
+                    }
+                }
+                continue;
+            }
+
+            let actual = match name {
+                "A" => registers.a as u16,
+                "F" => registers.f.bits() as u16,
+                "B" => registers.b as u16,
+                "C" => registers.c as u16,
+                "D" => registers.d as u16,
+                "E" => registers.e as u16,
+                "H" => registers.h as u16,
+                "L" => registers.l as u16,
+                "SP" => registers.sp,
+                "PC" => registers.pc,
+                _ => continue, // fields we don't track, e.g. LY from some loggers
+            };
+            let expected = u16::from_str_radix(value, 16)
+                .map_err(|e| format!("line {}: bad value for {}: {}", line_number + 1, name, e))?;
+            if actual != expected {
+                differences.push(format!("{}: {:#06x} vs {:#06x}", name, actual, expected));
+            }
+        }
+
+        if !differences.is_empty() {
+            let mut context = vec!["reference log before it:".to_string()];
+            context.extend(previous_lines.into_iter()
+                .map(|(number, line)| format!("  {:>6}: {}", number, line)));
+            context.push(format!("  {:>6}: {}", line_number + 1, line));
+            context.extend(recent_instructions(gameboy, "our instructions"));
+            return Ok(Some(Divergence {
+                instruction: line_number as u64,
+                cycle,
+                pc,
+                differences,
+                context,
+            }));
+        }
+
+        if previous_lines.len() == CONTEXT_LINES {
+            previous_lines.pop_front();
+        }
+        previous_lines.push_back((line_number + 1, line));
+    }
+
+    Ok(None)
+}
+
+// the last few instructions a core ran, the one that diverged last
+fn recent_instructions(gameboy: &GameBoy, name: &str) -> Vec<String> {
+    let trace: Vec<_> = gameboy.trace().iter().collect();
+    let recent = &trace[trace.len().saturating_sub(CONTEXT_LINES + 1)..];
+
+    let mut lines = vec![format!("{} before it:", name)];
+    lines.extend(recent.iter().map(|entry| format!("  {}", entry)));
+    lines
+}
+
+// the address and value of a `[C000]:12` field
+fn memory_field(field: &str) -> Option<(&str, &str)> {
+    let field = field.strip_prefix('[')?;
+    let close = field.find("]:")?;
+    Some((&field[..close], &field[close + 2..]))
+}
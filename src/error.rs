@@ -0,0 +1,84 @@
+// the error type used throughout the core. it's the same type with or without std,
+// and only implements std's Error trait when std is there to have one
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::fmt;
+
+use crate::cartridge::CartridgeType;
+use crate::gameboy::fault::CoreFault;
+
+#[derive(Debug)]
+pub enum Error {
+    // the cpu couldn't carry on, with the instructions that led up to it
+    Fault(Box<CoreFault>),
+    // a real cartridge type, but not one with a mapper we emulate
+    UnsupportedMapper(CartridgeType),
+    // anything else, already worded for the user
+    Message(String),
+}
+
+impl Error {
+    pub fn fault(&self) -> Option<&CoreFault> {
+        match self {
+            Error::Fault(fault) => Some(fault),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Fault(fault) => fault.fmt(f),
+            Error::UnsupportedMapper(cartridge_type) => write!(f, "cartridge type {:?} is not yet implemented", cartridge_type),
+            Error::Message(message) => f.write_str(message),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Fault(fault) => Some(&**fault),
+            _ => None,
+        }
+    }
+}
+
+impl From<CoreFault> for Error {
+    fn from(fault: CoreFault) -> Error {
+        Error::Fault(Box::new(fault))
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Error {
+        Error::Message(message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Error {
+        Error::Message(message.into())
+    }
+}
+
+// std-only helpers (frame dumps, PNGs and the like) hand back boxed errors, which may well be one of ours
+#[cfg(feature = "std")]
+impl From<Box<dyn std::error::Error>> for Error {
+    fn from(err: Box<dyn std::error::Error>) -> Error {
+        match err.downcast::<Error>() {
+            Ok(err) => *err,
+            Err(err) => Error::Message(err.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Error {
+        Error::Message(err.to_string())
+    }
+}
@@ -0,0 +1,155 @@
+// stands in for the boot ROM, which we don't ship: draws the cartridge's logo into VRAM,
+// scrolls it down the screen, then checks the header the way the boot ROM does.
+// the timing is only roughly the boot ROM's
+
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+use crate::cartridge::Header;
+use crate::gameboy::mmu::MMU;
+use crate::gameboy::registers::{Flags, Registers};
+
+// the boot ROM scrolls the logo from SCY 0x64 down to 0
+const SCROLL_START: u8 = 0x64;
+// frames the logo sits still for after scrolling, while the boot ROM plays its chime
+const HOLD_FRAMES: u32 = 60;
+
+// the ® drawn after the logo, which comes from the boot ROM rather than the cartridge
+const REGISTERED_MARK: [u8; 8] = [0x3C, 0x42, 0xB9, 0xA5, 0xB9, 0xA5, 0x42, 0x3C];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BootMode {
+    // start the cartridge straight away, as if the boot ROM had already run
+    Skip,
+    // show the logo, then start the cartridge whether or not its header would pass
+    Simulate,
+    // show the logo, and lock up like hardware does if the header fails the checks
+    Strict,
+}
+
+// the hardware the boot ROM ran on, which leaves the registers in a different state for each.
+// games check A in particular to tell them apart
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Model {
+    DMG,
+    // the Game Boy Pocket and Light
+    MGB,
+    // a Game Boy Color running a DMG game
+    CGB,
+}
+
+impl Model {
+    // the CPU's registers as the boot ROM hands over to the cartridge
+    pub fn registers(self, header: &Header) -> Registers {
+        // the DMG and MGB boot ROMs leave H and C set unless the header checksum is 0
+        let flags = match header.header_checksum {
+            0 => Flags::ZERO,
+            _ => Flags::ZERO | Flags::HALFCARRY | Flags::CARRY,
+        };
+        let dmg = Registers::new();
+        match self {
+            Model::DMG => Registers { f: flags, ..dmg },
+            Model::MGB => Registers { a: 0xFF, f: flags, ..dmg },
+            Model::CGB => {
+                // for Nintendo's own games the CGB boot ROM sums the title to pick a palette,
+                // and that sum's left in B. HL points at the logo for two of them
+                let nintendo = header.raw_old_licensee_code == 0x01
+                    || (header.raw_old_licensee_code == 0x33 && header.raw_new_licensee_code == *b"01");
+                let b = if nintendo { header.raw_title.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) } else { 0x00 };
+                let (h, l) = if nintendo && (b == 0x43 || b == 0x58) { (0x99, 0x1A) } else { (0x00, 0x7C) };
+                Registers { a: 0x11, f: Flags::ZERO, b, c: 0x00, d: 0x00, e: 0x08, h, l, ..dmg }
+            },
+        }
+    }
+
+    // DIV as the boot ROM hands over. the CGB's depends on how long it spent on the header,
+    // so it's left alone there
+    pub fn divider(self) -> Option<u8> {
+        match self {
+            Model::DMG | Model::MGB => Some(0xAB),
+            Model::CGB => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct BootSequence {
+    frame: u32,
+    lock_up: bool,
+}
+
+impl BootSequence {
+    pub fn new(mode: BootMode, mmu: &mut MMU) -> Option<BootSequence> {
+        let header = mmu.header();
+        let lock_up = match mode {
+            BootMode::Skip => return None,
+            BootMode::Simulate => false,
+            BootMode::Strict => !(header.logo_valid() && header.header_checksum_valid()),
+        };
+
+        // the boot ROM draws whatever is in the header, so a bad logo shows up garbled
+        let logo = header.raw_nintendo_logo;
+        BootSequence::draw_logo(&logo, mmu);
+
+        Some(BootSequence { frame: 0, lock_up })
+    }
+
+    // never true when strict mode caught a bad header, the logo just stays up like on hardware
+    pub fn finished(&self) -> bool {
+        !self.lock_up && self.frame >= SCROLL_START as u32 + HOLD_FRAMES
+    }
+
+    // runs a machine cycle of the boot sequence, returns true at the end of a frame
+    pub fn step(&mut self, mmu: &mut MMU) -> bool {
+        mmu.spin();
+        if !mmu.lcd.vblank_reached() {
+            return false;
+        }
+
+        self.frame = self.frame.saturating_add(1);
+        if self.frame <= SCROLL_START as u32 {
+            let scroll = SCROLL_START - self.frame as u8;
+            mmu.lcd.write_register(0xFF42, scroll, &mut mmu.interrupt);
+        }
+        true
+    }
+
+    fn draw_logo(logo: &[u8; 0x30], mmu: &mut MMU) {
+        // each nibble of the logo is a row of 4 pixels, doubled in both directions,
+        // so every 2 bytes make one 8x8 tile. tiles start at 1, leaving tile 0 blank
+        let mut addr = 0x0010;
+        for byte in logo.iter() {
+            for nibble in [byte >> 4, byte & 0x0F].iter() {
+                let row = DOUBLED_BITS[*nibble as usize];
+                for _ in 0..2 {
+                    mmu.lcd.poke_vram(addr, row);
+                    mmu.lcd.poke_vram(addr + 1, 0x00);
+                    addr += 2;
+                }
+            }
+        }
+        for row in REGISTERED_MARK.iter() {
+            mmu.lcd.poke_vram(addr, *row);
+            mmu.lcd.poke_vram(addr + 1, 0x00);
+            addr += 2;
+        }
+
+        // two rows of 12 tiles in the middle of the map, with the ® at the end of the top row
+        for tile in 0..12u8 {
+            mmu.lcd.poke_vram(0x1904 + tile as u16, tile + 1);
+            mmu.lcd.poke_vram(0x1924 + tile as u16, tile + 13);
+        }
+        mmu.lcd.poke_vram(0x1910, 25);
+
+        mmu.lcd.write_register(0xFF42, SCROLL_START, &mut mmu.interrupt);
+        mmu.lcd.write_register(0xFF47, 0xFC, &mut mmu.interrupt);
+        mmu.lcd.write_register(0xFF40, 0x91, &mut mmu.interrupt);
+    }
+}
+
+// each of 4 bits doubled up into 8
+const DOUBLED_BITS: [u8; 16] = [
+    0x00, 0x03, 0x0C, 0x0F, 0x30, 0x33, 0x3C, 0x3F,
+    0xC0, 0xC3, 0xCC, 0xCF, 0xF0, 0xF3, 0xFC, 0xFF,
+];
@@ -0,0 +1,113 @@
+// everything the cpu needs from the memory it's wired to. the MMU is the real thing, but any
+// type can stand in for it: flat RAM for testing instructions, or a wrapper around the MMU that
+// counts or fuzzes accesses. the cpu is generic over it, so the MMU's calls are still direct
+
+use alloc::vec::Vec;
+
+use crate::gameboy::fault::BusFault;
+
+pub trait Bus {
+    // each of these takes a machine cycle
+    fn read_u8(&mut self, addr: u16) -> u8;
+    // an opcode or operand, rather than data
+    fn fetch_u8(&mut self, addr: u16) -> u8;
+    fn write_u8(&mut self, addr: u16, value: u8);
+    // a machine cycle without touching memory
+    fn spin(&mut self);
+
+    // cycles since power on, for faults
+    fn get_cycles(&self) -> u128;
+
+    // IF and IE
+    fn interrupt_flag(&self) -> u8;
+    fn set_interrupt_flag(&mut self, value: u8);
+    fn interrupt_enable(&self) -> u8;
+
+    // interrupts both requested and enabled
+    fn pending_interrupts(&self) -> u8 {
+        self.interrupt_flag() & self.interrupt_enable() & 0x1F
+    }
+
+    // the ROM bank mapped at 0x4000-0x7FFF, for traces
+    fn rom_bank(&self) -> u16 { 1 }
+
+    // a bad access since the last call, which ends the instruction with a fault
+    fn take_fault(&mut self) -> Option<BusFault> { None }
+}
+
+// one machine cycle of bus activity, as recorded by FlatMemory
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BusActivity {
+    Read(u16, u8),
+    Write(u16, u8),
+    Idle,
+}
+
+// 64KiB of plain RAM with no cartridge or peripherals behind it,
+// for driving the cpu in isolation (e.g. single instruction tests)
+pub struct FlatMemory {
+    pub ram: Vec<u8>,
+    pub activity: Vec<BusActivity>,
+    cycles: u128,
+}
+
+impl FlatMemory {
+    pub fn new() -> FlatMemory {
+        FlatMemory::with_ram(&[])
+    }
+
+    // starting with the given bytes from 0x0000, anything past 0xFFFF is ignored
+    pub fn with_ram(ram: &[u8]) -> FlatMemory {
+        let mut memory = FlatMemory {
+            ram: vec![0x00; 0x10000],
+            activity: Vec::new(),
+            cycles: 0,
+        };
+        let len = core::cmp::min(ram.len(), memory.ram.len());
+        memory.ram[..len].copy_from_slice(&ram[..len]);
+        memory
+    }
+
+    fn tick(&mut self, activity: BusActivity) {
+        self.activity.push(activity);
+        self.cycles += 4;
+    }
+}
+
+impl Bus for FlatMemory {
+    fn read_u8(&mut self, addr: u16) -> u8 {
+        let value = self.ram[addr as usize];
+        self.tick(BusActivity::Read(addr, value));
+        value
+    }
+
+    fn fetch_u8(&mut self, addr: u16) -> u8 {
+        self.read_u8(addr)
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        self.ram[addr as usize] = value;
+        self.tick(BusActivity::Write(addr, value));
+    }
+
+    fn spin(&mut self) {
+        self.tick(BusActivity::Idle);
+    }
+
+    fn get_cycles(&self) -> u128 {
+        self.cycles
+    }
+
+    // IF and IE are just their bytes of ram, same as any other address
+    fn interrupt_flag(&self) -> u8 {
+        self.ram[0xFF0F]
+    }
+
+    fn set_interrupt_flag(&mut self, value: u8) {
+        self.ram[0xFF0F] = value;
+    }
+
+    fn interrupt_enable(&self) -> u8 {
+        self.ram[0xFFFF]
+    }
+}
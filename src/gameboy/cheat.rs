@@ -0,0 +1,72 @@
+// Game Genie and GameShark codes. a Game Genie sits between the cartridge and the console and
+// changes what's read from ROM, a GameShark writes its values into RAM every frame
+
+use alloc::format;
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+use crate::Error;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheatCode {
+    // reads from address give value instead, but only where the ROM has compare there if there is
+    // one, which keeps the patch to the right bank
+    GameGenie { address: u16, value: u8, compare: Option<u8> },
+    // value is written to address at the end of every frame
+    GameShark { address: u16, value: u8 },
+}
+
+impl CheatCode {
+    // what a read of rom gives with this applied, rom being what the cartridge had there
+    pub fn patch_rom(&self, addr: u16, rom: u8) -> u8 {
+        match *self {
+            CheatCode::GameGenie { address, value, compare } if address == addr => match compare {
+                Some(compare) if compare != rom => rom,
+                _ => value,
+            },
+            _ => rom,
+        }
+    }
+}
+
+// ABC-DEF or ABC-DEF-GHI for a Game Genie, where AB is the value, FCDE the address with F
+// inverted, and GI the value to compare against, rotated and scrambled. a GameShark's is
+// TTVVLLHH: a type, the value, then the address low byte first
+impl FromStr for CheatCode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<CheatCode, Error> {
+        let digits: Option<Vec<u8>> = s.chars().filter(|&c| c != '-')
+            .map(|c| c.to_digit(16).map(|digit| digit as u8))
+            .collect();
+        let digits = digits.ok_or_else(|| format!("bad cheat code {}, codes are hex", s))?;
+
+        match (s.contains('-'), digits.as_slice()) {
+            (true, [a, b, c, d, e, f, rest @ ..]) if rest.is_empty() || rest.len() == 3 => {
+                let address = (((*f ^ 0xF) as u16) << 12) | ((*c as u16) << 8) | ((*d as u16) << 4) | *e as u16;
+                if address >= 0x8000 {
+                    return Err(format!("bad Game Genie code {}, it patches {:04x} which isn't ROM", s, address).into());
+                }
+                let compare = match rest {
+                    [g, _, i] => Some(((g << 4) | i).rotate_right(2) ^ 0xBA),
+                    _ => None,
+                };
+                Ok(CheatCode::GameGenie { address, value: (a << 4) | b, compare })
+            },
+            (false, [t1, t2, v1, v2, l1, l2, h1, h2]) => {
+                // 01 is the usual write, 00 the same on some carts. the 8x and 9x types pick
+                // a CGB WRAM or SRAM bank, which there's no CGB to have
+                let kind = (t1 << 4) | t2;
+                if kind > 0x01 {
+                    return Err(format!("bad GameShark code {}, type {:02x} isn't supported", s, kind).into());
+                }
+                let address = ((((h1 << 4) | h2) as u16) << 8) | ((l1 << 4) | l2) as u16;
+                if address < 0x8000 {
+                    return Err(format!("bad GameShark code {}, it writes to {:04x} which is ROM", s, address).into());
+                }
+                Ok(CheatCode::GameShark { address, value: (v1 << 4) | v2 })
+            },
+            _ => Err(format!("bad cheat code {}, expected ABC-DEF(-GHI) or 8 hex digits", s).into()),
+        }
+    }
+}
@@ -0,0 +1,715 @@
+// breakpoints and the like. addresses in 0x4000-0x7FFF mean different code depending on
+// which ROM bank is mapped there, so everything here goes by bank:address
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem;
+use core::str::FromStr;
+
+use crate::Error;
+use crate::gameboy::disasm::Symbols;
+use crate::gameboy::interrupt::Interrupt;
+use crate::gameboy::registers::Registers;
+
+// how many interrupt events are kept, older ones are dropped as new ones come in
+pub const INTERRUPT_LOG_LENGTH: usize = 1024;
+// and OAM DMAs, most games do one a frame
+pub const DMA_LOG_LENGTH: usize = 1024;
+
+// WRAM and HRAM, where games keep their variables
+const SNAPSHOT_RANGES: [(u16, u16); 2] = [(0xC000, 0xDFFF), (0xFF80, 0xFFFE)];
+
+// an address along with the ROM bank it was in, 0 for anything outside the switchable bank
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BankedAddress {
+    pub bank: u16,
+    pub addr: u16,
+}
+
+impl BankedAddress {
+    // rom_bank is the bank currently mapped at 0x4000-0x7FFF
+    pub fn new(addr: u16, rom_bank: u16) -> BankedAddress {
+        let bank = match addr {
+            0x4000 ..= 0x7FFF => rom_bank,
+            _ => 0,
+        };
+        BankedAddress { bank, addr }
+    }
+}
+
+impl fmt::Display for BankedAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02x}:{:04x}", self.bank, self.addr)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Breakpoint {
+    // None breaks at the address whichever bank is mapped
+    pub bank: Option<u16>,
+    pub addr: u16,
+}
+
+impl Breakpoint {
+    pub fn matches(&self, pc: BankedAddress) -> bool {
+        self.addr == pc.addr && match self.bank {
+            Some(bank) => bank == pc.bank,
+            None => true,
+        }
+    }
+}
+
+// "bank:address" or just "address", both in hex, e.g. 1f:4a00 or 0150
+impl FromStr for Breakpoint {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Breakpoint, Error> {
+        let parse = |hex: &str| u16::from_str_radix(hex.trim_start_matches("0x"), 16)
+            .map_err(|e| format!("bad breakpoint {}: {}", s, e));
+
+        let mut parts = s.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(bank), Some(addr)) => Ok(Breakpoint { bank: Some(parse(bank)?), addr: parse(addr)? }),
+            (Some(addr), None) => Ok(Breakpoint { bank: None, addr: parse(addr)? }),
+            _ => Err(format!("bad breakpoint {}", s).into()),
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.bank {
+            Some(bank) => write!(f, "{:02x}:{:04x}", bank, self.addr),
+            None => write!(f, "{:04x}", self.addr),
+        }
+    }
+}
+
+// which instructions go in the trace log, by where they are
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceFilter {
+    // None matches the addresses whichever bank is mapped
+    pub bank: Option<u16>,
+    pub start: u16,
+    pub end: u16,
+}
+
+impl TraceFilter {
+    pub fn matches(&self, pc: BankedAddress) -> bool {
+        (self.start..=self.end).contains(&pc.addr) && match self.bank {
+            Some(bank) => bank == pc.bank,
+            None => true,
+        }
+    }
+}
+
+// "bank:start-end" or just "start-end", all in hex, e.g. 01:4000-7fff or 0150-01ff
+impl FromStr for TraceFilter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<TraceFilter, Error> {
+        let parse = |hex: &str| u16::from_str_radix(hex.trim_start_matches("0x"), 16)
+            .map_err(|e| format!("bad trace range {}: {}", s, e));
+
+        let (bank, range) = match s.split_once(':') {
+            Some((bank, range)) => (Some(parse(bank)?), range),
+            None => (None, s),
+        };
+        match range.split_once('-') {
+            Some((start, end)) => Ok(TraceFilter { bank, start: parse(start)?, end: parse(end)? }),
+            None => Err(format!("bad trace range {}, expected start-end", s).into()),
+        }
+    }
+}
+
+// where a one-shot `until` stops the GameBoy, to skip ahead without a breakpoint to clear up after
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Until {
+    // before running the instruction there
+    Address(Breakpoint),
+    // once the frame's done
+    VBlank,
+    // the next time LY gets to it
+    Line(u8),
+}
+
+// "vblank", "line N" with N in decimal, or a breakpoint's "[bank:]address"
+impl FromStr for Until {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Until, Error> {
+        match s.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["vblank"] => Ok(Until::VBlank),
+            ["line", line] => line.parse().map(Until::Line)
+                .map_err(|e| format!("bad line {}: {}", line, e).into()),
+            [address] => address.parse().map(Until::Address),
+            _ => Err(format!("bad until {}", s).into()),
+        }
+    }
+}
+
+impl fmt::Display for Until {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Until::Address(breakpoint) => write!(f, "{}", breakpoint),
+            Until::VBlank => write!(f, "vblank"),
+            Until::Line(line) => write!(f, "line {}", line),
+        }
+    }
+}
+
+// why GameBoy::run_until_break stopped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    VBlank,
+    Breakpoint(BankedAddress),
+    // the interrupt's been dispatched, so pc's at its vector
+    Interrupt(Dispatch),
+    Until(Until),
+}
+
+// the cpu jumping to an interrupt's vector
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Dispatch {
+    pub interrupt: Interrupt,
+    // IF and IE as they were, which between them picked this interrupt
+    pub flags: u8,
+    pub enabled: u8,
+    // where it returns to
+    pub pc: BankedAddress,
+}
+
+impl fmt::Display for Dispatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} dispatched from {}, IF {:02x} IE {:02x}", self.interrupt.name(), self.pc, self.flags, self.enabled)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InterruptEventKind {
+    // the interrupt's bit in IF was set, by hardware or a write
+    Requested,
+    // the cpu jumped to the interrupt's vector
+    Dispatched,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InterruptEvent {
+    pub kind: InterruptEventKind,
+    pub interrupt: Interrupt,
+    pub cycle: u128,
+    pub ly: u8,
+    // for requests, the instruction that was running. for dispatches, where it returns to
+    pub pc: BankedAddress,
+}
+
+impl fmt::Display for InterruptEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            InterruptEventKind::Requested => "requested",
+            InterruptEventKind::Dispatched => "dispatched",
+        };
+        write!(f, "cycle {} ly {:3} pc {} {} {}", self.cycle, self.ly, self.pc, self.interrupt.name(), kind)
+    }
+}
+
+// an OAM DMA, copying a page into the sprite table
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DmaTransfer {
+    // the high byte of the address copied from
+    pub page: u8,
+    pub cycle: u128,
+    pub ly: u8,
+    pub lcd_on: bool,
+    // the instruction that started it
+    pub pc: BankedAddress,
+}
+
+impl DmaTransfer {
+    // while the LCD's drawing, sprites can be copied half old and half new
+    pub fn mid_frame(&self) -> bool {
+        self.lcd_on && self.ly < 144
+    }
+
+    // the cpu can only reach HRAM during a DMA, code running anywhere else crashes on hardware
+    pub fn outside_hram(&self) -> bool {
+        self.pc.addr < 0xFF80
+    }
+}
+
+impl fmt::Display for DmaTransfer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cycle {} ly {:3} pc {} dma from {:02x}00", self.cycle, self.ly, self.pc, self.page)?;
+        // 0xE0-0xFD are echo RAM, mirroring WRAM, so only OAM and the registers above aren't memory to copy
+        if self.page >= 0xFE {
+            write!(f, ", not from ROM or RAM")?;
+        }
+        if self.outside_hram() {
+            write!(f, ", started outside HRAM")?;
+        }
+        if self.mid_frame() {
+            write!(f, ", mid-frame")?;
+        }
+        Ok(())
+    }
+}
+
+// how many instructions the cpu remembers, for when it crashes
+pub const TRACE_LENGTH: usize = 64;
+
+#[derive(Clone, Copy, Debug)]
+pub struct TraceEntry {
+    pub pc: BankedAddress,
+    // CB prefixed opcodes are 0xCBxx
+    pub opcode: u16,
+    // from just before the instruction ran
+    pub registers: Registers,
+}
+
+impl TraceEntry {
+    // like Display, with the name pc's at after it, e.g. 01:4a0c <DrawSprites+$0c>
+    pub fn annotated(&self, symbols: &Symbols) -> String {
+        let line = self.to_string();
+        match symbols.label(self.pc.bank, self.pc.addr) {
+            Some(label) => {
+                let (pc, rest) = line.split_at(self.pc.to_string().len());
+                format!("{} <{}>{}", pc, label, rest)
+            },
+            None => line,
+        }
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.opcode > 0xFF {
+            write!(f, "{} cb {:02x}  {}", self.pc, self.opcode as u8, self.registers)
+        } else {
+            write!(f, "{} {:02x}     {}", self.pc, self.opcode, self.registers)
+        }
+    }
+}
+
+// the last TRACE_LENGTH instructions the cpu ran, always kept so crashes can say how they got there
+pub struct InstructionTrace {
+    entries: Vec<TraceEntry>,
+    // where the next entry goes once entries is full
+    next: usize,
+}
+
+impl InstructionTrace {
+    pub fn new() -> InstructionTrace {
+        InstructionTrace {
+            entries: Vec::with_capacity(TRACE_LENGTH),
+            next: 0,
+        }
+    }
+
+    pub fn push(&mut self, entry: TraceEntry) {
+        if self.entries.len() < TRACE_LENGTH {
+            self.entries.push(entry);
+        } else {
+            self.entries[self.next] = entry;
+            self.next = (self.next + 1) % TRACE_LENGTH;
+        }
+    }
+
+    // oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &TraceEntry> {
+        let (newer, older) = self.entries.split_at(self.next);
+        older.iter().chain(newer.iter())
+    }
+}
+
+impl InstructionTrace {
+    // like Display, with names next to addresses
+    pub fn annotated(&self, symbols: &Symbols) -> String {
+        let mut text = format!("last {} instructions, oldest first:\n", self.entries.len());
+        for entry in self.iter() {
+            text += &format!("  {}\n", entry.annotated(symbols));
+        }
+        text
+    }
+}
+
+impl fmt::Display for InstructionTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "last {} instructions, oldest first:", self.entries.len())?;
+        for entry in self.iter() {
+            writeln!(f, "  {}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+// flags for each rom byte in a code/data log
+pub const CDL_CODE: u8 = 0x01;
+pub const CDL_DATA: u8 = 0x02;
+
+// which rom bytes have been run as code and which read as data, a byte of flags per rom byte.
+// written out as is, that's the .cdl layout FCEUX and friends use
+pub struct CodeDataLog {
+    flags: Vec<u8>,
+}
+
+impl CodeDataLog {
+    pub fn new(rom_len: usize) -> CodeDataLog {
+        CodeDataLog {
+            flags: vec![0x00; rom_len],
+        }
+    }
+
+    pub fn mark(&mut self, offset: usize, flag: u8) {
+        if let Some(flags) = self.flags.get_mut(offset) {
+            *flags |= flag;
+        }
+    }
+
+    // the .cdl file
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.flags
+    }
+
+    // how many rom bytes have had flag set
+    pub fn count(&self, flag: u8) -> usize {
+        self.flags.iter().filter(|&&flags| flags & flag != 0).count()
+    }
+}
+
+// how many times each address and opcode ran while profiling
+pub struct Profile {
+    pub instructions: u64,
+    pub addresses: BTreeMap<BankedAddress, u64>,
+    pub opcodes: [u64; 256],
+    // opcodes after a 0xCB prefix
+    pub cb_opcodes: [u64; 256],
+}
+
+impl Profile {
+    pub fn new() -> Profile {
+        Profile {
+            instructions: 0,
+            addresses: BTreeMap::new(),
+            opcodes: [0; 256],
+            cb_opcodes: [0; 256],
+        }
+    }
+
+    // cb_opcode is the byte after op, only counted if op is the 0xCB prefix
+    pub fn count(&mut self, pc: BankedAddress, op: u8, cb_opcode: u8) {
+        self.instructions += 1;
+        *self.addresses.entry(pc).or_insert(0) += 1;
+        self.opcodes[op as usize] += 1;
+        if op == 0xCB {
+            self.cb_opcodes[cb_opcode as usize] += 1;
+        }
+    }
+}
+
+// the report, hottest first
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let percent = |count: u64| count as f64 * 100.0 / self.instructions.max(1) as f64;
+
+        writeln!(f, "{} instructions", self.instructions)?;
+
+        let mut addresses: Vec<_> = self.addresses.iter().collect();
+        addresses.sort_by(|(_, a), (_, b)| b.cmp(a));
+        writeln!(f, "by address:")?;
+        for (pc, &count) in addresses {
+            writeln!(f, "  {}  {:>12}  {:6.2}%", pc, count, percent(count))?;
+        }
+
+        let mut opcodes: Vec<(u16, u64)> = self.opcodes.iter().enumerate()
+            .filter(|&(op, _)| op != 0xCB)
+            .map(|(op, &count)| (op as u16, count))
+            .chain(self.cb_opcodes.iter().enumerate().map(|(op, &count)| (0xCB00 | op as u16, count)))
+            .filter(|&(_, count)| count != 0)
+            .collect();
+        opcodes.sort_by(|(_, a), (_, b)| b.cmp(a));
+        writeln!(f, "by opcode:")?;
+        for (op, count) in opcodes {
+            if op > 0xFF {
+                write!(f, "  cb {:02x}", op as u8)?;
+            } else {
+                write!(f, "  {:02x}   ", op)?;
+            }
+            writeln!(f, "  {:>12}  {:6.2}%", count, percent(count))?;
+        }
+
+        Ok(())
+    }
+}
+
+// somewhere a game keeps its own data, for filling with a recognisable byte to catch it reading
+// memory it never wrote
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryRegion {
+    WRAM,
+    HRAM,
+    VRAM,
+}
+
+impl MemoryRegion {
+    pub fn from_name(name: &str) -> Option<MemoryRegion> {
+        match name {
+            "wram" => Some(MemoryRegion::WRAM),
+            "hram" => Some(MemoryRegion::HRAM),
+            "vram" => Some(MemoryRegion::VRAM),
+            _ => None,
+        }
+    }
+}
+
+// the addresses a RAM snapshot covers, in order
+pub fn snapshot_addresses() -> impl Iterator<Item = u16> {
+    SNAPSHOT_RANGES.iter().flat_map(|&(start, end)| start..=end)
+}
+
+// a byte that's different to what it was in the RAM snapshot
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RamChange {
+    pub addr: u16,
+    pub before: u8,
+    pub after: u8,
+}
+
+impl fmt::Display for RamChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04x}={:02x}>{:02x}", self.addr, self.before, self.after)
+    }
+}
+
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    // interrupts to break on the dispatch of
+    interrupt_breaks: Vec<Interrupt>,
+    until: Option<Until>,
+    // for Until::Line, whether LY's been off the line since, so it stops when it comes round again
+    until_armed: bool,
+    // None while interrupts aren't being logged
+    interrupt_log: Option<VecDeque<InterruptEvent>>,
+    // likewise OAM DMAs
+    dma_log: Option<VecDeque<DmaTransfer>>,
+    // kept after profiling stops, until it's started again
+    profile: Option<Profile>,
+    profiling: bool,
+    // what was at snapshot_addresses when the snapshot was taken
+    ram_snapshot: Option<Vec<u8>>,
+    // every instruction run since the log was last taken, None while not tracing
+    trace_log: Option<Vec<TraceEntry>>,
+    trace_filter: Option<TraceFilter>,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger {
+            breakpoints: Vec::new(),
+            interrupt_breaks: Vec::new(),
+            until: None,
+            until_armed: false,
+            interrupt_log: None,
+            dma_log: None,
+            profile: None,
+            profiling: false,
+            ram_snapshot: None,
+            trace_log: None,
+            trace_filter: None,
+        }
+    }
+
+    // whether the GameBoy has to check in with the debugger between instructions
+    pub fn watches_steps(&self) -> bool {
+        self.interrupt_log.is_some() || self.dma_log.is_some() || self.profiling || self.trace_log.is_some()
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.retain(|&b| b != breakpoint);
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn has_breakpoints(&self) -> bool {
+        !self.breakpoints.is_empty() || !self.interrupt_breaks.is_empty() || self.until.is_some()
+    }
+
+    pub fn breaks_at(&self, pc: BankedAddress) -> bool {
+        self.breakpoints.iter().any(|breakpoint| breakpoint.matches(pc))
+    }
+
+    // replaces any until that hasn't been reached yet
+    pub fn set_until(&mut self, until: Option<Until>) {
+        self.until = until;
+        self.until_armed = false;
+    }
+
+    pub fn until(&self) -> Option<Until> {
+        self.until
+    }
+
+    // whether the until's been reached, about to run the instruction at pc on line ly.
+    // it's cleared once it has, vblank's left to the end of the frame
+    pub fn reached_until(&mut self, pc: BankedAddress, ly: u8) -> bool {
+        let reached = match self.until {
+            Some(Until::Address(breakpoint)) => breakpoint.matches(pc),
+            Some(Until::Line(line)) if ly != line => {
+                self.until_armed = true;
+                false
+            },
+            Some(Until::Line(_)) => self.until_armed,
+            Some(Until::VBlank) | None => false,
+        };
+        if reached {
+            self.until = None;
+        }
+        reached
+    }
+
+    pub fn add_interrupt_break(&mut self, interrupt: Interrupt) {
+        if !self.interrupt_breaks.contains(&interrupt) {
+            self.interrupt_breaks.push(interrupt);
+        }
+    }
+
+    pub fn remove_interrupt_break(&mut self, interrupt: Interrupt) {
+        self.interrupt_breaks.retain(|&i| i != interrupt);
+    }
+
+    pub fn interrupt_breaks(&self) -> &[Interrupt] {
+        &self.interrupt_breaks
+    }
+
+    pub fn breaks_on(&self, interrupt: Interrupt) -> bool {
+        self.interrupt_breaks.contains(&interrupt)
+    }
+
+    // starts logging with an empty log, or stops and throws the log away
+    pub fn set_interrupt_logging(&mut self, enabled: bool) {
+        self.interrupt_log = if enabled {
+            Some(VecDeque::with_capacity(INTERRUPT_LOG_LENGTH))
+        } else {
+            None
+        };
+    }
+
+    pub fn interrupt_logging(&self) -> bool {
+        self.interrupt_log.is_some()
+    }
+
+    // oldest first
+    pub fn interrupt_log(&self) -> impl Iterator<Item = &InterruptEvent> {
+        self.interrupt_log.iter().flatten()
+    }
+
+    pub fn log_interrupt(&mut self, event: InterruptEvent) {
+        if let Some(log) = &mut self.interrupt_log {
+            if log.len() == INTERRUPT_LOG_LENGTH {
+                log.pop_front();
+            }
+            log.push_back(event);
+        }
+    }
+
+    // starts logging with an empty log, or stops and throws the log away
+    pub fn set_dma_logging(&mut self, enabled: bool) {
+        self.dma_log = if enabled {
+            Some(VecDeque::with_capacity(DMA_LOG_LENGTH))
+        } else {
+            None
+        };
+    }
+
+    pub fn dma_logging(&self) -> bool {
+        self.dma_log.is_some()
+    }
+
+    // oldest first
+    pub fn dma_log(&self) -> impl Iterator<Item = &DmaTransfer> {
+        self.dma_log.iter().flatten()
+    }
+
+    pub fn log_dma(&mut self, transfer: DmaTransfer) {
+        if let Some(log) = &mut self.dma_log {
+            if log.len() == DMA_LOG_LENGTH {
+                log.pop_front();
+            }
+            log.push_back(transfer);
+        }
+    }
+
+    // throws away any previous profile
+    pub fn start_profiling(&mut self) {
+        self.profile = Some(Profile::new());
+        self.profiling = true;
+    }
+
+    pub fn stop_profiling(&mut self) {
+        self.profiling = false;
+    }
+
+    pub fn profiling(&self) -> bool {
+        self.profiling
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    pub fn count_instruction(&mut self, pc: BankedAddress, op: u8, cb_opcode: u8) {
+        if let (true, Some(profile)) = (self.profiling, &mut self.profile) {
+            profile.count(pc, op, cb_opcode);
+        }
+    }
+
+    // replaces any earlier snapshot
+    pub fn set_ram_snapshot(&mut self, ram: Vec<u8>) {
+        self.ram_snapshot = Some(ram);
+    }
+
+    // compares ram, read from snapshot_addresses, with the snapshot. None if there isn't one
+    pub fn ram_changes(&self, ram: &[u8]) -> Option<Vec<RamChange>> {
+        let snapshot = self.ram_snapshot.as_ref()?;
+        Some(snapshot_addresses().zip(snapshot.iter().zip(ram))
+            .filter(|(_, (before, after))| before != after)
+            .map(|(addr, (&before, &after))| RamChange { addr, before, after })
+            .collect())
+    }
+
+    // logs every instruction run from now on, or only those filter matches. the log grows
+    // until it's taken, so whoever started it should take it every frame or so
+    pub fn start_trace_log(&mut self, filter: Option<TraceFilter>) {
+        self.trace_log = Some(Vec::new());
+        self.trace_filter = filter;
+    }
+
+    pub fn stop_trace_log(&mut self) {
+        self.trace_log = None;
+    }
+
+    pub fn trace_logging(&self) -> bool {
+        self.trace_log.is_some()
+    }
+
+    // oldest first, leaving the log empty
+    pub fn take_trace_log(&mut self) -> Vec<TraceEntry> {
+        self.trace_log.as_mut().map(mem::take).unwrap_or_default()
+    }
+
+    pub fn log_instruction(&mut self, entry: TraceEntry) {
+        if let Some(log) = &mut self.trace_log {
+            if self.trace_filter.is_none_or(|filter| filter.matches(entry.pc)) {
+                log.push(entry);
+            }
+        }
+    }
+}
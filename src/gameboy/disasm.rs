@@ -0,0 +1,446 @@
+// turns ROM bytes back into SM83 assembly, written the way RGBDS takes it. like the debugger,
+// everything goes by bank:address, and names come from an RGBDS/no$gmb style .sym file if
+// there is one
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::ops::{Bound, Range, RangeInclusive};
+
+use crate::Error;
+
+// operands in the order the low 3 bits of an opcode pick them
+const REGISTERS: [&str; 8] = ["b", "c", "d", "e", "h", "l", "[hl]", "a"];
+
+// 0x80-0xBF, picked by bits 3-5
+const ALU_OPS: [&str; 8] = ["add a, ", "adc a, ", "sub ", "sbc a, ", "and ", "xor ", "or ", "cp "];
+
+// 0xCB00-0xCB3F, picked by bits 3-5. the rest are bit, res and set
+const CB_SHIFTS: [&str; 8] = ["rlc", "rrc", "rl", "rr", "sla", "sra", "swap", "srl"];
+
+// the cartridge header, shown as the data it is rather than as code
+const HEADER_FIELDS: [(u16, u16, &str); 13] = [
+    (0x0104, 0x0134, "logo"),
+    (0x0134, 0x0143, "title"),
+    (0x0143, 0x0144, "cgb flag"),
+    (0x0144, 0x0146, "new licensee code"),
+    (0x0146, 0x0147, "sgb flag"),
+    (0x0147, 0x0148, "cartridge type"),
+    (0x0148, 0x0149, "rom size"),
+    (0x0149, 0x014A, "ram size"),
+    (0x014A, 0x014B, "destination code"),
+    (0x014B, 0x014C, "old licensee code"),
+    (0x014C, 0x014D, "version"),
+    (0x014D, 0x014E, "header checksum"),
+    (0x014E, 0x0150, "global checksum"),
+];
+const HEADER: Range<u16> = 0x0104..0x0150;
+
+// bytes per line of data
+const DATA_LINE_LENGTH: usize = 8;
+
+// a name only covers the rest of the area of memory it's in, a variable at the end of WRAM
+// isn't where HRAM's addresses are
+const MEMORY_AREAS: [RangeInclusive<u16>; 11] = [
+    0x0000..=0x3FFF, 0x4000..=0x7FFF, 0x8000..=0x9FFF, 0xA000..=0xBFFF, 0xC000..=0xDFFF, 0xE000..=0xFDFF,
+    0xFE00..=0xFE9F, 0xFEA0..=0xFEFF, 0xFF00..=0xFF7F, 0xFF80..=0xFFFE, 0xFFFF..=0xFFFF,
+];
+
+// names from a .sym file, one "bank:address name" per line, e.g. 01:4a00 DrawSprites
+#[derive(Debug, Default)]
+pub struct Symbols {
+    names: BTreeMap<(u16, u16), String>,
+}
+
+impl Symbols {
+    pub fn new() -> Symbols {
+        Symbols::default()
+    }
+
+    // lines that are blank or start with ; are skipped
+    pub fn parse(text: &str) -> Result<Symbols, Error> {
+        let mut symbols = Symbols::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let bad_line = || format!("bad symbol on line {}: {}", number + 1, line);
+
+            let mut parts = line.split_whitespace();
+            let (address, name) = match (parts.next(), parts.next()) {
+                (Some(address), Some(name)) => (address, name),
+                _ => return Err(bad_line().into()),
+            };
+            let mut address = address.splitn(2, ':');
+            let (bank, addr) = match (address.next(), address.next()) {
+                (Some(bank), Some(addr)) => (bank, addr),
+                _ => return Err(bad_line().into()),
+            };
+            let bank = u16::from_str_radix(bank, 16).map_err(|_| bad_line())?;
+            let addr = u16::from_str_radix(addr, 16).map_err(|_| bad_line())?;
+            symbols.names.insert((bank, addr), name.to_string());
+        }
+        Ok(symbols)
+    }
+
+    pub fn get(&self, bank: u16, addr: u16) -> Option<&str> {
+        self.names.get(&(bank, addr)).map(String::as_str)
+    }
+
+    // the nearest name at or before addr in bank, and how far past it addr is. ROM addresses have
+    // to be in the same bank to match, RAM's banks can't be told from the code so any will do
+    pub fn locate(&self, bank: u16, addr: u16) -> Option<(&str, u16)> {
+        let area = MEMORY_AREAS.iter().find(|area| area.contains(&addr))?;
+        let nearest = match addr {
+            0x0000 ..= 0x7FFF => {
+                let bank = if addr < 0x4000 { 0 } else { bank };
+                self.names.range((bank, *area.start())..=(bank, addr)).next_back()
+            },
+            _ => self.names.iter()
+                .filter(|((_, symbol_addr), _)| (*area.start()..=addr).contains(symbol_addr))
+                .max_by_key(|((_, symbol_addr), _)| *symbol_addr),
+        };
+        nearest.map(|((_, symbol_addr), name)| (name.as_str(), addr - symbol_addr))
+    }
+
+    // name+$offset for addr, or just the name if it's right on one
+    pub fn label(&self, bank: u16, addr: u16) -> Option<String> {
+        self.locate(bank, addr).map(|(name, offset)| match offset {
+            0 => name.to_string(),
+            _ => format!("{}+${:02x}", name, offset),
+        })
+    }
+
+    // the bank and addresses a name covers, up to the next name or the end of its area of memory
+    pub fn extent(&self, name: &str) -> Option<(u16, RangeInclusive<u16>)> {
+        let (&(bank, start), _) = self.names.iter().find(|(_, symbol)| *symbol == name)?;
+        let area = MEMORY_AREAS.iter().find(|area| area.contains(&start))?;
+        let end = self.names.range((Bound::Excluded((bank, start)), Bound::Included((bank, *area.end()))))
+            .next()
+            .map_or(*area.end(), |((_, next), _)| next - 1);
+        Some((bank, start..=end))
+    }
+}
+
+pub struct Instruction {
+    // bytes taken up, opcode included
+    pub len: u16,
+    pub text: String,
+    // what an address the instruction uses is, if it's an IO register
+    pub comment: Option<&'static str>,
+}
+
+// decodes the instruction at the start of bytes, which are at addr in bank.
+// None if it's an unused opcode or runs off the end of bytes
+pub fn decode(bytes: &[u8], bank: u16, addr: u16, symbols: &Symbols) -> Option<Instruction> {
+    let op = *bytes.first()?;
+    let register = |op: u8| REGISTERS[(op & 0x07) as usize];
+
+    let template = match op {
+        0xCB => {
+            let cb = *bytes.get(1)?;
+            let text = match cb {
+                0x00 ..= 0x3F => format!("{} {}", CB_SHIFTS[(cb >> 3) as usize], register(cb)),
+                _ => {
+                    let name = ["bit", "res", "set"][(cb >> 6) as usize - 1];
+                    format!("{} {}, {}", name, (cb >> 3) & 0x07, register(cb))
+                },
+            };
+            return Some(Instruction { len: 2, text, comment: None });
+        },
+        0x76 => "halt",
+        0x40 ..= 0x7F => {
+            let text = format!("ld {}, {}", register(op >> 3), register(op));
+            return Some(Instruction { len: 1, text, comment: None });
+        },
+        0x80 ..= 0xBF => {
+            let text = format!("{}{}", ALU_OPS[((op >> 3) & 0x07) as usize], register(op));
+            return Some(Instruction { len: 1, text, comment: None });
+        },
+        _ => template(op)?,
+    };
+
+    // the operand's placeholder, and how many bytes follow the opcode for it
+    let placeholders: [(&str, u16); 6] = [("{n8}", 1), ("{n16}", 2), ("{a8}", 1), ("{a16}", 2), ("{e8}", 1), ("{s8}", 1)];
+    let (placeholder, operand_len) = placeholders.iter()
+        .find(|(placeholder, _)| template.contains(placeholder))
+        .copied()
+        // stop is followed by a byte that's skipped
+        .unwrap_or(("", if op == 0x10 { 1 } else { 0 }));
+    let len = 1 + operand_len;
+    let operand = bytes.get(1..len as usize)?;
+    let u8_operand = operand.first().copied().unwrap_or(0);
+    let u16_operand = u16::from_le_bytes([u8_operand, operand.get(1).copied().unwrap_or(0)]);
+
+    let name_or_hex = |target: u16| symbols.label(bank, target)
+        .unwrap_or_else(|| format!("${:04x}", target));
+    let (value, target) = match placeholder {
+        "{n8}" => (format!("${:02x}", u8_operand), None),
+        "{n16}" => (format!("${:04x}", u16_operand), None),
+        "{a8}" => {
+            let target = 0xFF00 | u8_operand as u16;
+            (name_or_hex(target), Some(target))
+        },
+        "{a16}" => (name_or_hex(u16_operand), Some(u16_operand)),
+        "{e8}" => {
+            // relative to the instruction after this one
+            let target = addr.wrapping_add(len).wrapping_add(u8_operand as i8 as u16);
+            (name_or_hex(target), Some(target))
+        },
+        "{s8}" => {
+            let offset = u8_operand as i8;
+            let sign = if offset < 0 { '-' } else { '+' };
+            (format!("{}${:02x}", sign, offset.unsigned_abs()), None)
+        },
+        _ => (String::new(), None),
+    };
+
+    let text = if placeholder.is_empty() { template.to_string() } else { template.replace(placeholder, &value) };
+    let comment = target.and_then(io_register);
+    Some(Instruction { len, text, comment })
+}
+
+// writes out every instruction in addrs, which has to be within bank: 0000-3fff for bank 0,
+// 4000-7fff for the rest. the header's shown as data, as is anything that isn't an instruction
+pub fn listing(rom: &[u8], bank: u16, addrs: Range<u16>, symbols: &Symbols) -> Result<String, Error> {
+    let window = match bank {
+        0 => 0x0000..0x4000,
+        _ => 0x4000..0x8000,
+    };
+    if addrs.start < window.start || addrs.end > window.end {
+        return Err(format!("bank {:02x} is at {:04x}-{:04x}", bank, window.start, window.end - 1).into());
+    }
+    let bank_start = bank as usize * 0x4000;
+    if bank_start >= rom.len() {
+        return Err(format!("the rom only has {} banks", rom.len().div_ceil(0x4000)).into());
+    }
+
+    let mut out = String::new();
+    let mut addr = addrs.start;
+    while addr < addrs.end {
+        if let Some(name) = symbols.get(bank, addr) {
+            let _ = writeln!(out, "{}:", name);
+        }
+
+        // instructions mustn't run on into the header or the next bank
+        let limit = match addr {
+            0x0000 ..= 0x0103 if bank == 0 => HEADER.start,
+            _ => window.end,
+        };
+        let offset = bank_start + (addr - window.start) as usize;
+        let end = (bank_start + (limit - window.start) as usize).min(rom.len());
+        let bytes = rom.get(offset..end).unwrap_or(&[]);
+        if bytes.is_empty() {
+            break;
+        }
+
+        if bank == 0 && HEADER.contains(&addr) {
+            let &(_, field_end, field) = HEADER_FIELDS.iter()
+                .find(|(start, end, _)| (*start..*end).contains(&addr))
+                .unwrap();
+            let len = (field_end - addr).min(addrs.end - addr).min(DATA_LINE_LENGTH as u16) as usize;
+            write_data(&mut out, bank, addr, &bytes[..len.min(bytes.len())], Some(field));
+            addr += len as u16;
+            continue;
+        }
+
+        match decode(bytes, bank, addr, symbols) {
+            Some(instruction) => {
+                write_line(&mut out, bank, addr, &bytes[..instruction.len as usize], &instruction.text,
+                           instruction.comment);
+                addr = match addr.checked_add(instruction.len) {
+                    Some(next) => next,
+                    None => break,
+                };
+            },
+            None => {
+                write_data(&mut out, bank, addr, &bytes[..1], None);
+                addr += 1;
+            },
+        }
+    }
+    Ok(out)
+}
+
+fn write_data(out: &mut String, bank: u16, addr: u16, bytes: &[u8], comment: Option<&str>) {
+    let values: Vec<String> = bytes.iter().map(|byte| format!("${:02x}", byte)).collect();
+    // the bytes are all there in the text already
+    write_line(out, bank, addr, &[], &format!("db {}", values.join(", ")), comment);
+}
+
+// 00:0150  c3 50 01   jp Start    ; comment
+fn write_line(out: &mut String, bank: u16, addr: u16, bytes: &[u8], text: &str, comment: Option<&str>) {
+    let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let _ = write!(out, "{:02x}:{:04x}  {:<8}  ", bank, addr, hex.join(" "));
+    match comment {
+        Some(comment) => {
+            let _ = writeln!(out, "{:<23} ; {}", text, comment);
+        },
+        None => {
+            let _ = writeln!(out, "{}", text);
+        },
+    }
+}
+
+// everything but the 0x40-0xBF block and the 0xCB prefix
+fn template(op: u8) -> Option<&'static str> {
+    Some(match op {
+        0x00 => "nop",
+        0x01 => "ld bc, {n16}",
+        0x02 => "ld [bc], a",
+        0x03 => "inc bc",
+        0x04 => "inc b",
+        0x05 => "dec b",
+        0x06 => "ld b, {n8}",
+        0x07 => "rlca",
+        0x08 => "ld [{a16}], sp",
+        0x09 => "add hl, bc",
+        0x0A => "ld a, [bc]",
+        0x0B => "dec bc",
+        0x0C => "inc c",
+        0x0D => "dec c",
+        0x0E => "ld c, {n8}",
+        0x0F => "rrca",
+        0x10 => "stop",
+        0x11 => "ld de, {n16}",
+        0x12 => "ld [de], a",
+        0x13 => "inc de",
+        0x14 => "inc d",
+        0x15 => "dec d",
+        0x16 => "ld d, {n8}",
+        0x17 => "rla",
+        0x18 => "jr {e8}",
+        0x19 => "add hl, de",
+        0x1A => "ld a, [de]",
+        0x1B => "dec de",
+        0x1C => "inc e",
+        0x1D => "dec e",
+        0x1E => "ld e, {n8}",
+        0x1F => "rra",
+        0x20 => "jr nz, {e8}",
+        0x21 => "ld hl, {n16}",
+        0x22 => "ld [hl+], a",
+        0x23 => "inc hl",
+        0x24 => "inc h",
+        0x25 => "dec h",
+        0x26 => "ld h, {n8}",
+        0x27 => "daa",
+        0x28 => "jr z, {e8}",
+        0x29 => "add hl, hl",
+        0x2A => "ld a, [hl+]",
+        0x2B => "dec hl",
+        0x2C => "inc l",
+        0x2D => "dec l",
+        0x2E => "ld l, {n8}",
+        0x2F => "cpl",
+        0x30 => "jr nc, {e8}",
+        0x31 => "ld sp, {n16}",
+        0x32 => "ld [hl-], a",
+        0x33 => "inc sp",
+        0x34 => "inc [hl]",
+        0x35 => "dec [hl]",
+        0x36 => "ld [hl], {n8}",
+        0x37 => "scf",
+        0x38 => "jr c, {e8}",
+        0x39 => "add hl, sp",
+        0x3A => "ld a, [hl-]",
+        0x3B => "dec sp",
+        0x3C => "inc a",
+        0x3D => "dec a",
+        0x3E => "ld a, {n8}",
+        0x3F => "ccf",
+        0xC0 => "ret nz",
+        0xC1 => "pop bc",
+        0xC2 => "jp nz, {a16}",
+        0xC3 => "jp {a16}",
+        0xC4 => "call nz, {a16}",
+        0xC5 => "push bc",
+        0xC6 => "add a, {n8}",
+        0xC7 => "rst $00",
+        0xC8 => "ret z",
+        0xC9 => "ret",
+        0xCA => "jp z, {a16}",
+        0xCC => "call z, {a16}",
+        0xCD => "call {a16}",
+        0xCE => "adc a, {n8}",
+        0xCF => "rst $08",
+        0xD0 => "ret nc",
+        0xD1 => "pop de",
+        0xD2 => "jp nc, {a16}",
+        0xD4 => "call nc, {a16}",
+        0xD5 => "push de",
+        0xD6 => "sub {n8}",
+        0xD7 => "rst $10",
+        0xD8 => "ret c",
+        0xD9 => "reti",
+        0xDA => "jp c, {a16}",
+        0xDC => "call c, {a16}",
+        0xDE => "sbc a, {n8}",
+        0xDF => "rst $18",
+        0xE0 => "ldh [{a8}], a",
+        0xE1 => "pop hl",
+        0xE2 => "ldh [c], a",
+        0xE5 => "push hl",
+        0xE6 => "and {n8}",
+        0xE7 => "rst $20",
+        0xE8 => "add sp, {s8}",
+        0xE9 => "jp hl",
+        0xEA => "ld [{a16}], a",
+        0xEE => "xor {n8}",
+        0xEF => "rst $28",
+        0xF0 => "ldh a, [{a8}]",
+        0xF1 => "pop af",
+        0xF2 => "ldh a, [c]",
+        0xF3 => "di",
+        0xF5 => "push af",
+        0xF6 => "or {n8}",
+        0xF7 => "rst $30",
+        0xF8 => "ld hl, sp{s8}",
+        0xF9 => "ld sp, hl",
+        0xFA => "ld a, [{a16}]",
+        0xFB => "ei",
+        0xFE => "cp {n8}",
+        0xFF => "rst $38",
+        // d3, db, dd, e3, e4, eb, ec, ed, f4, fc and fd lock the cpu up
+        _ => return None,
+    })
+}
+
+// the names pandocs gives the registers games touch most
+fn io_register(addr: u16) -> Option<&'static str> {
+    Some(match addr {
+        0xFF00 => "P1",
+        0xFF01 => "SB",
+        0xFF02 => "SC",
+        0xFF04 => "DIV",
+        0xFF05 => "TIMA",
+        0xFF06 => "TMA",
+        0xFF07 => "TAC",
+        0xFF0F => "IF",
+        0xFF10 => "NR10",
+        0xFF11 => "NR11",
+        0xFF12 => "NR12",
+        0xFF13 => "NR13",
+        0xFF14 => "NR14",
+        0xFF24 => "NR50",
+        0xFF25 => "NR51",
+        0xFF26 => "NR52",
+        0xFF40 => "LCDC",
+        0xFF41 => "STAT",
+        0xFF42 => "SCY",
+        0xFF43 => "SCX",
+        0xFF44 => "LY",
+        0xFF45 => "LYC",
+        0xFF46 => "DMA",
+        0xFF47 => "BGP",
+        0xFF48 => "OBP0",
+        0xFF49 => "OBP1",
+        0xFF4A => "WY",
+        0xFF4B => "WX",
+        0xFF50 => "BOOT",
+        0xFFFF => "IE",
+        _ => return None,
+    })
+}
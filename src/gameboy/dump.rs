@@ -0,0 +1,127 @@
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use crate::gameboy::lcd::{SCREEN_WIDTH, SCREEN_HEIGHT};
+
+pub const TILE_DATA_WIDTH: u32 = 256;
+pub const TILE_DATA_HEIGHT: u32 = 96;
+
+// how many frames can be waiting on the worker before we start dropping them
+const QUEUE_LENGTH: usize = 2;
+
+struct Dump {
+    frame: Vec<u8>,
+    tile_data: Vec<u8>,
+}
+
+// writes frame.png and tiledata.png into a directory whenever the frame changes,
+// encoding on a worker thread so the emulator never waits on file I/O
+pub struct FrameDumper {
+    sender: Option<SyncSender<Dump>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl FrameDumper {
+    pub fn new(directory: &Path) -> Result<FrameDumper, Box<dyn Error>> {
+        fs::create_dir_all(directory)
+            .map_err(|e| format!("couldn't create dump directory {}: {}", directory.display(), e))?;
+
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_LENGTH);
+        let directory = directory.to_path_buf();
+        let worker = thread::Builder::new()
+            .name("frame dumper".to_string())
+            .spawn(move || FrameDumper::work(directory, receiver))?;
+
+        Ok(FrameDumper { sender: Some(sender), worker: Some(worker) })
+    }
+
+    // frame is RGBA SCREEN_WIDTH x SCREEN_HEIGHT, tile_data is RGBA TILE_DATA_WIDTH x TILE_DATA_HEIGHT
+    pub fn dump(&self, frame: Vec<u8>, tile_data: Vec<u8>) {
+        if let Some(sender) = &self.sender {
+            // if the worker is still busy, skip this frame rather than stall emulation
+            let _ = sender.try_send(Dump { frame, tile_data });
+        }
+    }
+
+    fn work(directory: PathBuf, receiver: Receiver<Dump>) {
+        let mut last_frame_hash = 0;
+        for dump in receiver {
+            let mut hasher = DefaultHasher::new();
+            dump.frame.hash(&mut hasher);
+            let frame_hash = hasher.finish();
+            if frame_hash == last_frame_hash {
+                continue;
+            }
+            last_frame_hash = frame_hash;
+
+            let frame_path = directory.join("frame.png");
+            if let Err(e) = save_png(&frame_path, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, &dump.frame) {
+                eprintln!("Problem saving {}: {}", frame_path.display(), e);
+            }
+            let tile_data_path = directory.join("tiledata.png");
+            if let Err(e) = save_png(&tile_data_path, TILE_DATA_WIDTH, TILE_DATA_HEIGHT, &dump.tile_data) {
+                eprintln!("Problem saving {}: {}", tile_data_path.display(), e);
+            }
+        }
+    }
+}
+
+impl Drop for FrameDumper {
+    fn drop(&mut self) {
+        // closing the channel ends the worker loop once it's written anything still queued
+        self.sender.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+pub fn save_png(path: &Path, width: u32, height: u32, data: &[u8]) -> Result<(), png::EncodingError> {
+    use std::fs::File;
+    use std::io::BufWriter;
+    let file = File::create(path)?;
+    encode_png(BufWriter::new(file), width, height, data)
+}
+
+// data is RGBA, width x height
+pub fn encode_png<W: Write>(w: W, width: u32, height: u32, data: &[u8]) -> Result<(), png::EncodingError> {
+    let mut png_encoder = png::Encoder::new(w, width, height);
+    png_encoder.set_color(png::ColorType::RGBA);
+    png_encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = png_encoder.write_header()?;
+    writer.write_image_data(data)?;
+
+    Ok(())
+}
+
+// any PNG as RGBA, with its width and height. transparency's dropped, nothing drawn with it has any
+pub fn load_png(path: &Path) -> Result<(u32, u32, Vec<u8>), png::DecodingError> {
+    let mut decoder = png::Decoder::new(fs::File::open(path)?);
+    // expand palettes and low bit depths, and strip 16 bit ones, so we always get 8-bit samples
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (info, mut reader) = decoder.read_info()?;
+    let mut buffer = vec![0x00; info.buffer_size()];
+    reader.next_frame(&mut buffer)?;
+
+    let samples = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::RGB | png::ColorType::Indexed => 3,
+        png::ColorType::RGBA => 4,
+    };
+    let mut pixels = Vec::with_capacity(info.width as usize * info.height as usize * 4);
+    for pixel in buffer.chunks(samples) {
+        match samples {
+            1 | 2 => pixels.extend_from_slice(&[pixel[0], pixel[0], pixel[0], 0xFF]),
+            _ => pixels.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 0xFF]),
+        }
+    }
+
+    Ok((info.width, info.height, pixels))
+}
@@ -0,0 +1,72 @@
+// when the core finds itself in a state it can't carry on from, it reports a fault
+// out of step/run_to_vblank rather than panicking, so a frontend can show what happened
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::fmt;
+
+use crate::gameboy::debugger::{BankedAddress, TraceEntry};
+
+// what went wrong, for code that wants to tell faults apart without reading their messages
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FaultKind {
+    UnknownOpcode(u8),
+    BadMemoryAccess,
+    BadInterrupt,
+}
+
+#[derive(Clone, Debug)]
+pub struct CoreFault {
+    pub kind: FaultKind,
+    pub message: String,
+    pub pc: BankedAddress,
+    pub cycle: u128,
+    // the memory access that went wrong, if it was one
+    pub address: Option<u16>,
+    // the instructions leading up to it, oldest first
+    pub trace: Vec<TraceEntry>,
+}
+
+impl fmt::Display for CoreFault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at pc {}, cycle {}", self.message, self.pc, self.cycle)?;
+        if let Some(address) = self.address {
+            write!(f, ", accessing {:04x}", address)?;
+        }
+        writeln!(f)?;
+
+        writeln!(f, "last {} instructions, oldest first:", self.trace.len())?;
+        for entry in self.trace.iter() {
+            writeln!(f, "  {}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CoreFault {}
+
+// a bad memory access, noted by the mmu and turned into a CoreFault once the instruction's done
+#[derive(Clone, Copy, Debug)]
+pub struct BusFault {
+    pub address: u16,
+    pub reason: &'static str,
+}
+
+// lets the mmu note a fault from its read paths, which only borrow it immutably
+#[derive(Default)]
+pub struct FaultLatch(Cell<Option<BusFault>>);
+
+impl FaultLatch {
+    pub fn set(&self, address: u16, reason: &'static str) {
+        // keep the first one, later faults are likely knock-on effects
+        if self.0.get().is_none() {
+            self.0.set(Some(BusFault { address, reason }));
+        }
+    }
+
+    pub fn take(&self) -> Option<BusFault> {
+        self.0.take()
+    }
+}
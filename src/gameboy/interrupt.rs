@@ -1,4 +1,7 @@
-#[derive(FromPrimitive)]
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromPrimitive)]
 pub enum Interrupt {
     VBlank = 0,
     LCDC = 1,
@@ -7,6 +10,45 @@ pub enum Interrupt {
     Joypad = 4,
 }
 
+impl Interrupt {
+    // where the cpu jumps to when it dispatches this interrupt
+    pub fn vector(self) -> u16 {
+        0x0040 + 8 * self as u16
+    }
+
+    // what debugger commands and logs call it
+    pub fn name(self) -> &'static str {
+        match self {
+            Interrupt::VBlank => "vblank",
+            Interrupt::LCDC => "stat",
+            Interrupt::Timer => "timer",
+            Interrupt::SerialIOComplete => "serial",
+            Interrupt::Joypad => "joypad",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Interrupt> {
+        match name {
+            "vblank" => Some(Interrupt::VBlank),
+            "stat" => Some(Interrupt::LCDC),
+            "timer" => Some(Interrupt::Timer),
+            "serial" => Some(Interrupt::SerialIOComplete),
+            "joypad" => Some(Interrupt::Joypad),
+            _ => None,
+        }
+    }
+
+    pub fn from_vector(addr: u16) -> Option<Interrupt> {
+        use num_traits::FromPrimitive;
+        match addr {
+            0x0040 ..= 0x0060 if addr & 0x07 == 0 => FromPrimitive::from_u16((addr - 0x0040) / 8),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct InterruptHandler {
     flag: u8,
     enable: u8,
@@ -34,8 +76,9 @@ impl InterruptHandler {
         self.enable = value;
     }
 
+    // only the low 5 bits are wired to anything, whatever's written to the top 3
     pub fn get_enabled_flags(&self) -> u8 {
-        self.flag & self.enable
+        self.flag & self.enable & 0x1F
     }
 
     pub fn set_interrupt(&mut self, interrupt: Interrupt) {
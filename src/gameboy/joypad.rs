@@ -1,8 +1,13 @@
-use num_traits::FromPrimitive;
+use alloc::vec::Vec;
+
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 
 use crate::gameboy::interrupt::{Interrupt, InterruptHandler};
 
 bitfield!{
+    #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
     struct Buttons(u8);
     impl Debug;
     start, _: 3;
@@ -23,6 +28,8 @@ impl From<Buttons> for u8 {
 }
 
 bitfield!{
+    #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
     struct Directions(u8);
     impl Debug;
     down, _: 3;
@@ -43,7 +50,8 @@ impl From<Directions> for u8 {
 }
 
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, FromPrimitive)]
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 enum JoypadSelection {
     Buttons = 0b0010_0000,
     Directions = 0b0001_0000,
@@ -51,6 +59,8 @@ enum JoypadSelection {
     Neither = 0b0000_0000,
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct Joypad {
     buttons: Buttons,
     directions: Directions,
@@ -102,7 +112,12 @@ impl Joypad {
         // only the selection bits can be written to, so mask the input to them
         // we also invert the input value since in actual hardware, 0 is selected and 1 is not
         let bits = !value & 0b0011_0000;
-        self.selection = FromPrimitive::from_u8(bits).expect("invalid selection bits");
+        self.selection = match bits {
+            0b0010_0000 => JoypadSelection::Buttons,
+            0b0001_0000 => JoypadSelection::Directions,
+            0b0011_0000 => JoypadSelection::Both,
+            _ => JoypadSelection::Neither,
+        };
     }
 
     pub fn as_u8(&self) -> u8 {
@@ -121,6 +136,8 @@ impl Joypad {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct Controls {
     pub left: bool,
     pub right: bool,
@@ -131,4 +148,123 @@ pub struct Controls {
     pub b: bool,
     pub start: bool,
     pub select: bool,
-}
\ No newline at end of file
+}
+
+impl Controls {
+    // a button is held if it's held in either set
+    pub fn merge(self, other: Controls) -> Controls {
+        Controls {
+            left: self.left || other.left,
+            right: self.right || other.right,
+            up: self.up || other.up,
+            down: self.down || other.down,
+
+            a: self.a || other.a,
+            b: self.b || other.b,
+            start: self.start || other.start,
+            select: self.select || other.select,
+        }
+    }
+
+    pub fn set(&mut self, button: Button, held: bool) {
+        match button {
+            Button::Left => self.left = held,
+            Button::Right => self.right = held,
+            Button::Up => self.up = held,
+            Button::Down => self.down = held,
+
+            Button::A => self.a = held,
+            Button::B => self.b = held,
+            Button::Start => self.start = held,
+            Button::Select => self.select = held,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum Button {
+    Left,
+    Right,
+    Up,
+    Down,
+
+    A,
+    B,
+    Start,
+    Select,
+}
+
+impl Button {
+    // as scripts and the remote control name them: a, b, start, select, up, down, left, right
+    pub fn from_name(name: &str) -> Option<Button> {
+        Some(match name {
+            "a" => Button::A,
+            "b" => Button::B,
+            "start" => Button::Start,
+            "select" => Button::Select,
+            "up" => Button::Up,
+            "down" => Button::Down,
+            "left" => Button::Left,
+            "right" => Button::Right,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+struct QueuedPress {
+    button: Button,
+    // frames counted from the queue's creation, end is exclusive
+    start: u64,
+    end: u64,
+}
+
+// button presses scheduled by frame, so scripts and bots can play without a frontend
+#[derive(Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct InputQueue {
+    frame: u64,
+    presses: Vec<QueuedPress>,
+}
+
+impl InputQueue {
+    pub fn new() -> InputQueue {
+        InputQueue {
+            frame: 0,
+            presses: Vec::new(),
+        }
+    }
+
+    // hold button for frames frames, starting delay frames from now
+    pub fn press(&mut self, button: Button, delay: u32, frames: u32) {
+        let start = self.frame + delay as u64;
+        self.presses.push(QueuedPress { button, start, end: start + frames as u64 });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.presses.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.presses.clear();
+    }
+
+    // the queued buttons held during the current frame
+    pub fn controls(&self) -> Controls {
+        let mut controls = Controls::default();
+        for press in &self.presses {
+            if press.start <= self.frame && self.frame < press.end {
+                controls.set(press.button, true);
+            }
+        }
+        controls
+    }
+
+    pub fn advance(&mut self) {
+        self.frame += 1;
+        let frame = self.frame;
+        self.presses.retain(|press| press.end > frame);
+    }
+}
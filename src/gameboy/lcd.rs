@@ -1,18 +1,54 @@
-use num_traits::FromPrimitive;
+use alloc::vec::Vec;
+use core::mem;
 
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "std")]
+use crate::gameboy::dump::{FrameDumper, TILE_DATA_WIDTH, TILE_DATA_HEIGHT};
 use crate::gameboy::interrupt::{Interrupt, InterruptHandler};
+use crate::gameboy::scheduler::Scheduled;
 
 pub const SCREEN_WIDTH: u8 = 160;
 pub const SCREEN_HEIGHT: u8 = 144;
+// 154 scanlines of 456 cycles each
+pub const CYCLES_PER_FRAME: u32 = 70224;
+// the bg map is 32x32 tiles
+pub const BG_MAP_SIZE: u16 = 256;
+
+// layouts frontends can ask for the finished frame in
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum PixelFormat {
+    RGBA8888,
+    BGRA8888,
+    RGB565, // little-endian u16s, red in the top 5 bits
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::RGBA8888 | PixelFormat::BGRA8888 => 4,
+            PixelFormat::RGB565 => 2,
+        }
+    }
 
-#[derive(Clone, Copy, Debug, FromPrimitive)]
+    pub fn frame_size(self) -> usize {
+        SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize * self.bytes_per_pixel()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
 pub enum TileDataAddressRange {
     TileDataAddr8800_97FF = 0,
     TileDataAddr8000_8FFF = 1,
 }
 impl From<u8> for TileDataAddressRange {
     fn from(value: u8) -> TileDataAddressRange {
-        FromPrimitive::from_u8(value).expect("invalid tile data address bit")
+        match value & 0b1 {
+            0 => TileDataAddressRange::TileDataAddr8800_97FF,
+            _ => TileDataAddressRange::TileDataAddr8000_8FFF,
+        }
     }
 }
 impl From<TileDataAddressRange> for u8 {
@@ -21,14 +57,17 @@ impl From<TileDataAddressRange> for u8 {
     }
 }
 
-#[derive(Clone, Copy, Debug, FromPrimitive)]
+#[derive(Clone, Copy, Debug)]
 pub enum TileMapAddressRange {
     TileMapAddr9800_9BFF = 0,
     TileMapAddr9C00_9FFF = 1,
 }
 impl From<u8> for TileMapAddressRange {
     fn from(value: u8) -> TileMapAddressRange {
-        FromPrimitive::from_u8(value).expect("invalid tile map address bit")
+        match value & 0b1 {
+            0 => TileMapAddressRange::TileMapAddr9800_9BFF,
+            _ => TileMapAddressRange::TileMapAddr9C00_9FFF,
+        }
     }
 }
 impl From<TileMapAddressRange> for u8 {
@@ -37,14 +76,17 @@ impl From<TileMapAddressRange> for u8 {
     }
 }
 
-#[derive(Clone, Copy, Debug, FromPrimitive)]
+#[derive(Clone, Copy, Debug)]
 pub enum SpriteSizes {
     Size8x8 = 0,
     Size8x16 = 1,
 }
 impl From<u8> for SpriteSizes {
     fn from(value: u8) -> SpriteSizes {
-        FromPrimitive::from_u8(value).expect("invalid sprite size bit")
+        match value & 0b1 {
+            0 => SpriteSizes::Size8x8,
+            _ => SpriteSizes::Size8x16,
+        }
     }
 }
 impl From<SpriteSizes> for u8 {
@@ -54,6 +96,8 @@ impl From<SpriteSizes> for u8 {
 }
 
 bitfield!{
+    #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
     struct Control(u8);
     impl Debug;
     // get, set: msb,lsb,count;
@@ -68,7 +112,7 @@ bitfield!{
     from into u8, bits, set_bits: 7,0;
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, FromPrimitive)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Mode {
     HBlank = 0b00,
     VBlank = 0b01,
@@ -77,7 +121,12 @@ enum Mode {
 }
 impl From<u8> for Mode {
     fn from(value: u8) -> Mode {
-        FromPrimitive::from_u8(value).expect("invalid mode")
+        match value & 0b11 {
+            0b00 => Mode::HBlank,
+            0b01 => Mode::VBlank,
+            0b10 => Mode::OAMSearch,
+            _ => Mode::Transfer,
+        }
     }
 }
 impl From<Mode> for u8 {
@@ -87,6 +136,8 @@ impl From<Mode> for u8 {
 }
 
 bitfield!{
+    #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
     struct Status(u8);
     impl Debug;
     // get, set: msb,lsb,count;
@@ -101,6 +152,7 @@ bitfield!{
 
 bitfield!{
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
     struct Attributes(u8);
     impl Debug;
     // get, set: msb,lsb,count;
@@ -113,6 +165,7 @@ bitfield!{
 }
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct OAM {
     y_position: u8,
     x_position: u8,
@@ -132,6 +185,8 @@ impl OAM {
 }
 
 bitfield!{
+    #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
     struct Palette(u8);
     impl Debug;
     // get, set: msb,lsb,count;
@@ -139,7 +194,7 @@ bitfield!{
     from into u8, bits, set_bits: 7,0;
 }
 
-#[derive(Clone, Copy, Debug, FromPrimitive)]
+#[derive(Clone, Copy, Debug)]
 enum Shade {
     White = 0b00,
     LightGray = 0b01,
@@ -148,7 +203,12 @@ enum Shade {
 }
 impl From<u8> for Shade {
     fn from(value: u8) -> Shade {
-        FromPrimitive::from_u8(value).expect("invalid shade")
+        match value & 0b11 {
+            0b00 => Shade::White,
+            0b01 => Shade::LightGray,
+            0b10 => Shade::DarkGray,
+            _ => Shade::Black,
+        }
     }
 }
 impl From<Shade> for u8 {
@@ -168,11 +228,68 @@ impl Shade {
     }
 }
 
+// the DMG's three palettes (BGP, OBP0 and OBP1) as the game has set them, and any shades put in
+// place of their entries to see what's drawn with what. shades go 0 for white to 3 for black.
+// CGB palettes will want adding once there are any
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Palettes {
+    pub registers: [u8; 3],
+    pub overrides: [[Option<u8>; 4]; 3],
+}
+
+// the registers raster effects change part way down the screen, as they were when a line was drawn
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ScanlineRegisters {
+    pub lcdc: u8,
+    pub scy: u8,
+    pub scx: u8,
+    pub wy: u8,
+    pub wx: u8,
+    pub bgp: u8,
+    pub obp0: u8,
+    pub obp1: u8,
+}
+
+// where a composed pixel came from, which decides the palette it's coloured with
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+enum PixelSource {
+    Blank, // bg disabled, always white
+    Background,
+    Window,
+    Sprite(u8), // sprite palette number
+}
+
+// one pixel of the line being drawn, kept as palette indices until the line is finished
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+struct LinePixel {
+    source: PixelSource,
+    index: u8,
+    // the bg/window index underneath, which decides sprite priority
+    bg_index: u8,
+}
+
+impl LinePixel {
+    const BLANK: LinePixel = LinePixel { source: PixelSource::Blank, index: 0, bg_index: 0 };
+}
+
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct LCD {
-    pub vram_tile_data: [u8; 0x1800], //0x8000-0x97FF
-    pub vram_bg_maps: [u8; 0x0800],   //0x9800-0x9FFF
+    #[cfg_attr(feature = "serialize", serde(with = "crate::utils::big_array"))]
+    vram_tile_data: [u8; 0x1800], //0x8000-0x97FF
+    #[cfg_attr(feature = "serialize", serde(with = "crate::utils::big_array"))]
+    vram_bg_maps: [u8; 0x0800],   //0x9800-0x9FFF
+    #[cfg_attr(feature = "serialize", serde(with = "crate::utils::big_array"))]
     pub vram_oam: [OAM; 40],          //0xFE00-0xFE9F
 
+    // palette indices for each 8 pixel tile row, decoded on demand after VRAM writes.
+    // left out of save states, loading one marks every row dirty instead
+    #[cfg_attr(feature = "serialize", serde(skip, default = "undecoded_tile_rows"))]
+    decoded_tile_rows: [[u8; 8]; LCD::TILE_ROW_COUNT],
+    #[cfg_attr(feature = "serialize", serde(skip, default = "all_tile_rows_dirty"))]
+    dirty_tile_rows: [bool; LCD::TILE_ROW_COUNT],
+
     control: Control,
     status: Status,
 
@@ -180,6 +297,8 @@ pub struct LCD {
     scroll_x: u8,
 
     scanline_cycle_count: i16,
+    // cycles since the last blank frame was put out while the LCD is switched off
+    disabled_cycle_count: u32,
     lcd_y: u8, //TODO: more specialised than u8?
     lcd_y_compare: u8,
 
@@ -189,11 +308,50 @@ pub struct LCD {
 
     window_y: u8,
     window_x: u8,
+    // set once LY has matched WY this frame, the window can show from then on
+    window_y_triggered: bool,
+    // the window's own line counter, which only moves on for lines the window was drawn on
+    window_line: u8,
 
+    // the current line, composed from the bg, window and sprites before being coloured into frame
+    #[cfg_attr(feature = "serialize", serde(with = "crate::utils::big_array"))]
+    line: [LinePixel; SCREEN_WIDTH as usize],
+
+    #[cfg_attr(feature = "serialize", serde(with = "crate::utils::big_array"))]
     frame: [u8; SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize * 4],
-    last_frame_hash: u64,
+    #[cfg(feature = "std")]
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    frame_dumper: Option<FrameDumper>,
+
+    pixel_format: PixelFormat,
+    // the last finished frame in pixel_format, unused for RGBA8888
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    converted_frame: Vec<u8>,
+    // percentage of the last frame that lingers into the next, like the DMG's slow LCD
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    ghosting: u8,
+    // the last frame as shown with ghosting, empty while it's off
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    ghosted_frame: Vec<u8>,
+    // tint each pixel by where it came from and outline the sprites, see overlay_line
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    layer_overlay: bool,
+    // see Palettes, only the shades shown change, the registers read back as the game set them
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    palette_overrides: [[Option<u8>; 4]; 3],
+    // a ScanlineRegisters for each line drawn so far this frame, and for every line of the last
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    current_scanline_registers: Vec<ScanlineRegisters>,
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    scanline_registers: Vec<ScanlineRegisters>,
 
     vblank_set: bool,
+    // frames finished since power on, blank ones included
+    frame_count: u64,
+    first_line_after_enable: bool,
+    first_frame_after_enable: bool,
+    // the STAT interrupt sources OR'd together, the interrupt fires when this goes high
+    stat_line: bool,
 }
 
 impl LCD {
@@ -201,7 +359,26 @@ impl LCD {
     const MODE2_CYCLE_RANGE: i16 = LCD::SCANLINE_CYCLE_TOTAL - 80;
     const MODE3_CYCLE_RANGE: i16 = LCD::MODE2_CYCLE_RANGE - 172;
 
-    const VBLANK_HEIGHT: u8 = 154;
+    // what the layer overlay mixes into pixels from each source, half and half
+    const OVERLAY_BG: [u8; 3] = [0x40, 0x80, 0xFF];
+    const OVERLAY_WINDOW: [u8; 3] = [0x40, 0xFF, 0x60];
+    const OVERLAY_SPRITE_0: [u8; 3] = [0xFF, 0x40, 0x40];
+    const OVERLAY_SPRITE_1: [u8; 3] = [0xFF, 0x40, 0xFF];
+    // drawn over the sprites' edges as they are
+    const OVERLAY_OUTLINE: [u8; 4] = [0xFF, 0xD0, 0x00, 0xFF];
+
+    // lines 144 to 153 are vblank
+    const LAST_LINE: u8 = 153;
+    // cycles at the start of a line where LY has changed but LYC hasn't been compared against it yet
+    const LY_COMPARE_DELAY: i16 = 4;
+    // cycles at the start of line 153 where LY still reads 153, compared against LYC for the last few
+    const LAST_LINE_LY_CYCLES: i16 = 2 * LCD::LY_COMPARE_DELAY;
+
+    // the window starts at WX - 7, so anything past this is off the right edge
+    const WINDOW_X_MAX: u8 = SCREEN_WIDTH + 6;
+
+    // 2 bytes per tile row
+    const TILE_ROW_COUNT: usize = 0x1800 / 2;
 
     pub fn new() -> LCD {
         LCD {
@@ -209,6 +386,10 @@ impl LCD {
             vram_bg_maps: [0x00; 0x0800],
             vram_oam: [OAM::new(); 40],
 
+            // zeroed tile data decodes to zeroed rows, so nothing starts dirty
+            decoded_tile_rows: [[0x00; 8]; LCD::TILE_ROW_COUNT],
+            dirty_tile_rows: [false; LCD::TILE_ROW_COUNT],
+
             control: Control(0x80),
             status: Status(0x00),
 
@@ -216,6 +397,7 @@ impl LCD {
             scroll_x: 0x00,
 
             scanline_cycle_count: LCD::SCANLINE_CYCLE_TOTAL,
+            disabled_cycle_count: 0,
             lcd_y: 0x00,
             lcd_y_compare: 0x00,
 
@@ -225,21 +407,40 @@ impl LCD {
 
             window_y: 0x00,
             window_x: 0x00,
+            window_y_triggered: false,
+            window_line: 0,
+
+            line: [LinePixel::BLANK; SCREEN_WIDTH as usize],
 
             frame: [0x00; SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize * 4],
-            last_frame_hash: 0,
+            #[cfg(feature = "std")]
+            frame_dumper: None,
+
+            pixel_format: PixelFormat::RGBA8888,
+            converted_frame: Vec::new(),
+            ghosting: 0,
+            ghosted_frame: Vec::new(),
+            layer_overlay: false,
+            palette_overrides: [[None; 4]; 3],
+            current_scanline_registers: Vec::with_capacity(SCREEN_HEIGHT as usize),
+            scanline_registers: Vec::with_capacity(SCREEN_HEIGHT as usize),
 
             vblank_set: false,
+            frame_count: 0,
+            first_line_after_enable: false,
+            first_frame_after_enable: false,
+            stat_line: false,
         }
     }
 
-    pub fn read_register(&self, addr: u16) -> u8 {
-        match addr {
+    // None for addresses outside 0xFF40 - 0xFF4B
+    pub fn read_register(&self, addr: u16) -> Option<u8> {
+        Some(match addr {
             0xFF40 => self.control.bits(),
-            0xFF41 => self.status.bits(),
+            0xFF41 => self.status.bits() | 0x80, // bit 7 is unused and always reads as set
             0xFF42 => self.scroll_y,
             0xFF43 => self.scroll_x,
-            0xFF44 => self.lcd_y,
+            0xFF44 => self.ly(),
             0xFF45 => self.lcd_y_compare,
             0xFF46 => 0xFF, // DMA Transfer // TODO: write-only, I'm assuming the read value here
             0xFF47 => self.bg_palette.bits(), // BG/Window palette
@@ -247,22 +448,33 @@ impl LCD {
             0xFF49 => self.sprite_palette_1.bits(), // sprite palette 1
             0xFF4A => self.window_y,
             0xFF4B => self.window_x,
-            _ => unreachable!(), // mmu will only send us addresses in 0xFF40 - 0xFF4B range
-        }
+            _ => return None,
+        })
     }
 
-    pub fn write_register(&mut self, addr: u16, value: u8) {
+    pub fn write_register(&mut self, addr: u16, value: u8, ih: &mut InterruptHandler) -> Option<()> {
         match addr {
             0xFF40 => {
-                if self.control.enable() != (value & 0x80 > 0) {
+                let enable = value & 0x80 > 0;
+                if self.control.enable() != enable {
                     self.lcd_y = 0;
-                    self.scanline_cycle_count = LCD::SCANLINE_CYCLE_TOTAL;
-                    let mode = if value & 0x80 > 0 { Mode::OAMSearch } else { Mode::HBlank };
-                    self.status.set_mode_flag(mode);
+                    self.disabled_cycle_count = 0;
+                    self.stat_line = false;
+                    self.start_frame();
+                    self.status.set_mode_flag(Mode::HBlank);
+                    if enable {
+                        // the first line after switching on skips OAM search and is a cycle short,
+                        // and the first frame never makes it to the screen
+                        self.scanline_cycle_count = LCD::SCANLINE_CYCLE_TOTAL - 4;
+                        self.first_line_after_enable = true;
+                        self.first_frame_after_enable = true;
+                    } else {
+                        self.scanline_cycle_count = LCD::SCANLINE_CYCLE_TOTAL;
+                    }
                 }
                 self.control.set_bits(value)
             },
-            0xFF41 => self.status.set_bits(value),
+            0xFF41 => self.write_status(value, ih),
             0xFF42 => self.scroll_y = value,
             0xFF43 => self.scroll_x = value,
             0xFF44 => self.lcd_y = 0x00, // writing resets this counter TODO: no it doesn't
@@ -273,63 +485,86 @@ impl LCD {
             0xFF49 => self.sprite_palette_1.set_bits(value), // sprite palette 1
             0xFF4A => self.window_y = value,
             0xFF4B => self.window_x = value,
-            _ => unreachable!(), // mmu will only send us addresses in 0xFF40 - 0xFF4B range
+            _ => return None,
         }
+        Some(())
     }
 
-    pub fn read_oam(&self, addr: u16) -> u8 {
-        let oam_addr = (addr / 4) as usize;
-        match addr % 4 {
-            0x0 => self.vram_oam[oam_addr].y_position,
-            0x1 => self.vram_oam[oam_addr].x_position,
-            0x2 => self.vram_oam[oam_addr].tile_number,
-            0x3 => self.vram_oam[oam_addr].attributes.bits() as u8,
-            _ => unreachable!(),
+    fn write_status(&mut self, value: u8, ih: &mut InterruptHandler) {
+        // DMG bug: for a cycle during the write every interrupt source is enabled,
+        // so writing in hblank, vblank, or on a coincidence interrupts even if the game enables none of them
+        let bug_line = self.control.enable() && match self.status.mode_flag() {
+            Mode::HBlank | Mode::VBlank => true,
+            Mode::OAMSearch | Mode::Transfer => self.status.coincidence_flag(),
+        };
+        if bug_line && !self.stat_line {
+            self.lcdc_interrupt(ih);
+            self.stat_line = true;
         }
+
+        // the mode and coincidence bits are read-only
+        self.status.set_bits((value & 0b0111_1000) | (self.status.bits() & 0b0000_0111));
     }
 
-    pub fn write_oam(&mut self, addr: u16, value: u8) {
-        let oam_addr = (addr / 4) as usize;
-        match addr % 4 {
-            0x0 => self.vram_oam[oam_addr].y_position = value,
-            0x1 => self.vram_oam[oam_addr].x_position = value,
-            0x2 => self.vram_oam[oam_addr].tile_number = value,
-            0x3 => self.vram_oam[oam_addr].attributes.set_bits(value),
-            _ => unreachable!(),
-        }
+    // tile data and both maps, whatever mode the LCD's in
+    pub fn fill_vram(&mut self, value: u8) {
+        self.vram_tile_data = [value; 0x1800];
+        self.vram_bg_maps = [value; 0x0800];
+        self.dirty_tile_rows = [true; LCD::TILE_ROW_COUNT];
     }
 
-    pub fn step(&mut self, ih: &mut InterruptHandler) {
-        self.set_status(ih);
+    // the cpu's access to VRAM, addr being from 0x8000. the LCD has it to itself while it's
+    // transferring pixels: reads give 0xFF and writes are dropped
+    pub fn read_vram(&self, addr: u16) -> u8 {
+        if self.vram_locked() { 0xFF } else { self.peek_vram(addr) }
+    }
 
-        if !self.control.enable() {
-            return;
+    pub fn write_vram(&mut self, addr: u16, value: u8) {
+        if !self.vram_locked() {
+            self.poke_vram(addr, value);
         }
+    }
 
-        self.scanline_cycle_count -= 4;
-        if self.scanline_cycle_count > 0 {
-            return;
+    // VRAM whatever mode the LCD's in, for the boot logo and debugging tools
+    pub fn peek_vram(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000 ..= 0x17FF => self.vram_tile_data[addr as usize],
+            _ => self.vram_bg_maps[(addr as usize - 0x1800) % 0x0800],
         }
+    }
 
-        self.scanline_cycle_count = LCD::SCANLINE_CYCLE_TOTAL;
-        match self.lcd_y {
-            0..=SCREEN_HEIGHT if self.lcd_y < SCREEN_HEIGHT => {
-                self.draw_scanline();
-                self.lcd_y += 1;
-            },
-            SCREEN_HEIGHT => {
-                ih.set_interrupt(Interrupt::VBlank);
-                self.lcd_y += 1;
+    pub fn poke_vram(&mut self, addr: u16, value: u8) {
+        match addr {
+            0x0000 ..= 0x17FF => {
+                self.vram_tile_data[addr as usize] = value;
+                self.dirty_tile_rows[addr as usize / 2] = true;
             },
-            // TODO: pad this out to reduce lag?
-            // (give the emulated cpu more time than
-            // the actual hardware cpu would have had
-            // to process each frame)
-            LCD::VBLANK_HEIGHT => self.lcd_y = 0,
-            _ => self.lcd_y += 1,
+            _ => self.vram_bg_maps[(addr as usize - 0x1800) % 0x0800] = value,
         }
     }
 
+    // None for addresses past the end of OAM
+    pub fn read_oam(&self, addr: u16) -> Option<u8> {
+        let sprite = self.vram_oam.get((addr / 4) as usize)?;
+        Some(match addr % 4 {
+            0x0 => sprite.y_position,
+            0x1 => sprite.x_position,
+            0x2 => sprite.tile_number,
+            _ => sprite.attributes.bits() as u8,
+        })
+    }
+
+    pub fn write_oam(&mut self, addr: u16, value: u8) -> Option<()> {
+        let sprite = self.vram_oam.get_mut((addr / 4) as usize)?;
+        match addr % 4 {
+            0x0 => sprite.y_position = value,
+            0x1 => sprite.x_position = value,
+            0x2 => sprite.tile_number = value,
+            _ => sprite.attributes.set_bits(value),
+        }
+        Some(())
+    }
+
     pub fn vblank_reached(&mut self) -> bool {
         if self.vblank_set {
             self.vblank_set = false;
@@ -339,83 +574,228 @@ impl LCD {
         }
     }
 
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    // the frame in the configured pixel format
     pub fn get_frame(&self) -> &[u8] {
+        match self.pixel_format {
+            PixelFormat::RGBA8888 => &self.frame,
+            _ => &self.converted_frame,
+        }
+    }
+
+    // the frame as RGBA whatever the configured pixel format is
+    pub fn rgba_frame(&self) -> &[u8] {
         &self.frame
     }
 
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.pixel_format
+    }
+
+    pub fn set_pixel_format(&mut self, format: PixelFormat) {
+        self.pixel_format = format;
+        self.convert_frame();
+    }
+
+    // some games flicker sprites on alternate frames for transparency, relying on the LCD
+    // being too slow to show it. clamped to 90%, any more and the picture barely moves
+    pub fn set_ghosting(&mut self, percent: u8) {
+        self.ghosting = percent.min(90);
+        self.ghosted_frame = Vec::new();
+    }
+
+    pub fn set_layer_overlay(&mut self, on: bool) {
+        self.layer_overlay = on;
+    }
+
+    pub fn palettes(&self) -> Palettes {
+        Palettes {
+            registers: [self.bg_palette.bits(), self.sprite_palette_0.bits(), self.sprite_palette_1.bits()],
+            overrides: self.palette_overrides,
+        }
+    }
+
+    // one for each line of the last frame, empty if it was blank
+    pub fn scanline_registers(&self) -> &[ScanlineRegisters] {
+        &self.scanline_registers
+    }
+
+    // palette 0 to 2 for BGP, OBP0 and OBP1, None to go back to the game's shade
+    pub fn set_palette_override(&mut self, palette: usize, entry: usize, shade: Option<u8>) {
+        self.palette_overrides[palette][entry] = shade.map(|shade| shade & 0b11);
+    }
+
+    // everything a save state has, for snapshots. the frontend's settings stay as they are
+    pub fn copy_state_from(&mut self, other: &LCD) {
+        self.vram_tile_data = other.vram_tile_data;
+        self.vram_bg_maps = other.vram_bg_maps;
+        self.vram_oam = other.vram_oam;
+        self.decoded_tile_rows = other.decoded_tile_rows;
+        self.dirty_tile_rows = other.dirty_tile_rows;
+        self.control = other.control;
+        self.status = other.status;
+        self.scroll_y = other.scroll_y;
+        self.scroll_x = other.scroll_x;
+        self.scanline_cycle_count = other.scanline_cycle_count;
+        self.disabled_cycle_count = other.disabled_cycle_count;
+        self.lcd_y = other.lcd_y;
+        self.lcd_y_compare = other.lcd_y_compare;
+        self.bg_palette = other.bg_palette;
+        self.sprite_palette_0 = other.sprite_palette_0;
+        self.sprite_palette_1 = other.sprite_palette_1;
+        self.window_y = other.window_y;
+        self.window_x = other.window_x;
+        self.window_y_triggered = other.window_y_triggered;
+        self.window_line = other.window_line;
+        self.line = other.line;
+        self.frame = other.frame;
+        self.vblank_set = other.vblank_set;
+        self.frame_count = other.frame_count;
+        self.first_line_after_enable = other.first_line_after_enable;
+        self.first_frame_after_enable = other.first_frame_after_enable;
+        self.stat_line = other.stat_line;
+    }
+
+    // keep the frontend's settings from the LCD a save state is replacing
+    #[cfg(feature = "serialize")]
+    pub fn keep_settings_from(&mut self, old: &mut LCD) {
+        #[cfg(feature = "std")]
+        {
+            self.frame_dumper = old.frame_dumper.take();
+        }
+        self.set_pixel_format(old.pixel_format);
+        self.set_ghosting(old.ghosting);
+        self.layer_overlay = old.layer_overlay;
+        self.palette_overrides = old.palette_overrides;
+    }
+
+    #[cfg(feature = "std")]
+    pub fn set_frame_dumper(&mut self, dumper: Option<FrameDumper>) {
+        self.frame_dumper = dumper;
+    }
+
+    pub fn vram_locked(&self) -> bool {
+        self.control.enable() && self.status.mode_flag() == Mode::Transfer
+    }
+
+    // the cpu can't access OAM while the LCD is searching it or transferring pixels
+    pub fn oam_locked(&self) -> bool {
+        self.control.enable() && match self.status.mode_flag() {
+            Mode::OAMSearch | Mode::Transfer => true,
+            Mode::HBlank | Mode::VBlank => false,
+        }
+    }
+
     fn set_status(&mut self, ih: &mut InterruptHandler) {
-        // if the LCD is disabled, reset scanline cycles and y position, and force VBlank mode
+        // if the LCD is disabled, reset scanline cycles and y position, and hold it in HBlank mode
         if !self.control.enable() {
             self.scanline_cycle_count = LCD::SCANLINE_CYCLE_TOTAL;
             self.lcd_y = 0;
-            self.status.set_mode_flag(Mode::VBlank);
+            self.status.set_mode_flag(Mode::HBlank);
             return;
         }
 
         // store current mode so we can detect changes
         let prev_mode = self.status.mode_flag();
         // set mode based on scanline y position and cycle count
-        if self.lcd_y >= SCREEN_HEIGHT {
-            self.status.set_mode_flag(Mode::VBlank);
-        } else {
-            if self.scanline_cycle_count >= LCD::MODE2_CYCLE_RANGE as i16 {
-                self.status.set_mode_flag(Mode::OAMSearch);
-            } else if self.scanline_cycle_count >= LCD::MODE3_CYCLE_RANGE as i16 {
-                self.status.set_mode_flag(Mode::Transfer);
-            } else {
-                self.status.set_mode_flag(Mode::HBlank);
-            }
+        self.status.set_mode_flag(self.mode_at(self.scanline_cycle_count));
+        if prev_mode != self.status.mode_flag() && self.status.mode_flag() == Mode::VBlank {
+            self.vblank(ih);
         }
-        // if mode changed, and interrupts for the new mode are enabled, set LCDC interrupt
-        if prev_mode != self.status.mode_flag() {
-            match self.status.mode_flag() {
-                Mode::HBlank => self.hblank(ih),
-                Mode::VBlank => self.vblank(ih),
-                Mode::OAMSearch => self.oam_search(ih),
-                Mode::Transfer => (),
-            }
+
+        // flag when we're on the game-specified scanline lcd_y_compare
+        self.status.set_coincidence_flag(self.coincidence());
+
+        // all the STAT sources share one interrupt line, so a source going high
+        // while another is already holding the line up doesn't interrupt again
+        let stat_line = self.stat_line_level();
+        if stat_line && !self.stat_line {
+            self.lcdc_interrupt(ih);
         }
+        self.stat_line = stat_line;
+    }
 
-        // flag and interrupt when we're on the game-specified scanline lcd_y_compare
-        if self.lcd_y == self.lcd_y_compare {
-            self.status.set_coincidence_flag(true);
-            if self.status.ly_coincidence_interrupt() {
-                self.lcdc_interrupt(ih)
-            }
+    // cycles spent on the current line so far
+    fn line_cycles(&self) -> i16 {
+        LCD::SCANLINE_CYCLE_TOTAL - self.scanline_cycle_count
+    }
+
+    // LY as the cpu sees it, line 153 only reads as 153 briefly before reading as 0 for the rest of the line
+    fn ly(&self) -> u8 {
+        if self.lcd_y == LCD::LAST_LINE && self.line_cycles() >= LCD::LAST_LINE_LY_CYCLES {
+            0
         } else {
-            self.status.set_coincidence_flag(false);
+            self.lcd_y
+        }
+    }
+
+    fn coincidence(&self) -> bool {
+        // LY changes at the start of the line but isn't compared until a cycle later,
+        // except on line 0 where LY has already read as 0 since early in line 153
+        if self.lcd_y != 0 && self.line_cycles() < LCD::LY_COMPARE_DELAY {
+            return false;
         }
+        self.ly() == self.lcd_y_compare
+    }
+
+    fn stat_line_level(&self) -> bool {
+        let mode_interrupt = match self.status.mode_flag() {
+            Mode::HBlank => self.status.hblank_interrupt(),
+            Mode::VBlank => self.status.vblank_interrupt(),
+            Mode::OAMSearch => self.status.oam_interrupt(),
+            Mode::Transfer => false,
+        };
+        mode_interrupt || (self.status.coincidence_flag() && self.status.ly_coincidence_interrupt())
     }
 
-    fn hblank(&self, ih: &mut InterruptHandler) {
-        if self.status.hblank_interrupt() {
-            self.lcdc_interrupt(ih)
+    fn mode_at(&self, scanline_cycle_count: i16) -> Mode {
+        if self.lcd_y >= SCREEN_HEIGHT {
+            Mode::VBlank
+        } else if scanline_cycle_count >= LCD::MODE2_CYCLE_RANGE {
+            if self.first_line_after_enable { Mode::HBlank } else { Mode::OAMSearch }
+        } else if scanline_cycle_count >= LCD::MODE3_CYCLE_RANGE {
+            Mode::Transfer
+        } else {
+            Mode::HBlank
         }
     }
 
     fn vblank(&mut self, ih: &mut InterruptHandler) {
-        if self.status.vblank_interrupt() {
-            self.lcdc_interrupt(ih);
+        ih.set_interrupt(Interrupt::VBlank);
+        if self.first_frame_after_enable {
+            self.first_frame_after_enable = false;
+            self.blank_frame();
+        } else {
+            self.finish_frame();
         }
+    }
+
+    // a white screen, put out for the first frame after the LCD is switched on, and every frame's
+    // worth of cycles while it's off so frontends still have frames to keep pacing and input going
+    fn blank_frame(&mut self) {
+        self.current_scanline_registers.clear();
+        for byte in self.frame.iter_mut() {
+            *byte = 0xFF;
+        }
+        self.finish_frame();
+    }
 
+    fn finish_frame(&mut self) {
         self.vblank_set = true;
-        
-//        use std::hash::{Hash, Hasher};
-//        use std::collections::hash_map::DefaultHasher;
-//        let mut hasher = DefaultHasher::new();
-//        self.frame.hash(&mut hasher);
-//        let frame_hash = hasher.finish();
-//        if frame_hash != self.last_frame_hash {
-//            self.last_frame_hash = frame_hash;
-//            let _ = self.save_frame();
-//            self.frame = [0x00; SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize * 4];
-//            let _ = self.save_tile_data();
-//        }
-    }
-
-    fn oam_search(&self, ih: &mut InterruptHandler) {
-        if self.status.oam_interrupt() {
-            self.lcdc_interrupt(ih);
+        self.frame_count += 1;
+        mem::swap(&mut self.current_scanline_registers, &mut self.scanline_registers);
+        self.current_scanline_registers.clear();
+        self.apply_ghosting();
+        self.convert_frame();
+
+        #[cfg(feature = "std")]
+        {
+            if let Some(dumper) = &self.frame_dumper {
+                dumper.dump(self.frame.to_vec(), self.render_tile_data());
+            }
         }
     }
 
@@ -423,87 +803,224 @@ impl LCD {
         ih.set_interrupt(Interrupt::LCDC);
     }
 
+    fn start_frame(&mut self) {
+        self.window_y_triggered = false;
+        self.window_line = 0;
+    }
+
     fn draw_scanline(&mut self) {
+        if self.current_scanline_registers.len() < SCREEN_HEIGHT as usize {
+            self.current_scanline_registers.push(ScanlineRegisters {
+                lcdc: self.control.bits(),
+                scy: self.scroll_y,
+                scx: self.scroll_x,
+                wy: self.window_y,
+                wx: self.window_x,
+                bgp: self.bg_palette.bits(),
+                obp0: self.sprite_palette_0.bits(),
+                obp1: self.sprite_palette_1.bits(),
+            });
+        }
+
+        // WY is compared against every line, so a mid-frame change takes effect when LY next matches it
+        if self.lcd_y == self.window_y {
+            self.window_y_triggered = true;
+        }
+
+        // on the DMG, clearing the bg enable bit blanks both the bg and the window to white,
+        // and sprites then always draw over it
         if self.control.bg_enable() {
             self.draw_bg();
+        } else {
+            self.clear_bg();
         }
 
         if self.control.sprite_enable() {
             self.draw_sprites();
         }
+
+        self.colour_line();
+        if self.layer_overlay {
+            self.overlay_line();
+        }
     }
 
     fn draw_bg(&mut self) {
         use TileDataAddressRange::*;
-        use TileMapAddressRange::*;
-        let in_window = self.control.window_enable() && self.lcd_y >= self.window_y;
+
+        let window_visible = self.control.window_enable()
+            && self.window_y_triggered
+            && self.window_x <= LCD::WINDOW_X_MAX;
+        // WX below 7 pushes the window's left edge off the screen rather than wrapping
+        let window_start = self.window_x as i16 - 7;
 
         let tile_data_offset = match self.control.tile_data() {
             TileDataAddr8000_8FFF => 0x0000 as u16,
             TileDataAddr8800_97FF => 0x0800 as u16,
         };
 
-        let map = if in_window { self.control.window_map() } else { self.control.bg_map() };
-        let tile_map_offset = match map {
-            TileMapAddr9800_9BFF => 0x0000 as u16,
-            TileMapAddr9C00_9FFF => 0x0400 as u16,
-        };
-
-        let map_y = if in_window {
-            self.lcd_y - self.window_y
-        } else {
-            self.scroll_y.wrapping_add(self.lcd_y)
-        };
+        let bg_map_offset = LCD::tile_map_offset(self.control.bg_map());
+        let window_map_offset = LCD::tile_map_offset(self.control.window_map());
+        let bg_y = self.scroll_y.wrapping_add(self.lcd_y);
 
-        let tile_y = (map_y / 8) as u16;
+        // the map address, row within the tile, and decoded row of the last tile we drew from,
+        // so each tile is only looked up once per scanline
+        let mut current_tile: Option<(u16, u8, [u8; 8])> = None;
 
         for pixel_x in 0..SCREEN_WIDTH {
-            // TODO: optimize this loop to do blocks of 8 pixels?
-            // otherwise we calculate the addresses of and read the same bytes 8 times
-            let map_x = if in_window && pixel_x >= self.window_x - 7 {
+            let (source, tile_map_offset, map_x, map_y) = if window_visible && pixel_x as i16 >= window_start {
                 // translate to window space if we're in it
-                pixel_x - (self.window_x - 7)
+                (PixelSource::Window, window_map_offset, (pixel_x as i16 - window_start) as u8, self.window_line)
             } else {
-                pixel_x.wrapping_add(self.scroll_x)
+                (PixelSource::Background, bg_map_offset, pixel_x.wrapping_add(self.scroll_x), bg_y)
             };
 
             let tile_x = (map_x / 8) as u16;
+            let tile_y = (map_y / 8) as u16;
+            let tile_row = map_y % 8;
 
             let tile_map_addr = tile_map_offset + (tile_y * 32) + tile_x;
 
-            let tile_id = match self.control.tile_data() {
-                TileDataAddr8000_8FFF => self.vram_bg_maps[tile_map_addr as usize] as u16,
-                TileDataAddr8800_97FF => {
-                    (self.vram_bg_maps[tile_map_addr as usize] as i8 as i16 + 128) as u16
-                }
+            let row = match current_tile {
+                Some((addr, current_row, row)) if addr == tile_map_addr && current_row == tile_row => row,
+                _ => {
+                    let tile_id = match self.control.tile_data() {
+                        TileDataAddr8000_8FFF => self.vram_bg_maps[tile_map_addr as usize] as u16,
+                        TileDataAddr8800_97FF => {
+                            (self.vram_bg_maps[tile_map_addr as usize] as i8 as i16 + 128) as u16
+                        }
+                    };
+
+                    let tile_index = (tile_data_offset / 16 + tile_id) as usize;
+                    let row = self.tile_row(tile_index * 8 + tile_row as usize);
+                    current_tile = Some((tile_map_addr, tile_row, row));
+                    row
+                },
             };
 
-            let tile_data_addr = tile_data_offset + (tile_id * 16);
-            let tile_row_offset = ((map_y % 8) * 2) as u16;
+            let index = row[(map_x % 8) as usize];
+            self.line[pixel_x as usize] = LinePixel { source, index, bg_index: index };
+        }
+
+        if window_visible {
+            self.window_line += 1;
+        }
+    }
+
+    fn clear_bg(&mut self) {
+        self.line = [LinePixel::BLANK; SCREEN_WIDTH as usize];
+    }
+
+    // turn the finished line's palette indices into colours in the frame
+    fn colour_line(&mut self) {
+        let [bg, sprite_0, sprite_1] = self.shown_palettes();
+        let line_start = self.lcd_y as usize * SCREEN_WIDTH as usize * 4;
+        let line_end = line_start + SCREEN_WIDTH as usize * 4;
+        let frame_line = &mut self.frame[line_start..line_end];
+        for (line_pixel, pixel) in self.line.iter().zip(frame_line.chunks_exact_mut(4)) {
+            let index = line_pixel.index as usize;
+            let shade = match line_pixel.source {
+                PixelSource::Blank => Shade::White,
+                PixelSource::Background | PixelSource::Window => bg[index],
+                PixelSource::Sprite(0) => sprite_0[index],
+                PixelSource::Sprite(_) => sprite_1[index],
+            };
+            pixel.clone_from_slice(shade.into_pixel());
+        }
+    }
+
+    // tints the coloured line by where each pixel came from: blue for the bg, green for the window,
+    // red and magenta for the two sprite palettes. then outlines every sprite on the line, drawn
+    // or not, so ones hidden by priority or transparency still show where they are
+    fn overlay_line(&mut self) {
+        let y_size = self.sprite_height();
+        let line_start = self.lcd_y as usize * SCREEN_WIDTH as usize * 4;
+        let line_end = line_start + SCREEN_WIDTH as usize * 4;
+        let frame_line = &mut self.frame[line_start..line_end];
+        for (line_pixel, pixel) in self.line.iter().zip(frame_line.chunks_exact_mut(4)) {
+            let tint = match line_pixel.source {
+                PixelSource::Blank => continue,
+                PixelSource::Background => LCD::OVERLAY_BG,
+                PixelSource::Window => LCD::OVERLAY_WINDOW,
+                PixelSource::Sprite(0) => LCD::OVERLAY_SPRITE_0,
+                PixelSource::Sprite(_) => LCD::OVERLAY_SPRITE_1,
+            };
+            for (channel, tint) in pixel.iter_mut().zip(tint.iter()) {
+                *channel = ((*channel as u16 + *tint as u16) / 2) as u8;
+            }
+        }
 
-            let pixel_start = (tile_data_addr + tile_row_offset) as usize;
-            let pixel_end = pixel_start + 2;
-            let pixel_data = &self.vram_tile_data[pixel_start..pixel_end];
+        if !self.control.sprite_enable() {
+            return;
+        }
+        for sprite in self.vram_oam.iter() {
+            let y_pos = sprite.y_position;
+            if y_pos <= (self.lcd_y + 16 - y_size) || y_pos > (self.lcd_y + 16) {
+                continue;
+            }
+            let sprite_line = self.lcd_y + 16 - y_pos;
+            let left = sprite.x_position as i16 - 8;
+            // the whole width on the top and bottom lines, just the sides in between
+            let columns: &[i16] = if sprite_line == 0 || sprite_line == y_size - 1 {
+                &[0, 1, 2, 3, 4, 5, 6, 7]
+            } else {
+                &[0, 7]
+            };
+            for column in columns {
+                let pixel_x = left + column;
+                if (0..SCREEN_WIDTH as i16).contains(&pixel_x) {
+                    let offset = pixel_x as usize * 4;
+                    frame_line[offset..offset + 4].copy_from_slice(&LCD::OVERLAY_OUTLINE);
+                }
+            }
+        }
+    }
 
-            let pixel_bit = 7 - (map_x % 8);
+    // each palette's shades with the overrides in place
+    fn shown_palettes(&self) -> [[Shade; 4]; 3] {
+        let mut shown = [[Shade::White; 4]; 3];
+        let palettes = [&self.bg_palette, &self.sprite_palette_0, &self.sprite_palette_1];
+        for (palette, shades) in shown.iter_mut().enumerate() {
+            for (entry, shade) in shades.iter_mut().enumerate() {
+                *shade = match self.palette_overrides[palette][entry] {
+                    Some(overridden) => Shade::from(overridden),
+                    None => palettes[palette].colour(entry),
+                };
+            }
+        }
+        shown
+    }
 
-            let shade = self.get_shade(pixel_data, pixel_bit, &self.bg_palette);
-            let pixel = shade.into_pixel();
+    fn tile_map_offset(map: TileMapAddressRange) -> u16 {
+        match map {
+            TileMapAddressRange::TileMapAddr9800_9BFF => 0x0000,
+            TileMapAddressRange::TileMapAddr9C00_9FFF => 0x0400,
+        }
+    }
 
-            let frame_pixel_start =
-                (self.lcd_y as usize * SCREEN_WIDTH as usize * 4) + (pixel_x as usize * 4);
-            let frame_pixel_end = frame_pixel_start + 4;
-            let pixel_slice = &mut self.frame[frame_pixel_start..frame_pixel_end];
-            pixel_slice.clone_from_slice(&pixel[..4]);
+    // palette indices for a row of tile pixels, left to right
+    fn tile_row(&mut self, row_index: usize) -> [u8; 8] {
+        if self.dirty_tile_rows[row_index] {
+            let pixel_data = &self.vram_tile_data[row_index * 2..row_index * 2 + 2];
+            let mut row = [0x00; 8];
+            for (column, index) in row.iter_mut().enumerate() {
+                *index = self.get_palette_index(pixel_data, 7 - column as u8) as u8;
+            }
+            self.decoded_tile_rows[row_index] = row;
+            self.dirty_tile_rows[row_index] = false;
         }
+        self.decoded_tile_rows[row_index]
     }
 
-    fn draw_sprites(&mut self) {
-        // set sprite height from control register
-        let y_size = match self.control.sprite_size() {
+    fn sprite_height(&self) -> u8 {
+        match self.control.sprite_size() {
             SpriteSizes::Size8x8 => 8,
             SpriteSizes::Size8x16 => 16,
-        };
+        }
+    }
+
+    fn draw_sprites(&mut self) {
+        let y_size = self.sprite_height();
 
         for sprite in self.vram_oam.iter() {
             let y_pos = sprite.y_position;
@@ -538,26 +1055,20 @@ impl LCD {
                     7 - sprite_column
                 };
 
-                let palette = match sprite.attributes.palette() {
-                    0 => &self.sprite_palette_0,
-                    1 => &self.sprite_palette_1,
-                    _ => unreachable!(), // 1 bit field
-                };
-
                 let palette_index = self.get_palette_index(pixel_data, pixel_bit);
                 // palette index 0 is transparent for sprites
                 if palette_index == 0 {
                     continue;
                 }
 
-                let shade = palette.colour(palette_index);
-                let pixel = shade.into_pixel();
+                let line_pixel = &mut self.line[pixel_x as usize];
+                // behind-bg sprites only show through bg colour 0
+                if sprite.attributes.obj_to_bg_priority() && line_pixel.bg_index != 0 {
+                    continue;
+                }
 
-                let frame_pixel_start =
-                    (self.lcd_y as usize * SCREEN_WIDTH as usize * 4) + (pixel_x as usize * 4);
-                let frame_pixel_end = frame_pixel_start + 4;
-                let pixel_slice = &mut self.frame[frame_pixel_start..frame_pixel_end];
-                pixel_slice.clone_from_slice(&pixel[..4]);
+                line_pixel.source = PixelSource::Sprite(sprite.attributes.palette());
+                line_pixel.index = palette_index as u8;
             }
         }
     }
@@ -573,29 +1084,54 @@ impl LCD {
         palette.colour(palette_index)
     }
 
-    fn save_frame(&self) -> Result<(), png::EncodingError> {
-        use std::fs::File;
-        use std::io::BufWriter;
-        use std::path::Path;
-        let path = Path::new(r"./frame.png");
-        let file = File::create(path)?;
-        let ref mut w = BufWriter::new(file);
+    // blends the finished frame with the last one shown, in place
+    fn apply_ghosting(&mut self) {
+        if self.ghosting == 0 {
+            return;
+        }
+        if self.ghosted_frame.len() != self.frame.len() {
+            // nothing to linger from yet
+            self.ghosted_frame = self.frame.to_vec();
+            return;
+        }
+
+        let kept = self.ghosting as u16;
+        for (new, shown) in self.frame.iter_mut().zip(self.ghosted_frame.iter_mut()) {
+            *new = ((*new as u16 * (100 - kept) + *shown as u16 * kept) / 100) as u8;
+            *shown = *new;
+        }
+    }
 
-        let mut png_encoder =
-            png::Encoder::new(w, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32);
-        png_encoder.set_color(png::ColorType::RGBA);
-        png_encoder.set_depth(png::BitDepth::Eight);
-        let mut writer = png_encoder.write_header()?;
-        writer.write_image_data(&self.frame)?;
+    fn convert_frame(&mut self) {
+        let format = self.pixel_format;
+        if format == PixelFormat::RGBA8888 {
+            self.converted_frame = Vec::new();
+            return;
+        }
 
-        Ok(())
+        self.converted_frame.resize(format.frame_size(), 0x00);
+        let bytes_per_pixel = format.bytes_per_pixel();
+        let pixels = self.frame.chunks_exact(4).zip(self.converted_frame.chunks_exact_mut(bytes_per_pixel));
+        for (rgba, out) in pixels {
+            match format {
+                PixelFormat::RGBA8888 => out.copy_from_slice(rgba), // not converted, see above
+                PixelFormat::BGRA8888 => out.copy_from_slice(&[rgba[2], rgba[1], rgba[0], rgba[3]]),
+                PixelFormat::RGB565 => {
+                    let (r, g, b) = (rgba[0] as u16, rgba[1] as u16, rgba[2] as u16);
+                    let rgb565 = ((r >> 3) << 11) | ((g >> 2) << 5) | (b >> 3);
+                    out.copy_from_slice(&rgb565.to_le_bytes());
+                },
+            }
+        }
     }
 
-    fn save_tile_data(&self) -> Result<(), png::EncodingError> {
-        let mut tile_pixels = [0x00; 256 * 96 * 4];
-        for line in 0..96 {
+    // all 384 tiles in the bg palette, 32 to a row
+    #[cfg(feature = "std")]
+    pub fn render_tile_data(&self) -> Vec<u8> {
+        let mut tile_pixels = vec![0x00; TILE_DATA_WIDTH as usize * TILE_DATA_HEIGHT as usize * 4];
+        for line in 0..TILE_DATA_HEIGHT as u16 {
             let tile_row_offset = (line % 8) * 2;
-            for col in 0..256u16 {
+            for col in 0..TILE_DATA_WIDTH as u16 {
                 let tile_id = (line / 8) * 32 + (col / 8);
                 let tile_data_offset = tile_id * 16;
 
@@ -608,26 +1144,140 @@ impl LCD {
                 let shade = self.get_shade(pixel_data, pixel_bit as u8, &self.bg_palette);
                 let pixel = shade.into_pixel();
 
-                let pixel_start = (line as usize * 256 as usize * 4) + (col as usize * 4);
+                let pixel_start = (line as usize * TILE_DATA_WIDTH as usize * 4) + (col as usize * 4);
                 let pixel_end = pixel_start + 4;
                 let pixel_slice = &mut tile_pixels[pixel_start..pixel_end];
                 pixel_slice.clone_from_slice(&pixel[..4]);
             }
         }
-        
-        use std::path::Path;
-        use std::fs::File;
-        use std::io::BufWriter;
-        let path = Path::new(r"./tiledata.png");
-        let file = File::create(path)?;
-        let ref mut w = BufWriter::new(file);
 
-        let mut png_encoder = png::Encoder::new(w, 256, 96);
-        png_encoder.set_color(png::ColorType::RGBA);
-        png_encoder.set_depth(png::BitDepth::Eight);
-        let mut writer = png_encoder.write_header()?;
-        writer.write_image_data(&tile_pixels)?;
+        tile_pixels
+    }
+
+    // the whole of the bg map currently in use, BG_MAP_SIZE square, in the bg palette
+    pub fn render_bg_map(&self) -> Vec<u8> {
+        let mut map_pixels = vec![0x00; BG_MAP_SIZE as usize * BG_MAP_SIZE as usize * 4];
+        let bg_map_offset = LCD::tile_map_offset(self.control.bg_map());
+        for y in 0..BG_MAP_SIZE {
+            for x in 0..BG_MAP_SIZE {
+                let tile_map_addr = bg_map_offset + (y / 8) * 32 + (x / 8);
+                let tile_byte = self.vram_bg_maps[tile_map_addr as usize];
+                let tile_index = match self.control.tile_data() {
+                    TileDataAddressRange::TileDataAddr8000_8FFF => tile_byte as u16,
+                    TileDataAddressRange::TileDataAddr8800_97FF => (tile_byte as i8 as i16 + 256) as u16,
+                };
+
+                let pixel_start = (tile_index * 16 + (y % 8) * 2) as usize;
+                let pixel_data = &self.vram_tile_data[pixel_start..pixel_start + 2];
+                let shade = self.get_shade(pixel_data, 7 - (x % 8) as u8, &self.bg_palette);
+
+                let pixel_start = (y as usize * BG_MAP_SIZE as usize + x as usize) * 4;
+                map_pixels[pixel_start..pixel_start + 4].clone_from_slice(&shade.into_pixel()[..4]);
+            }
+        }
+
+        map_pixels
+    }
+}
+
+impl Scheduled for LCD {
+    fn quiet_steps(&self) -> u64 {
+        if !self.control.enable() {
+            // once set_status has reset everything, a disabled LCD only has the next blank frame to do
+            let reset = self.scanline_cycle_count == LCD::SCANLINE_CYCLE_TOTAL
+                && self.lcd_y == 0
+                && self.status.mode_flag() == Mode::HBlank;
+            return if reset {
+                ((CYCLES_PER_FRAME - self.disabled_cycle_count) / 4 - 1) as u64
+            } else {
+                0
+            };
+        }
+
+        // LY and the LY compare settle in the first cycle of a line, or the first two of line 153,
+        // so step through those
+        let settle = if self.lcd_y == LCD::LAST_LINE { LCD::LAST_LINE_LY_CYCLES } else { LCD::LY_COMPARE_DELAY };
+        if self.line_cycles() < settle {
+            return 0;
+        }
+
+        // the status is reapplied every step, so we can only skip while it's already up to date
+        let mode = self.mode_at(self.scanline_cycle_count);
+        if mode != self.status.mode_flag()
+            || self.coincidence() != self.status.coincidence_flag()
+            || self.stat_line_level() != self.stat_line {
+            return 0;
+        }
+
+        // skip until the step that either changes mode, or takes the cycle count to 0 and ends the line
+        let mode_floor = match mode {
+            Mode::OAMSearch => LCD::MODE2_CYCLE_RANGE,
+            Mode::Transfer => LCD::MODE3_CYCLE_RANGE,
+            Mode::HBlank | Mode::VBlank => 0,
+        };
+        let floor = core::cmp::max(mode_floor, 5);
+        if self.scanline_cycle_count < floor {
+            0
+        } else {
+            ((self.scanline_cycle_count - floor) / 4 + 1) as u64
+        }
+    }
+
+    fn skip(&mut self, steps: u64) {
+        // a disabled LCD is only ever skipped once it's been reset, and then only the blank frame timer ticks
+        if self.control.enable() {
+            self.scanline_cycle_count -= (steps * 4) as i16;
+        } else {
+            self.disabled_cycle_count += (steps * 4) as u32;
+        }
+    }
+
+    fn step(&mut self, ih: &mut InterruptHandler) {
+        self.set_status(ih);
+
+        if !self.control.enable() {
+            self.disabled_cycle_count += 4;
+            if self.disabled_cycle_count >= CYCLES_PER_FRAME {
+                self.disabled_cycle_count = 0;
+                self.blank_frame();
+            }
+            return;
+        }
+
+        self.scanline_cycle_count -= 4;
+        if self.scanline_cycle_count > 0 {
+            return;
+        }
 
-        Ok(())
+        self.scanline_cycle_count = LCD::SCANLINE_CYCLE_TOTAL;
+        self.first_line_after_enable = false;
+        match self.lcd_y {
+            0..=SCREEN_HEIGHT if self.lcd_y < SCREEN_HEIGHT => {
+                self.draw_scanline();
+                self.lcd_y += 1;
+            },
+            // TODO: pad this out to reduce lag?
+            // (give the emulated cpu more time than
+            // the actual hardware cpu would have had
+            // to process each frame)
+            LCD::LAST_LINE => {
+                self.lcd_y = 0;
+                self.start_frame();
+            },
+            _ => self.lcd_y += 1,
+        }
+
+        // the new line's LY is visible straight away, so bring the status up to date with it
+        self.set_status(ih);
     }
 }
+
+#[cfg(feature = "serialize")]
+fn undecoded_tile_rows() -> [[u8; 8]; LCD::TILE_ROW_COUNT] {
+    [[0x00; 8]; LCD::TILE_ROW_COUNT]
+}
+
+#[cfg(feature = "serialize")]
+fn all_tile_rows_dirty() -> [bool; LCD::TILE_ROW_COUNT] {
+    [true; LCD::TILE_ROW_COUNT]
+}
\ No newline at end of file
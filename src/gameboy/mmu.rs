@@ -1,27 +1,112 @@
-use crate::cartridge::Cartridge;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+use crate::cartridge::{Cartridge, Header};
 use crate::gameboy::interrupt::InterruptHandler;
 use crate::gameboy::timer::Timer;
 use crate::gameboy::lcd::LCD;
 use crate::gameboy::joypad::Joypad;
+use crate::gameboy::serial::{Serial, SerialDirection, SerialExchange, SerialSink};
+use crate::gameboy::scheduler::EventClock;
+use crate::gameboy::debugger::{CodeDataLog, MemoryRegion, CDL_CODE, CDL_DATA};
+use crate::gameboy::bus::Bus;
+use crate::gameboy::cheat::CheatCode;
+use crate::gameboy::fault::{BusFault, FaultLatch};
+use crate::gameboy::snapshot::Snapshot;
+use crate::clock::ClockSource;
+use crate::motion::MotionSource;
 
 //TODO: all basic stubs in here, should be rom/ram banks, vram, etc
 
+// what reads from the unusable 0xFEA0-0xFEFF region return, which differs between models
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum UnusableRegion {
+    // always 0xFF, as if nothing is driving the bus
+    OpenBus,
+    // DMG: 0x00, or 0xFF while the LCD has OAM locked (modes 2 and 3)
+    DMG,
+    // CGB revision E / AGB: the high nibble of the low address byte twice, e.g. 0xFEAx reads 0xAA
+    CGBRevE,
+}
+
+// what the 0xE000-0xFDFF echo of work RAM does
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum EchoRAM {
+    // reads and writes go through to 0xC000-0xDDFF, as on hardware
+    Mirror,
+    // reads return 0xFF and writes are dropped, handy for catching games that rely on echo RAM
+    OpenBus,
+}
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct MemoryOptions {
+    pub unusable_region: UnusableRegion,
+    pub echo_ram: EchoRAM,
+}
+
+impl Default for MemoryOptions {
+    fn default() -> MemoryOptions {
+        MemoryOptions {
+            unusable_region: UnusableRegion::OpenBus,
+            echo_ram: EchoRAM::Mirror,
+        }
+    }
+}
+
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct MMU {
+    #[cfg_attr(feature = "serialize", serde(with = "crate::cartridge::state"))]
     cart: Cartridge,
+    #[cfg_attr(feature = "serialize", serde(with = "crate::utils::big_array"))]
     system_ram: [u8; 0x2000], //0xC000-0xDFFF
+    #[cfg_attr(feature = "serialize", serde(with = "crate::utils::big_array"))]
     high_ram: [u8; 0x7F],     //0xFF80-0xFFFE
+    // channel 3's 32 4-bit samples, high nibble first. plain RAM while the channel's off,
+    // which without an APU it always is. states from before it was kept load with it zeroed
+    #[cfg_attr(feature = "serialize", serde(default))]
+    wave_ram: [u8; 0x10],     //0xFF30-0xFF3F
 
-    serial: u8,
+    serial: Serial,
 
     pub interrupt: InterruptHandler,
 
     cycles: u128,
     prev_cycles: u128,
     timer: Timer,
+    timer_clock: EventClock,
+    serial_clock: EventClock,
 
     pub lcd: LCD,
+    lcd_clock: EventClock,
 
     pub joypad: Joypad,
+
+    pub options: MemoryOptions,
+
+    // when set, rom reads are marked in it as code (fetch_u8) or data (read_u8)
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    pub code_data_log: Option<CodeDataLog>,
+
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    fault: FaultLatch,
+
+    // told about every byte sent or received over the serial port
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    serial_logger: Option<Box<dyn FnMut(SerialExchange) + Send>>,
+
+    // the page the last OAM DMA copied from, until the debugger takes it
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    dma_page: Option<u8>,
+
+    // Game Genie codes patch rom reads, GameShark codes are written at apply_cheats
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    cheats: Vec<CheatCode>,
 }
 
 impl MMU {
@@ -30,21 +115,154 @@ impl MMU {
             cart: cartridge,
             system_ram: [0x0; 0x2000],
             high_ram: [0x0; 0x7F],
+            wave_ram: [0x0; 0x10],
 
-            serial: 0x00,
+            serial: Serial::new(),
 
             interrupt: InterruptHandler::new(),
 
             cycles: 0,
             prev_cycles: 0,
             timer: Timer::new(),
+            timer_clock: EventClock::new(),
+            serial_clock: EventClock::new(),
 
             lcd: LCD::new(),
+            lcd_clock: EventClock::new(),
 
             joypad: Joypad::new(),
+
+            options: MemoryOptions::default(),
+
+            code_data_log: None,
+
+            fault: FaultLatch::default(),
+
+            serial_logger: None,
+
+            dma_page: None,
+            cheats: Vec::new(),
         }
     }
 
+    pub fn header(&self) -> &Header {
+        &self.cart.header
+    }
+
+    pub fn cartridge(&self) -> &Cartridge {
+        &self.cart
+    }
+
+    pub fn cartridge_mut(&mut self) -> &mut Cartridge {
+        &mut self.cart
+    }
+
+    // a deserialized MMU only has its cartridge's mapper state, so this puts the state into
+    // the running cartridge from old and takes that over, along with the LCD's frontend settings
+    #[cfg(feature = "serialize")]
+    pub fn take_cartridge_from(&mut self, old: &mut MMU) -> Result<(), crate::Error> {
+        old.cart.load_state(&self.cart.save_state())?;
+        core::mem::swap(&mut self.cart, &mut old.cart);
+        self.lcd.keep_settings_from(&mut old.lcd);
+        self.serial.keep_sink_from(&mut old.serial);
+        self.serial_logger = old.serial_logger.take();
+        self.cheats = core::mem::take(&mut old.cheats);
+        Ok(())
+    }
+
+    pub fn save_snapshot(&self, snapshot: &mut Snapshot) {
+        snapshot.system_ram = self.system_ram;
+        snapshot.high_ram = self.high_ram;
+        snapshot.wave_ram = self.wave_ram;
+        snapshot.serial.copy_state_from(&self.serial);
+        snapshot.interrupt = self.interrupt.clone();
+        snapshot.cycles = self.cycles;
+        snapshot.prev_cycles = self.prev_cycles;
+        snapshot.timer = self.timer.clone();
+        snapshot.timer_clock = self.timer_clock.clone();
+        snapshot.serial_clock = self.serial_clock.clone();
+        snapshot.lcd.copy_state_from(&self.lcd);
+        snapshot.lcd_clock = self.lcd_clock.clone();
+        snapshot.joypad = self.joypad.clone();
+        snapshot.mapper = self.cart.save_state();
+    }
+
+    // snapshot has to come from a machine running the same rom
+    pub fn load_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), crate::Error> {
+        self.cart.load_state(&snapshot.mapper)?;
+        self.system_ram = snapshot.system_ram;
+        self.high_ram = snapshot.high_ram;
+        self.wave_ram = snapshot.wave_ram;
+        self.serial.copy_state_from(&snapshot.serial);
+        self.interrupt = snapshot.interrupt.clone();
+        self.cycles = snapshot.cycles;
+        self.prev_cycles = snapshot.prev_cycles;
+        self.timer = snapshot.timer.clone();
+        self.timer_clock = snapshot.timer_clock.clone();
+        self.serial_clock = snapshot.serial_clock.clone();
+        self.lcd.copy_state_from(&snapshot.lcd);
+        self.lcd_clock = snapshot.lcd_clock.clone();
+        self.joypad = snapshot.joypad.clone();
+        self.dma_page = None;
+        Ok(())
+    }
+
+    pub fn set_rumble_callback(&mut self, callback: Box<dyn FnMut(bool) + Send>) {
+        self.cart.set_rumble_callback(callback);
+    }
+
+    pub fn set_motion_source(&mut self, source: Box<dyn MotionSource>) {
+        self.cart.set_motion_source(source);
+    }
+
+    pub fn set_clock_source(&mut self, source: Box<dyn ClockSource>) {
+        self.cart.set_clock_source(source);
+    }
+
+    pub fn wave_ram(&self) -> [u8; 0x10] {
+        self.wave_ram
+    }
+
+    pub fn set_wave_ram(&mut self, wave: [u8; 0x10]) {
+        self.wave_ram = wave;
+    }
+
+    // straight into the region, not through the bus, so it works while VRAM's locked
+    pub fn fill(&mut self, region: MemoryRegion, value: u8) {
+        match region {
+            MemoryRegion::WRAM => self.system_ram = [value; 0x2000],
+            MemoryRegion::HRAM => self.high_ram = [value; 0x7F],
+            MemoryRegion::VRAM => {
+                self.sync_lcd();
+                self.lcd.fill_vram(value);
+            },
+        }
+    }
+
+    pub fn set_serial_sink(&mut self, sink: SerialSink) {
+        self.serial.set_sink(sink);
+    }
+
+    pub fn set_serial_logger(&mut self, logger: Option<Box<dyn FnMut(SerialExchange) + Send>>) {
+        self.serial_logger = logger;
+    }
+
+    fn log_serial(&mut self, direction: SerialDirection) {
+        if let Some(logger) = &mut self.serial_logger {
+            logger(SerialExchange {
+                direction,
+                byte: self.serial.data(),
+                internal_clock: self.serial.internal_clock(),
+                cycle: self.cycles,
+            });
+        }
+    }
+
+    // total clock cycles since power on
+    pub fn get_cycles(&self) -> u128 {
+        self.cycles
+    }
+
     pub fn get_cycle_diff(&mut self) -> u8 {
         let cycle_diff = self.cycles - self.prev_cycles;
         self.prev_cycles = self.cycles;
@@ -53,27 +271,28 @@ impl MMU {
 
     fn read_addr_map(&self, addr: u16) -> u8 {
         match addr {
-            0x0000 ..= 0x3FFF => self.cart.read(addr), // cart rom bank 0
-            0x4000 ..= 0x7FFF => self.cart.read(addr), // switchable cart rom banks 1+
-            0x8000 ..= 0x97FF => self.lcd.vram_tile_data[(addr - 0x8000) as usize],
-            0x9800 ..= 0x9BFF => self.lcd.vram_bg_maps[(addr - 0x9800) as usize], // Map 1
-            0x9C00 ..= 0x9FFF => self.lcd.vram_bg_maps[(addr - 0x9800) as usize], // Map 2
+            0x0000 ..= 0x3FFF => self.read_rom(addr), // cart rom bank 0
+            0x4000 ..= 0x7FFF => self.read_rom(addr), // switchable cart rom banks 1+
+            0x8000 ..= 0x9FFF => self.lcd.read_vram(addr - 0x8000), // tile data, then maps 1 and 2
             0xA000 ..= 0xBFFF => self.cart.read(addr), // switchable cart ram banks
             0xC000 ..= 0xDFFF => self.system_ram[(addr - 0xC000) as usize],
-            0xE000 ..= 0xFDFF => self.system_ram[(addr - 0xE000) as usize], // echo RAM
-            0xFE00 ..= 0xFE9F => self.lcd.read_oam(addr - 0xFE00), // object attribute memory
-            0xFEA0 ..= 0xFEFF => 0xFF, // unusable OAM region
+            0xE000 ..= 0xFDFF => self.read_echo_ram(addr),
+            0xFE00 ..= 0xFE9F => self.lcd.read_oam(addr - 0xFE00) // object attribute memory
+                .unwrap_or_else(|| self.read_fault(addr, "OAM read out of range")),
+            0xFEA0 ..= 0xFEFF => self.read_unusable_region(addr),
             0xFF00 => self.joypad.as_u8(), // joypad
-            0xFF01 => 0xFF, // serial byte
-            0xFF02 => 0xFF, // serial control
+            0xFF01 ..= 0xFF02 => self.serial.read_register(addr) // serial data and control
+                .unwrap_or_else(|| self.read_fault(addr, "not a serial register")),
             0xFF03 => 0xFF, // unusable
-            0xFF04 ..= 0xFF07 => self.timer.read_register(addr),
+            0xFF04 ..= 0xFF07 => self.timer.read_register(addr)
+                .unwrap_or_else(|| self.read_fault(addr, "not a timer register")),
             0xFF08 ..= 0xFF0E => 0xFF, // unusable
             0xFF0F => self.interrupt.get_flag(),
             0xFF10 ..= 0xFF26 => 0xFF, // 'NR' sound registers
             0xFF27 ..= 0xFF2F => 0xFF, // unusable
-            0xFF30 ..= 0xFF3F => 0xFF, // wave pattern RAM
-            0xFF40 ..= 0xFF4B => self.lcd.read_register(addr), // LCD control registers
+            0xFF30 ..= 0xFF3F => self.wave_ram[(addr - 0xFF30) as usize], // wave pattern RAM
+            0xFF40 ..= 0xFF4B => self.lcd.read_register(addr) // LCD control registers
+                .unwrap_or_else(|| self.read_fault(addr, "not an LCD register")),
             0xFF4C ..= 0xFF4F => 0xFF, // unusable
             0xFF50 => 0xFF, // boot rom disable (unreadable - I think that just means 0xFF)
             0xFF51 ..= 0xFF7F => 0xFF, // unusable
@@ -85,27 +304,34 @@ impl MMU {
     fn write_addr_map(&mut self, addr: u16, value: u8) {
         match addr {
             0x0000 ..= 0x7FFF => self.cart.write(addr, value), // cart mbc control writes
-            0x8000 ..= 0x97FF => self.lcd.vram_tile_data[(addr - 0x8000) as usize] = value,
-            0x9800 ..= 0x9BFF => self.lcd.vram_bg_maps[(addr - 0x9800) as usize] = value, // Map 1
-            0x9C00 ..= 0x9FFF => self.lcd.vram_bg_maps[(addr - 0x9800) as usize] = value, // Map 2
+            0x8000 ..= 0x9FFF => self.lcd.write_vram(addr - 0x8000, value), // tile data, then maps 1 and 2
             0xA000 ..= 0xBFFF => self.cart.write(addr, value), // switchable cart ram banks
             0xC000 ..= 0xDFFF => self.system_ram[(addr - 0xC000) as usize] = value,
-            0xE000 ..= 0xFDFF => self.system_ram[(addr - 0xE000) as usize] = value, // echo RAM
-            0xFE00 ..= 0xFE9F => self.lcd.write_oam(addr - 0xFE00, value), // object attribute memory, writes to this region draw sprites
+            0xE000 ..= 0xFDFF => self.write_echo_ram(addr, value),
+            0xFE00 ..= 0xFE9F => self.lcd.write_oam(addr - 0xFE00, value) // object attribute memory, writes to this region draw sprites
+                .unwrap_or_else(|| self.write_fault(addr, "OAM write out of range")),
             0xFEA0 ..= 0xFEFF => (), // unusable OAM region
             0xFF00 => self.joypad.write_select_bits(value), // joypad
-            0xFF01 => self.serial = value, // serial data
-            0xFF02 => { print!("{}", self.serial as char); }, // serial IO control
+            0xFF01 ..= 0xFF02 => { // serial data and control
+                self.serial.write_register(addr, value)
+                    .unwrap_or_else(|| self.write_fault(addr, "not a serial register"));
+                if addr == 0xFF02 && self.serial.transferring() {
+                    self.log_serial(SerialDirection::Sent);
+                }
+            },
             0xFF03 => (), // unusable
-            0xFF04 ..= 0xFF07 => self.timer.write_register(addr, value),
+            0xFF04 ..= 0xFF07 => self.timer.write_register(addr, value)
+                .unwrap_or_else(|| self.write_fault(addr, "not a timer register")),
             0xFF08 ..= 0xFF0E => (), // unusable
             0xFF0F => self.interrupt.set_flag(value),
             0xFF10 ..= 0xFF26 => (), // 'NR' sound registers
             0xFF27 ..= 0xFF2F => (), // unusable
-            0xFF30 ..= 0xFF3F => (), // wave pattern RAM
-            0xFF40 ..= 0xFF45 => self.lcd.write_register(addr, value), // GPU control registers
+            0xFF30 ..= 0xFF3F => self.wave_ram[(addr - 0xFF30) as usize] = value, // wave pattern RAM
+            0xFF40 ..= 0xFF45 => self.lcd.write_register(addr, value, &mut self.interrupt) // GPU control registers
+                .unwrap_or_else(|| self.write_fault(addr, "not an LCD register")),
             0xFF46 => self.dma_transfer(value), // DMA transfer to OAM
-            0xFF47 ..= 0xFF4B => self.lcd.write_register(addr, value), // GPU control registers
+            0xFF47 ..= 0xFF4B => self.lcd.write_register(addr, value, &mut self.interrupt) // GPU control registers
+                .unwrap_or_else(|| self.write_fault(addr, "not an LCD register")),
             0xFF4C ..= 0xFF4F => (), // unusable
             0xFF50 => (), // boot rom disable
             0xFF51 ..= 0xFF7F => (), // unusable
@@ -114,25 +340,132 @@ impl MMU {
         }
     }
 
+    fn read_rom(&self, addr: u16) -> u8 {
+        let rom = self.cart.read(addr);
+        // compared against what the cartridge has, not another code's patch
+        self.cheats.iter().map(|cheat| cheat.patch_rom(addr, rom)).find(|&value| value != rom).unwrap_or(rom)
+    }
+
+    pub fn set_cheats(&mut self, codes: Vec<CheatCode>) {
+        self.cheats = codes;
+    }
+
+    // the GameShark codes' writes, once a frame
+    pub fn apply_cheats(&mut self) {
+        for i in 0..self.cheats.len() {
+            if let CheatCode::GameShark { address, value } = self.cheats[i] {
+                self.sync_peripheral_at(address);
+                self.write_addr_map(address, value);
+            }
+        }
+    }
+
+    // a peripheral didn't recognise an address the address map gave it.
+    // reads as open bus, and the cpu reports it once the instruction's done
+    fn read_fault(&self, addr: u16, reason: &'static str) -> u8 {
+        self.fault.set(addr, reason);
+        0xFF
+    }
+
+    fn write_fault(&self, addr: u16, reason: &'static str) {
+        self.fault.set(addr, reason);
+    }
+
+    pub fn take_fault(&mut self) -> Option<BusFault> {
+        self.fault.take()
+    }
+
+    fn read_echo_ram(&self, addr: u16) -> u8 {
+        match self.options.echo_ram {
+            EchoRAM::Mirror => self.system_ram[(addr - 0xE000) as usize],
+            EchoRAM::OpenBus => 0xFF,
+        }
+    }
+
+    fn write_echo_ram(&mut self, addr: u16, value: u8) {
+        match self.options.echo_ram {
+            EchoRAM::Mirror => self.system_ram[(addr - 0xE000) as usize] = value,
+            EchoRAM::OpenBus => (),
+        }
+    }
+
+    fn read_unusable_region(&self, addr: u16) -> u8 {
+        match self.options.unusable_region {
+            UnusableRegion::OpenBus => 0xFF,
+            UnusableRegion::DMG => if self.lcd.oam_locked() { 0xFF } else { 0x00 },
+            UnusableRegion::CGBRevE => {
+                let nibble = (addr as u8 & 0xF0) >> 4;
+                (nibble << 4) | nibble
+            },
+        }
+    }
+
     pub fn read_u8(&mut self, addr: u16) -> u8 {
+        self.log_rom_access(addr, CDL_DATA);
+        self.bus_read(addr)
+    }
+
+    // a read by the cpu of the instruction it's about to run, including its operands
+    pub fn fetch_u8(&mut self, addr: u16) -> u8 {
+        self.log_rom_access(addr, CDL_CODE);
+        self.bus_read(addr)
+    }
+
+    fn log_rom_access(&mut self, addr: u16, flag: u8) {
+        if let Some(log) = &mut self.code_data_log {
+            if let Some(offset) = self.cart.rom_offset(addr) {
+                log.mark(offset, flag);
+            }
+        }
+    }
+
+    fn bus_read(&mut self, addr: u16) -> u8 {
         self.step();
+        self.sync_peripheral_at(addr);
         self.read_addr_map(addr)
     }
 
+    // reads without taking any time, for debugging tools
+    pub fn peek(&mut self, addr: u16) -> u8 {
+        self.sync_peripheral_at(addr);
+        match addr {
+            // even while the LCD has it locked
+            0x8000 ..= 0x9FFF => self.lcd.peek_vram(addr - 0x8000),
+            _ => self.read_addr_map(addr),
+        }
+    }
+
     pub fn write_u8(&mut self, addr: u16, value: u8) {
         self.step();
+        self.sync_peripheral_at(addr);
         self.write_addr_map(addr, value);
+        // the write may have changed when the peripheral's next event is
+        self.sync_peripheral_at(addr);
     }
 
     pub fn dma_transfer(&mut self, value: u8) {
-        // copies data from rom/ram to oam sprite memory
-        // the value written is the address to read from, divided by 0x100
-        // takes 160 cycles, 40 single byte read/writes of 4 cycles each
-        let addr = (value as u16) << 8;
+        // copies 0xA0 bytes to oam sprite memory from the value written times 0x100, a byte a
+        // machine cycle. the source is read through the address map like the cpu would, so ROM
+        // and cartridge RAM come from whichever banks are switched in. past WRAM the DMA's own
+        // wiring mirrors it, whatever echo RAM's set to
+        let addr = match (value as u16) << 8 {
+            addr @ 0xE000 ..= 0xFFFF => addr - 0x2000,
+            addr => addr,
+        };
         for offset in 0x00..0xA0 {
-            let data = self.read_u8(addr + offset);
-            self.write_u8(0xFE00 + offset, data);
+            self.log_rom_access(addr + offset, CDL_DATA);
+            self.step();
+            self.sync_peripheral_at(addr + offset);
+            let data = self.read_addr_map(addr + offset);
+            self.sync_lcd();
+            self.lcd.write_oam(offset, data);
         }
+        self.dma_page = Some(value);
+    }
+
+    // the page the last OAM DMA since the previous call copied from
+    pub fn take_dma_page(&mut self) -> Option<u8> {
+        self.dma_page.take()
     }
 
     fn add_machine_cycles(&mut self, machine_cycles: u8) {
@@ -141,8 +474,50 @@ impl MMU {
 
     fn step(&mut self) {
         self.add_machine_cycles(1);
-        self.timer.step(&mut self.interrupt);
-        self.lcd.step(&mut self.interrupt);
+
+        // peripherals are only advanced when they have an event due,
+        // or when the cpu touches memory they own (see sync_peripheral_at)
+        if self.timer_clock.is_due(self.cycles) {
+            self.sync_timer();
+        }
+        if self.serial_clock.is_due(self.cycles) {
+            self.sync_serial();
+        }
+        if self.lcd_clock.is_due(self.cycles) {
+            self.sync_lcd();
+        }
+    }
+
+    fn sync_peripheral_at(&mut self, addr: u16) {
+        match addr {
+            0x8000 ..= 0x9FFF => self.sync_lcd(), // vram, so scanlines are drawn with the right data
+            0xFE00 ..= 0xFEFF => self.sync_lcd(), // oam and the unusable region, which depends on the lcd mode
+            0xFF01 ..= 0xFF02 => self.sync_serial(),
+            0xFF04 ..= 0xFF07 => self.sync_timer(),
+            0xFF40 ..= 0xFF4B => self.sync_lcd(),
+            _ => (),
+        }
+    }
+
+    pub fn set_divider(&mut self, value: u8) {
+        self.sync_timer();
+        self.timer.set_divider(value);
+    }
+
+    fn sync_timer(&mut self) {
+        self.timer_clock.catch_up(self.cycles, &mut self.timer, &mut self.interrupt);
+    }
+
+    fn sync_serial(&mut self) {
+        let transferring = self.serial.transferring();
+        self.serial_clock.catch_up(self.cycles, &mut self.serial, &mut self.interrupt);
+        if transferring && !self.serial.transferring() {
+            self.log_serial(SerialDirection::Received);
+        }
+    }
+
+    fn sync_lcd(&mut self) {
+        self.lcd_clock.catch_up(self.cycles, &mut self.lcd, &mut self.interrupt);
     }
 
     // for mysterious extra instruction delays. adds 1 machine cycle to the cycle counter
@@ -150,3 +525,45 @@ impl MMU {
         self.step();
     }
 }
+
+impl Bus for MMU {
+    fn read_u8(&mut self, addr: u16) -> u8 {
+        MMU::read_u8(self, addr)
+    }
+
+    fn fetch_u8(&mut self, addr: u16) -> u8 {
+        MMU::fetch_u8(self, addr)
+    }
+
+    fn write_u8(&mut self, addr: u16, value: u8) {
+        MMU::write_u8(self, addr, value)
+    }
+
+    fn spin(&mut self) {
+        MMU::spin(self)
+    }
+
+    fn get_cycles(&self) -> u128 {
+        self.cycles
+    }
+
+    fn interrupt_flag(&self) -> u8 {
+        self.interrupt.get_flag()
+    }
+
+    fn set_interrupt_flag(&mut self, value: u8) {
+        self.interrupt.set_flag(value)
+    }
+
+    fn interrupt_enable(&self) -> u8 {
+        self.interrupt.get_enable()
+    }
+
+    fn rom_bank(&self) -> u16 {
+        self.cart.rom_bank()
+    }
+
+    fn take_fault(&mut self) -> Option<BusFault> {
+        MMU::take_fault(self)
+    }
+}
@@ -5,43 +5,617 @@ pub mod interrupt;
 pub mod timer;
 pub mod lcd;
 pub mod joypad;
+pub mod serial;
+pub mod scheduler;
+pub mod boot;
+pub mod bus;
+pub mod cheat;
+pub mod debugger;
+pub mod disasm;
+pub mod fault;
+pub mod script;
+pub mod snapshot;
+#[cfg(feature = "std")]
+pub mod dump;
 
-use std::error::Error;
+#[cfg(feature = "std")]
+use std::path::Path;
 
-use crate::cartridge::Cartridge;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+use crate::cartridge::{Cartridge, Header};
 use crate::gameboy;
+use crate::clock::ClockSource;
+use crate::motion::MotionSource;
+
+// DMG master clock, 4.19MHz
+pub const CYCLES_PER_SECOND: u32 = 4_194_304;
 
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct GameBoy {
     cpu: gameboy::cpu::CPU,
     mmu: gameboy::mmu::MMU,
+
+    // held through set_controls, queued presses are added on top
+    controls: joypad::Controls,
+    input_queue: joypad::InputQueue,
+
+    // the frame_count of the last frame handed out by take_frame
+    taken_frame: u64,
+
+    // the simulated boot ROM, run before the cartridge while it's set
+    boot: Option<boot::BootSequence>,
+
+    // breakpoints belong to whoever's debugging, not the machine, so save states leave them out
+    #[cfg_attr(feature = "serialize", serde(skip, default = "debugger::Debugger::new"))]
+    debugger: debugger::Debugger,
+}
+
+// what a run_cycles call got through
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CyclesRun {
+    // instructions can't be split, so this can be over the budget by the rest of the last one,
+    // under 24 cycles. frontends pacing themselves can take that off the next budget
+    pub cycles: u64,
+    // frames finished along the way
+    pub frames: u32,
+}
+
+// embedders run the core on worker threads, so anything a GameBoy owns
+// (mappers, sensors, callbacks) has to stay Send. this fails to compile if it isn't
+#[allow(dead_code)]
+fn assert_gameboy_is_send() {
+    fn is_send<T: Send>() {}
+    is_send::<GameBoy>();
 }
 
 impl GameBoy {
+    // nothing here is shared between instances, so any number can run side by side,
+    // each on its own thread if need be
     pub fn new(cartridge: Cartridge) -> GameBoy {
-        println!("{:#?}", cartridge.header);
-        println!("read_rom_size: {}", cartridge.rom_len());
-
         let cpu = gameboy::cpu::CPU::new();
         let mmu = gameboy::mmu::MMU::new(cartridge);
 
-        GameBoy { cpu, mmu }
+        GameBoy {
+            cpu,
+            mmu,
+            controls: joypad::Controls::default(),
+            input_queue: joypad::InputQueue::new(),
+            taken_frame: 0,
+            boot: None,
+            debugger: debugger::Debugger::new(),
+        }
+    }
+
+    // clock cycles emulated since power on
+    pub fn elapsed_cycles(&self) -> u128 {
+        self.mmu.get_cycles()
+    }
+
+    // full frames' worth of emulated time since power on,
+    // counted even while the LCD is switched off
+    pub fn elapsed_frames(&self) -> u128 {
+        self.mmu.get_cycles() / lcd::CYCLES_PER_FRAME as u128
+    }
+
+    // frames the LCD has finished since power on, including the blank ones it puts out while off.
+    // a jump of more than 1 between frames a frontend shows means it dropped some
+    pub fn frame_count(&self) -> u64 {
+        self.mmu.lcd.frame_count()
+    }
+
+    pub fn elapsed_instructions(&self) -> u64 {
+        self.cpu.get_instructions()
+    }
+
+    pub fn set_memory_options(&mut self, options: mmu::MemoryOptions) {
+        self.mmu.options = options;
+    }
+
+    // call before running anything, the cartridge starts once the boot sequence is done
+    pub fn set_boot_mode(&mut self, mode: boot::BootMode) {
+        self.boot = boot::BootSequence::new(mode, &mut self.mmu);
+    }
+
+    // starts the cartridge with the registers model's boot ROM leaves, call before running anything.
+    // without it they're the DMG's, other than DIV
+    pub fn set_model(&mut self, model: boot::Model) {
+        let registers = model.registers(self.mmu.header());
+        *self.cpu.registers_mut() = registers::Registers { pc: self.cpu.registers().pc, ..registers };
+        if let Some(divider) = model.divider() {
+            self.mmu.set_divider(divider);
+        }
+    }
+
+    pub fn booting(&self) -> bool {
+        self.boot.is_some()
+    }
+
+    // start from the address the header's entry point jumps to, rather than at 0x100.
+    // false, leaving pc alone, if the entry point isn't a jump
+    pub fn start_at_entry_target(&mut self) -> bool {
+        match self.mmu.header().entry_target() {
+            Some(target) => {
+                self.cpu.registers_mut().pc = target;
+                true
+            },
+            None => false,
+        }
+    }
+
+    // replaces the running machine with a deserialized save state,
+    // keeping this one's rom, callbacks and frontend settings
+    #[cfg(feature = "serialize")]
+    pub fn load_state(&mut self, mut state: GameBoy) -> Result<(), Error> {
+        state.mmu.take_cartridge_from(&mut self.mmu)?;
+        *self = state;
+        Ok(())
+    }
+
+    // the machine's state, kept in memory. see snapshot.rs
+    pub fn snapshot(&self) -> snapshot::Snapshot {
+        let mut snapshot = snapshot::Snapshot::new();
+        self.save_snapshot(&mut snapshot);
+        snapshot
+    }
+
+    // like snapshot, but over an old one, so rewinding every frame doesn't allocate every frame
+    pub fn save_snapshot(&self, snapshot: &mut snapshot::Snapshot) {
+        snapshot.cpu.copy_state_from(&self.cpu);
+        self.mmu.save_snapshot(snapshot);
+        snapshot.controls = self.controls;
+        snapshot.input_queue.clone_from(&self.input_queue);
+        snapshot.taken_frame = self.taken_frame;
+        snapshot.boot.clone_from(&self.boot);
+    }
+
+    // goes back to a snapshot, which has to come from a machine running the same rom.
+    // breakpoints and the rest of the debugger are left alone
+    pub fn load_snapshot(&mut self, snapshot: &snapshot::Snapshot) -> Result<(), Error> {
+        self.mmu.load_snapshot(snapshot)?;
+        self.cpu.copy_state_from(&snapshot.cpu);
+        self.cpu.take_dispatch();
+        self.controls = snapshot.controls;
+        self.input_queue.clone_from(&snapshot.input_queue);
+        self.taken_frame = snapshot.taken_frame;
+        self.boot.clone_from(&snapshot.boot);
+        Ok(())
+    }
+
+    // changes whenever the game writes to the cartridge's RAM, see Cartridge::ram_writes
+    pub fn ram_writes(&self) -> u64 {
+        self.mmu.cartridge().ram_writes()
+    }
+
+    // the cartridge's RAM, all banks, whether it's battery backed or not
+    pub fn cartridge_ram(&self) -> &[u8] {
+        self.mmu.cartridge().ram()
+    }
+
+    // see Cartridge::write_ram
+    pub fn write_cartridge_ram(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Error> {
+        self.mmu.cartridge_mut().write_ram(offset, bytes)
+    }
+
+    // the cartridge's RAM as a .sav file, or None if it isn't battery backed
+    pub fn battery_save(&mut self) -> Option<Vec<u8>> {
+        let cartridge = self.mmu.cartridge_mut();
+        if cartridge.has_battery() {
+            Some(cartridge.battery_save())
+        } else {
+            None
+        }
+    }
+
+    // Game Genie and GameShark codes to apply from now on, replacing any from before.
+    // they're kept over loading a state, like the rest of the frontend's settings
+    pub fn set_cheats(&mut self, codes: Vec<cheat::CheatCode>) {
+        self.mmu.set_cheats(codes);
     }
 
     pub fn set_controls(&mut self, controls: joypad::Controls) {
+        self.controls = controls;
+        self.apply_controls();
+    }
+
+    // hold button for the next frames calls to run_to_vblank
+    pub fn press(&mut self, button: joypad::Button, frames: u32) {
+        self.press_after(0, button, frames);
+    }
+
+    // like press, but starting delay frames from now
+    pub fn press_after(&mut self, delay: u32, button: joypad::Button, frames: u32) {
+        self.input_queue.press(button, delay, frames);
+        self.apply_controls();
+    }
+
+    pub fn clear_queued_input(&mut self) {
+        self.input_queue.clear();
+        self.apply_controls();
+    }
+
+    fn apply_controls(&mut self) {
+        let controls = self.controls.merge(self.input_queue.controls());
         self.mmu.joypad.set_from_controls(controls, &mut self.mmu.interrupt);
     }
 
+    // called with every byte sent or received over the serial port, for watching a link protocol
+    pub fn set_serial_logger<F: FnMut(serial::SerialExchange) + Send + 'static>(&mut self, logger: F) {
+        self.mmu.set_serial_logger(Some(Box::new(logger)));
+    }
+
+    pub fn set_rumble_callback<F: FnMut(bool) + Send + 'static>(&mut self, callback: F) {
+        self.mmu.set_rumble_callback(Box::new(callback));
+    }
+
+    // where a tilt cartridge's accelerometer reads from, it lies flat otherwise
+    pub fn set_motion_source<M: MotionSource + 'static>(&mut self, source: M) {
+        self.mmu.set_motion_source(Box::new(source));
+    }
+
+    // what a real time clock cartridge's clock reads, see clock.rs
+    pub fn set_clock_source<C: ClockSource + 'static>(&mut self, source: C) {
+        self.mmu.set_clock_source(Box::new(source));
+    }
+
+    // where bytes sent over the serial port go. discarded by default,
+    // so instances running side by side don't talk over each other
+    pub fn set_serial_sink(&mut self, sink: serial::SerialSink) {
+        self.mmu.set_serial_sink(sink);
+    }
+
+    // write each new frame and the tile data out as PNGs in directory, or stop with None
+    #[cfg(feature = "std")]
+    pub fn set_frame_dumping(&mut self, directory: Option<&Path>) -> Result<(), Error> {
+        let dumper = match directory {
+            Some(directory) => Some(dump::FrameDumper::new(directory)?),
+            None => None,
+        };
+        self.mmu.lcd.set_frame_dumper(dumper);
+
+        Ok(())
+    }
+
+    pub fn set_pixel_format(&mut self, format: lcd::PixelFormat) {
+        self.mmu.lcd.set_pixel_format(format);
+    }
+
+    // percentage of each frame that lingers into the next, 0 (the default) for none
+    pub fn set_ghosting(&mut self, percent: u8) {
+        self.mmu.lcd.set_ghosting(percent);
+    }
+
+    // tints the picture by which layer each pixel came from and outlines the sprites, for
+    // tracking down priority and positioning bugs. from the next line drawn
+    pub fn set_layer_overlay(&mut self, on: bool) {
+        self.mmu.lcd.set_layer_overlay(on);
+    }
+
+    pub fn palettes(&self) -> lcd::Palettes {
+        self.mmu.lcd.palettes()
+    }
+
+    // LCDC, the scroll and window positions and the palettes for each line of the last frame,
+    // for seeing what raster effects change where
+    pub fn scanline_registers(&self) -> &[lcd::ScanlineRegisters] {
+        self.mmu.lcd.scanline_registers()
+    }
+
+    // shows a different shade for one of a palette's entries until it's set back to None,
+    // see lcd::Palettes. the game can't tell
+    pub fn set_palette_override(&mut self, palette: usize, entry: usize, shade: Option<u8>) {
+        self.mmu.lcd.set_palette_override(palette, entry, shade);
+    }
+
+    // all the tiles in VRAM, RGBA TILE_DATA_WIDTH x TILE_DATA_HEIGHT, for tile viewers
+    #[cfg(feature = "std")]
+    pub fn tile_data_rgba(&self) -> Vec<u8> {
+        self.mmu.lcd.render_tile_data()
+    }
+
+    // the bg map in use, RGBA lcd::BG_MAP_SIZE square, for map viewers
+    pub fn bg_map_rgba(&self) -> Vec<u8> {
+        self.mmu.lcd.render_bg_map()
+    }
+
+    // frame needs to be lcd::PixelFormat::frame_size() bytes for the current format
     pub fn draw_frame(&self, frame: &mut [u8]) {
         frame.clone_from_slice(self.mmu.lcd.get_frame());
     }
 
-    pub fn run_to_vblank(&mut self) -> Result<(), Box<dyn Error>> {
-        self.cpu.run_to_vblank(&mut self.mmu)?;
+    // the current frame as RGBA, SCREEN_WIDTH x SCREEN_HEIGHT, regardless of the pixel format
+    pub fn screenshot_rgba(&self) -> Vec<u8> {
+        self.mmu.lcd.rgba_frame().to_vec()
+    }
+
+    // the current frame encoded as a PNG file
+    #[cfg(feature = "std")]
+    pub fn screenshot(&self) -> Vec<u8> {
+        let mut png = Vec::new();
+        dump::encode_png(&mut png, lcd::SCREEN_WIDTH as u32, lcd::SCREEN_HEIGHT as u32, self.mmu.lcd.rgba_frame())
+            .expect("encoding a frame-sized image into memory can't fail");
+        png
+    }
+
+    // like draw_frame, but only if a frame has finished since the last take_frame.
+    // returns its frame_count, or None without touching frame if it would be a duplicate
+    pub fn take_frame(&mut self, frame: &mut [u8]) -> Option<u64> {
+        let count = self.frame_count();
+        if count == self.taken_frame {
+            return None;
+        }
+
+        self.draw_frame(frame);
+        self.taken_frame = count;
+        Some(count)
+    }
+
+    // runs a single instruction, interrupt dispatch, or halted cycle
+    pub fn step(&mut self) -> Result<(), Error> {
+        if let Some(boot) = &mut self.boot {
+            boot.step(&mut self.mmu);
+            if boot.finished() {
+                self.boot = None;
+            }
+            return Ok(());
+        }
+
+        self.debug_step()
+    }
+
+    pub fn registers(&self) -> registers::Registers {
+        *self.cpu.registers()
+    }
+
+    // the last instructions run, oldest first
+    pub fn trace(&self) -> &debugger::InstructionTrace {
+        self.cpu.trace()
+    }
+
+    // whether the cpu has halted in a way it can never wake up from
+    pub fn locked_up(&self) -> bool {
+        self.cpu.locked_up(&self.mmu)
+    }
+
+    // the ROM bank currently mapped at 0x4000-0x7FFF
+    pub fn rom_bank(&self) -> u16 {
+        self.mmu.cartridge().rom_bank()
+    }
+
+    // the cartridge's header, as it was read when the ROM was loaded
+    pub fn header(&self) -> &Header {
+        self.mmu.header()
+    }
+
+    // pc along with the bank it's in, which is what traces and breakpoints should go by
+    pub fn pc(&self) -> debugger::BankedAddress {
+        debugger::BankedAddress::new(self.cpu.registers().pc, self.rom_bank())
+    }
+
+    // starts marking which rom bytes are run as code and which are read as data,
+    // or stops and throws the log away
+    pub fn set_code_data_logging(&mut self, enabled: bool) {
+        self.mmu.code_data_log = if enabled {
+            Some(debugger::CodeDataLog::new(self.mmu.cartridge().rom_len()))
+        } else {
+            None
+        };
+    }
+
+    pub fn code_data_log(&self) -> Option<&debugger::CodeDataLog> {
+        self.mmu.code_data_log.as_ref()
+    }
+
+    pub fn debugger(&self) -> &debugger::Debugger {
+        &self.debugger
+    }
+
+    pub fn debugger_mut(&mut self) -> &mut debugger::Debugger {
+        &mut self.debugger
+    }
+
+    // reads memory the way the cpu would, without advancing the clock
+    pub fn peek(&mut self, addr: u16) -> u8 {
+        self.mmu.peek(addr)
+    }
+
+    // copies WRAM and HRAM, for ram_changes to compare against later
+    pub fn snapshot_ram(&mut self) {
+        let ram = self.snapshot_ram_bytes();
+        self.debugger.set_ram_snapshot(ram);
+    }
+
+    // what's changed in WRAM and HRAM since snapshot_ram, None if it hasn't been called
+    pub fn ram_changes(&mut self) -> Option<Vec<debugger::RamChange>> {
+        let ram = self.snapshot_ram_bytes();
+        self.debugger.ram_changes(&ram)
+    }
+
+    fn snapshot_ram_bytes(&mut self) -> Vec<u8> {
+        debugger::snapshot_addresses().map(|addr| self.mmu.peek(addr)).collect()
+    }
+
+    // overwrites all of region with value, e.g. 0xDB or 0xFF to see whether a game relies on
+    // memory being cleared at power on, which real hardware doesn't do
+    pub fn fill_memory(&mut self, region: debugger::MemoryRegion, value: u8) {
+        self.mmu.fill(region, value);
+    }
+
+    // channel 3's wave pattern, 0xFF30-0xFF3F, for editing in debug tools
+    pub fn wave_ram(&self) -> [u8; 16] {
+        self.mmu.wave_ram()
+    }
+
+    pub fn set_wave_ram(&mut self, wave: [u8; 16]) {
+        self.mmu.set_wave_ram(wave);
+    }
+
+    pub fn run_to_vblank(&mut self) -> Result<(), Error> {
+        self.run_frame(false)?;
+        Ok(())
+    }
+
+    // like run_to_vblank, but stops before running an instruction at a breakpoint.
+    // the next call carries on from there, running the instruction it stopped at
+    pub fn run_until_break(&mut self) -> Result<debugger::Stop, Error> {
+        self.run_frame(true)
+    }
+
+    fn run_frame(&mut self, breaks: bool) -> Result<debugger::Stop, Error> {
+        if let Some(boot) = &mut self.boot {
+            while !boot.step(&mut self.mmu) {}
+            if boot.finished() {
+                self.boot = None;
+            }
+        } else if (breaks && self.debugger.has_breakpoints()) || self.debugger.watches_steps() {
+            // anything dispatched or copied before the debugger was watching has been and gone
+            self.cpu.take_dispatch();
+            self.mmu.take_dma_page();
+            let mut resuming = true;
+            loop {
+                let pc = self.pc();
+                if breaks && !resuming && self.debugger.breaks_at(pc) {
+                    return Ok(debugger::Stop::Breakpoint(pc));
+                }
+                if let Some(until) = self.debugger.until().filter(|_| breaks && !resuming) {
+                    if self.debugger.reached_until(pc, self.mmu.peek(0xFF44)) {
+                        return Ok(debugger::Stop::Until(until));
+                    }
+                }
+                resuming = false;
+
+                self.debug_step()?;
+                if let Some(dispatch) = self.cpu.take_dispatch() {
+                    if breaks && self.debugger.breaks_on(dispatch.interrupt) {
+                        return Ok(debugger::Stop::Interrupt(dispatch));
+                    }
+                }
+                if self.mmu.lcd.vblank_reached() {
+                    break;
+                }
+            }
+        } else {
+            self.cpu.run_to_vblank(&mut self.mmu)?;
+        }
+
+        self.end_frame();
+        if breaks && self.debugger.until() == Some(debugger::Until::VBlank) {
+            self.debugger.set_until(None);
+            return Ok(debugger::Stop::Until(debugger::Until::VBlank));
+        }
+        Ok(debugger::Stop::VBlank)
+    }
+
+    // a cpu step, telling the debugger what happened during it
+    fn debug_step(&mut self) -> Result<(), Error> {
+        if !self.debugger.watches_steps() {
+            return self.cpu.step(&mut self.mmu);
+        }
+
+        let pc = self.pc();
+        let instructions = self.cpu.get_instructions();
+        let flags = self.mmu.interrupt.get_flag();
+        let op = self.mmu.peek(pc.addr);
+        let cb_opcode = self.mmu.peek(pc.addr.wrapping_add(1));
+        let registers = *self.cpu.registers();
+
+        self.cpu.step(&mut self.mmu)?;
+
+        if self.cpu.get_instructions() != instructions {
+            self.debugger.count_instruction(pc, op, cb_opcode);
+            if self.debugger.trace_logging() {
+                let opcode = if op == 0xCB { 0xCB00 | cb_opcode as u16 } else { op as u16 };
+                self.debugger.log_instruction(debugger::TraceEntry { pc, opcode, registers });
+            }
+        }
+
+        if let Some(page) = self.mmu.take_dma_page() {
+            self.debugger.log_dma(debugger::DmaTransfer {
+                page,
+                cycle: self.mmu.get_cycles(),
+                ly: self.mmu.peek(0xFF44),
+                lcd_on: self.mmu.peek(0xFF40) & 0x80 != 0,
+                pc,
+            });
+        }
+
+        if self.debugger.interrupt_logging() {
+            let cycle = self.mmu.get_cycles();
+            let ly = self.mmu.peek(0xFF44);
+            let requested = self.mmu.interrupt.get_flag() & !flags & 0x1F;
+            for bit in 0..5 {
+                if requested & (1 << bit) == 0 {
+                    continue;
+                }
+                if let Some(interrupt) = num_traits::FromPrimitive::from_u8(bit) {
+                    self.debugger.log_interrupt(debugger::InterruptEvent {
+                        kind: debugger::InterruptEventKind::Requested, interrupt, cycle, ly, pc,
+                    });
+                }
+            }
+
+            // dispatching doesn't count as an instruction, and waking from halt doesn't move pc
+            let new_pc = self.cpu.registers().pc;
+            if self.cpu.get_instructions() == instructions && new_pc != pc.addr {
+                if let Some(interrupt) = interrupt::Interrupt::from_vector(new_pc) {
+                    self.debugger.log_interrupt(debugger::InterruptEvent {
+                        kind: debugger::InterruptEventKind::Dispatched, interrupt, cycle, ly, pc,
+                    });
+                }
+            }
+        }
 
         Ok(())
     }
 
-    pub fn run_forever(&mut self) -> Result<(), Box<dyn Error>> {
+    fn end_frame(&mut self) {
+        self.mmu.apply_cheats();
+
+        // queued input is counted in frames, so it only moves on here
+        if !self.input_queue.is_empty() {
+            self.input_queue.advance();
+            self.apply_controls();
+        }
+    }
+
+    // runs at least cycles clock cycles rather than to a frame, for frontends that pace
+    // themselves by the audio they need or a netplay tick. the last instruction always runs
+    // to the end, so it can go over (see CyclesRun). breakpoints are ignored
+    pub fn run_cycles(&mut self, cycles: u64) -> Result<CyclesRun, Error> {
+        let start = self.mmu.get_cycles();
+        let end = start + cycles as u128;
+        let mut frames = 0;
+        while self.mmu.get_cycles() < end {
+            let frame_ended = match &mut self.boot {
+                Some(boot) => {
+                    let frame_ended = boot.step(&mut self.mmu);
+                    if boot.finished() {
+                        self.boot = None;
+                    }
+                    frame_ended
+                },
+                None => {
+                    self.debug_step()?;
+                    self.mmu.lcd.vblank_reached()
+                },
+            };
+            if frame_ended {
+                self.end_frame();
+                frames += 1;
+            }
+        }
+        Ok(CyclesRun { cycles: (self.mmu.get_cycles() - start) as u64, frames })
+    }
+
+    pub fn run_forever(&mut self) -> Result<(), Error> {
+        while self.boot.is_some() {
+            self.run_to_vblank()?;
+        }
         self.cpu.run_forever(&mut self.mmu)?;
 
         Ok(())
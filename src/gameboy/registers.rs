@@ -1,6 +1,10 @@
-use std::fmt;
+use core::fmt;
+
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 
 bitflags!{
+    #[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
     pub struct Flags: u8 {
         const ZERO = 0x80;
         const NEGATIVE = 0x40;
@@ -29,7 +33,8 @@ pub enum Register16Bit {
     AF, BC, DE, HL, SP
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct Registers {
     pub a: u8,
     pub f: Flags,
@@ -44,6 +49,7 @@ pub struct Registers {
 }
 
 impl Registers {
+    // what the DMG boot ROM leaves behind, see boot::Model for the others
     pub fn new() -> Registers {
         Registers {
             a: 0x01,
@@ -0,0 +1,63 @@
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+use crate::gameboy::interrupt::InterruptHandler;
+
+// a peripheral that can be left alone until it next has something observable to do,
+// then caught up in one go instead of being stepped on every machine cycle
+pub trait Scheduled {
+    // machine cycles we can skip over where nothing changes except internal counters
+    fn quiet_steps(&self) -> u64;
+    // fast-forward through the given number of quiet steps
+    fn skip(&mut self, steps: u64);
+    // one machine cycle, done the slow way
+    fn step(&mut self, ih: &mut InterruptHandler);
+
+    // machine cycles until the peripheral next needs to be stepped on time,
+    // i.e. when it might raise an interrupt or change state the cpu can see without a register read
+    fn steps_to_next_event(&self) -> u64 {
+        self.quiet_steps().saturating_add(1)
+    }
+
+    fn advance(&mut self, mut steps: u64, ih: &mut InterruptHandler) {
+        while steps > 0 {
+            let quiet = core::cmp::min(self.quiet_steps(), steps);
+            self.skip(quiet);
+            steps -= quiet;
+
+            if steps > 0 {
+                self.step(ih);
+                steps -= 1;
+            }
+        }
+    }
+}
+
+// tracks how far a peripheral has been advanced, and when it must next be caught up
+#[derive(Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct EventClock {
+    synced: u128,
+    due: u128,
+}
+
+impl EventClock {
+    pub fn new() -> EventClock {
+        EventClock {
+            synced: 0,
+            due: 0,
+        }
+    }
+
+    pub fn is_due(&self, cycles: u128) -> bool {
+        cycles >= self.due
+    }
+
+    // advance the peripheral to the given cycle count and work out when its next event is
+    pub fn catch_up<P: Scheduled>(&mut self, cycles: u128, peripheral: &mut P, ih: &mut InterruptHandler) {
+        let steps = ((cycles - self.synced) / 4) as u64;
+        peripheral.advance(steps, ih);
+        self.synced = cycles;
+        self.due = cycles.saturating_add(peripheral.steps_to_next_event() as u128 * 4);
+    }
+}
@@ -0,0 +1,83 @@
+// button presses at set frames, read from a text file, for driving a game the same way every
+// time without a person or a bot at the controls. one press per line:
+//
+//   # skip the intro
+//   frame 120: press start
+//   frame 200: press a for 10
+//   +30: press a, up
+//
+// frame counts from when the script's queued, +N from the line before. presses last
+// PRESS_FRAMES frames unless they say otherwise, and anything after a # is ignored
+
+use alloc::vec::Vec;
+
+use crate::Error;
+use crate::gameboy::GameBoy;
+use crate::gameboy::joypad::Button;
+
+// long enough for games that only read the buttons every few frames
+const PRESS_FRAMES: u32 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ScriptedPress {
+    frame: u32,
+    button: Button,
+    frames: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct InputScript {
+    presses: Vec<ScriptedPress>,
+}
+
+impl InputScript {
+    pub fn parse(text: &str) -> Result<InputScript, Error> {
+        let mut presses = Vec::new();
+        let mut last_frame = 0;
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let bad_line = |why: &str| format!("line {} {}: {}", number + 1, why, line);
+
+            let mut parts = line.splitn(2, ':');
+            let (when, what) = match (parts.next(), parts.next()) {
+                (Some(when), Some(what)) => (when.trim(), what.trim()),
+                _ => return Err(bad_line("needs a frame, a colon, then what to press").into()),
+            };
+            let frame = match when.strip_prefix('+') {
+                Some(after) => after.trim().parse::<u32>().map(|after| last_frame + after),
+                None => when.strip_prefix("frame").unwrap_or(when).trim().parse(),
+            }.map_err(|_| bad_line("has a bad frame"))?;
+
+            let what = what.strip_prefix("press").or_else(|| what.strip_prefix("hold"))
+                .ok_or_else(|| bad_line("should say press"))?;
+            let (buttons, frames) = match what.rsplit_once(" for ") {
+                Some((buttons, frames)) => {
+                    let frames = frames.trim().parse().map_err(|_| bad_line("has a bad length"))?;
+                    (buttons, frames)
+                },
+                None => (what, PRESS_FRAMES),
+            };
+            if frames == 0 {
+                return Err(bad_line("presses for no frames").into());
+            }
+            for name in buttons.split(',').map(str::trim) {
+                let button = Button::from_name(&name.to_ascii_lowercase())
+                    .ok_or_else(|| bad_line("names a button that isn't a, b, start, select or a direction"))?;
+                presses.push(ScriptedPress { frame, button, frames });
+            }
+            last_frame = frame;
+        }
+        Ok(InputScript { presses })
+    }
+
+    // queues every press on gameboy, counting frames from now. they play out as it runs,
+    // frame by frame, so the same script always lands on the same frames
+    pub fn queue(&self, gameboy: &mut GameBoy) {
+        for press in &self.presses {
+            gameboy.press_after(press.frame, press.button, press.frames);
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
+use std::io::Write;
+
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+use crate::gameboy::interrupt::{Interrupt, InterruptHandler};
+use crate::gameboy::scheduler::Scheduled;
+
+// where the bytes a game sends over the serial port go.
+// test roms report their results this way, but games spamming a terminal frontend don't help anyone
+#[derive(Default)]
+pub enum SerialSink {
+    #[default]
+    Discard,
+    #[cfg(feature = "std")]
+    Stdout,
+    // a file, a TCP connection, etc. dropped if a write fails
+    #[cfg(feature = "std")]
+    Writer(Box<dyn Write + Send>),
+    Callback(Box<dyn FnMut(u8) + Send>),
+}
+
+impl SerialSink {
+    fn send(&mut self, byte: u8) {
+        match self {
+            SerialSink::Discard => (),
+            #[cfg(feature = "std")]
+            SerialSink::Stdout => print!("{}", byte as char),
+            #[cfg(feature = "std")]
+            SerialSink::Writer(writer) => {
+                if let Err(err) = writer.write_all(&[byte]).and_then(|()| writer.flush()) {
+                    eprintln!("Problem writing serial output, discarding it from now on: {}", err);
+                    *self = SerialSink::Discard;
+                }
+            },
+            SerialSink::Callback(callback) => callback(byte),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SerialDirection {
+    // written to SB and shifted out when the game started the transfer
+    Sent,
+    // what SB held once the transfer finished
+    Received,
+}
+
+// a byte over the serial port, for logging what a game says over the link
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SerialExchange {
+    pub direction: SerialDirection,
+    pub byte: u8,
+    // whether this end was driving the clock
+    pub internal_clock: bool,
+    pub cycle: u128,
+}
+
+impl core::fmt::Display for SerialExchange {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let direction = match self.direction {
+            SerialDirection::Sent => "sent",
+            SerialDirection::Received => "received",
+        };
+        let clock = if self.internal_clock { "internal" } else { "external" };
+        write!(f, "cycle {} {} {:02x} {} clock", self.cycle, direction, self.byte, clock)
+    }
+}
+
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct Serial {
+    data: u8,
+    transferring: bool,
+    internal_clock: bool,
+    bits_remaining: u8,
+    counter: u32,
+    // where what's sent goes belongs to whoever's running this machine, not its state
+    #[cfg_attr(feature = "serialize", serde(skip))]
+    sink: SerialSink,
+}
+
+impl Serial {
+    // the internal clock shifts a bit every 512 cycles (8192Hz)
+    const CYCLES_PER_BIT: u32 = 512;
+
+    pub fn new() -> Serial {
+        Serial {
+            data: 0x00,
+            transferring: false,
+            internal_clock: false,
+            bits_remaining: 0,
+            counter: 0,
+            sink: SerialSink::Discard,
+        }
+    }
+
+    pub fn transferring(&self) -> bool {
+        self.transferring
+    }
+
+    pub fn internal_clock(&self) -> bool {
+        self.internal_clock
+    }
+
+    pub fn data(&self) -> u8 {
+        self.data
+    }
+
+    pub fn set_sink(&mut self, sink: SerialSink) {
+        self.sink = sink;
+    }
+
+    // everything a save state has, for snapshots. the sink stays as it is
+    pub fn copy_state_from(&mut self, other: &Serial) {
+        self.data = other.data;
+        self.transferring = other.transferring;
+        self.internal_clock = other.internal_clock;
+        self.bits_remaining = other.bits_remaining;
+        self.counter = other.counter;
+    }
+
+    // keep the frontend's sink from the serial port a save state is replacing
+    #[cfg(feature = "serialize")]
+    pub fn keep_sink_from(&mut self, old: &mut Serial) {
+        self.sink = core::mem::take(&mut old.sink);
+    }
+
+    // None for addresses outside 0xFF01 - 0xFF02
+    pub fn read_register(&self, addr: u16) -> Option<u8> {
+        Some(match addr {
+            0xFF01 => self.data,
+            // unused bits read as 1
+            0xFF02 => 0b0111_1110
+                | if self.transferring { 1 << 7 } else { 0 }
+                | if self.internal_clock { 1 } else { 0 },
+            _ => return None,
+        })
+    }
+
+    pub fn write_register(&mut self, addr: u16, value: u8) -> Option<()> {
+        match addr {
+            0xFF01 => self.data = value,
+            0xFF02 => self.set_control(value),
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn set_control(&mut self, value: u8) {
+        self.transferring = value & 0x80 != 0;
+        self.internal_clock = value & 0x01 != 0;
+
+        if self.transferring {
+            self.sink.send(self.data);
+            self.bits_remaining = 8;
+            self.counter = 0;
+        }
+    }
+}
+
+impl Scheduled for Serial {
+    fn quiet_steps(&self) -> u64 {
+        if !self.transferring || !self.internal_clock {
+            return u64::MAX;
+        }
+
+        if self.counter + 4 >= Serial::CYCLES_PER_BIT {
+            0
+        } else {
+            ((Serial::CYCLES_PER_BIT - self.counter) / 4 - 1) as u64
+        }
+    }
+
+    fn skip(&mut self, steps: u64) {
+        if self.transferring && self.internal_clock {
+            self.counter += (steps * 4) as u32;
+        }
+    }
+
+    fn steps_to_next_event(&self) -> u64 {
+        // individual bits shifting in are only visible through register reads,
+        // so we just need to be on time for the end of the transfer
+        if !self.transferring || !self.internal_clock || self.bits_remaining == 0 {
+            return self.quiet_steps().saturating_add(1);
+        }
+
+        let remaining_bits = (self.bits_remaining - 1) as u64;
+        (self.quiet_steps() + 1) + remaining_bits * (Serial::CYCLES_PER_BIT / 4) as u64
+    }
+
+    fn step(&mut self, ih: &mut InterruptHandler) {
+        // with an external clock we'd be waiting on a link partner that doesn't exist,
+        // so the transfer never completes (just like real hardware with no cable plugged in)
+        if !self.transferring || !self.internal_clock {
+            return;
+        }
+
+        self.counter += 4;
+        if self.counter < Serial::CYCLES_PER_BIT {
+            return;
+        }
+        self.counter -= Serial::CYCLES_PER_BIT;
+
+        // nothing is connected, so the line floats high and we shift in 1s
+        self.data = (self.data << 1) | 0x01;
+        self.bits_remaining -= 1;
+
+        if self.bits_remaining == 0 {
+            self.transferring = false;
+            ih.set_interrupt(Interrupt::SerialIOComplete);
+        }
+    }
+}
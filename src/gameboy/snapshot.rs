@@ -0,0 +1,83 @@
+// the machine's state kept in memory, for rewinding and rollback netplay. it holds the same
+// things a save state does, but as plain copies rather than serialized, so saving and loading
+// one is a handful of memcpys. saving over an old snapshot reuses it, keep a ring of them
+// rather than making a new one every frame
+
+use crate::cartridge::MapperState;
+use crate::gameboy::boot::BootSequence;
+use crate::gameboy::cpu::CPU;
+use crate::gameboy::interrupt::InterruptHandler;
+use crate::gameboy::joypad::{Controls, InputQueue, Joypad};
+use crate::gameboy::lcd::LCD;
+use crate::gameboy::scheduler::EventClock;
+use crate::gameboy::serial::Serial;
+use crate::gameboy::timer::Timer;
+
+pub struct Snapshot {
+    pub(crate) cpu: CPU,
+
+    pub(crate) system_ram: [u8; 0x2000],
+    pub(crate) high_ram: [u8; 0x7F],
+    pub(crate) wave_ram: [u8; 0x10],
+    pub(crate) serial: Serial,
+    pub(crate) interrupt: InterruptHandler,
+    pub(crate) cycles: u128,
+    pub(crate) prev_cycles: u128,
+    pub(crate) timer: Timer,
+    pub(crate) timer_clock: EventClock,
+    pub(crate) serial_clock: EventClock,
+    pub(crate) lcd: LCD,
+    pub(crate) lcd_clock: EventClock,
+    pub(crate) joypad: Joypad,
+    pub(crate) mapper: MapperState,
+
+    pub(crate) controls: Controls,
+    pub(crate) input_queue: InputQueue,
+    pub(crate) taken_frame: u64,
+    pub(crate) boot: Option<BootSequence>,
+}
+
+impl Default for Snapshot {
+    fn default() -> Snapshot {
+        Snapshot {
+            cpu: CPU::new(),
+
+            system_ram: [0x00; 0x2000],
+            high_ram: [0x00; 0x7F],
+            wave_ram: [0x00; 0x10],
+            serial: Serial::new(),
+            interrupt: InterruptHandler::new(),
+            cycles: 0,
+            prev_cycles: 0,
+            timer: Timer::new(),
+            timer_clock: EventClock::new(),
+            serial_clock: EventClock::new(),
+            lcd: LCD::new(),
+            lcd_clock: EventClock::new(),
+            joypad: Joypad::new(),
+            mapper: MapperState::default(),
+
+            controls: Controls::default(),
+            input_queue: InputQueue::new(),
+            taken_frame: 0,
+            boot: None,
+        }
+    }
+}
+
+impl Snapshot {
+    // an empty one to save into
+    pub fn new() -> Snapshot {
+        Snapshot::default()
+    }
+
+    // clock cycles since power on when it was saved
+    pub fn elapsed_cycles(&self) -> u128 {
+        self.cycles
+    }
+
+    // the LCD's frame_count when it was saved
+    pub fn frame_count(&self) -> u64 {
+        self.lcd.frame_count()
+    }
+}
@@ -1,8 +1,11 @@
-use num_traits::FromPrimitive;
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
 
 use crate::gameboy::interrupt::{InterruptHandler, Interrupt};
+use crate::gameboy::scheduler::Scheduled;
 
-#[derive(Clone, Copy, Debug, FromPrimitive)]
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub enum Clock {
     Clk4096Hz = 0,
     Clk262144Hz = 1,
@@ -23,10 +26,17 @@ impl Clock {
 }
 impl From<u8> for Clock {
     fn from(value: u8) -> Clock {
-        FromPrimitive::from_u8(value).unwrap_or_else(|| panic!("Invalid clock selection {}", value))
+        match value & 0b11 {
+            0 => Clock::Clk4096Hz,
+            1 => Clock::Clk262144Hz,
+            2 => Clock::Clk65536Hz,
+            _ => Clock::Clk16384Hz,
+        }
     }
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct Timer {
     divider: u8,
     counter: u32,
@@ -48,24 +58,26 @@ impl Timer {
         }
     }
 
-    pub fn read_register(&self, addr: u16) -> u8 {
-        match addr {
+    // None for addresses outside 0xFF04 - 0xFF07
+    pub fn read_register(&self, addr: u16) -> Option<u8> {
+        Some(match addr {
             0xFF04 => self.get_divider(),
             0xFF05 => self.get_counter(),
             0xFF06 => self.get_modulo(),
             0xFF07 => self.get_control(),
-            _ => unreachable!(), // mmu will only send us addresses in 0xFF04 - 0xFF07 range
-        }
+            _ => return None,
+        })
     }
 
-    pub fn write_register(&mut self, addr: u16, value: u8) {
+    pub fn write_register(&mut self, addr: u16, value: u8) -> Option<()> {
         match addr {
             0xFF04 => self.reset_divider(),
             0xFF05 => self.set_counter(value),
             0xFF06 => self.set_modulo(value),
             0xFF07 => self.set_control(value),
-            _ => unreachable!(), // mmu will only send us addresses in 0xFF04 - 0xFF07 range
+            _ => return None,
         }
+        Some(())
     }
 
     fn get_divider(&self) -> u8 {
@@ -76,6 +88,11 @@ impl Timer {
         self.divider = 0;
     }
 
+    // for starting where a boot ROM would have left it, games can only reset it
+    pub fn set_divider(&mut self, value: u8) {
+        self.divider = value;
+    }
+
     fn get_counter(&self) -> u8 {
         self.tima
     }
@@ -100,8 +117,41 @@ impl Timer {
         self.enabled = (value >> 2) & 0x1 == 1;
         self.clock = Clock::from(value & 0x3);
     }
+}
+
+impl Scheduled for Timer {
+    fn quiet_steps(&self) -> u64 {
+        if !self.enabled {
+            return u64::MAX;
+        }
+
+        // TIMA only changes on the step that takes the counter up to the clock ratio
+        if self.counter + 4 >= self.clock.ratio() {
+            0
+        } else {
+            ((self.clock.ratio() - self.counter) / 4 - 1) as u64
+        }
+    }
+
+    fn skip(&mut self, steps: u64) {
+        self.divider = self.divider.wrapping_add((steps * 4) as u8);
+        if self.enabled {
+            self.counter += (steps * 4) as u32;
+        }
+    }
+
+    fn steps_to_next_event(&self) -> u64 {
+        // TIMA increments are only visible through register reads,
+        // so the only thing we need to be on time for is the overflow interrupt
+        if !self.enabled || self.counter >= self.clock.ratio() {
+            return self.quiet_steps().saturating_add(1);
+        }
+
+        let increments_to_overflow = 0xFF - self.tima as u64;
+        (self.quiet_steps() + 1) + increments_to_overflow * (self.clock.ratio() / 4) as u64
+    }
 
-    pub fn step(&mut self, ih: &mut InterruptHandler) {
+    fn step(&mut self, ih: &mut InterruptHandler) {
         self.divider = self.divider.wrapping_add(4);
 
         if self.enabled {
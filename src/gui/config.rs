@@ -0,0 +1,141 @@
+// settings changed while playing that should stick for next time, kept as JSON in the
+// user's config directory
+
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use winit::event::VirtualKeyCode;
+
+use crate::gui::hotkeys::Hotkey;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // bilinear smoothing rather than crisp pixels when the screen's scaled up
+    pub smooth: bool,
+    // ROMs played, newest first
+    pub recent: Vec<PathBuf>,
+    // what's kept for each ROM: how long it's been played, for the library, and its own settings
+    pub games: BTreeMap<PathBuf, GameConfig>,
+    // shortcuts moved off their default keys, see hotkeys.rs
+    pub hotkeys: BTreeMap<Hotkey, VirtualKeyCode>,
+    // quit straight away, rather than asking first when the game's only just saved
+    pub quit_without_asking: bool,
+    pub accessibility: Accessibility,
+    // what fills the window around the screen when their shapes don't match
+    pub backdrop: Backdrop,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Accessibility {
+    // no gamepad rumble, whatever --rumble says
+    pub no_rumble: bool,
+    // hold back full screen flashes, see filter::FlashLimiter
+    pub reduce_flashing: bool,
+    // the shades spread evenly, see filter::high_contrast
+    pub high_contrast: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Backdrop {
+    // RGB, black unless it's set
+    pub color: [u8; 3],
+    // a bevel around the screen, shaded from the color
+    pub frame: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    // seconds
+    pub playtime: u64,
+    // seconds since the unix epoch, 0 if it's never been played
+    pub last_played: u64,
+    // show each frame mixed half and half with the one before, for games that flicker
+    // sprites on alternate frames to fake transparency
+    pub frame_blend: bool,
+}
+
+impl GameConfig {
+    pub fn playtime(&self) -> Duration {
+        Duration::from_secs(self.playtime)
+    }
+
+    pub fn last_played(&self) -> Option<SystemTime> {
+        match self.last_played {
+            0 => None,
+            secs => Some(UNIX_EPOCH + Duration::from_secs(secs)),
+        }
+    }
+}
+
+const MAX_RECENT: usize = 10;
+
+impl Config {
+    // the defaults if there's no config yet, or it can't be read
+    pub fn load() -> Config {
+        let path = match path() {
+            Some(path) if path.exists() => path,
+            _ => return Config::default(),
+        };
+        match fs::read_to_string(&path).map_err(Box::<dyn Error>::from)
+            .and_then(|json| Ok(serde_json::from_str(&json)?)) {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("Problem reading config {}: {}", path.display(), err);
+                Config::default()
+            },
+        }
+    }
+
+    // also marks it as played now
+    pub fn add_recent(&mut self, rom: &Path) {
+        let rom = canonical(rom);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        self.game_mut(&rom).last_played = now.as_secs();
+        self.recent.retain(|recent| *recent != rom);
+        self.recent.insert(0, rom);
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    pub fn add_playtime(&mut self, rom: &Path, playtime: Duration) {
+        self.game_mut(rom).playtime += playtime.as_secs();
+    }
+
+    pub fn game(&self, rom: &Path) -> GameConfig {
+        self.games.get(&canonical(rom)).copied().unwrap_or_default()
+    }
+
+    pub fn game_mut(&mut self, rom: &Path) -> &mut GameConfig {
+        self.games.entry(canonical(rom)).or_default()
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = path().ok_or("no config directory to save to")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+// the same ROM's always filed under the same path, however it was given
+fn canonical(rom: &Path) -> PathBuf {
+    rom.canonicalize().unwrap_or_else(|_| rom.to_path_buf())
+}
+
+// gboxide/config.json in the platform's usual place for it
+fn path() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(dir.join("gboxide").join("config.json"))
+}
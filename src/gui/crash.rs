@@ -0,0 +1,77 @@
+// when the core stops with an error, everything needed to look into it gets written to a new
+// crash-<date>-<time> directory: what went wrong with the instructions leading up to it, a save
+// state to load and step into the fault, the screen, and the cartridge's header.
+// the time's UTC, so bundles from different people still sort properly
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::gameboy::GameBoy;
+use crate::gameboy::disasm::Symbols;
+
+// writes the bundle into a new directory under directory and returns where it went
+pub fn write_bundle(directory: &Path, gameboy: &GameBoy, error: &crate::Error, symbols: &Symbols)
+                    -> Result<PathBuf, Box<dyn Error>> {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs());
+    let mut bundle = directory.join(format!("crash-{}", timestamp(secs)));
+    // a second crash in the same second shouldn't overwrite the first
+    let mut attempt = 1;
+    while bundle.exists() {
+        attempt += 1;
+        bundle = directory.join(format!("crash-{}-{}", timestamp(secs), attempt));
+    }
+    fs::create_dir_all(&bundle)
+        .map_err(|e| format!("couldn't create {}: {}", bundle.display(), e))?;
+
+    let report = match error.fault() {
+        Some(fault) => {
+            let mut report = format!("{} at pc {}, cycle {}", fault.message, fault.pc, fault.cycle);
+            if let Some(address) = fault.address {
+                report += &format!(", accessing {:04x}", address);
+            }
+            report += &format!("\nlast {} instructions, oldest first:\n", fault.trace.len());
+            for entry in fault.trace.iter() {
+                report += &format!("  {}\n", entry.annotated(symbols));
+            }
+            report
+        },
+        None => format!("{}\n{}", error, gameboy.trace().annotated(symbols)),
+    };
+    let files: [(&str, Vec<u8>); 4] = [
+        ("fault.txt", report.into_bytes()),
+        ("state.json", serde_json::to_vec(gameboy).map_err(|e| format!("couldn't save state: {}", e))?),
+        ("screen.png", gameboy.screenshot()),
+        ("header.json", serde_json::to_vec_pretty(gameboy.header()).map_err(|e| format!("couldn't save header: {}", e))?),
+    ];
+    for (name, contents) in files.iter() {
+        let path = bundle.join(name);
+        fs::write(&path, contents)
+            .map_err(|e| format!("couldn't save {}: {}", path.display(), e))?;
+    }
+    Ok(bundle)
+}
+
+// e.g. 20261016-142501
+fn timestamp(secs: u64) -> String {
+    let days = secs / 86400;
+    let time = secs % 86400;
+    let (year, month, day) = civil_from_days(days as i64);
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}", year, month, day, time / 3600, time / 60 % 60, time % 60)
+}
+
+// days since the unix epoch to a year, month and day, see
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
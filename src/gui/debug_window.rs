@@ -0,0 +1,80 @@
+// extra windows alongside the game's for looking inside it, each with its own pixels surface on
+// the same event loop. each asks the emulator thread for a new picture once the last one has
+// arrived, so they keep up with the game without queueing requests behind it
+
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+use pixels::{Error, Pixels, SurfaceTexture};
+use winit::dpi::LogicalSize;
+use winit::event_loop::EventLoopWindowTarget;
+use winit::window::{Window, WindowBuilder, WindowId};
+
+use crate::gui::emulator::{Command, EmulatorThread, View};
+use crate::gui::physical_size;
+
+pub struct DebugWindow {
+    view: View,
+    window: Window,
+    pixels: Pixels,
+    // the picture asked for and not arrived yet
+    pending: Option<Receiver<Vec<u8>>>,
+}
+
+impl DebugWindow {
+    pub fn open<T: 'static>(view: View, event_loop: &EventLoopWindowTarget<T>) -> Result<DebugWindow, Error> {
+        let (width, height) = view.size();
+        let scale = 2.0;
+        let title = match view {
+            View::Tiles => "GBOxide - Tiles",
+            View::Map => "GBOxide - Map",
+        };
+
+        let window = WindowBuilder::new()
+            .with_inner_size(LogicalSize::new(width as f64 * scale, height as f64 * scale))
+            .with_title(title)
+            .build(event_loop)
+            .unwrap();
+        let surface = pixels::wgpu::Surface::create(&window);
+        let (surface_width, surface_height) = physical_size(&window);
+        let pixels = Pixels::new(width, height, SurfaceTexture::new(surface_width, surface_height, surface))?;
+
+        Ok(DebugWindow { view, window, pixels, pending: None })
+    }
+
+    pub fn view(&self) -> View {
+        self.view
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    // after the window's been resized or moved to a monitor with a different scale
+    pub fn resize(&mut self) {
+        let (width, height) = physical_size(&self.window);
+        self.pixels.resize(width, height);
+    }
+
+    pub fn render(&mut self) {
+        self.pixels.render();
+    }
+
+    // shows the last picture asked for if it's arrived, and asks for the next
+    pub fn update(&mut self, emulator: &EmulatorThread) {
+        if let Some(pending) = &self.pending {
+            match pending.try_recv() {
+                Ok(picture) => {
+                    self.pixels.get_frame().copy_from_slice(&picture);
+                    self.window.request_redraw();
+                },
+                Err(TryRecvError::Empty) => return,
+                // the emulator's stopped, it'll have said why
+                Err(TryRecvError::Disconnected) => (),
+            }
+        }
+
+        let (reply, pending) = mpsc::channel();
+        emulator.send(Command::View(self.view, reply));
+        self.pending = Some(pending);
+    }
+}
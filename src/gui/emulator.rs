@@ -0,0 +1,574 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvError, Sender, SyncSender, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::achievements::Achievements;
+use crate::gameboy::{GameBoy, CYCLES_PER_SECOND};
+use crate::gameboy::cheat::CheatCode;
+use crate::gameboy::debugger::{Stop, TraceFilter, CDL_CODE, CDL_DATA};
+use crate::gameboy::disasm::Symbols;
+use crate::gameboy::dump::{TILE_DATA_WIDTH, TILE_DATA_HEIGHT};
+use crate::gameboy::joypad::Controls;
+use crate::gameboy::lcd::{Palettes, ScanlineRegisters, BG_MAP_SIZE, CYCLES_PER_FRAME, SCREEN_WIDTH, SCREEN_HEIGHT};
+use crate::gui::crash;
+use crate::gui::remote::{Request, Response};
+
+// how far behind real time we let emulation fall before giving up on catching up
+const MAX_FRAMES_BEHIND: u32 = 5;
+
+// emulation speed limits, as a percentage of real time
+pub const MIN_SPEED: u32 = 25;
+pub const MAX_SPEED: u32 = 400;
+
+// frames waiting for the ui, any more than this and the emulator drops them
+const FRAME_QUEUE_LENGTH: usize = 2;
+
+// loading a save state deserializes a whole GameBoy at once,
+// which takes far more than the default 2MiB of stack in debug builds
+const STACK_SIZE: usize = 32 * 1024 * 1024;
+
+// the screen as it was when a save state or battery save was written, next to it as <file>.png
+pub fn thumbnail_path(path: &Path) -> PathBuf {
+    let mut thumbnail = OsString::from(path.as_os_str());
+    thumbnail.push(".png");
+    PathBuf::from(thumbnail)
+}
+
+pub enum Command {
+    Controls(Controls),
+    // buttons to press and release repeatedly for as long as they're held
+    Turbo(Controls),
+    FastForward(bool),
+    // percentage of real time to run at, clamped to MIN_SPEED..=MAX_SPEED
+    Speed(u32),
+    Remote(Request, Sender<Response>),
+    // the current frame's number and RGBA pixels
+    Frame(Sender<(u64, Vec<u8>)>),
+    // RGBA pixels of some part of VRAM, for the debug windows
+    View(View, Sender<Vec<u8>>),
+    // see GameBoy::set_layer_overlay
+    LayerOverlay(bool),
+    Palettes(Sender<Palettes>),
+    // palette, entry and shade, see GameBoy::set_palette_override
+    OverridePalette(usize, usize, Option<u8>),
+    // see GameBoy::scanline_registers
+    ScanlineRegisters(Sender<Vec<ScanlineRegisters>>),
+    // channel 3's wave pattern, see GameBoy::wave_ram
+    WaveRam(Sender<[u8; 16]>),
+    SetWaveRam([u8; 16]),
+    // the cartridge's RAM, all banks, see GameBoy::cartridge_ram
+    CartridgeRam(Sender<Vec<u8>>),
+    // a byte at an offset into it
+    SetCartridgeRam(usize, u8),
+    // the codes of the cheats that are switched on, see GameBoy::set_cheats
+    Cheats(Vec<CheatCode>),
+    // when the game last wrote to its cartridge's RAM, None if it hasn't yet
+    LastSave(Sender<Option<Instant>>),
+    Quit,
+}
+
+// what a debug window can show
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum View {
+    // every tile, TILE_DATA_WIDTH x TILE_DATA_HEIGHT
+    Tiles,
+    // the bg map in use, BG_MAP_SIZE square
+    Map,
+}
+
+impl View {
+    pub fn size(self) -> (u32, u32) {
+        match self {
+            View::Tiles => (TILE_DATA_WIDTH, TILE_DATA_HEIGHT),
+            View::Map => (BG_MAP_SIZE as u32, BG_MAP_SIZE as u32),
+        }
+    }
+}
+
+pub enum Update {
+    Frame(Vec<u8>),
+    // something to tell the player, like an achievement unlocking
+    Message(String),
+    Error(String),
+}
+
+// runs a GameBoy on its own thread at real time speed,
+// so the ui stays responsive while the window is dragged or the emulator is fast-forwarding
+pub struct EmulatorThread {
+    commands: Sender<Command>,
+    updates: Receiver<Update>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl EmulatorThread {
+    // turbo buttons toggle between pressed and released every turbo_interval frames.
+    // the cartridge's battery backed RAM is written to battery_save when the thread stops,
+    // and the code/data log to code_data_log. achievements are checked every frame, and
+    // symbols name addresses in traces. if the gameboy errors, a crash bundle goes in crash_dump
+    pub fn new(gameboy: GameBoy, turbo_interval: u32, battery_save: Option<PathBuf>,
+               code_data_log: Option<PathBuf>, achievements: Option<Achievements>, symbols: Symbols,
+               crash_dump: Option<PathBuf>) -> EmulatorThread {
+        let (commands, command_receiver) = mpsc::channel();
+        let (update_sender, updates) = mpsc::sync_channel(FRAME_QUEUE_LENGTH);
+
+        let worker = thread::Builder::new()
+            .name("emulator".to_string())
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                let mut gameboy = EmulatorThread::run(gameboy, turbo_interval, achievements, symbols, crash_dump,
+                                                   command_receiver, update_sender);
+                if let Some(path) = battery_save {
+                    EmulatorThread::write_battery_save(&mut gameboy, &path);
+                }
+                if let Some(path) = code_data_log {
+                    EmulatorThread::write_code_data_log(&gameboy, &path);
+                }
+            })
+            .expect("couldn't start the emulator thread");
+
+        EmulatorThread { commands, updates, worker: Some(worker) }
+    }
+
+    // for handing to other threads that need to drive the emulator, like the remote control
+    pub fn command_sender(&self) -> Sender<Command> {
+        self.commands.clone()
+    }
+
+    pub fn send(&self, command: Command) {
+        // if the thread has stopped it's already reported why through an update
+        let _ = self.commands.send(command);
+    }
+
+    // waits for the thread to finish up, including writing the battery save
+    pub fn stop(&mut self) {
+        self.send(Command::Quit);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+
+    // the newest frame since the last call, if there is one.
+    // messages and errors come back as soon as they're reached, the frames after them wait for the next call
+    pub fn latest_update(&self) -> Option<Update> {
+        let mut latest = None;
+        while let Ok(update) = self.updates.try_recv() {
+            match update {
+                Update::Message(_) | Update::Error(_) => return Some(update),
+                Update::Frame(_) => latest = Some(update),
+            }
+        }
+        latest
+    }
+
+    // runs until told to quit or the gameboy errors, then hands it back
+    fn run(gameboy: GameBoy, turbo_interval: u32, achievements: Option<Achievements>, symbols: Symbols,
+           crash_dump: Option<PathBuf>, commands: Receiver<Command>, updates: SyncSender<Update>) -> GameBoy {
+        let real_frame_time = Duration::from_nanos(
+            CYCLES_PER_FRAME as u64 * 1_000_000_000 / CYCLES_PER_SECOND as u64);
+        let mut state = State {
+            gameboy,
+            keyboard_controls: Controls::default(),
+            remote_controls: Controls::default(),
+            turbo_controls: Controls::default(),
+            turbo_interval: turbo_interval.max(1),
+            turbo_frame: 0,
+            fast_forward: false,
+            speed: 100,
+            paused: false,
+            lockup_reported: false,
+            achievements,
+            ram_writes: 0,
+            last_save: None,
+            symbols,
+            trace_log: None,
+        };
+        let mut next_frame = Instant::now();
+
+        loop {
+            loop {
+                // while paused there's nothing to do until a command comes in
+                let was_paused = state.paused;
+                let command = if was_paused {
+                    commands.recv().map_err(|RecvError| TryRecvError::Disconnected)
+                } else {
+                    commands.try_recv()
+                };
+                match command {
+                    Ok(Command::Quit) | Err(TryRecvError::Disconnected) => {
+                        state.write_trace_log();
+                        return state.gameboy;
+                    },
+                    Ok(command) => state.handle(command),
+                    Err(TryRecvError::Empty) => break,
+                }
+                if was_paused && !state.paused {
+                    // don't try to catch up on the time spent paused
+                    next_frame = Instant::now();
+                }
+            }
+
+            state.advance_turbo();
+
+            let stop = state.gameboy.run_until_break();
+            state.write_trace_log();
+            match stop {
+                Ok(Stop::VBlank) => (),
+                Ok(Stop::Breakpoint(pc)) => {
+                    match state.symbols.label(pc.bank, pc.addr) {
+                        Some(label) => eprintln!("Hit breakpoint at {} <{}>", pc, label),
+                        None => eprintln!("Hit breakpoint at {}", pc),
+                    }
+                    state.paused = true;
+                    continue;
+                },
+                Ok(Stop::Interrupt(dispatch)) => {
+                    eprintln!("Hit interrupt break, {}", dispatch);
+                    state.paused = true;
+                    continue;
+                },
+                Ok(Stop::Until(until)) => {
+                    eprintln!("Reached {}, pc {}", until, state.gameboy.pc());
+                    state.paused = true;
+                    continue;
+                },
+                Err(err) => {
+                    if let Some(directory) = &crash_dump {
+                        match crash::write_bundle(directory, &state.gameboy, &err, &state.symbols) {
+                            Ok(bundle) => eprintln!("Wrote crash bundle to {}", bundle.display()),
+                            Err(bundle_err) => eprintln!("Problem writing crash bundle: {}", bundle_err),
+                        }
+                    }
+                    let _ = updates.send(Update::Error(err.to_string()));
+                    return state.gameboy;
+                },
+            }
+
+            // the game's stuck for good, say how it got there once rather than every frame
+            let locked_up = state.gameboy.locked_up();
+            if locked_up && !state.lockup_reported {
+                eprint!("CPU locked up at {}, {}", state.gameboy.pc(), state.gameboy.trace().annotated(&state.symbols));
+            }
+            state.lockup_reported = locked_up;
+
+            let ram_writes = state.gameboy.ram_writes();
+            if ram_writes != state.ram_writes {
+                state.ram_writes = ram_writes;
+                state.last_save = Some(Instant::now());
+            }
+
+            if let Some(achievements) = &mut state.achievements {
+                achievements.check(&mut state.gameboy, |achievement| {
+                    let message = format!("Achievement unlocked: {}", achievement.title);
+                    eprintln!("{} - {}", message, achievement.description);
+                    // it's already on stderr, so it's not lost if the ui is too far behind to show it
+                    let _ = updates.try_send(Update::Message(message));
+                });
+            }
+
+            let mut frame = vec![0x00; SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize * 4];
+            state.gameboy.draw_frame(&mut frame);
+            // the ui only shows the newest frame, so don't wait on it if it's behind
+            let _ = updates.try_send(Update::Frame(frame));
+
+            let frame_time = real_frame_time * 100 / state.speed;
+            let now = Instant::now();
+            next_frame += frame_time;
+            if state.fast_forward || now > next_frame + frame_time * MAX_FRAMES_BEHIND {
+                next_frame = now;
+            } else if next_frame > now {
+                thread::sleep(next_frame - now);
+            }
+        }
+    }
+
+    // written next to the old save and moved over it once it's safely on disk,
+    // so being cut off part way through can't leave a truncated save behind. the screen goes
+    // alongside it, see thumbnail_path
+    fn write_battery_save(gameboy: &mut GameBoy, path: &PathBuf) {
+        if let Some(save) = gameboy.battery_save() {
+            let temp = path.with_extension("sav.tmp");
+            let written = File::create(&temp)
+                .and_then(|mut file| file.write_all(&save).and_then(|_| file.sync_all()))
+                .and_then(|_| fs::rename(&temp, path));
+            if let Err(err) = written {
+                let _ = fs::remove_file(&temp);
+                eprintln!("Problem writing battery save {}: {}", path.display(), err);
+                return;
+            }
+            let thumbnail = thumbnail_path(path);
+            if let Err(err) = fs::write(&thumbnail, gameboy.screenshot()) {
+                eprintln!("Problem writing {}: {}", thumbnail.display(), err);
+            }
+        }
+    }
+
+    fn write_code_data_log(gameboy: &GameBoy, path: &PathBuf) {
+        if let Some(log) = gameboy.code_data_log() {
+            if let Err(err) = fs::write(path, log.as_bytes()) {
+                eprintln!("Problem writing code/data log {}: {}", path.display(), err);
+            }
+        }
+    }
+}
+
+// everything the emulator thread owns
+struct State {
+    gameboy: GameBoy,
+    keyboard_controls: Controls,
+    remote_controls: Controls,
+    turbo_controls: Controls,
+    turbo_interval: u32,
+    // frames since the turbo buttons were pressed, to time the toggling
+    turbo_frame: u32,
+    fast_forward: bool,
+    speed: u32,
+    paused: bool,
+    lockup_reported: bool,
+    achievements: Option<Achievements>,
+    // the cartridge's RAM write count as of the last frame, and when it last changed
+    ram_writes: u64,
+    last_save: Option<Instant>,
+    symbols: Symbols,
+    // where the debugger's trace log goes while it's on
+    trace_log: Option<BufWriter<File>>,
+}
+
+impl State {
+    fn handle(&mut self, command: Command) {
+        match command {
+            Command::Controls(controls) => {
+                self.keyboard_controls = controls;
+                self.update_controls();
+            },
+            Command::Turbo(controls) => {
+                if controls != self.turbo_controls {
+                    self.turbo_controls = controls;
+                    self.turbo_frame = 0;
+                    self.update_controls();
+                }
+            },
+            Command::FastForward(enabled) => self.fast_forward = enabled,
+            Command::Speed(speed) => self.speed = speed.max(MIN_SPEED).min(MAX_SPEED),
+            Command::Remote(request, reply) => {
+                let response = self.handle_remote(request);
+                let _ = reply.send(response);
+            },
+            Command::Frame(reply) => {
+                let _ = reply.send((self.gameboy.frame_count(), self.gameboy.screenshot_rgba()));
+            },
+            Command::View(view, reply) => {
+                let _ = reply.send(match view {
+                    View::Tiles => self.gameboy.tile_data_rgba(),
+                    View::Map => self.gameboy.bg_map_rgba(),
+                });
+            },
+            Command::LayerOverlay(on) => self.gameboy.set_layer_overlay(on),
+            Command::Palettes(reply) => {
+                let _ = reply.send(self.gameboy.palettes());
+            },
+            Command::OverridePalette(palette, entry, shade) => self.gameboy.set_palette_override(palette, entry, shade),
+            Command::ScanlineRegisters(reply) => {
+                let _ = reply.send(self.gameboy.scanline_registers().to_vec());
+            },
+            Command::WaveRam(reply) => {
+                let _ = reply.send(self.gameboy.wave_ram());
+            },
+            Command::SetWaveRam(wave) => self.gameboy.set_wave_ram(wave),
+            Command::Cheats(codes) => self.gameboy.set_cheats(codes),
+            Command::CartridgeRam(reply) => {
+                let _ = reply.send(self.gameboy.cartridge_ram().to_vec());
+            },
+            Command::SetCartridgeRam(offset, value) => {
+                if let Err(err) = self.gameboy.write_cartridge_ram(offset, &[value]) {
+                    eprintln!("Problem editing cartridge RAM: {}", err);
+                }
+            },
+            Command::LastSave(reply) => {
+                let _ = reply.send(self.last_save);
+            },
+            Command::Quit => (), // handled by the run loop
+        }
+    }
+
+    fn update_controls(&mut self) {
+        let mut controls = self.keyboard_controls.merge(self.remote_controls);
+        // pressed for the first interval, released for the next, and so on
+        if (self.turbo_frame / self.turbo_interval) % 2 == 0 {
+            controls = controls.merge(self.turbo_controls);
+        }
+        self.gameboy.set_controls(controls);
+    }
+
+    fn advance_turbo(&mut self) {
+        if self.turbo_controls == Controls::default() {
+            return;
+        }
+        self.turbo_frame = self.turbo_frame.wrapping_add(1);
+        if self.turbo_frame % self.turbo_interval == 0 {
+            self.update_controls();
+        }
+    }
+
+    // writes out what the debugger's traced since last time, if it's tracing
+    fn write_trace_log(&mut self) {
+        let writer = match &mut self.trace_log {
+            Some(writer) => writer,
+            None => return,
+        };
+        let symbols = &self.symbols;
+        let written = self.gameboy.debugger_mut().take_trace_log().iter()
+            .try_for_each(|entry| writeln!(writer, "{}", entry.annotated(symbols)))
+            .and_then(|_| writer.flush());
+        if let Err(err) = written {
+            eprintln!("Problem writing trace log: {}", err);
+            self.gameboy.debugger_mut().stop_trace_log();
+            self.trace_log = None;
+        }
+    }
+
+    fn handle_remote(&mut self, request: Request) -> Response {
+        match request {
+            Request::Pause => self.paused = true,
+            Request::Resume => self.paused = false,
+            Request::SaveState(path) => {
+                let state = serde_json::to_vec(&self.gameboy).map_err(|e| format!("couldn't save state: {}", e))?;
+                fs::write(&path, state)
+                    .map_err(|e| format!("couldn't save {}: {}", path.display(), e))?;
+                let thumbnail = thumbnail_path(&path);
+                fs::write(&thumbnail, self.gameboy.screenshot())
+                    .map_err(|e| format!("couldn't save {}: {}", thumbnail.display(), e))?;
+            },
+            Request::LoadState(path) => {
+                let state = fs::read(&path)
+                    .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+                let state = serde_json::from_slice(&state)
+                    .map_err(|e| format!("{} isn't a save state: {}", path.display(), e))?;
+                self.gameboy.load_state(state).map_err(|e| e.to_string())?;
+                // the buttons held now, not when the state was saved
+                self.update_controls();
+            },
+            Request::Screenshot(path) => {
+                fs::write(&path, self.gameboy.screenshot())
+                    .map_err(|e| format!("couldn't save {}: {}", path.display(), e))?;
+            },
+            Request::SetButtons(controls) => {
+                self.remote_controls = controls;
+                self.update_controls();
+            },
+            Request::ReadMemory(addr, length) => {
+                let bytes: Vec<String> = (0..length)
+                    .map(|offset| format!("{:02x}", self.gameboy.peek(addr.wrapping_add(offset))))
+                    .collect();
+                return Ok(bytes.join(" "));
+            },
+            Request::Break(breakpoint) => self.gameboy.debugger_mut().add_breakpoint(breakpoint),
+            Request::Unbreak(breakpoint) => self.gameboy.debugger_mut().remove_breakpoint(breakpoint),
+            Request::Until(until) => {
+                self.gameboy.debugger_mut().set_until(Some(until));
+                self.paused = false;
+            },
+            Request::BreakInterrupt(interrupt) => self.gameboy.debugger_mut().add_interrupt_break(interrupt),
+            Request::UnbreakInterrupt(interrupt) => self.gameboy.debugger_mut().remove_interrupt_break(interrupt),
+            Request::Pc => return Ok(self.gameboy.pc().to_string()),
+            Request::InterruptLogging(enabled) => self.gameboy.debugger_mut().set_interrupt_logging(enabled),
+            Request::DumpInterruptLog(path) => {
+                if !self.gameboy.debugger().interrupt_logging() {
+                    return Err("interrupt logging isn't started".to_string());
+                }
+                let log: String = self.gameboy.debugger().interrupt_log()
+                    .map(|event| format!("{}\n", event))
+                    .collect();
+                fs::write(&path, log)
+                    .map_err(|e| format!("couldn't save {}: {}", path.display(), e))?;
+            },
+            Request::DmaLogging(enabled) => self.gameboy.debugger_mut().set_dma_logging(enabled),
+            Request::DumpDmaLog(path) => {
+                if !self.gameboy.debugger().dma_logging() {
+                    return Err("DMA logging isn't started".to_string());
+                }
+                let log: String = self.gameboy.debugger().dma_log()
+                    .map(|transfer| format!("{}\n", transfer))
+                    .collect();
+                fs::write(&path, log)
+                    .map_err(|e| format!("couldn't save {}: {}", path.display(), e))?;
+            },
+            Request::CodeDataLogging(enabled) => self.gameboy.set_code_data_logging(enabled),
+            Request::DumpCodeDataLog(path) => {
+                let log = self.gameboy.code_data_log()
+                    .ok_or_else(|| "code/data logging isn't started".to_string())?;
+                fs::write(&path, log.as_bytes())
+                    .map_err(|e| format!("couldn't save {}: {}", path.display(), e))?;
+                return Ok(format!("{} code bytes, {} data bytes", log.count(CDL_CODE), log.count(CDL_DATA)));
+            },
+            Request::Profiling(true) => self.gameboy.debugger_mut().start_profiling(),
+            Request::Profiling(false) => self.gameboy.debugger_mut().stop_profiling(),
+            Request::DumpProfile(path) => {
+                let profile = self.gameboy.debugger().profile()
+                    .ok_or_else(|| "profiling hasn't been started".to_string())?;
+                fs::write(&path, profile.to_string())
+                    .map_err(|e| format!("couldn't save {}: {}", path.display(), e))?;
+            },
+            Request::TraceLog(path, filter) => {
+                let filter = match filter {
+                    Some(filter) if filter.contains('-') => Some(filter.parse::<TraceFilter>().map_err(|e| e.to_string())?),
+                    Some(name) => {
+                        let (bank, addrs) = self.symbols.extent(&name)
+                            .ok_or_else(|| format!("there's no symbol called {}", name))?;
+                        Some(TraceFilter { bank: Some(bank), start: *addrs.start(), end: *addrs.end() })
+                    },
+                    None => None,
+                };
+                let file = File::create(&path)
+                    .map_err(|e| format!("couldn't create {}: {}", path.display(), e))?;
+                self.write_trace_log();
+                self.trace_log = Some(BufWriter::new(file));
+                self.gameboy.debugger_mut().start_trace_log(filter);
+            },
+            Request::StopTraceLog => {
+                self.write_trace_log();
+                self.gameboy.debugger_mut().stop_trace_log();
+                self.trace_log = None;
+            },
+            Request::Snapshot => self.gameboy.snapshot_ram(),
+            Request::Diff(direction) => {
+                let changes = self.gameboy.ram_changes()
+                    .ok_or_else(|| "there's no snapshot to diff against".to_string())?;
+                let changes: Vec<String> = changes.iter()
+                    .filter(|change| direction.is_none_or(|direction| change.after.cmp(&change.before) == direction))
+                    .map(|change| change.to_string())
+                    .collect();
+                return Ok(changes.join(" "));
+            },
+            Request::Fill(region, value) => self.gameboy.fill_memory(region, value),
+            Request::ExportCartridgeRam(path) => {
+                let ram = self.gameboy.cartridge_ram();
+                if ram.is_empty() {
+                    return Err("the cartridge has no RAM".to_string());
+                }
+                fs::write(&path, ram)
+                    .map_err(|e| format!("couldn't save {}: {}", path.display(), e))?;
+            },
+            Request::ImportCartridgeRam(path) => {
+                let ram = fs::read(&path)
+                    .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+                let len = self.gameboy.cartridge_ram().len();
+                if ram.len() != len {
+                    return Err(format!("{} is {} bytes, the cartridge has {} bytes of RAM", path.display(), ram.len(), len));
+                }
+                self.gameboy.write_cartridge_ram(0, &ram).map_err(|e| e.to_string())?;
+            },
+        }
+
+        Ok(String::new())
+    }
+}
+
+impl Drop for EmulatorThread {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
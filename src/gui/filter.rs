@@ -0,0 +1,143 @@
+// the pixels surface only samples nearest neighbour, so smoothing is done here instead: the
+// image is drawn SCALE times bigger, either as blocks for crisp pixels or bilinear filtered.
+// the GPU scaling that up the rest of the way barely shows at this size
+
+pub const SCALE: usize = 4;
+
+// image is RGBA, width x height, and out is RGBA at SCALE times that in each direction
+pub fn upscale(image: &[u8], width: usize, height: usize, smooth: bool, out: &mut [u8]) {
+    let columns: Vec<Sample> = (0..width * SCALE).map(|x| Sample::new(x, width, smooth)).collect();
+    let out_row_length = width * SCALE * 4;
+
+    for (y, out_row) in out.chunks_mut(out_row_length).take(height * SCALE).enumerate() {
+        let row = Sample::new(y, height, smooth);
+        let above = &image[row.first * width * 4..][..width * 4];
+        let below = &image[row.second * width * 4..][..width * 4];
+
+        for (column, pixel) in columns.iter().zip(out_row.chunks_mut(4)) {
+            for channel in 0..4 {
+                let top = column.blend(above[column.first * 4 + channel], above[column.second * 4 + channel]);
+                let bottom = column.blend(below[column.first * 4 + channel], below[column.second * 4 + channel]);
+                pixel[channel] = ((top * (STEPS - row.weight) + bottom * row.weight) / (STEPS * STEPS)) as u8;
+            }
+        }
+    }
+}
+
+// mixes frame half and half into previous, both RGBA and the same size
+pub fn blend(frame: &[u8], previous: &mut [u8]) {
+    for (new, old) in frame.iter().zip(previous.iter_mut()) {
+        *old = ((*new as u16 + *old as u16) / 2) as u8;
+    }
+}
+
+// full screen flashes, like the palette inverted for a frame or two when something explodes, are let
+// through once but any more within FLASH_WINDOW of one are held to FLASH_LIMIT by mixing them with what
+// was shown before. WCAG allows three flashes a second
+pub struct FlashLimiter {
+    // the last frame shown, RGBA, and its brightness
+    shown: Vec<u8>,
+    brightness: Option<u32>,
+    // since one was let through
+    frames_since_flash: u32,
+}
+
+// how much the average brightness, out of 255, can change between frames before it's a flash
+const FLASH_LIMIT: u32 = 80;
+// a third of a second
+const FLASH_WINDOW: u32 = 20;
+
+impl FlashLimiter {
+    pub fn new() -> FlashLimiter {
+        FlashLimiter { shown: Vec::new(), brightness: None, frames_since_flash: FLASH_WINDOW }
+    }
+
+    // forget the last frame, when it wasn't passed through here
+    pub fn reset(&mut self) {
+        *self = FlashLimiter::new();
+    }
+
+    // frame is RGBA, and is changed in place
+    pub fn limit(&mut self, frame: &mut [u8]) {
+        let mut brightness = average_brightness(frame);
+        self.frames_since_flash = self.frames_since_flash.saturating_add(1);
+
+        if let Some(last) = self.brightness.filter(|_| self.shown.len() == frame.len()) {
+            let change = brightness.abs_diff(last);
+            if change > FLASH_LIMIT {
+                if self.frames_since_flash < FLASH_WINDOW {
+                    // only as far towards the new frame as the limit allows
+                    for (new, old) in frame.iter_mut().zip(self.shown.iter()) {
+                        *new = ((*new as u32 * FLASH_LIMIT + *old as u32 * (change - FLASH_LIMIT)) / change) as u8;
+                    }
+                    brightness = average_brightness(frame);
+                } else {
+                    self.frames_since_flash = 0;
+                }
+            }
+        }
+
+        self.brightness = Some(brightness);
+        self.shown.clear();
+        self.shown.extend_from_slice(frame);
+    }
+}
+
+// out of 255, from the pixels' RGB
+fn average_brightness(frame: &[u8]) -> u32 {
+    let pixels = (frame.len() / 4).max(1) as u64;
+    let total: u64 = frame.chunks(4).map(|pixel| pixel[..3].iter().map(|&c| c as u64).sum::<u64>() / 3).sum();
+    (total / pixels) as u32
+}
+
+// the LCD's four shades (see Shade in lcd.rs) and where high_contrast moves them to
+const LCD_SHADES: [u8; 4] = [0x00, 0x77, 0xCC, 0xFF];
+const HIGH_CONTRAST_SHADES: [u8; 4] = [0x00, 0x55, 0xAA, 0xFF];
+
+// spreads the shades evenly from black to white. light gray is much closer to white than dark gray
+// is to black, too close for some players to tell apart. anything between the shades, from
+// blending or the overlay, moves along with them. frame is RGBA
+pub fn high_contrast(frame: &mut [u8]) {
+    for pixel in frame.chunks_mut(4) {
+        for channel in pixel[..3].iter_mut() {
+            let above = LCD_SHADES.iter().position(|&shade| shade >= *channel).unwrap_or(3).max(1);
+            let (low, high) = (LCD_SHADES[above - 1] as u32, LCD_SHADES[above] as u32);
+            let (to_low, to_high) = (HIGH_CONTRAST_SHADES[above - 1] as u32, HIGH_CONTRAST_SHADES[above] as u32);
+            *channel = (to_low + (*channel as u32 - low) * (to_high - to_low) / (high - low)) as u8;
+        }
+    }
+}
+
+// weights are out of this, the number of half steps between two source pixels
+const STEPS: u32 = 2 * SCALE as u32;
+
+// where an output pixel falls between two source pixels along one axis
+struct Sample {
+    first: usize,
+    second: usize,
+    // how far towards second, out of STEPS
+    weight: u32,
+}
+
+impl Sample {
+    fn new(position: usize, length: usize, smooth: bool) -> Sample {
+        if !smooth {
+            let first = position / SCALE;
+            return Sample { first, second: first, weight: 0 };
+        }
+
+        // the output pixel's centre in source pixels, in half steps. the outer half pixels have
+        // nothing further out to blend with
+        let centre = (2 * position + 1).saturating_sub(SCALE);
+        let first = (centre / (2 * SCALE)).min(length - 1);
+        Sample {
+            first,
+            second: (first + 1).min(length - 1),
+            weight: (centre % (2 * SCALE)) as u32,
+        }
+    }
+
+    fn blend(&self, first: u8, second: u8) -> u32 {
+        first as u32 * (STEPS - self.weight) + second as u32 * self.weight
+    }
+}
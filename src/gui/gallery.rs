@@ -0,0 +1,185 @@
+// picks a save state to load by what the screen looked like when it was saved. each game keeps its
+// states in <rom>.states next to it, a new one every time, and this window shows the newest as a
+// grid of their thumbnails. the one under the mouse says how old it is in the title
+
+use std::cmp::Reverse;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pixels::{Error, Pixels, SurfaceTexture};
+use winit::dpi::{LogicalPosition, LogicalSize};
+use winit::event_loop::EventLoopWindowTarget;
+use winit::window::{Window, WindowBuilder, WindowId};
+
+use crate::gameboy::dump;
+use crate::gameboy::lcd::{SCREEN_WIDTH, SCREEN_HEIGHT};
+use crate::gui::emulator::thumbnail_path;
+use crate::gui::menu::ago;
+use crate::gui::physical_size;
+
+const COLUMNS: usize = 4;
+const ROWS: usize = 4;
+
+// around each thumbnail, and where the one under the mouse is highlighted
+const PADDING: usize = 4;
+const CELL_WIDTH: usize = SCREEN_WIDTH as usize + PADDING * 2;
+const CELL_HEIGHT: usize = SCREEN_HEIGHT as usize + PADDING * 2;
+const WIDTH: usize = CELL_WIDTH * COLUMNS;
+const HEIGHT: usize = CELL_HEIGHT * ROWS;
+
+const BACKGROUND: [u8; 4] = [0x20, 0x20, 0x20, 0xFF];
+const HIGHLIGHT: [u8; 4] = [0xF0, 0xC0, 0x40, 0xFF];
+
+pub struct SaveState {
+    pub path: PathBuf,
+    pub saved: SystemTime,
+    // RGBA, SCREEN_WIDTH x SCREEN_HEIGHT. None if it's missing, or from something else
+    thumbnail: Option<Vec<u8>>,
+}
+
+// a path for a new state for rom, named for when it's saved
+pub fn new_state_path(rom: &Path) -> io::Result<PathBuf> {
+    let directory = rom.with_extension("states");
+    fs::create_dir_all(&directory)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    Ok(directory.join(format!("{}.state", now.as_millis())))
+}
+
+// rom's save states, newest first
+pub fn save_states(rom: &Path) -> Vec<SaveState> {
+    let mut states: Vec<SaveState> = match rom.with_extension("states").read_dir() {
+        Ok(entries) => entries.filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "state"))
+            .map(|path| {
+                let saved = fs::metadata(&path).and_then(|metadata| metadata.modified()).unwrap_or(UNIX_EPOCH);
+                SaveState { path, saved, thumbnail: None }
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    states.sort_by_key(|state| Reverse(state.saved));
+    states
+}
+
+pub struct Gallery {
+    rom: PathBuf,
+    window: Window,
+    pixels: Pixels,
+    // the newest COLUMNS x ROWS of them
+    states: Vec<SaveState>,
+    hovered: Option<usize>,
+}
+
+impl Gallery {
+    pub fn open<T: 'static>(rom: &Path, event_loop: &EventLoopWindowTarget<T>) -> Result<Gallery, Error> {
+        let window = WindowBuilder::new()
+            .with_inner_size(LogicalSize::new(WIDTH as f64, HEIGHT as f64))
+            .with_title("GBOxide - Save States")
+            .build(event_loop)
+            .unwrap();
+        let surface = pixels::wgpu::Surface::create(&window);
+        let (surface_width, surface_height) = physical_size(&window);
+        let pixels = Pixels::new(WIDTH as u32, HEIGHT as u32, SurfaceTexture::new(surface_width, surface_height, surface))?;
+
+        let mut gallery = Gallery { rom: rom.to_path_buf(), window, pixels, states: Vec::new(), hovered: None };
+        gallery.refresh();
+        Ok(gallery)
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    // after the window's been resized or moved to a monitor with a different scale
+    pub fn resize(&mut self) {
+        let (width, height) = physical_size(&self.window);
+        self.pixels.resize(width, height);
+    }
+
+    pub fn render(&mut self) {
+        self.pixels.render();
+    }
+
+    // looks for new states, after one's been saved
+    pub fn refresh(&mut self) {
+        self.states = save_states(&self.rom);
+        self.states.truncate(COLUMNS * ROWS);
+        for state in &mut self.states {
+            state.thumbnail = match dump::load_png(&thumbnail_path(&state.path)) {
+                Ok((width, height, pixels)) if width == SCREEN_WIDTH as u32 && height == SCREEN_HEIGHT as u32 => Some(pixels),
+                _ => None,
+            };
+        }
+        self.hovered = None;
+        self.draw();
+    }
+
+    // the mouse moved to position, in the window
+    pub fn hover(&mut self, position: LogicalPosition) {
+        let hovered = self.state_at(position);
+        if hovered != self.hovered {
+            self.hovered = hovered;
+            self.draw();
+        }
+    }
+
+    // the state under the mouse
+    pub fn hovered(&self) -> Option<&Path> {
+        self.hovered.map(|index| self.states[index].path.as_path())
+    }
+
+    // pixels draws the grid as big as it'll go at a whole number scale, in the middle of the window
+    fn state_at(&self, position: LogicalPosition) -> Option<usize> {
+        let position = position.to_physical(self.window.hidpi_factor());
+        let (width, height) = physical_size(&self.window);
+        let scale = (width as f64 / WIDTH as f64).min(height as f64 / HEIGHT as f64).max(1.0).floor();
+        let x = (position.x - (width as f64 - WIDTH as f64 * scale) / 2.0) / scale;
+        let y = (position.y - (height as f64 - HEIGHT as f64 * scale) / 2.0) / scale;
+        if x < 0.0 || y < 0.0 || x >= WIDTH as f64 || y >= HEIGHT as f64 {
+            return None;
+        }
+        let index = y as usize / CELL_HEIGHT * COLUMNS + x as usize / CELL_WIDTH;
+        if index < self.states.len() { Some(index) } else { None }
+    }
+
+    fn draw(&mut self) {
+        let frame = self.pixels.get_frame();
+        for pixel in frame.chunks_mut(4) {
+            pixel.copy_from_slice(&BACKGROUND);
+        }
+
+        for (index, state) in self.states.iter().enumerate() {
+            let left = index % COLUMNS * CELL_WIDTH;
+            let top = index / COLUMNS * CELL_HEIGHT;
+            let hovered = self.hovered == Some(index);
+            for y in 0..CELL_HEIGHT {
+                for x in 0..CELL_WIDTH {
+                    let in_thumbnail = (PADDING..CELL_WIDTH - PADDING).contains(&x)
+                        && (PADDING..CELL_HEIGHT - PADDING).contains(&y);
+                    let colour = match &state.thumbnail {
+                        _ if !in_thumbnail && !hovered => continue,
+                        _ if !in_thumbnail => &HIGHLIGHT,
+                        Some(thumbnail) => {
+                            let offset = ((y - PADDING) * SCREEN_WIDTH as usize + x - PADDING) * 4;
+                            &thumbnail[offset..offset + 4]
+                        },
+                        None => &[0x00, 0x00, 0x00, 0xFF],
+                    };
+                    let offset = ((top + y) * WIDTH + left + x) * 4;
+                    frame[offset..offset + 4].copy_from_slice(colour);
+                }
+            }
+        }
+
+        let title = match self.hovered {
+            Some(index) => format!("GBOxide - Save States - saved {}", ago(self.states[index].saved)),
+            None if self.states.is_empty() => "GBOxide - Save States - none yet".to_string(),
+            None => "GBOxide - Save States".to_string(),
+        };
+        self.window.set_title(&title);
+        self.window.request_redraw();
+    }
+}
@@ -0,0 +1,164 @@
+// the emulator's own shortcuts, as opposed to the GameBoy's buttons. each can be rebound in the
+// config file's hotkeys table with winit's name for the key, e.g.
+//
+//   "hotkeys": { "pause": "P", "screenshot": "F12" }
+//
+// and anything left out keeps its default
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use winit::event::VirtualKeyCode;
+use winit_input_helper::WinitInputHelper;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Hotkey {
+    Quit,
+    Pause,
+    Reset,
+    SaveState,
+    LoadState,
+    StateGallery,
+    Screenshot,
+    // held rather than pressed
+    FastForward,
+    Fullscreen,
+    SpeedDown,
+    SpeedUp,
+    SpeedReset,
+    Smooth,
+    FrameBlend,
+    TileViewer,
+    MapViewer,
+    LayerOverlay,
+    PaletteEditor,
+    ScanlineViewer,
+    WaveEditor,
+    CartridgeRamEditor,
+    // the window at that many times the screen's size
+    Scale1,
+    Scale2,
+    Scale3,
+    Scale4,
+    Scale5,
+    Scale6,
+}
+
+impl Hotkey {
+    pub const ALL: [Hotkey; 27] = [
+        Hotkey::Quit, Hotkey::Pause, Hotkey::Reset, Hotkey::SaveState, Hotkey::LoadState,
+        Hotkey::StateGallery, Hotkey::Screenshot, Hotkey::FastForward, Hotkey::Fullscreen,
+        Hotkey::SpeedDown, Hotkey::SpeedUp, Hotkey::SpeedReset, Hotkey::Smooth, Hotkey::FrameBlend,
+        Hotkey::TileViewer, Hotkey::MapViewer, Hotkey::LayerOverlay,
+        Hotkey::PaletteEditor, Hotkey::ScanlineViewer, Hotkey::WaveEditor, Hotkey::CartridgeRamEditor,
+        Hotkey::Scale1, Hotkey::Scale2, Hotkey::Scale3, Hotkey::Scale4, Hotkey::Scale5, Hotkey::Scale6,
+    ];
+
+    // in order, the first being 1x
+    pub const SCALES: [Hotkey; 6] = [
+        Hotkey::Scale1, Hotkey::Scale2, Hotkey::Scale3, Hotkey::Scale4, Hotkey::Scale5, Hotkey::Scale6,
+    ];
+
+    fn default_key(self) -> VirtualKeyCode {
+        match self {
+            Hotkey::Quit => VirtualKeyCode::Escape,
+            Hotkey::Pause => VirtualKeyCode::P,
+            Hotkey::Reset => VirtualKeyCode::R,
+            Hotkey::SaveState => VirtualKeyCode::F5,
+            Hotkey::LoadState => VirtualKeyCode::F8,
+            Hotkey::StateGallery => VirtualKeyCode::F7,
+            Hotkey::Screenshot => VirtualKeyCode::F12,
+            Hotkey::FastForward => VirtualKeyCode::Tab,
+            Hotkey::Fullscreen => VirtualKeyCode::F11,
+            Hotkey::SpeedDown => VirtualKeyCode::Minus,
+            Hotkey::SpeedUp => VirtualKeyCode::Equals,
+            Hotkey::SpeedReset => VirtualKeyCode::Key0,
+            Hotkey::Smooth => VirtualKeyCode::F,
+            Hotkey::FrameBlend => VirtualKeyCode::B,
+            Hotkey::TileViewer => VirtualKeyCode::F1,
+            Hotkey::MapViewer => VirtualKeyCode::F2,
+            Hotkey::LayerOverlay => VirtualKeyCode::F3,
+            Hotkey::PaletteEditor => VirtualKeyCode::F4,
+            Hotkey::ScanlineViewer => VirtualKeyCode::F6,
+            Hotkey::WaveEditor => VirtualKeyCode::F9,
+            Hotkey::CartridgeRamEditor => VirtualKeyCode::F10,
+            Hotkey::Scale1 => VirtualKeyCode::Key1,
+            Hotkey::Scale2 => VirtualKeyCode::Key2,
+            Hotkey::Scale3 => VirtualKeyCode::Key3,
+            Hotkey::Scale4 => VirtualKeyCode::Key4,
+            Hotkey::Scale5 => VirtualKeyCode::Key5,
+            Hotkey::Scale6 => VirtualKeyCode::Key6,
+        }
+    }
+
+    // for showing alongside the key in the ui
+    pub fn description(self) -> &'static str {
+        match self {
+            Hotkey::Quit => "Quit",
+            Hotkey::Pause => "Pause",
+            Hotkey::Reset => "Reset",
+            Hotkey::SaveState => "Save state",
+            Hotkey::LoadState => "Load newest state",
+            Hotkey::StateGallery => "Save state gallery",
+            Hotkey::Screenshot => "Screenshot",
+            Hotkey::FastForward => "Fast forward (hold)",
+            Hotkey::Fullscreen => "Fullscreen",
+            Hotkey::SpeedDown => "Slower",
+            Hotkey::SpeedUp => "Faster",
+            Hotkey::SpeedReset => "Full speed",
+            Hotkey::Smooth => "Smooth scaling",
+            Hotkey::FrameBlend => "Frame blending",
+            Hotkey::TileViewer => "Tile viewer",
+            Hotkey::MapViewer => "BG map viewer",
+            Hotkey::LayerOverlay => "Layer overlay",
+            Hotkey::PaletteEditor => "Palette editor",
+            Hotkey::ScanlineViewer => "Scanline viewer",
+            Hotkey::WaveEditor => "Wave RAM editor",
+            Hotkey::CartridgeRamEditor => "Cartridge RAM editor",
+            Hotkey::Scale1 => "Window 1x",
+            Hotkey::Scale2 => "Window 2x",
+            Hotkey::Scale3 => "Window 3x",
+            Hotkey::Scale4 => "Window 4x",
+            Hotkey::Scale5 => "Window 5x",
+            Hotkey::Scale6 => "Window 6x",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Hotkeys {
+    keys: BTreeMap<Hotkey, VirtualKeyCode>,
+}
+
+impl Hotkeys {
+    // the defaults with the config's rebindings on top
+    pub fn new(rebound: &BTreeMap<Hotkey, VirtualKeyCode>) -> Hotkeys {
+        let mut keys: BTreeMap<_, _> = Hotkey::ALL.iter().map(|&hotkey| (hotkey, hotkey.default_key())).collect();
+        keys.extend(rebound);
+        Hotkeys { keys }
+    }
+
+    pub fn key(&self, hotkey: Hotkey) -> VirtualKeyCode {
+        self.keys[&hotkey]
+    }
+
+    // the key's name, as the config file has it
+    pub fn key_name(&self, hotkey: Hotkey) -> String {
+        format!("{:?}", self.key(hotkey))
+    }
+
+    pub fn pressed(&self, input: &WinitInputHelper<()>, hotkey: Hotkey) -> bool {
+        input.key_pressed(self.key(hotkey))
+    }
+
+    pub fn held(&self, input: &WinitInputHelper<()>, hotkey: Hotkey) -> bool {
+        input.key_held(self.key(hotkey))
+    }
+}
+
+impl Default for Hotkeys {
+    fn default() -> Hotkeys {
+        Hotkeys::new(&BTreeMap::new())
+    }
+}
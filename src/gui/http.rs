@@ -0,0 +1,263 @@
+// a small HTTP server for driving a running instance from a browser or web service, for
+// "Twitch plays" setups and remote debugging. one request per connection:
+//
+//   GET  /screenshot                     the current frame as a PNG
+//   GET  /stream                         frames as they change, as a multipart PNG stream (MJPEG style,
+//                                        so an <img> tag shows it live)
+//   GET  /memory?address=c000&length=16  hex bytes
+//   GET  /pc                             bank:address
+//   POST /buttons                        body is the buttons to hold, e.g. a,start (empty releases them all)
+//   POST /pause, /resume
+//
+// replies are 200 with the result, 400 for requests we don't understand, 403 for requests from other sites,
+// or 500 if the emulator refused. nothing here touches files, that's left to the remote control port
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::Duration;
+
+use crate::gameboy::dump;
+use crate::gameboy::lcd::{SCREEN_WIDTH, SCREEN_HEIGHT};
+use crate::gui::emulator::Command;
+use crate::gui::remote::{self, Request};
+
+// the stream checks for a new frame this often, a PNG of every frame would swamp most connections
+const STREAM_INTERVAL: Duration = Duration::from_millis(33);
+
+// nobody needs to send us more than a command
+const MAX_BODY_LENGTH: usize = 4096;
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    host: Option<String>,
+    origin: Option<String>,
+    body: String,
+}
+
+impl HttpRequest {
+    fn param(&self, name: &str) -> Result<&str, String> {
+        self.query.iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+            .ok_or_else(|| format!("missing {} parameter", name))
+    }
+
+    // a page on another site can make a browser send us simple POSTs without asking first, and can rebind
+    // its own name to our address, so only take requests naming this server with no foreign Origin
+    fn is_for_this_server(&self, local: SocketAddr) -> bool {
+        let host = match &self.host {
+            Some(host) => host,
+            None => return false,
+        };
+        let named_here = match host.parse::<SocketAddr>() {
+            Ok(addr) => addr.port() == local.port() && (addr.ip() == local.ip() || local.ip().is_unspecified()),
+            Err(_) => host.eq_ignore_ascii_case(&format!("localhost:{}", local.port())) && local.ip().is_loopback(),
+        };
+        let same_origin = match &self.origin {
+            Some(origin) => origin.eq_ignore_ascii_case(&format!("http://{}", host)),
+            None => true,
+        };
+        named_here && same_origin
+    }
+}
+
+// accepts connections on addr (e.g. 127.0.0.1:8080) and forwards their requests to the emulator thread
+pub fn listen(addr: &str, commands: Sender<Command>) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    thread::Builder::new()
+        .name("http server".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        eprintln!("HTTP connection failed: {}", err);
+                        continue;
+                    },
+                };
+                let local = match stream.local_addr() {
+                    Ok(local) => local,
+                    Err(_) => continue,
+                };
+                let commands = commands.clone();
+                let _ = thread::Builder::new()
+                    .name("http client".to_string())
+                    .spawn(move || {
+                        // a client going away mid stream isn't worth reporting
+                        let _ = serve(stream, local, commands);
+                    });
+            }
+        })?;
+
+    Ok(())
+}
+
+fn serve(stream: TcpStream, local: SocketAddr, commands: Sender<Command>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let request = match read_request(BufReader::new(stream)) {
+        Ok(request) => request,
+        Err(err) => return respond(&mut writer, "400 Bad Request", "text/plain", err.as_bytes()),
+    };
+    if !request.is_for_this_server(local) {
+        return respond(&mut writer, "403 Forbidden", "text/plain", b"requests must come from this server");
+    }
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/screenshot") => match frame(&commands) {
+            Some((_, png)) => respond(&mut writer, "200 OK", "image/png", &png),
+            None => respond(&mut writer, "500 Internal Server Error", "text/plain", b"emulator stopped"),
+        },
+        ("GET", "/stream") => stream_frames(&mut writer, &commands),
+        _ => {
+            let (status, body) = match route(&request) {
+                Some(Ok(request)) => match send(&commands, request) {
+                    Ok(message) => ("200 OK", message),
+                    Err(err) => ("500 Internal Server Error", err),
+                },
+                Some(Err(err)) => ("400 Bad Request", err),
+                None => ("404 Not Found", format!("no {} {}", request.method, request.path)),
+            };
+            respond(&mut writer, status, "text/plain", body.as_bytes())
+        },
+    }
+}
+
+// the remote control request an endpoint stands for, or None if there's no such endpoint
+fn route(request: &HttpRequest) -> Option<Result<Request, String>> {
+    let line = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/memory") => request.param("address").and_then(|address| {
+            Ok(format!("read-memory {} {}", address, request.param("length")?))
+        }),
+        ("GET", "/pc") => Ok("pc".to_string()),
+        ("POST", "/buttons") => Ok(format!("buttons {}", request.body.trim())),
+        ("POST", "/pause") => Ok("pause".to_string()),
+        ("POST", "/resume") => Ok("resume".to_string()),
+        _ => return None,
+    };
+    Some(line.and_then(|line| remote::parse_request(&line)))
+}
+
+fn send(commands: &Sender<Command>, request: Request) -> Result<String, String> {
+    let (reply, response) = mpsc::channel();
+    commands.send(Command::Remote(request, reply)).map_err(|_| "emulator stopped".to_string())?;
+    response.recv().unwrap_or_else(|_| Err("emulator stopped".to_string()))
+}
+
+// the current frame's number and PNG, or None if the emulator's stopped
+fn frame(commands: &Sender<Command>) -> Option<(u64, Vec<u8>)> {
+    let (reply, response) = mpsc::channel();
+    commands.send(Command::Frame(reply)).ok()?;
+    let (number, rgba) = response.recv().ok()?;
+
+    let mut png = Vec::new();
+    dump::encode_png(&mut png, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, &rgba)
+        .expect("encoding a frame-sized image into memory can't fail");
+    Some((number, png))
+}
+
+// runs until the client hangs up or the emulator stops
+fn stream_frames(writer: &mut TcpStream, commands: &Sender<Command>) -> io::Result<()> {
+    write!(writer, "HTTP/1.1 200 OK\r\n\
+                    Content-Type: multipart/x-mixed-replace; boundary=frame\r\n\
+                    Cache-Control: no-cache\r\n\
+                    Connection: close\r\n\r\n")?;
+
+    let mut last_frame = None;
+    while let Some((number, png)) = frame(commands) {
+        if last_frame != Some(number) {
+            write!(writer, "--frame\r\nContent-Type: image/png\r\nContent-Length: {}\r\n\r\n", png.len())?;
+            writer.write_all(&png)?;
+            writer.write_all(b"\r\n")?;
+            writer.flush()?;
+            last_frame = Some(number);
+        }
+        thread::sleep(STREAM_INTERVAL);
+    }
+
+    Ok(())
+}
+
+fn read_request<R: BufRead>(mut reader: R) -> Result<HttpRequest, String> {
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    let mut words = line.split_whitespace();
+    let (method, target) = match (words.next(), words.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target),
+        _ => return Err("bad request line".to_string()),
+    };
+    let (path, query) = match target.find('?') {
+        Some(start) => (&target[..start], &target[start + 1..]),
+        None => (target, ""),
+    };
+    let query = query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = match pair.find('=') {
+                Some(equals) => (&pair[..equals], &pair[equals + 1..]),
+                None => (pair, ""),
+            };
+            Ok((percent_decode(key)?, percent_decode(value)?))
+        })
+        .collect::<Result<_, String>>()?;
+    let path = path.to_string();
+
+    let mut content_length = 0;
+    let mut host = None;
+    let mut origin = None;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).map_err(|e| e.to_string())?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(colon) = header.find(':') {
+            let (name, value) = (&header[..colon], header[colon + 1..].trim());
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.parse()
+                    .map_err(|e| format!("bad content length: {}", e))?;
+            } else if name.eq_ignore_ascii_case("host") {
+                host = Some(value.to_string());
+            } else if name.eq_ignore_ascii_case("origin") {
+                origin = Some(value.to_string());
+            }
+        }
+    }
+    if content_length > MAX_BODY_LENGTH {
+        return Err("body too long".to_string());
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+    let body = String::from_utf8(body).map_err(|_| "body isn't UTF-8".to_string())?;
+
+    Ok(HttpRequest { method, path, query, host, origin, body })
+}
+
+fn percent_decode(text: &str) -> Result<String, String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.bytes();
+    while let Some(byte) = rest.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex: Vec<u8> = rest.by_ref().take(2).collect();
+                let hex = std::str::from_utf8(&hex).map_err(|_| format!("bad escape in {}", text))?;
+                bytes.push(u8::from_str_radix(hex, 16).map_err(|_| format!("bad escape in {}", text))?);
+            },
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| format!("{} isn't UTF-8", text))
+}
+
+fn respond(writer: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) -> io::Result<()> {
+    write!(writer, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+           status, content_type, body.len())?;
+    writer.write_all(body)?;
+    writer.flush()
+}
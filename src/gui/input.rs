@@ -0,0 +1,118 @@
+use gilrs::{Axis, Button, Gamepad, Gilrs};
+use winit::event::VirtualKeyCode;
+use winit_input_helper::WinitInputHelper;
+
+use crate::gameboy::joypad::Controls;
+
+// how far an analog stick has to be pushed to count as a dpad press
+const STICK_THRESHOLD: f32 = 0.5;
+
+// which devices are allowed to drive the joypad, everything enabled is combined
+#[derive(Clone, Copy, Debug)]
+pub struct Sources {
+    pub keyboard: bool,
+    pub gamepads: bool,
+}
+
+impl Default for Sources {
+    fn default() -> Sources {
+        Sources {
+            keyboard: true,
+            gamepads: true,
+        }
+    }
+}
+
+// the normal and turbo buttons currently held across all enabled sources
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Held {
+    pub controls: Controls,
+    pub turbo: Controls,
+    // for tilt cartridges, see MotionSource
+    pub tilt: (f32, f32),
+}
+
+impl Held {
+    fn merge(self, other: Held) -> Held {
+        let add = |a: f32, b: f32| (a + b).clamp(-1.0, 1.0);
+        Held {
+            controls: self.controls.merge(other.controls),
+            turbo: self.turbo.merge(other.turbo),
+            tilt: (add(self.tilt.0, other.tilt.0), add(self.tilt.1, other.tilt.1)),
+        }
+    }
+}
+
+pub fn read(sources: Sources, keyboard: &WinitInputHelper<()>, gilrs: Option<&Gilrs>) -> Held {
+    let mut held = Held::default();
+
+    if sources.keyboard {
+        held = held.merge(read_keyboard(keyboard));
+    }
+
+    if sources.gamepads {
+        if let Some(gilrs) = gilrs {
+            for (_, gamepad) in gilrs.gamepads() {
+                held = held.merge(read_gamepad(&gamepad));
+            }
+        }
+    }
+
+    held
+}
+
+fn read_keyboard(input: &WinitInputHelper<()>) -> Held {
+    let axis = |negative, positive| match (input.key_held(negative), input.key_held(positive)) {
+        (true, false) => -1.0,
+        (false, true) => 1.0,
+        _ => 0.0,
+    };
+
+    Held {
+        controls: Controls {
+            left: input.key_held(VirtualKeyCode::Left),
+            right: input.key_held(VirtualKeyCode::Right),
+            up: input.key_held(VirtualKeyCode::Up),
+            down: input.key_held(VirtualKeyCode::Down),
+
+            a: input.key_held(VirtualKeyCode::X),
+            b: input.key_held(VirtualKeyCode::Z),
+            start: input.key_held(VirtualKeyCode::Return),
+            select: input.key_held(VirtualKeyCode::Space),
+        },
+        turbo: Controls {
+            a: input.key_held(VirtualKeyCode::S),
+            b: input.key_held(VirtualKeyCode::A),
+            ..Controls::default()
+        },
+        // all the way over while they're held
+        tilt: (axis(VirtualKeyCode::J, VirtualKeyCode::L), axis(VirtualKeyCode::I, VirtualKeyCode::K)),
+    }
+}
+
+fn read_gamepad(gamepad: &Gamepad) -> Held {
+    let stick_x = gamepad.value(Axis::LeftStickX);
+    let stick_y = gamepad.value(Axis::LeftStickY);
+
+    // the face buttons are laid out like a Nintendo pad, so B (south) is below A (east)
+    Held {
+        controls: Controls {
+            left: gamepad.is_pressed(Button::DPadLeft) || stick_x < -STICK_THRESHOLD,
+            right: gamepad.is_pressed(Button::DPadRight) || stick_x > STICK_THRESHOLD,
+            up: gamepad.is_pressed(Button::DPadUp) || stick_y > STICK_THRESHOLD,
+            down: gamepad.is_pressed(Button::DPadDown) || stick_y < -STICK_THRESHOLD,
+
+            a: gamepad.is_pressed(Button::East),
+            b: gamepad.is_pressed(Button::South),
+            start: gamepad.is_pressed(Button::Start),
+            select: gamepad.is_pressed(Button::Select),
+        },
+        turbo: Controls {
+            a: gamepad.is_pressed(Button::North),
+            b: gamepad.is_pressed(Button::West),
+            ..Controls::default()
+        },
+        // the stick's y is up, tilting's towards the player
+        tilt: (gamepad.value(Axis::RightStickX), -gamepad.value(Axis::RightStickY)),
+    }
+}
@@ -0,0 +1,147 @@
+// what's shown instead of a game when gboxide's pointed at a directory: every ROM in it, with
+// what its header says and how long it's been played, drawn by the menu. picking one starts it
+// the same way the menu's Open does
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use pixels::{Error, PixelsBuilder, SurfaceTexture};
+use winit::event::{Event, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit_input_helper::WinitInputHelper;
+
+use crate::cartridge::Header;
+use crate::gui::config::Config;
+use crate::gui::menu::{Action, Menu};
+use crate::gui::{physical_size, relaunch};
+
+// the header's at 0x100-0x14F, nothing past it's needed
+const HEADER_END: usize = 0x150;
+
+pub struct Game {
+    pub path: PathBuf,
+    // the header's title, or the file's name if it hasn't got one
+    pub title: String,
+    pub mapper: String,
+    pub playtime: Duration,
+    pub last_played: Option<SystemTime>,
+}
+
+pub struct Library {
+    pub directory: PathBuf,
+    // by title
+    pub games: Vec<Game>,
+}
+
+impl Library {
+    // every .gb and .gbc in directory, not looking in subdirectories
+    pub fn scan(directory: &Path, config: &Config) -> Library {
+        let mut games: Vec<Game> = match directory.read_dir() {
+            Ok(entries) => entries.filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    let extension = path.extension().and_then(|e| e.to_str()).map(str::to_lowercase);
+                    matches!(extension.as_deref(), Some("gb") | Some("gbc"))
+                })
+                .map(|path| Game::load(path, config))
+                .collect(),
+            Err(err) => {
+                eprintln!("Problem reading {}: {}", directory.display(), err);
+                Vec::new()
+            },
+        };
+        games.sort_by_key(|game| game.title.to_lowercase());
+
+        Library { directory: directory.to_path_buf(), games }
+    }
+}
+
+impl Game {
+    fn load(path: PathBuf, config: &Config) -> Game {
+        let file_name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        let (title, mapper) = match read_header(&path) {
+            Ok(header) if header.title.trim().is_empty() => (file_name, header.cartridge_type.to_string()),
+            Ok(header) => (header.title.trim().to_string(), header.cartridge_type.to_string()),
+            Err(err) => (file_name, format!("bad header: {}", err)),
+        };
+        let game = config.game(&path);
+
+        Game { title, mapper, playtime: game.playtime(), last_played: game.last_played(), path }
+    }
+}
+
+fn read_header(path: &Path) -> Result<Header, crate::Error> {
+    let mut bytes = Vec::with_capacity(HEADER_END);
+    File::open(path)?.take(HEADER_END as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < HEADER_END {
+        return Err("too small to be a ROM".into());
+    }
+    Header::from_slice(&bytes[0x100..])
+}
+
+// shows the library until a game's picked, then starts it in place of this
+pub fn run(directory: PathBuf) -> Result<(), Error> {
+    let event_loop = EventLoop::new();
+    let mut input = WinitInputHelper::new();
+
+    let window = winit::window::WindowBuilder::new()
+        .with_inner_size(winit::dpi::LogicalSize::new(640.0, 480.0))
+        .with_title("GBOxide - Library")
+        .build(&event_loop)
+        .unwrap();
+    let surface = pixels::wgpu::Surface::create(&window);
+    let (width, height) = physical_size(&window);
+
+    let mut menu = Menu::new();
+    // a single pixel for the menu to draw over
+    let mut pixels = PixelsBuilder::new(1, 1, SurfaceTexture::new(width, height, surface))
+        .add_render_pass(menu.render_pass())
+        .build()?;
+    pixels.get_frame().copy_from_slice(&[0x20, 0x20, 0x20, 0xFF]);
+    menu.status_mut().library = Some(Library::scan(&directory, &Config::load()));
+
+    event_loop.run(move |event, _, control_flow| {
+        // the menu keeps its own time, so it only needs waking for input
+        *control_flow = ControlFlow::WaitUntil(Instant::now() + Duration::from_millis(100));
+
+        if let Event::WindowEvent { event: WindowEvent::RedrawRequested, .. } = event {
+            pixels.render();
+        }
+
+        if input.update(event) {
+            if input.quit() {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+
+            for action in menu.take_actions() {
+                match action {
+                    Action::Open(rom) => {
+                        if let Err(err) = relaunch(Some(&directory), &rom) {
+                            eprintln!("Problem starting {}: {}", rom.display(), err);
+                        }
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    },
+                    Action::Quit => {
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    },
+                    _ => (),
+                }
+            }
+
+            if menu.update(&input, physical_size(&window), window.hidpi_factor()) {
+                window.request_redraw();
+            }
+
+            if input.window_resized().is_some() || input.hidpi_changed().is_some() {
+                let (width, height) = physical_size(&window);
+                pixels.resize(width, height);
+                window.request_redraw();
+            }
+        }
+    });
+}
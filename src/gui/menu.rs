@@ -0,0 +1,738 @@
+// a menu bar drawn over the top of the screen with imgui, for the things that would otherwise
+// need a hotkey or a command line flag. it's drawn by a render pass after the game's, shows up
+// while the mouse is moving over the window, and hands what was picked back as Actions
+
+use std::cell::{RefCell, RefMut};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime};
+
+use imgui::{im_str, ColorButton, Condition, Context, ImStr, ImString, InputText, MenuItem, MouseButton, PlotLines,
+            Selectable, Slider, StyleVar, VerticalSlider, Window};
+use imgui_wgpu::Renderer;
+use pixels::{BoxedRenderPass, Device, Queue, RenderPass};
+use pixels::wgpu::{self, Extent3d, TextureView};
+use winit_input_helper::WinitInputHelper;
+
+use crate::gameboy::lcd::{Palettes, ScanlineRegisters};
+use crate::gui::config::{Accessibility, Backdrop};
+use crate::gui::emulator::View;
+use crate::gui::hotkeys::{Hotkey, Hotkeys};
+use crate::gui::library::Library;
+
+// how long the menu bar stays up after the mouse stops moving
+const SHOW_DURATION: Duration = Duration::from_secs(3);
+
+// the backdrop colors offered in the menu, any other can go in the config
+const BACKDROPS: [(&str, [u8; 3]); 5] = [
+    ("Black", [0x00, 0x00, 0x00]),
+    ("Charcoal", [0x20, 0x20, 0x24]),
+    ("DMG Gray", [0xC4, 0xBE, 0xBB]),
+    ("DMG Green", [0x9B, 0xBC, 0x0F]),
+    ("White", [0xFF, 0xFF, 0xFF]),
+];
+// how wide the backdrop's frame is, before the hidpi factor
+const FRAME_WIDTH: f32 = 6.0;
+
+// the palette editor's swatches, shaded as the LCD draws them
+const SHADES: [f32; 4] = [1.0, 0.8, 0.467, 0.0];
+const SHADE_NAMES: [&str; 4] = ["white", "light gray", "dark gray", "black"];
+const PALETTE_NAMES: [&str; 3] = ["BGP", "OBP0", "OBP1"];
+
+// the scanline viewer's values that are different from the line above
+const CHANGED: [f32; 4] = [1.0, 0.8, 0.2, 1.0];
+
+// how much of the cartridge's RAM the editor shows at once, 16 rows of 16
+const RAM_PAGE_SIZE: usize = 0x100;
+// the size of each bank the mapper switches into 0xA000-0xBFFF
+const RAM_BANK_SIZE: usize = 0x2000;
+
+pub enum Action {
+    // restart the emulator with another ROM
+    Open(PathBuf),
+    Quit,
+    Pause(bool),
+    // restart the emulator with the same ROM
+    Reset,
+    SaveState,
+    // the newest
+    LoadState,
+    LoadStateFrom(PathBuf),
+    // open or close the save state gallery
+    Gallery(bool),
+    Screenshot,
+    Scale(u32),
+    Fullscreen(bool),
+    Smooth(bool),
+    FrameBlend(bool),
+    Accessibility(Accessibility),
+    Backdrop(Backdrop),
+    ToggleViewer(View),
+    // tint the screen by layer and outline the sprites
+    LayerOverlay(bool),
+    PaletteEditor(bool),
+    // palette, entry and shade, None for the game's own
+    OverridePalette(usize, usize, Option<u8>),
+    ScanlineViewer(bool),
+    WaveEditor(bool),
+    SetWaveRam([u8; 16]),
+    CartridgeRamEditor(bool),
+    // which RAM_PAGE_SIZE page of it to show
+    CartridgeRamPage(usize),
+    // a byte at an offset into it
+    SetCartridgeRam(usize, u8),
+    // to and from <rom>.sram next to the ROM
+    ExportCartridgeRam,
+    ImportCartridgeRam,
+    // switch the cheat at this index on or off
+    ToggleCheat(usize),
+}
+
+// what the menu shows ticked, kept up to date by the ui
+#[derive(Default)]
+pub struct Status {
+    pub paused: bool,
+    pub fullscreen: bool,
+    pub smooth: bool,
+    pub frame_blend: bool,
+    pub accessibility: Accessibility,
+    pub backdrop: Backdrop,
+    // the size pixels is given the screen at, with any border, before it scales it up to fit the window
+    pub screen_size: [f32; 2],
+    pub gallery: bool,
+    // the ROM being played, and others to switch to
+    pub rom: Option<PathBuf>,
+    pub recent: Vec<PathBuf>,
+    pub viewers: Vec<View>,
+    pub layer_overlay: bool,
+    // the palettes, while the editor's open
+    pub palettes: Option<Palettes>,
+    // the registers for each line of the last frame, while the scanline viewer's open
+    pub scanlines: Option<Vec<ScanlineRegisters>>,
+    // channel 3's wave pattern, while the editor's open
+    pub wave_ram: Option<[u8; 16]>,
+    // the cartridge's RAM while its editor's open, and the page shown
+    pub cartridge_ram: Option<Vec<u8>>,
+    pub cartridge_ram_page: usize,
+    // each cheat's name and whether it's on
+    pub cheats: Vec<(String, bool)>,
+    pub hotkeys: Hotkeys,
+    // shown over the whole window instead of the menu bar, to pick a game from
+    pub library: Option<Library>,
+}
+
+struct Shared {
+    imgui: Context,
+    // made once pixels has a device for it
+    renderer: Option<Renderer>,
+    device: Option<Device>,
+    last_frame: Instant,
+    // until when the bar's shown, None to leave it hidden
+    shown_until: Option<Instant>,
+    status: Status,
+    actions: Vec<Action>,
+}
+
+pub struct Menu {
+    shared: Rc<RefCell<Shared>>,
+}
+
+impl Menu {
+    pub fn new() -> Menu {
+        let mut imgui = Context::create();
+        // nothing worth remembering between runs
+        imgui.set_ini_filename(None);
+
+        Menu {
+            shared: Rc::new(RefCell::new(Shared {
+                imgui,
+                renderer: None,
+                device: None,
+                last_frame: Instant::now(),
+                shown_until: None,
+                status: Status::default(),
+                actions: Vec::new(),
+            })),
+        }
+    }
+
+    // for PixelsBuilder::add_render_pass, so the menu's drawn over the game
+    pub fn render_pass(&self) -> impl Fn(Device, Queue, &TextureView, &Extent3d) -> BoxedRenderPass + 'static {
+        let shared = self.shared.clone();
+        move |device, queue, _, _| {
+            {
+                let mut shared = shared.borrow_mut();
+                let Shared { imgui, renderer, .. } = &mut *shared;
+                *renderer = Some(Renderer::new_static(imgui, &device, &mut queue.borrow_mut(),
+                                                      wgpu::TextureFormat::Bgra8UnormSrgb, None));
+                shared.device = Some(device);
+            }
+            Box::new(MenuPass { shared: shared.clone() })
+        }
+    }
+
+    pub fn status_mut(&mut self) -> RefMut<'_, Status> {
+        RefMut::map(self.shared.borrow_mut(), |shared| &mut shared.status)
+    }
+
+    // feeds imgui the window's physical size and the mouse. true if the menu needs drawing again
+    pub fn update(&mut self, input: &WinitInputHelper<()>, (width, height): (u32, u32), hidpi_factor: f64) -> bool {
+        let mut shared = self.shared.borrow_mut();
+        let io = shared.imgui.io_mut();
+        io.display_size = [width as f32, height as f32];
+        io.font_global_scale = hidpi_factor as f32;
+        io.mouse_pos = match input.mouse() {
+            Some((x, y)) => [x, y],
+            None => [f32::MAX, f32::MAX],
+        };
+        for button in 0..3 {
+            io.mouse_down[button] = input.mouse_held(button);
+        }
+        let using_menu = io.want_capture_mouse;
+
+        let moved = input.mouse_diff() != (0.0, 0.0) || (0..3).any(|button| input.mouse_pressed(button)
+            || input.mouse_released(button));
+        let was_shown = shared.shown_until.is_some();
+        if moved || using_menu {
+            shared.shown_until = Some(Instant::now() + SHOW_DURATION);
+        } else if shared.shown_until.is_some_and(|until| Instant::now() >= until) {
+            shared.shown_until = None;
+        }
+
+        moved || was_shown != shared.shown_until.is_some()
+    }
+
+    pub fn take_actions(&mut self) -> Vec<Action> {
+        self.shared.borrow_mut().actions.drain(..).collect()
+    }
+}
+
+struct MenuPass {
+    shared: Rc<RefCell<Shared>>,
+}
+
+impl RenderPass for MenuPass {
+    fn render(&self, encoder: &mut wgpu::CommandEncoder, render_target: &TextureView) {
+        let mut shared = self.shared.borrow_mut();
+        let Shared { imgui, renderer, device, last_frame, shown_until, status, actions } = &mut *shared;
+        let (renderer, device) = match (renderer, device) {
+            (Some(renderer), Some(device)) => (renderer, device),
+            _ => return,
+        };
+
+        *last_frame = imgui.io_mut().update_delta_time(*last_frame);
+        let ui = imgui.frame();
+        // first, so everything else goes over it
+        build_backdrop(&ui, status.backdrop, status.screen_size);
+        if let Some(library) = &status.library {
+            build_library(&ui, library, actions);
+        } else if shown_until.is_some() {
+            build(&ui, status, actions);
+        }
+        if let Some(palettes) = &status.palettes {
+            build_palettes(&ui, palettes, actions);
+        }
+        if let Some(scanlines) = &status.scanlines {
+            build_scanlines(&ui, scanlines, actions);
+        }
+        if let Some(wave_ram) = &status.wave_ram {
+            build_wave_editor(&ui, wave_ram, actions);
+        }
+        if let Some(ram) = &status.cartridge_ram {
+            build_cartridge_ram_editor(&ui, ram, status.cartridge_ram_page, actions);
+        }
+        if let Err(err) = renderer.render(ui, device, encoder, render_target) {
+            eprintln!("Problem drawing the menu: {:?}", err);
+        }
+    }
+
+    fn update_bindings(&mut self, _: &TextureView, _: &Extent3d) {}
+}
+
+fn build(ui: &imgui::Ui, status: &Status, actions: &mut Vec<Action>) {
+    let hotkeys = &status.hotkeys;
+    let has_rom = status.rom.is_some();
+
+    ui.main_menu_bar(|| {
+        ui.menu(im_str!("File"), true, || {
+            let others = rom_directory_listing(status.rom.as_deref());
+            ui.menu(im_str!("Open"), !others.is_empty(), || {
+                for rom in &others {
+                    if MenuItem::new(&file_name(rom)).build(ui) {
+                        actions.push(Action::Open(rom.clone()));
+                    }
+                }
+            });
+            ui.menu(im_str!("Open Recent"), !status.recent.is_empty(), || {
+                for rom in &status.recent {
+                    if MenuItem::new(&ImString::new(rom.display().to_string())).build(ui) {
+                        actions.push(Action::Open(rom.clone()));
+                    }
+                }
+            });
+            ui.separator();
+            if item(ui, im_str!("Quit"), hotkeys, Hotkey::Quit, false, true) {
+                actions.push(Action::Quit);
+            }
+        });
+
+        ui.menu(im_str!("Emulation"), true, || {
+            if item(ui, im_str!("Pause"), hotkeys, Hotkey::Pause, status.paused, true) {
+                actions.push(Action::Pause(!status.paused));
+            }
+            if item(ui, im_str!("Reset"), hotkeys, Hotkey::Reset, false, has_rom) {
+                actions.push(Action::Reset);
+            }
+            ui.separator();
+            if item(ui, im_str!("Save State"), hotkeys, Hotkey::SaveState, false, has_rom) {
+                actions.push(Action::SaveState);
+            }
+            if item(ui, im_str!("Load Newest State"), hotkeys, Hotkey::LoadState, false, has_rom) {
+                actions.push(Action::LoadState);
+            }
+            if item(ui, im_str!("State Gallery"), hotkeys, Hotkey::StateGallery, status.gallery, has_rom) {
+                actions.push(Action::Gallery(!status.gallery));
+            }
+            ui.separator();
+            if item(ui, im_str!("Screenshot"), hotkeys, Hotkey::Screenshot, false, true) {
+                actions.push(Action::Screenshot);
+            }
+        });
+
+        ui.menu(im_str!("Cheats"), !status.cheats.is_empty(), || {
+            for (index, (name, enabled)) in status.cheats.iter().enumerate() {
+                if MenuItem::new(&ImString::new(name.as_str())).selected(*enabled).build(ui) {
+                    actions.push(Action::ToggleCheat(index));
+                }
+            }
+        });
+
+        ui.menu(im_str!("View"), true, || {
+            ui.menu(im_str!("Scale"), !status.fullscreen, || {
+                for (scale, &hotkey) in (1..).zip(Hotkey::SCALES.iter()) {
+                    if item(ui, &im_str!("{}x", scale), hotkeys, hotkey, false, true) {
+                        actions.push(Action::Scale(scale));
+                    }
+                }
+            });
+            if item(ui, im_str!("Fullscreen"), hotkeys, Hotkey::Fullscreen, status.fullscreen, true) {
+                actions.push(Action::Fullscreen(!status.fullscreen));
+            }
+            if item(ui, im_str!("Smooth Scaling"), hotkeys, Hotkey::Smooth, status.smooth, true) {
+                actions.push(Action::Smooth(!status.smooth));
+            }
+            if item(ui, im_str!("Frame Blending"), hotkeys, Hotkey::FrameBlend, status.frame_blend, has_rom) {
+                actions.push(Action::FrameBlend(!status.frame_blend));
+            }
+            ui.menu(im_str!("Backdrop"), true, || {
+                let backdrop = status.backdrop;
+                for &(name, color) in &BACKDROPS {
+                    if MenuItem::new(&ImString::new(name)).selected(backdrop.color == color).build(ui) {
+                        actions.push(Action::Backdrop(Backdrop { color, ..backdrop }));
+                    }
+                }
+                ui.separator();
+                if MenuItem::new(im_str!("Frame")).selected(backdrop.frame).build(ui) {
+                    actions.push(Action::Backdrop(Backdrop { frame: !backdrop.frame, ..backdrop }));
+                }
+            });
+            ui.menu(im_str!("Accessibility"), true, || {
+                let accessibility = status.accessibility;
+                if MenuItem::new(im_str!("No Rumble")).selected(accessibility.no_rumble).build(ui) {
+                    actions.push(Action::Accessibility(Accessibility { no_rumble: !accessibility.no_rumble, ..accessibility }));
+                }
+                if MenuItem::new(im_str!("Reduce Flashing")).selected(accessibility.reduce_flashing).build(ui) {
+                    actions.push(Action::Accessibility(
+                        Accessibility { reduce_flashing: !accessibility.reduce_flashing, ..accessibility }));
+                }
+                if MenuItem::new(im_str!("High Contrast")).selected(accessibility.high_contrast).build(ui) {
+                    actions.push(Action::Accessibility(Accessibility { high_contrast: !accessibility.high_contrast, ..accessibility }));
+                }
+            });
+            ui.separator();
+            for &(view, label, hotkey) in &[(View::Tiles, im_str!("Tile Viewer"), Hotkey::TileViewer),
+                                            (View::Map, im_str!("BG Map Viewer"), Hotkey::MapViewer)] {
+                if item(ui, label, hotkeys, hotkey, status.viewers.contains(&view), true) {
+                    actions.push(Action::ToggleViewer(view));
+                }
+            }
+            if item(ui, im_str!("Layer Overlay"), hotkeys, Hotkey::LayerOverlay, status.layer_overlay, true) {
+                actions.push(Action::LayerOverlay(!status.layer_overlay));
+            }
+            let editing = status.palettes.is_some();
+            if item(ui, im_str!("Palette Editor"), hotkeys, Hotkey::PaletteEditor, editing, true) {
+                actions.push(Action::PaletteEditor(!editing));
+            }
+            let viewing = status.scanlines.is_some();
+            if item(ui, im_str!("Scanline Viewer"), hotkeys, Hotkey::ScanlineViewer, viewing, true) {
+                actions.push(Action::ScanlineViewer(!viewing));
+            }
+            let editing_wave = status.wave_ram.is_some();
+            if item(ui, im_str!("Wave RAM Editor"), hotkeys, Hotkey::WaveEditor, editing_wave, true) {
+                actions.push(Action::WaveEditor(!editing_wave));
+            }
+            let editing_ram = status.cartridge_ram.is_some();
+            if item(ui, im_str!("Cartridge RAM Editor"), hotkeys, Hotkey::CartridgeRamEditor, editing_ram, true) {
+                actions.push(Action::CartridgeRamEditor(!editing_ram));
+            }
+        });
+
+        ui.menu(im_str!("Help"), true, || {
+            ui.text(format!("GBOxide {}", env!("CARGO_PKG_VERSION")));
+            ui.separator();
+            ui.text("Arrow keys: DPad");
+            ui.text("X / Z: A / B");
+            ui.text("Return / Space: Start / Select");
+            ui.text("S / A: Turbo A / Turbo B");
+            ui.separator();
+            for &hotkey in Hotkey::ALL.iter() {
+                ui.text(format!("{}: {}", hotkeys.key_name(hotkey), hotkey.description()));
+            }
+        });
+    });
+}
+
+// fills the window around the screen, which pixels leaves black. the screen's where pixels puts
+// it, the biggest whole multiple of screen_size that fits, centred
+fn build_backdrop(ui: &imgui::Ui, backdrop: Backdrop, screen_size: [f32; 2]) {
+    if backdrop == Backdrop::default() || screen_size[0] == 0.0 || screen_size[1] == 0.0 {
+        return;
+    }
+    let [width, height] = ui.io().display_size;
+    let scale = (width / screen_size[0]).min(height / screen_size[1]).floor().max(1.0);
+    let left = (width - screen_size[0] * scale) / 2.0;
+    let top = (height - screen_size[1] * scale) / 2.0;
+    let (right, bottom) = (width - left, height - top);
+
+    let style = ui.push_style_vars(&[StyleVar::WindowBorderSize(0.0), StyleVar::WindowPadding([0.0, 0.0])]);
+    Window::new(im_str!("##backdrop"))
+        .position([0.0, 0.0], Condition::Always)
+        .size([width, height], Condition::Always)
+        .no_decoration()
+        .no_inputs()
+        .draw_background(false)
+        .focus_on_appearing(false)
+        .bring_to_front_on_focus(false)
+        .save_settings(false)
+        .build(ui, || {
+            let draw_list = ui.get_window_draw_list();
+            let color = shade(backdrop.color, 0.0);
+            for &(p1, p2) in &[([0.0, 0.0], [width, top]), ([0.0, bottom], [width, height]),
+                               ([0.0, top], [left, bottom]), ([right, top], [width, bottom])] {
+                draw_list.add_rect(p1, p2, color).filled(true).build();
+            }
+            if backdrop.frame {
+                // lit nearest the screen and fading out into the backdrop, with a shadow line
+                // right against it
+                let frame_width = (FRAME_WIDTH * ui.io().font_global_scale).round().max(2.0);
+                let steps = frame_width as u32;
+                for step in (1..=steps).rev() {
+                    let lift = if step == 1 { -0.5 } else { 0.3 * (steps - step + 1) as f32 / steps as f32 };
+                    let offset = step as f32 - 0.5;
+                    draw_list.add_rect([left - offset, top - offset], [right + offset, bottom + offset],
+                                       shade(backdrop.color, lift))
+                        .build();
+                }
+            }
+        });
+    style.pop(ui);
+}
+
+// color lightened towards white by lift, or darkened towards black by a negative lift, as imgui
+// wants it: linear, since it draws straight to the sRGB surface without converting
+fn shade(color: [u8; 3], lift: f32) -> [f32; 4] {
+    let channel = |c: u8| {
+        let c = c as f32 / 255.0;
+        let c = if lift >= 0.0 { c + (1.0 - c) * lift } else { c * (1.0 + lift) };
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    };
+    [channel(color[0]), channel(color[1]), channel(color[2]), 1.0]
+}
+
+fn build_library(ui: &imgui::Ui, library: &Library, actions: &mut Vec<Action>) {
+    Window::new(im_str!("Library"))
+        .position([0.0, 0.0], Condition::Always)
+        .size(ui.io().display_size, Condition::Always)
+        .title_bar(false)
+        .resizable(false)
+        .movable(false)
+        .collapsible(false)
+        .build(ui, || {
+            if library.games.is_empty() {
+                ui.text(format!("No ROMs in {}", library.directory.display()));
+                return;
+            }
+            ui.text(format!("{} ROMs in {}, pick one to play", library.games.len(), library.directory.display()));
+            ui.separator();
+
+            ui.columns(5, im_str!("games"), true);
+            for heading in &[im_str!("Title"), im_str!("Mapper"), im_str!("File"), im_str!("Played"), im_str!("Last Played")] {
+                ui.text(heading);
+                ui.next_column();
+            }
+            ui.separator();
+            for (index, game) in library.games.iter().enumerate() {
+                // titles aren't unique, imgui tells the rows apart by what's after the ##
+                let label = ImString::new(format!("{}##{}", game.title, index));
+                if Selectable::new(&label).span_all_columns(true).build(ui) {
+                    actions.push(Action::Open(game.path.clone()));
+                }
+                ui.next_column();
+                ui.text(&game.mapper);
+                ui.next_column();
+                ui.text(file_name(&game.path));
+                ui.next_column();
+                ui.text(playtime(game.playtime));
+                ui.next_column();
+                ui.text(game.last_played.map_or_else(|| "never".to_string(), ago));
+                ui.next_column();
+            }
+            ui.columns(1, im_str!("games"), false);
+        });
+}
+
+// a row of swatches for each palette. clicking one shows the next shade in its place,
+// right clicking puts the game's back
+fn build_palettes(ui: &imgui::Ui, palettes: &Palettes, actions: &mut Vec<Action>) {
+    let mut opened = true;
+    Window::new(im_str!("Palettes"))
+        .position([20.0, 40.0], Condition::FirstUseEver)
+        .always_auto_resize(true)
+        .opened(&mut opened)
+        .build(ui, || {
+            for (palette, (&register, overrides)) in palettes.registers.iter().zip(&palettes.overrides).enumerate() {
+                ui.text(format!("{:<4} ${:02x}", PALETTE_NAMES[palette], register));
+                for (entry, &overridden) in overrides.iter().enumerate() {
+                    let game_shade = (register >> (entry * 2)) & 0b11;
+                    let shade = overridden.unwrap_or(game_shade);
+                    let grey = SHADES[shade as usize];
+                    let description = match overridden {
+                        Some(_) => format!("{} {}: {} (the game's is {})##{}{}", PALETTE_NAMES[palette], entry,
+                                           SHADE_NAMES[shade as usize], SHADE_NAMES[game_shade as usize], palette, entry),
+                        None => format!("{} {}: {}##{}{}", PALETTE_NAMES[palette], entry,
+                                        SHADE_NAMES[shade as usize], palette, entry),
+                    };
+                    ui.same_line(0.0);
+                    if ColorButton::new(&ImString::new(description), [grey, grey, grey, 1.0]).size([24.0, 24.0]).build(ui) {
+                        actions.push(Action::OverridePalette(palette, entry, Some((shade + 1) % 4)));
+                    }
+                    if ui.is_item_clicked(MouseButton::Right) {
+                        actions.push(Action::OverridePalette(palette, entry, None));
+                    }
+                }
+                if overrides.iter().any(Option::is_some) {
+                    ui.same_line(0.0);
+                    ui.text("edited");
+                }
+            }
+            ui.separator();
+            if ui.small_button(im_str!("Put the game's back")) {
+                for palette in 0..palettes.overrides.len() {
+                    for entry in 0..4 {
+                        actions.push(Action::OverridePalette(palette, entry, None));
+                    }
+                }
+            }
+        });
+    if !opened {
+        actions.push(Action::PaletteEditor(false));
+    }
+}
+
+// a row for each line of the last frame, with anything a raster effect changed since the line
+// above picked out
+fn build_scanlines(ui: &imgui::Ui, scanlines: &[ScanlineRegisters], actions: &mut Vec<Action>) {
+    let mut opened = true;
+    Window::new(im_str!("Scanlines"))
+        .position([40.0, 60.0], Condition::FirstUseEver)
+        .size([420.0, 360.0], Condition::FirstUseEver)
+        .opened(&mut opened)
+        .build(ui, || {
+            if scanlines.is_empty() {
+                ui.text("The LCD was off for the last frame");
+                return;
+            }
+            ui.columns(9, im_str!("scanlines"), true);
+            for heading in &["LY", "LCDC", "SCY", "SCX", "WY", "WX", "BGP", "OBP0", "OBP1"] {
+                ui.text(heading);
+                ui.next_column();
+            }
+            ui.separator();
+            // in the same order as the headings
+            let columns = |line: &ScanlineRegisters| [line.lcdc, line.scy, line.scx, line.wy, line.wx,
+                                                      line.bgp, line.obp0, line.obp1];
+            let mut above: Option<[u8; 8]> = None;
+            for (ly, line) in scanlines.iter().enumerate() {
+                ui.text(format!("{}", ly));
+                ui.next_column();
+                let values = columns(line);
+                for (column, value) in values.iter().enumerate() {
+                    let text = format!("{:02x}", value);
+                    match above {
+                        Some(above) if above[column] != *value => ui.text_colored(CHANGED, text),
+                        _ => ui.text(text),
+                    }
+                    ui.next_column();
+                }
+                above = Some(values);
+            }
+            ui.columns(1, im_str!("scanlines"), false);
+        });
+    if !opened {
+        actions.push(Action::ScanlineViewer(false));
+    }
+}
+
+// channel 3's 32 samples as a plot, with a slider under each to change it
+fn build_wave_editor(ui: &imgui::Ui, wave_ram: &[u8; 16], actions: &mut Vec<Action>) {
+    let mut opened = true;
+    Window::new(im_str!("Wave RAM"))
+        .position([60.0, 80.0], Condition::FirstUseEver)
+        .always_auto_resize(true)
+        .opened(&mut opened)
+        .build(ui, || {
+            // two to a byte, high nibble first
+            let mut samples = Vec::with_capacity(32);
+            for &byte in wave_ram {
+                samples.push(byte >> 4);
+                samples.push(byte & 0x0F);
+            }
+            let plotted: Vec<f32> = samples.iter().map(|&sample| sample as f32).collect();
+            PlotLines::new(ui, im_str!("##wave"), &plotted)
+                .scale_min(0.0)
+                .scale_max(15.0)
+                .graph_size([32.0 * 14.0, 60.0])
+                .build();
+
+            let mut changed = false;
+            for (index, sample) in samples.iter_mut().enumerate() {
+                if index > 0 {
+                    ui.same_line_with_spacing(0.0, 2.0);
+                }
+                let label = ImString::new(format!("##sample{}", index));
+                changed |= VerticalSlider::new(&label, [12.0, 80.0], 0..=15).display_format(im_str!("")).build(ui, sample);
+            }
+            if changed {
+                let mut wave = [0x00; 16];
+                for (byte, pair) in wave.iter_mut().zip(samples.chunks(2)) {
+                    *byte = (pair[0] << 4) | pair[1];
+                }
+                actions.push(Action::SetWaveRam(wave));
+            }
+
+            let bytes: Vec<String> = wave_ram.iter().map(|byte| format!("{:02x}", byte)).collect();
+            ui.text(bytes.join(" "));
+        });
+    if !opened {
+        actions.push(Action::WaveEditor(false));
+    }
+}
+
+// a page of the cartridge's RAM as hex, each byte editable, where enter writes it back.
+// rows are labelled bank:address as the game would see them
+fn build_cartridge_ram_editor(ui: &imgui::Ui, ram: &[u8], page: usize, actions: &mut Vec<Action>) {
+    let mut opened = true;
+    Window::new(im_str!("Cartridge RAM"))
+        .position([80.0, 100.0], Condition::FirstUseEver)
+        .always_auto_resize(true)
+        .opened(&mut opened)
+        .build(ui, || {
+            if ram.is_empty() {
+                ui.text("The cartridge has no RAM");
+                return;
+            }
+
+            let last_page = (ram.len() - 1) / RAM_PAGE_SIZE;
+            let mut picked = page.min(last_page) as u32;
+            if last_page > 0 && Slider::new(im_str!("page"), 0..=last_page as u32).build(ui, &mut picked) {
+                actions.push(Action::CartridgeRamPage(picked as usize));
+            }
+
+            let start = picked as usize * RAM_PAGE_SIZE;
+            let end = (start + RAM_PAGE_SIZE).min(ram.len());
+            for row in (start..end).step_by(16) {
+                ui.text(format!("{:02x}:{:04x}", row / RAM_BANK_SIZE, 0xA000 + row % RAM_BANK_SIZE));
+                for (offset, &byte) in ram[row..end.min(row + 16)].iter().enumerate().map(|(i, byte)| (row + i, byte)) {
+                    ui.same_line_with_spacing(0.0, if offset % 8 == 0 { 8.0 } else { 2.0 });
+                    ui.set_next_item_width(22.0);
+                    let mut text = ImString::with_capacity(2);
+                    text.push_str(&format!("{:02x}", byte));
+                    let label = ImString::new(format!("##ram{}", offset));
+                    let entered = InputText::new(ui, &label, &mut text)
+                        .chars_hexadecimal(true)
+                        .auto_select_all(true)
+                        .enter_returns_true(true)
+                        .build();
+                    if let (true, Ok(value)) = (entered, u8::from_str_radix(text.to_str(), 16)) {
+                        actions.push(Action::SetCartridgeRam(offset, value));
+                    }
+                }
+            }
+
+            ui.separator();
+            if ui.small_button(im_str!("Export")) {
+                actions.push(Action::ExportCartridgeRam);
+            }
+            ui.same_line(0.0);
+            if ui.small_button(im_str!("Import")) {
+                actions.push(Action::ImportCartridgeRam);
+            }
+            ui.same_line(0.0);
+            ui.text(format!("{} bytes, to and from <rom>.sram", ram.len()));
+        });
+    if !opened {
+        actions.push(Action::CartridgeRamEditor(false));
+    }
+}
+
+// 1h 05m, or 12m under an hour
+fn playtime(playtime: Duration) -> String {
+    let minutes = playtime.as_secs() / 60;
+    match minutes {
+        0 => "-".to_string(),
+        1 ..= 59 => format!("{}m", minutes),
+        _ => format!("{}h {:02}m", minutes / 60, minutes % 60),
+    }
+}
+
+pub fn ago(time: SystemTime) -> String {
+    let secs = time.elapsed().unwrap_or_default().as_secs();
+    let (count, unit) = match secs {
+        0 ..= 59 => return "just now".to_string(),
+        60 ..= 3599 => (secs / 60, "minute"),
+        3600 ..= 86399 => (secs / 3600, "hour"),
+        _ => (secs / 86400, "day"),
+    };
+    format!("{} {}{} ago", count, unit, if count == 1 { "" } else { "s" })
+}
+
+// a menu item showing the key that does the same
+fn item(ui: &imgui::Ui, label: &ImStr, hotkeys: &Hotkeys, hotkey: Hotkey, selected: bool, enabled: bool) -> bool {
+    let key = ImString::new(hotkeys.key_name(hotkey));
+    MenuItem::new(label).shortcut(&key).selected(selected).enabled(enabled).build(ui)
+}
+
+// the other ROMs next to the one being played
+fn rom_directory_listing(rom: Option<&Path>) -> Vec<PathBuf> {
+    let directory = match rom.and_then(Path::parent) {
+        Some(directory) if directory.as_os_str().is_empty() => Path::new("."),
+        Some(directory) => directory,
+        None => return Vec::new(),
+    };
+    let mut roms: Vec<PathBuf> = match directory.read_dir() {
+        Ok(entries) => entries.filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                let extension = path.extension().and_then(|e| e.to_str()).map(str::to_lowercase);
+                matches!(extension.as_deref(), Some("gb") | Some("gbc"))
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    roms.sort();
+    roms
+}
+
+fn file_name(path: &Path) -> ImString {
+    ImString::new(path.file_name().unwrap_or_default().to_string_lossy())
+}
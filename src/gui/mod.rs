@@ -1,21 +1,147 @@
-use crate::cartridge::Cartridge;
+mod config;
+mod crash;
+mod debug_window;
+mod emulator;
+mod filter;
+mod gallery;
+mod hotkeys;
+mod http;
+pub mod library;
+pub mod input;
+mod menu;
+mod motion;
+mod remote;
+mod rumble;
+
+use std::env;
+use std::ffi::OsString;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::time::{Duration, Instant};
+
+use crate::achievements::Achievements;
+use crate::border::{Border, BORDER_WIDTH, BORDER_HEIGHT};
+use crate::cheats::{enabled_codes, Cheat};
 use crate::gameboy::GameBoy;
-use crate::gameboy::lcd::{SCREEN_WIDTH, SCREEN_HEIGHT};
-use crate::gameboy::joypad::Controls;
+use crate::gameboy::disasm::Symbols;
+use crate::gameboy::lcd::{Palettes, ScanlineRegisters, SCREEN_WIDTH, SCREEN_HEIGHT};
+use crate::gui::config::Config;
+use crate::gui::debug_window::DebugWindow;
+use crate::gui::gallery::Gallery;
+use crate::gui::filter::FlashLimiter;
+use crate::gui::emulator::{Command, EmulatorThread, Update, View, MIN_SPEED, MAX_SPEED};
+use crate::gui::hotkeys::{Hotkey, Hotkeys};
+use crate::gui::menu::{Action, Menu};
+use crate::gui::motion::SharedTilt;
+use crate::gui::remote::{Request, Response};
+use crate::gui::rumble::{Rumble, RumbleSwitch};
 
-use pixels::{Error, Pixels, SurfaceTexture};
-use winit::event::{Event, VirtualKeyCode, WindowEvent};
-use winit::event_loop::{ControlFlow, EventLoop};
+use gilrs::Gilrs;
+use pixels::{Error, PixelsBuilder, SurfaceTexture};
+use winit::event::{ElementState, Event, MouseButton, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget};
+use winit::window::Fullscreen;
 use winit_input_helper::WinitInputHelper;
 
-pub fn run(cartridge: Cartridge) -> Result<(), Error> {
+const FRAME_POLL_INTERVAL: Duration = Duration::from_millis(4);
+
+// how long messages like achievement unlocks stay in the window title
+const MESSAGE_DURATION: Duration = Duration::from_secs(5);
+
+// quitting within this long of the game writing its save asks to be sure first,
+// the game could be part way through it
+const RECENT_SAVE: Duration = Duration::from_secs(3);
+
+// the speeds the speed up and down hotkeys step through
+const SPEED_STEPS: [u32; 8] = [25, 50, 75, 100, 150, 200, 300, 400];
+
+pub struct Options {
+    // address to listen on for remote control commands
+    pub remote: Option<String>,
+    // address to serve the HTTP control and streaming endpoints on
+    pub serve: Option<String>,
+    // frames the turbo buttons spend pressed, then released, while held
+    pub turbo_interval: u32,
+    pub input_sources: input::Sources,
+    // percentage of real time to run at, from 25 to 400
+    pub speed: u32,
+    // the window's starting size, as a multiple of the screen
+    pub scale: u32,
+    // how hard gamepads rumble for the cartridge's motor, as a percentage. 0 turns it off
+    pub rumble: u32,
+    // where to write the cartridge's battery backed RAM on exit
+    pub battery_save: Option<PathBuf>,
+    // where to write the code/data log on exit, if one's being kept
+    pub code_data_log: Option<PathBuf>,
+    // the game's proper name, for the window title
+    pub game_name: Option<String>,
+    // achievements to watch for, announced in the window title as they unlock
+    pub achievements: Option<Achievements>,
+    // drawn around the screen, making the window SGB sized
+    pub border: Option<Border>,
+    // the ROM being played, for the menu to reset, find other ROMs next to, and keep save states by
+    pub rom: Option<PathBuf>,
+    // names for addresses in traces
+    pub symbols: Symbols,
+    // where to write a crash bundle if the core stops with an error, see crash::write_bundle
+    pub crash_dump: Option<PathBuf>,
+    // for the menu to switch on and off, the gameboy should start with the enabled ones set
+    pub cheats: Vec<Cheat>,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            remote: None,
+            serve: None,
+            turbo_interval: 4,
+            input_sources: input::Sources::default(),
+            speed: 100,
+            scale: 3,
+            rumble: 75,
+            battery_save: None,
+            code_data_log: None,
+            game_name: None,
+            achievements: None,
+            border: None,
+            rom: None,
+            symbols: Symbols::new(),
+            crash_dump: None,
+            cheats: Vec::new(),
+        }
+    }
+}
+
+pub fn run(mut gameboy: GameBoy, mut options: Options) -> Result<(), Error> {
     let event_loop = EventLoop::new();
     let mut input = WinitInputHelper::new();
 
-    let (window, surface, width, height, mut hidpi_factor) = {
-        let scale = 3.0;
-        let width = SCREEN_WIDTH as f64 * scale;
-        let height = SCREEN_HEIGHT as f64 * scale;
+    let mut config = Config::load();
+    let hotkeys = Hotkeys::new(&config.hotkeys);
+    if let Some(rom) = &options.rom {
+        config.add_recent(rom);
+        if let Err(err) = config.save() {
+            eprintln!("Problem saving config: {}", err);
+        }
+    }
+
+    // the screen, with its border if there is one, before it's scaled up
+    let (image_width, image_height) = match options.border {
+        Some(_) => (BORDER_WIDTH, BORDER_HEIGHT),
+        None => (SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize),
+    };
+    let mut image = vec![0xFF; image_width * image_height * 4];
+    // the last frame as the emulator drew it, to blend the next one with
+    let mut previous_frame = vec![0xFF; SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize * 4];
+    let mut frame_blend = options.rom.as_ref().is_some_and(|rom| config.game(rom).frame_blend);
+    let mut flash_limiter = FlashLimiter::new();
+
+    let (window, surface) = {
+        let width = (image_width as u32 * options.scale) as f64;
+        let height = (image_height as u32 * options.scale) as f64;
 
         let window = winit::window::WindowBuilder::new()
             .with_inner_size(winit::dpi::LogicalSize::new(width, height))
@@ -23,70 +149,617 @@ pub fn run(cartridge: Cartridge) -> Result<(), Error> {
             .build(&event_loop)
             .unwrap();
         let surface = pixels::wgpu::Surface::create(&window);
-        let hidpi_factor = window.hidpi_factor();
-        let size = window.inner_size().to_physical(hidpi_factor);
-
-        (
-            window,
-            surface,
-            size.width.round() as u32,
-            size.height.round() as u32,
-            hidpi_factor
-        )
+
+        (window, surface)
     };
 
+    let (width, height) = physical_size(&window);
     let surface_texture = SurfaceTexture::new(width, height, surface);
-    let mut pixels = Pixels::new(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, surface_texture)?;
-    let mut gameboy = GameBoy::new(cartridge);
+    let mut menu = Menu::new();
+    let mut pixels = PixelsBuilder::new((image_width * filter::SCALE) as u32, (image_height * filter::SCALE) as u32,
+                                        surface_texture)
+        .add_render_pass(menu.render_pass())
+        .build()?;
+    filter::upscale(&image, image_width, image_height, config.smooth, pixels.get_frame());
+
+    let mut gilrs = Gilrs::new()
+        .map_err(|err| eprintln!("Gamepad support unavailable: {}", err))
+        .ok();
+    let rumble = RumbleSwitch::new(rumble::new(gilrs.as_mut(), options.rumble), !config.accessibility.no_rumble);
+    gameboy.set_rumble_callback({
+        let mut rumble = rumble.clone();
+        move |on| rumble.set(on)
+    });
+    let tilt = SharedTilt::new();
+    gameboy.set_motion_source(tilt.clone());
+
+    let mut emulator = EmulatorThread::new(gameboy, options.turbo_interval,
+                                           options.battery_save.clone(), options.code_data_log.clone(),
+                                           options.achievements.take(), mem::take(&mut options.symbols),
+                                           options.crash_dump.clone());
+    if let Some(addr) = &options.remote {
+        if let Err(err) = remote::listen(addr, emulator.command_sender()) {
+            eprintln!("Problem starting remote control on {}: {}", addr, err);
+        }
+    }
+    if let Some(addr) = &options.serve {
+        if let Err(err) = http::listen(addr, emulator.command_sender()) {
+            eprintln!("Problem starting HTTP server on {}: {}", addr, err);
+        }
+    }
+    let mut prev_held = input::Held::default();
+    let mut fast_forward = false;
+    let mut speed = options.speed.max(MIN_SPEED).min(MAX_SPEED);
+    emulator.send(Command::Speed(speed));
+    // the message showing in the title and when it goes away
+    let mut message: Option<(String, Instant)> = None;
+    set_title(&window, options.game_name.as_deref(), speed, None);
+    let mut debug_windows: Vec<DebugWindow> = Vec::new();
+    let mut state_gallery: Option<Gallery> = None;
+    // the state clicked on in the gallery, loaded with the rest of the actions
+    let mut picked_state: Option<PathBuf> = None;
+    let mut paused = false;
+    let mut layer_overlay = false;
+    // the latest palettes while the editor's open, and the next ones asked for
+    let mut palettes: Option<Palettes> = None;
+    let mut pending_palettes: Option<Receiver<Palettes>> = None;
+    // the same for the scanline viewer
+    let mut scanlines: Option<Vec<ScanlineRegisters>> = None;
+    let mut pending_scanlines: Option<Receiver<Vec<ScanlineRegisters>>> = None;
+    // and the wave RAM editor
+    let mut wave_ram: Option<[u8; 16]> = None;
+    let mut pending_wave_ram: Option<Receiver<[u8; 16]>> = None;
+    // and the cartridge RAM editor, with the page it's showing
+    let mut cartridge_ram: Option<Vec<u8>> = None;
+    let mut pending_cartridge_ram: Option<Receiver<Vec<u8>>> = None;
+    let mut cartridge_ram_page = 0;
+    let mut cheats = mem::take(&mut options.cheats);
+    let mut fullscreen = false;
+    // quitting again before this goes through even if the game's just saved
+    let mut quit_confirmed_until: Option<Instant> = None;
+    // taken when the playtime's added to the config
+    let mut playing_since = Some(Instant::now());
+
+    event_loop.run(move |event, event_loop, control_flow| {
+        // wake up often enough to pick up new frames from the emulator thread
+        *control_flow = ControlFlow::WaitUntil(Instant::now() + FRAME_POLL_INTERVAL);
+
+        if let Event::LoopDestroyed = event {
+            record_playtime(options.rom.as_deref(), &mut playing_since);
+            return;
+        }
+
+        if let Event::WindowEvent { window_id, event: window_event } = &event {
+            if let Some(index) = debug_windows.iter().position(|debug| debug.id() == *window_id) {
+                match window_event {
+                    WindowEvent::CloseRequested => {
+                        debug_windows.remove(index);
+                    },
+                    WindowEvent::Resized(_) | WindowEvent::HiDpiFactorChanged(_) => debug_windows[index].resize(),
+                    WindowEvent::RedrawRequested => debug_windows[index].render(),
+                    _ => (),
+                }
+                // the input helper doesn't look at which window an event's for, and would
+                // take closing a debug window for quitting
+                return;
+            }
+            if let Some(gallery) = state_gallery.as_mut().filter(|gallery| gallery.id() == *window_id) {
+                match window_event {
+                    WindowEvent::CloseRequested => state_gallery = None,
+                    WindowEvent::Resized(_) | WindowEvent::HiDpiFactorChanged(_) => gallery.resize(),
+                    WindowEvent::RedrawRequested => gallery.render(),
+                    WindowEvent::CursorMoved { position, .. } => gallery.hover(*position),
+                    WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. } => {
+                        picked_state = gallery.hovered().map(Path::to_path_buf);
+                    },
+                    _ => (),
+                }
+                return;
+            }
+        }
 
-    event_loop.run(move |event, _, control_flow| {
         if let Event::WindowEvent {
             event: WindowEvent::RedrawRequested,
             ..
         } = event
         {
-            gameboy.draw_frame(pixels.get_frame());
             pixels.render();
         }
 
+        if let Some(gilrs) = gilrs.as_mut() {
+            // keep gilrs' gamepad state (and force feedback) ticking over
+            while gilrs.next_event().is_some() {}
+        }
+
         if input.update(event) {
-            if input.key_pressed(VirtualKeyCode::Escape) || input.quit() {
-                *control_flow = ControlFlow::Exit;
-                return;
+            let held = input::read(options.input_sources, &input, gilrs.as_ref());
+            if held.controls != prev_held.controls {
+                emulator.send(Command::Controls(held.controls));
+            }
+            if held.turbo != prev_held.turbo {
+                emulator.send(Command::Turbo(held.turbo));
+            }
+            if held.tilt != prev_held.tilt {
+                tilt.set(held.tilt);
             }
+            prev_held = held;
 
-            let controls = Controls {
-                left: input.key_held(VirtualKeyCode::Left),
-                right: input.key_held(VirtualKeyCode::Right),
-                up: input.key_held(VirtualKeyCode::Up),
-                down: input.key_held(VirtualKeyCode::Down),
+            // held to run as fast as possible
+            if hotkeys.held(&input, Hotkey::FastForward) != fast_forward {
+                fast_forward = !fast_forward;
+                emulator.send(Command::FastForward(fast_forward));
+            }
 
-                a: input.key_held(VirtualKeyCode::X),
-                b: input.key_held(VirtualKeyCode::Z),
-                start: input.key_held(VirtualKeyCode::Return),
-                select: input.key_held(VirtualKeyCode::Space),
+            let new_speed = if hotkeys.pressed(&input, Hotkey::SpeedDown) {
+                SPEED_STEPS.iter().rev().find(|&&step| step < speed).copied().unwrap_or(speed)
+            } else if hotkeys.pressed(&input, Hotkey::SpeedUp) {
+                SPEED_STEPS.iter().find(|&&step| step > speed).copied().unwrap_or(speed)
+            } else if hotkeys.pressed(&input, Hotkey::SpeedReset) {
+                100
+            } else {
+                speed
             };
-            gameboy.set_controls(controls);
+            if new_speed != speed {
+                speed = new_speed;
+                emulator.send(Command::Speed(speed));
+                set_title(&window, options.game_name.as_deref(), speed, message.as_ref().map(|(text, _)| text.as_str()));
+            }
 
-            if let Some(factor) = input.hidpi_changed() {
-                hidpi_factor = factor;
+            // the hotkeys (and closing the window) do the same as picking from the menu
+            let mut actions = menu.take_actions();
+            if let Some(path) = picked_state.take() {
+                actions.push(Action::LoadStateFrom(path));
+            }
+            if input.quit() {
+                actions.push(Action::Quit);
+            }
+            for &hotkey in Hotkey::ALL.iter() {
+                if !hotkeys.pressed(&input, hotkey) {
+                    continue;
+                }
+                actions.push(match hotkey {
+                    Hotkey::Quit => Action::Quit,
+                    Hotkey::Pause => Action::Pause(!paused),
+                    Hotkey::Reset => Action::Reset,
+                    Hotkey::SaveState => Action::SaveState,
+                    Hotkey::LoadState => Action::LoadState,
+                    Hotkey::StateGallery => Action::Gallery(state_gallery.is_none()),
+                    Hotkey::Screenshot => Action::Screenshot,
+                    Hotkey::Fullscreen => Action::Fullscreen(!fullscreen),
+                    Hotkey::Smooth => Action::Smooth(!config.smooth),
+                    Hotkey::FrameBlend => Action::FrameBlend(!frame_blend),
+                    Hotkey::TileViewer => Action::ToggleViewer(View::Tiles),
+                    Hotkey::MapViewer => Action::ToggleViewer(View::Map),
+                    Hotkey::LayerOverlay => Action::LayerOverlay(!layer_overlay),
+                    Hotkey::PaletteEditor => Action::PaletteEditor(palettes.is_none()),
+                    Hotkey::ScanlineViewer => Action::ScanlineViewer(scanlines.is_none()),
+                    Hotkey::WaveEditor => Action::WaveEditor(wave_ram.is_none()),
+                    Hotkey::CartridgeRamEditor => Action::CartridgeRamEditor(cartridge_ram.is_none()),
+                    Hotkey::Scale1 => Action::Scale(1),
+                    Hotkey::Scale2 => Action::Scale(2),
+                    Hotkey::Scale3 => Action::Scale(3),
+                    Hotkey::Scale4 => Action::Scale(4),
+                    Hotkey::Scale5 => Action::Scale(5),
+                    Hotkey::Scale6 => Action::Scale(6),
+                    // handled above
+                    Hotkey::FastForward | Hotkey::SpeedDown | Hotkey::SpeedUp | Hotkey::SpeedReset => continue,
+                });
             }
 
-            if let Some(size) = input.window_resized() {
-                let size = size.to_physical(hidpi_factor);
-                let width = size.width.round() as u32;
-                let height = size.height.round() as u32;
+            for action in actions {
+                match action {
+                    Action::Open(rom) => {
+                        emulator.stop();
+                        // before the next one starts and reads the config
+                        record_playtime(options.rom.as_deref(), &mut playing_since);
+                        if let Err(err) = relaunch(options.rom.as_deref(), &rom) {
+                            eprintln!("Problem starting {}: {}", rom.display(), err);
+                        }
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    },
+                    Action::Reset => if let Some(rom) = &options.rom {
+                        // starting again is the only way to power cycle everything, the frontend included
+                        emulator.stop();
+                        record_playtime(Some(rom), &mut playing_since);
+                        if let Err(err) = relaunch(Some(rom), rom) {
+                            eprintln!("Problem restarting: {}", err);
+                        }
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    },
+                    Action::Quit => {
+                        let asked = quit_confirmed_until.is_some_and(|until| Instant::now() < until);
+                        if !asked && !config.quit_without_asking && saved_recently(&emulator) {
+                            let text = format!("The game's just saved, {} again to quit anyway",
+                                               hotkeys.key_name(Hotkey::Quit));
+                            set_title(&window, options.game_name.as_deref(), speed, Some(&text));
+                            message = Some((text, Instant::now() + MESSAGE_DURATION));
+                            quit_confirmed_until = Some(Instant::now() + MESSAGE_DURATION);
+                            continue;
+                        }
+                        // winit exits the process without dropping anything, so the save has to be written now
+                        emulator.stop();
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    },
+                    Action::Pause(pause) => {
+                        paused = pause;
+                        let _ = request(&emulator, if pause { Request::Pause } else { Request::Resume });
+                    },
+                    Action::SaveState => if let Some(rom) = &options.rom {
+                        let text = match gallery::new_state_path(rom) {
+                            Ok(path) => request(&emulator, Request::SaveState(path))
+                                .map(|_| "Saved state".to_string())
+                                .unwrap_or_else(|err| err),
+                            Err(err) => format!("couldn't save state: {}", err),
+                        };
+                        if let Some(gallery) = &mut state_gallery {
+                            gallery.refresh();
+                        }
+                        set_title(&window, options.game_name.as_deref(), speed, Some(&text));
+                        message = Some((text, Instant::now() + MESSAGE_DURATION));
+                    },
+                    Action::LoadState | Action::LoadStateFrom(_) => if let Some(rom) = &options.rom {
+                        let path = match action {
+                            Action::LoadStateFrom(path) => Some(path),
+                            _ => gallery::save_states(rom).into_iter().next().map(|state| state.path),
+                        };
+                        let text = match path {
+                            Some(path) => request(&emulator, Request::LoadState(path))
+                                .map(|_| "Loaded state".to_string())
+                                .unwrap_or_else(|err| err),
+                            None => "No save states yet".to_string(),
+                        };
+                        set_title(&window, options.game_name.as_deref(), speed, Some(&text));
+                        message = Some((text, Instant::now() + MESSAGE_DURATION));
+                    },
+                    Action::Gallery(open) => match (&options.rom, open) {
+                        (Some(rom), true) if state_gallery.is_none() => match Gallery::open(rom, event_loop) {
+                            Ok(gallery) => state_gallery = Some(gallery),
+                            Err(err) => eprintln!("Problem opening the save state gallery: {}", err),
+                        },
+                        (_, false) => state_gallery = None,
+                        _ => (),
+                    },
+                    Action::Screenshot => {
+                        let path = screenshot_path(options.rom.as_deref());
+                        let text = request(&emulator, Request::Screenshot(path.clone()))
+                            .map(|_| format!("Saved {}", path.display()))
+                            .unwrap_or_else(|err| err);
+                        set_title(&window, options.game_name.as_deref(), speed, Some(&text));
+                        message = Some((text, Instant::now() + MESSAGE_DURATION));
+                    },
+                    // fullscreen's size is the monitor's
+                    Action::Scale(scale) => if !fullscreen {
+                        window.set_inner_size(winit::dpi::LogicalSize::new(
+                            (image_width as u32 * scale) as f64, (image_height as u32 * scale) as f64));
+                    },
+                    Action::Fullscreen(on) => {
+                        fullscreen = on;
+                        window.set_fullscreen(if on { Some(Fullscreen::Borderless(window.current_monitor())) } else { None });
+                    },
+                    Action::Smooth(on) => {
+                        config.smooth = on;
+                        if let Err(err) = config.save() {
+                            eprintln!("Problem saving config: {}", err);
+                        }
+                        filter::upscale(&image, image_width, image_height, config.smooth, pixels.get_frame());
+                        window.request_redraw();
+                    },
+                    Action::FrameBlend(on) => if let Some(rom) = &options.rom {
+                        // each game remembers its own, only some need it
+                        frame_blend = on;
+                        config.game_mut(rom).frame_blend = on;
+                        if let Err(err) = config.save() {
+                            eprintln!("Problem saving config: {}", err);
+                        }
+                    },
+                    Action::Accessibility(accessibility) => {
+                        if accessibility.reduce_flashing != config.accessibility.reduce_flashing {
+                            flash_limiter.reset();
+                        }
+                        rumble.set_enabled(!accessibility.no_rumble);
+                        config.accessibility = accessibility;
+                        if let Err(err) = config.save() {
+                            eprintln!("Problem saving config: {}", err);
+                        }
+                    },
+                    Action::Backdrop(backdrop) => {
+                        config.backdrop = backdrop;
+                        if let Err(err) = config.save() {
+                            eprintln!("Problem saving config: {}", err);
+                        }
+                        window.request_redraw();
+                    },
+                    Action::ToggleViewer(view) => toggle_viewer(&mut debug_windows, view, event_loop),
+                    Action::LayerOverlay(on) => {
+                        layer_overlay = on;
+                        emulator.send(Command::LayerOverlay(on));
+                    },
+                    Action::PaletteEditor(open) => {
+                        // filled in once the emulator answers
+                        palettes = if open { Some(Palettes::default()) } else { None };
+                        pending_palettes = None;
+                    },
+                    Action::OverridePalette(palette, entry, shade) => {
+                        emulator.send(Command::OverridePalette(palette, entry, shade));
+                    },
+                    Action::ScanlineViewer(open) => {
+                        scanlines = if open { Some(Vec::new()) } else { None };
+                        pending_scanlines = None;
+                    },
+                    Action::WaveEditor(open) => {
+                        wave_ram = if open { Some([0x00; 16]) } else { None };
+                        pending_wave_ram = None;
+                    },
+                    Action::SetWaveRam(wave) => {
+                        // shown straight away rather than flickering back until the emulator's answered
+                        wave_ram = Some(wave);
+                        emulator.send(Command::SetWaveRam(wave));
+                    },
+                    Action::CartridgeRamEditor(open) => {
+                        cartridge_ram = if open { Some(Vec::new()) } else { None };
+                        pending_cartridge_ram = None;
+                    },
+                    Action::CartridgeRamPage(page) => cartridge_ram_page = page,
+                    Action::ToggleCheat(index) => if let Some(cheat) = cheats.get_mut(index) {
+                        cheat.enabled = !cheat.enabled;
+                        emulator.send(Command::Cheats(enabled_codes(&cheats)));
+                    },
+                    Action::SetCartridgeRam(offset, value) => {
+                        if let Some(byte) = cartridge_ram.as_mut().and_then(|ram| ram.get_mut(offset)) {
+                            *byte = value;
+                        }
+                        emulator.send(Command::SetCartridgeRam(offset, value));
+                    },
+                    Action::ExportCartridgeRam | Action::ImportCartridgeRam => if let Some(rom) = &options.rom {
+                        let path = rom.with_extension("sram");
+                        let text = match action {
+                            Action::ExportCartridgeRam => request(&emulator, Request::ExportCartridgeRam(path.clone()))
+                                .map(|_| format!("Exported {}", path.display())),
+                            _ => request(&emulator, Request::ImportCartridgeRam(path.clone()))
+                                .map(|_| format!("Imported {}", path.display())),
+                        }.unwrap_or_else(|err| err);
+                        set_title(&window, options.game_name.as_deref(), speed, Some(&text));
+                        message = Some((text, Instant::now() + MESSAGE_DURATION));
+                    },
+                }
+            }
 
+            {
+                let mut status = menu.status_mut();
+                status.paused = paused;
+                status.fullscreen = fullscreen;
+                status.smooth = config.smooth;
+                status.frame_blend = frame_blend;
+                status.accessibility = config.accessibility;
+                status.backdrop = config.backdrop;
+                status.screen_size = [(image_width * filter::SCALE) as f32, (image_height * filter::SCALE) as f32];
+                status.gallery = state_gallery.is_some();
+                status.rom = options.rom.clone();
+                status.recent = config.recent.clone();
+                status.viewers = debug_windows.iter().map(DebugWindow::view).collect();
+                status.layer_overlay = layer_overlay;
+                status.palettes = palettes;
+                status.scanlines = scanlines.clone();
+                status.wave_ram = wave_ram;
+                status.cartridge_ram = cartridge_ram.clone();
+                status.cartridge_ram_page = cartridge_ram_page;
+                status.cheats = cheats.iter().map(|cheat| (cheat.name.clone(), cheat.enabled)).collect();
+                status.hotkeys = hotkeys.clone();
+            }
+            if menu.update(&input, physical_size(&window), window.hidpi_factor()) {
+                window.request_redraw();
+            }
+
+            // moving to a monitor with a different scale changes the window's physical size
+            // without necessarily resizing it, so the surface follows either
+            if input.window_resized().is_some() || input.hidpi_changed().is_some() {
+                let (width, height) = physical_size(&window);
                 pixels.resize(width, height);
             }
+        }
 
-            gameboy.run_to_vblank()
-                .unwrap_or_else(
-                    |err| {
-                        panic!("Gameboy Error: {}", err);
-                    }
-                );
-            window.request_redraw();
+        match emulator.latest_update() {
+            Some(Update::Frame(mut frame)) => {
+                if config.accessibility.reduce_flashing {
+                    flash_limiter.limit(&mut frame);
+                }
+                if config.accessibility.high_contrast {
+                    filter::high_contrast(&mut frame);
+                }
+                let shown = if frame_blend {
+                    filter::blend(&frame, &mut previous_frame);
+                    &previous_frame
+                } else {
+                    &frame
+                };
+                match &options.border {
+                    Some(border) => border.compose(shown, &mut image),
+                    None => image.copy_from_slice(shown),
+                }
+                previous_frame = frame;
+                filter::upscale(&image, image_width, image_height, config.smooth, pixels.get_frame());
+                window.request_redraw();
+            },
+            Some(Update::Message(text)) => {
+                set_title(&window, options.game_name.as_deref(), speed, Some(&text));
+                message = Some((text, Instant::now() + MESSAGE_DURATION));
+            },
+            Some(Update::Error(err)) => {
+                eprintln!("Gameboy Error: {}", err);
+                emulator.stop();
+                *control_flow = ControlFlow::Exit;
+            },
+            None => (),
+        }
+
+        for debug in &mut debug_windows {
+            debug.update(&emulator);
+        }
+
+        if palettes.is_some() {
+            if let Some(latest) = poll(&emulator, &mut pending_palettes, Command::Palettes) {
+                if palettes != Some(latest) {
+                    palettes = Some(latest);
+                    menu.status_mut().palettes = palettes;
+                    window.request_redraw();
+                }
+            }
+        }
+        if wave_ram.is_some() {
+            if let Some(latest) = poll(&emulator, &mut pending_wave_ram, Command::WaveRam) {
+                if wave_ram != Some(latest) {
+                    wave_ram = Some(latest);
+                    menu.status_mut().wave_ram = wave_ram;
+                    window.request_redraw();
+                }
+            }
+        }
+        if cartridge_ram.is_some() {
+            if let Some(latest) = poll(&emulator, &mut pending_cartridge_ram, Command::CartridgeRam) {
+                if cartridge_ram.as_ref() != Some(&latest) {
+                    menu.status_mut().cartridge_ram = Some(latest.clone());
+                    cartridge_ram = Some(latest);
+                    window.request_redraw();
+                }
+            }
+        }
+        if scanlines.is_some() {
+            if let Some(latest) = poll(&emulator, &mut pending_scanlines, Command::ScanlineRegisters) {
+                if scanlines.as_ref() != Some(&latest) {
+                    menu.status_mut().scanlines = Some(latest.clone());
+                    scanlines = Some(latest);
+                    window.request_redraw();
+                }
+            }
+        }
+
+        if let Some((_, expires)) = &message {
+            if Instant::now() >= *expires {
+                message = None;
+                set_title(&window, options.game_name.as_deref(), speed, None);
+            }
         }
     });
-}
\ No newline at end of file
+}
+
+// opens the viewer, or closes it if it's open
+fn toggle_viewer(debug_windows: &mut Vec<DebugWindow>, view: View, event_loop: &EventLoopWindowTarget<()>) {
+    match debug_windows.iter().position(|debug| debug.view() == view) {
+        Some(index) => {
+            debug_windows.remove(index);
+        },
+        None => match DebugWindow::open(view, event_loop) {
+            Ok(debug) => debug_windows.push(debug),
+            Err(err) => eprintln!("Problem opening the {:?} viewer: {}", view, err),
+        },
+    }
+}
+
+// adds the time since playing_since to the ROM's playtime, once. loads the config afresh in case
+// something else has saved it since, like a game the library started
+fn record_playtime(rom: Option<&Path>, playing_since: &mut Option<Instant>) {
+    if let (Some(rom), Some(since)) = (rom, playing_since.take()) {
+        let mut config = Config::load();
+        config.add_playtime(rom, since.elapsed());
+        if let Err(err) = config.save() {
+            eprintln!("Problem saving config: {}", err);
+        }
+    }
+}
+
+// the first of <rom>-1.png, <rom>-2.png and so on that isn't taken
+fn screenshot_path(rom: Option<&Path>) -> PathBuf {
+    let stem = rom.and_then(Path::file_stem).unwrap_or_else(|| "screenshot".as_ref());
+    let directory = rom.and_then(Path::parent).unwrap_or_else(|| Path::new(""));
+    (1..).map(|n| directory.join(format!("{}-{}.png", stem.to_string_lossy(), n)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+// whether the game wrote to its save within RECENT_SAVE
+fn saved_recently(emulator: &EmulatorThread) -> bool {
+    let (reply, last_save) = mpsc::channel();
+    emulator.send(Command::LastSave(reply));
+    match last_save.recv() {
+        Ok(Some(last_save)) => last_save.elapsed() < RECENT_SAVE,
+        // not saved, or the emulator's stopped
+        _ => false,
+    }
+}
+
+// like the debug windows, asks the emulator for something again once the last answer's arrived,
+// so the ui keeps up without queueing requests behind the game. the answer if there's a new one
+fn poll<T>(emulator: &EmulatorThread, pending: &mut Option<Receiver<T>>, ask: fn(Sender<T>) -> Command) -> Option<T> {
+    let received = match pending.as_ref().map(Receiver::try_recv) {
+        Some(Err(TryRecvError::Empty)) => return None,
+        Some(Ok(received)) => Some(received),
+        // not asked yet, or the emulator's stopped
+        _ => None,
+    };
+    let (reply, next) = mpsc::channel();
+    emulator.send(ask(reply));
+    *pending = Some(next);
+    received
+}
+
+// sends a remote control request from the ui and waits for the answer
+fn request(emulator: &EmulatorThread, request: Request) -> Response {
+    let (reply, response) = mpsc::channel();
+    emulator.send(Command::Remote(request, reply));
+    response.recv().unwrap_or_else(|_| Err("emulator stopped".to_string()))
+}
+
+// starts another instance with the same arguments, but playing rom instead of current.
+// winit can't start a second event loop in one process, so this is how the menu changes games
+fn relaunch(current: Option<&Path>, rom: &Path) -> io::Result<()> {
+    let mut args: Vec<OsString> = env::args_os().skip(1).collect();
+    if current != Some(rom) {
+        // these files are the old ROM's, the new one finds its own
+        for flag in &["--save", "--border", "--sym"] {
+            if let Some(index) = args.iter().position(|arg| arg == *flag) {
+                args.drain(index..(index + 2).min(args.len()));
+            }
+            let prefix = format!("{}=", flag);
+            args.retain(|arg| !arg.to_string_lossy().starts_with(&prefix));
+        }
+        // a .cht file is the old ROM's too, but a directory of them isn't
+        if let Some(index) = args.iter().position(|arg| arg == "--cheats") {
+            if args.get(index + 1).is_some_and(|path| Path::new(path).is_file()) {
+                args.drain(index..index + 2);
+            }
+        }
+        args.retain(|arg| !arg.to_string_lossy().strip_prefix("--cheats=").is_some_and(|path| Path::new(path).is_file()));
+    }
+    match args.iter_mut().find(|arg| current == Some(Path::new(arg.as_os_str()))) {
+        Some(arg) => *arg = rom.as_os_str().to_os_string(),
+        None => args.push(rom.as_os_str().to_os_string()),
+    }
+
+    process::Command::new(env::current_exe()?).args(args).spawn()?;
+    Ok(())
+}
+
+// the window's size in physical pixels, which is what the surface has to match. asks the window
+// for its scale factor rather than tracking it, so it can't go stale
+fn physical_size(window: &winit::window::Window) -> (u32, u32) {
+    let size = window.inner_size().to_physical(window.hidpi_factor());
+    // never zero, a minimized window still needs a surface
+    ((size.width.round() as u32).max(1), (size.height.round() as u32).max(1))
+}
+
+fn set_title(window: &winit::window::Window, game_name: Option<&str>, speed: u32, message: Option<&str>) {
+    let mut title = match game_name {
+        Some(name) => format!("GBOxide - {}", name),
+        None => "GBOxide".to_string(),
+    };
+    if speed != 100 {
+        title += &format!(" ({}%)", speed);
+    }
+    if let Some(message) = message {
+        title += &format!(" - {}", message);
+    }
+    window.set_title(&title);
+}
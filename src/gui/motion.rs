@@ -0,0 +1,26 @@
+// tilt read on the ui thread with the rest of the input, for the cartridge on the emulator thread
+
+use std::sync::{Arc, Mutex};
+
+use crate::motion::MotionSource;
+
+#[derive(Clone, Default)]
+pub struct SharedTilt {
+    tilt: Arc<Mutex<(f32, f32)>>,
+}
+
+impl SharedTilt {
+    pub fn new() -> SharedTilt {
+        SharedTilt::default()
+    }
+
+    pub fn set(&self, tilt: (f32, f32)) {
+        *self.tilt.lock().unwrap() = tilt;
+    }
+}
+
+impl MotionSource for SharedTilt {
+    fn tilt(&mut self) -> (f32, f32) {
+        *self.tilt.lock().unwrap()
+    }
+}
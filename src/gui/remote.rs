@@ -0,0 +1,255 @@
+// a line based command channel on a local TCP socket, so bots and test scripts can drive
+// a running instance. one command per line, each answered with a single `ok [...]` or `error ...` line:
+//
+//   pause
+//   resume
+//   save-state <file>                               (the whole machine, as JSON, and the screen as <file>.png)
+//   load-state <file>
+//   screenshot <file.png>
+//   buttons [a,b,start,select,up,down,left,right]   (holds these until the next buttons command)
+//   read-memory <hex address> <length>              (answered with hex bytes)
+//   break <[bank:]address>                          (pauses before running the instruction there)
+//   unbreak <[bank:]address>
+//   until <[bank:]address>|vblank|line <n>         (resumes, then pauses once it's there, just the once)
+//   break-interrupt vblank|stat|timer|serial|joypad (pauses once it's dispatched, saying what IF and IE were)
+//   unbreak-interrupt vblank|stat|timer|serial|joypad
+//   pc                                              (answered with bank:address)
+//   interrupt-log start|stop
+//   interrupt-log dump <file>                       (the last 1024 interrupt requests and dispatches)
+//   dma-log start|stop
+//   dma-log dump <file>                             (the last 1024 OAM DMAs, with anything suspect about them)
+//   profile start|stop                              (counts how often each address and opcode runs)
+//   profile dump <file>                             (the counts, hottest first)
+//   cdl start|stop                                  (marks which rom bytes run as code or are read as data)
+//   cdl dump <file>                                 (answered with how many bytes of each were seen)
+//   trace start <file> [[bank:]start-end|symbol]    (writes every instruction run there, or anywhere, to file)
+//   trace stop
+//   snapshot                                        (remembers what's in WRAM and HRAM)
+//   diff [increased|decreased]                      (answered with what's changed since, as address=old>new)
+//   fill wram|hram|vram <hex byte>                  (overwrites the whole region, to flush out reads of memory never written)
+//   sram export <file>                              (the cartridge's RAM, all banks, as raw bytes)
+//   sram import <file>                              (the same, read back in. it has to be the same size)
+
+use std::cmp::Ordering;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+use crate::gameboy::debugger::{Breakpoint, MemoryRegion, Until};
+use crate::gameboy::interrupt::Interrupt;
+use crate::gameboy::joypad::{Button, Controls};
+use crate::gui::emulator::Command;
+
+pub enum Request {
+    Pause,
+    Resume,
+    SaveState(PathBuf),
+    LoadState(PathBuf),
+    Screenshot(PathBuf),
+    SetButtons(Controls),
+    ReadMemory(u16, u16),
+    Break(Breakpoint),
+    Unbreak(Breakpoint),
+    Until(Until),
+    BreakInterrupt(Interrupt),
+    UnbreakInterrupt(Interrupt),
+    Pc,
+    InterruptLogging(bool),
+    DumpInterruptLog(PathBuf),
+    DmaLogging(bool),
+    DumpDmaLog(PathBuf),
+    Profiling(bool),
+    DumpProfile(PathBuf),
+    CodeDataLogging(bool),
+    DumpCodeDataLog(PathBuf),
+    // the file, and the address range or symbol to keep it to
+    TraceLog(PathBuf, Option<String>),
+    StopTraceLog,
+    Snapshot,
+    // only bytes that went up or down, or None for any change
+    Diff(Option<Ordering>),
+    Fill(MemoryRegion, u8),
+    ExportCartridgeRam(PathBuf),
+    ImportCartridgeRam(PathBuf),
+}
+
+// Ok holds anything to send back after the `ok`
+pub type Response = Result<String, String>;
+
+// accepts connections on addr (e.g. 127.0.0.1:7474) and forwards their requests to the emulator thread
+pub fn listen(addr: &str, commands: Sender<Command>) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    thread::Builder::new()
+        .name("remote control".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        eprintln!("Remote control connection failed: {}", err);
+                        continue;
+                    },
+                };
+                let commands = commands.clone();
+                let _ = thread::Builder::new()
+                    .name("remote control client".to_string())
+                    .spawn(move || {
+                        if let Err(err) = serve(stream, commands) {
+                            eprintln!("Remote control client error: {}", err);
+                        }
+                    });
+            }
+        })?;
+
+    Ok(())
+}
+
+fn serve(stream: TcpStream, commands: Sender<Command>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match parse_request(&line) {
+            Ok(request) => {
+                let (reply, response) = mpsc::channel();
+                if commands.send(Command::Remote(request, reply)).is_err() {
+                    // the emulator has shut down
+                    return Ok(());
+                }
+                response.recv().unwrap_or_else(|_| Err("emulator stopped".to_string()))
+            },
+            Err(err) => Err(err),
+        };
+
+        match response {
+            Ok(ref message) if message.is_empty() => writeln!(writer, "ok")?,
+            Ok(message) => writeln!(writer, "ok {}", message)?,
+            Err(err) => writeln!(writer, "error {}", err)?,
+        }
+    }
+
+    Ok(())
+}
+
+pub fn parse_request(line: &str) -> Result<Request, String> {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or("");
+    let args: Vec<&str> = words.collect();
+
+    let path_arg = |args: &[&str]| match args {
+        [path] => Ok(PathBuf::from(path)),
+        _ => Err(format!("{} takes a file name", command)),
+    };
+
+    match command {
+        "pause" => Ok(Request::Pause),
+        "resume" => Ok(Request::Resume),
+        "save-state" => Ok(Request::SaveState(path_arg(&args)?)),
+        "load-state" => Ok(Request::LoadState(path_arg(&args)?)),
+        "screenshot" => Ok(Request::Screenshot(path_arg(&args)?)),
+        "buttons" => {
+            let mut controls = Controls::default();
+            for button in args.iter().flat_map(|arg| arg.split(',')).filter(|b| !b.is_empty()) {
+                match Button::from_name(button) {
+                    Some(button) => controls.set(button, true),
+                    None => return Err(format!("unknown button {}", button)),
+                }
+            }
+            Ok(Request::SetButtons(controls))
+        },
+        "read-memory" => match args.as_slice() {
+            [addr, length] => {
+                let addr = u16::from_str_radix(addr.trim_start_matches("0x"), 16)
+                    .map_err(|e| format!("bad address {}: {}", addr, e))?;
+                let length = length.parse()
+                    .map_err(|e| format!("bad length {}: {}", length, e))?;
+                Ok(Request::ReadMemory(addr, length))
+            },
+            _ => Err("read-memory takes an address and a length".to_string()),
+        },
+        "break" | "unbreak" => match args.as_slice() {
+            [breakpoint] => {
+                let breakpoint = breakpoint.parse::<Breakpoint>().map_err(|e| e.to_string())?;
+                if command == "break" {
+                    Ok(Request::Break(breakpoint))
+                } else {
+                    Ok(Request::Unbreak(breakpoint))
+                }
+            },
+            _ => Err(format!("{} takes an address, optionally with a bank, e.g. 1f:4a00", command)),
+        },
+        "until" => args.join(" ").parse::<Until>().map(Request::Until)
+            .map_err(|e| format!("{}, until takes an address, vblank or line <n>", e)),
+        "break-interrupt" | "unbreak-interrupt" => match args.as_slice() {
+            [name] => {
+                let interrupt = Interrupt::from_name(name)
+                    .ok_or_else(|| format!("unknown interrupt {}", name))?;
+                if command == "break-interrupt" {
+                    Ok(Request::BreakInterrupt(interrupt))
+                } else {
+                    Ok(Request::UnbreakInterrupt(interrupt))
+                }
+            },
+            _ => Err(format!("{} takes vblank, stat, timer, serial or joypad", command)),
+        },
+        "pc" => Ok(Request::Pc),
+        "interrupt-log" => match args.as_slice() {
+            ["start"] => Ok(Request::InterruptLogging(true)),
+            ["stop"] => Ok(Request::InterruptLogging(false)),
+            ["dump", rest @ ..] => Ok(Request::DumpInterruptLog(path_arg(rest)?)),
+            _ => Err("interrupt-log takes start, stop or dump <file>".to_string()),
+        },
+        "dma-log" => match args.as_slice() {
+            ["start"] => Ok(Request::DmaLogging(true)),
+            ["stop"] => Ok(Request::DmaLogging(false)),
+            ["dump", rest @ ..] => Ok(Request::DumpDmaLog(path_arg(rest)?)),
+            _ => Err("dma-log takes start, stop or dump <file>".to_string()),
+        },
+        "profile" => match args.as_slice() {
+            ["start"] => Ok(Request::Profiling(true)),
+            ["stop"] => Ok(Request::Profiling(false)),
+            ["dump", rest @ ..] => Ok(Request::DumpProfile(path_arg(rest)?)),
+            _ => Err("profile takes start, stop or dump <file>".to_string()),
+        },
+        "cdl" => match args.as_slice() {
+            ["start"] => Ok(Request::CodeDataLogging(true)),
+            ["stop"] => Ok(Request::CodeDataLogging(false)),
+            ["dump", rest @ ..] => Ok(Request::DumpCodeDataLog(path_arg(rest)?)),
+            _ => Err("cdl takes start, stop or dump <file>".to_string()),
+        },
+        "trace" => match args.as_slice() {
+            ["start", path] => Ok(Request::TraceLog(PathBuf::from(path), None)),
+            ["start", path, filter] => Ok(Request::TraceLog(PathBuf::from(path), Some(filter.to_string()))),
+            ["stop"] => Ok(Request::StopTraceLog),
+            _ => Err("trace takes start <file> [range or symbol], or stop".to_string()),
+        },
+        "snapshot" => Ok(Request::Snapshot),
+        "diff" => match args.as_slice() {
+            [] => Ok(Request::Diff(None)),
+            ["increased"] => Ok(Request::Diff(Some(Ordering::Greater))),
+            ["decreased"] => Ok(Request::Diff(Some(Ordering::Less))),
+            _ => Err("diff takes nothing, increased or decreased".to_string()),
+        },
+        "fill" => match args.as_slice() {
+            [region, value] => {
+                let region = MemoryRegion::from_name(region)
+                    .ok_or_else(|| format!("unknown region {}", region))?;
+                let value = u8::from_str_radix(value.trim_start_matches("0x"), 16)
+                    .map_err(|e| format!("bad byte {}: {}", value, e))?;
+                Ok(Request::Fill(region, value))
+            },
+            _ => Err("fill takes wram, hram or vram and a hex byte".to_string()),
+        },
+        "sram" => match args.as_slice() {
+            ["export", rest @ ..] => Ok(Request::ExportCartridgeRam(path_arg(rest)?)),
+            ["import", rest @ ..] => Ok(Request::ImportCartridgeRam(path_arg(rest)?)),
+            _ => Err("sram takes export <file> or import <file>".to_string()),
+        },
+        _ => Err(format!("unknown command {}", command)),
+    }
+}
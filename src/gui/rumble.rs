@@ -0,0 +1,113 @@
+// something that shakes in the player's hands. the cartridge's rumble motor drives it for now,
+// but anything that wants to (SGB sound effects, say) can start and stop it
+
+use std::sync::{Arc, Mutex};
+
+use gilrs::Gilrs;
+use gilrs::ff::{BaseEffect, BaseEffectType, Effect, EffectBuilder, Repeat};
+
+pub trait Rumble: Send {
+    fn set(&mut self, on: bool);
+}
+
+// for when there's nothing to rumble, or it's turned off
+pub struct NoRumble;
+
+impl Rumble for NoRumble {
+    fn set(&mut self, _on: bool) {}
+}
+
+// a continuous effect on every connected gamepad that supports force feedback,
+// started and stopped as the motor turns on and off
+pub struct GamepadRumble {
+    effect: Effect,
+    on: bool,
+}
+
+impl GamepadRumble {
+    // intensity is a percentage of the strongest rumble the gamepads can do.
+    // None if no gamepad supports force feedback
+    pub fn new(gilrs: &mut Gilrs, intensity: u32) -> Option<GamepadRumble> {
+        let gamepads: Vec<_> = gilrs.gamepads()
+            .filter(|(_, gamepad)| gamepad.is_ff_supported())
+            .map(|(id, _)| id)
+            .collect();
+        if gamepads.is_empty() {
+            return None;
+        }
+
+        let magnitude = (u16::MAX as u32 * intensity.min(100) / 100) as u16;
+        let effect = EffectBuilder::new()
+            .add_effect(BaseEffect {
+                kind: BaseEffectType::Strong { magnitude },
+                ..Default::default()
+            })
+            .gamepads(&gamepads)
+            .repeat(Repeat::Infinitely)
+            .finish(gilrs)
+            .map_err(|err| eprintln!("Couldn't create rumble effect: {}", err))
+            .ok()?;
+
+        Some(GamepadRumble { effect, on: false })
+    }
+}
+
+impl Rumble for GamepadRumble {
+    fn set(&mut self, on: bool) {
+        if on == self.on {
+            return;
+        }
+        self.on = on;
+        let result = if on { self.effect.play() } else { self.effect.stop() };
+        if let Err(err) = result {
+            eprintln!("Rumble error: {}", err);
+        }
+    }
+}
+
+// gamepad rumble if there's a gamepad for it and intensity isn't 0, otherwise nothing
+pub fn new(gilrs: Option<&mut Gilrs>, intensity: u32) -> Box<dyn Rumble> {
+    match gilrs {
+        Some(gilrs) if intensity > 0 => match GamepadRumble::new(gilrs, intensity) {
+            Some(rumble) => Box::new(rumble),
+            None => Box::new(NoRumble),
+        },
+        _ => Box::new(NoRumble),
+    }
+}
+
+// a rumble that can be switched off from the ui thread while the cartridge drives it from the
+// emulator's. switching it off stops a motor that's already going, and back on picks it up again
+#[derive(Clone)]
+pub struct RumbleSwitch {
+    shared: Arc<Mutex<Switched>>,
+}
+
+struct Switched {
+    rumble: Box<dyn Rumble>,
+    // whether the cartridge wants it on, and whether it's allowed to be
+    on: bool,
+    enabled: bool,
+}
+
+impl RumbleSwitch {
+    pub fn new(rumble: Box<dyn Rumble>, enabled: bool) -> RumbleSwitch {
+        RumbleSwitch { shared: Arc::new(Mutex::new(Switched { rumble, on: false, enabled })) }
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.enabled = enabled;
+        let on = shared.on && enabled;
+        shared.rumble.set(on);
+    }
+}
+
+impl Rumble for RumbleSwitch {
+    fn set(&mut self, on: bool) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.on = on;
+        let on = on && shared.enabled;
+        shared.rumble.set(on);
+    }
+}
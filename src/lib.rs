@@ -1,3 +1,7 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg_attr(not(feature = "std"), macro_use)]
+extern crate alloc;
 #[macro_use]
 extern crate bitfield;
 #[macro_use]
@@ -5,7 +9,37 @@ extern crate bitflags;
 #[macro_use]
 extern crate num_derive;
 
+#[cfg(feature = "achievements")]
+pub mod achievements;
+#[cfg(feature = "std")]
+pub mod analyze;
+pub mod battery;
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod border;
+#[cfg(feature = "std")]
+pub mod bench;
+pub mod camera;
 pub mod cartridge;
+#[cfg(feature = "std")]
+pub mod cheats;
+pub mod clock;
+#[cfg(feature = "std")]
+pub mod diff;
+mod error;
 pub mod gameboy;
+#[cfg(feature = "gui")]
 pub mod gui;
+pub mod motion;
+#[cfg(feature = "std")]
+pub mod movie;
+#[cfg(feature = "std")]
+pub mod romdb;
+#[cfg(feature = "stream")]
+pub mod stream;
+#[cfg(feature = "testing")]
+pub mod testing;
 mod utils;
+
+pub use crate::error::Error;
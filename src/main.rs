@@ -3,10 +3,32 @@ extern crate gboxide;
 #[macro_use]
 extern crate clap;
 
+use std::fs::{self, File};
+use std::io::{BufReader, LineWriter, Write};
+use std::net::TcpStream;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::process;
 
+use gboxide::achievements::Achievements;
+use gboxide::analyze;
+use gboxide::batch;
+use gboxide::bench;
+use gboxide::border::Border;
+use gboxide::camera;
 use gboxide::cartridge::Cartridge;
+use gboxide::cheats::{self, Cheat};
+use gboxide::clock::{ClockSource, ScaledClock, SystemClock};
+use gboxide::diff;
+use gboxide::Error;
+use gboxide::gameboy::GameBoy;
+use gboxide::gameboy::boot::{BootMode, Model};
+use gboxide::gameboy::disasm::{self, Symbols};
+use gboxide::gameboy::script::InputScript;
+use gboxide::gameboy::serial::SerialSink;
 use gboxide::gui;
+use gboxide::movie::Movie;
+use gboxide::romdb::{self, RomDatabase, RomEntry};
 
 fn main() {
     let args = clap::App::new(crate_name!())
@@ -14,21 +36,800 @@ fn main() {
                         .author(crate_authors!())
                         .about(crate_description!())
                         .arg(clap::Arg::with_name("ROMFILE")
-                            .help("GameBoy ROM to load")
+                            .help("GameBoy ROM to load, or a directory of them to pick from")
                             .required(true)
                             .index(1))
+                        .arg(clap::Arg::with_name("camera-image")
+                            .long("camera-image")
+                            .value_name("PNGFILE")
+                            .help("Image for the Pocket Camera to take pictures of"))
+                        .arg(clap::Arg::with_name("webcam")
+                            .long("webcam")
+                            .value_name("DEVICE")
+                            .conflicts_with("camera-image")
+                            .help("Webcam device for the Pocket Camera (requires the webcam feature)"))
+                        .arg(clap::Arg::with_name("dump-frames")
+                            .long("dump-frames")
+                            .value_name("DIR")
+                            .help("Write each new frame and the tile data to PNGs in DIR"))
+                        .arg(clap::Arg::with_name("remote")
+                            .long("remote")
+                            .value_name("ADDR")
+                            .help("Accept remote control commands on a TCP address, e.g. 127.0.0.1:7474"))
+                        .arg(clap::Arg::with_name("serve")
+                            .long("serve")
+                            .value_name("PORT")
+                            .help("Serve screenshots, a live stream and controls over HTTP on localhost:PORT, or a full ADDR:PORT"))
+                        .arg(clap::Arg::with_name("turbo-interval")
+                            .long("turbo-interval")
+                            .value_name("FRAMES")
+                            .default_value("4")
+                            .help("Frames turbo A (S key) and turbo B (A key) stay pressed, then released, while held"))
+                        .arg(clap::Arg::with_name("speed")
+                            .long("speed")
+                            .value_name("PERCENT")
+                            .default_value("100")
+                            .help("Emulation speed as a percentage of real time, from 25 to 400"))
+                        .arg(clap::Arg::with_name("scale")
+                            .long("scale")
+                            .value_name("N")
+                            .default_value("3")
+                            .help("Starting window size, as a multiple of the screen's 160x144"))
+                        .arg(clap::Arg::with_name("rumble")
+                            .long("rumble")
+                            .value_name("PERCENT")
+                            .default_value("75")
+                            .help("How hard gamepads rumble for rumble cartridges, 0 turns it off"))
+                        .arg(clap::Arg::with_name("boot")
+                            .long("boot")
+                            .value_name("MODE")
+                            .possible_values(&["skip", "simulate", "strict"])
+                            .default_value("skip")
+                            .help("Show the boot logo first, strict locks up on a bad header like hardware does"))
+                        .arg(clap::Arg::with_name("model")
+                            .long("model")
+                            .value_name("MODEL")
+                            .possible_values(&["dmg", "mgb", "cgb"])
+                            .default_value("dmg")
+                            .help("The Game Boy the cartridge thinks it's in, going by the registers it starts with"))
+                        .arg(clap::Arg::with_name("start-at-entry-target")
+                            .long("start-at-entry-target")
+                            .help("Start at the address the header's entry point jumps to, instead of 0x100"))
+                        .arg(clap::Arg::with_name("save")
+                            .long("save")
+                            .value_name("SAVFILE")
+                            .help("Battery save to load and write back on exit, defaults to the ROM's name with .sav"))
+                        .arg(clap::Arg::with_name("ghosting")
+                            .long("ghosting")
+                            .value_name("PERCENT")
+                            .default_value("0")
+                            .help("How much of each frame lingers into the next, like the DMG's slow LCD, up to 90"))
+                        .arg(clap::Arg::with_name("rtc-speed")
+                            .long("rtc-speed")
+                            .value_name("MULTIPLIER")
+                            .default_value("1")
+                            .help("How fast a cartridge's real time clock runs, 60 for an hour a minute. The game itself runs at the usual speed"))
+                        .arg(clap::Arg::with_name("script")
+                            .long("script")
+                            .value_name("FILE")
+                            .help("Press buttons at set frames, one per line like \"frame 120: press start\""))
+                        .arg(clap::Arg::with_name("movie")
+                            .long("movie")
+                            .value_name("MOVIEFILE")
+                            .conflicts_with_all(&["script", "save"])
+                            .help("Play back a VisualBoyAdvance .vbm or BizHawk .bk2 input movie from power on"))
+                        .arg(clap::Arg::with_name("border")
+                            .long("border")
+                            .value_name("PNGFILE")
+                            .help("256x224 border to draw around the screen, defaults to the ROM's name with .border.png if there is one"))
+                        .arg(clap::Arg::with_name("sym")
+                            .long("sym")
+                            .value_name("SYMFILE")
+                            .help("RGBDS or no$gmb symbol file to name addresses in traces with, defaults to the ROM's name with .sym if there is one"))
+                        .arg(clap::Arg::with_name("cdl")
+                            .long("cdl")
+                            .value_name("CDLFILE")
+                            .help("Log which ROM bytes run as code and which are read as data, written to CDLFILE on exit"))
+                        .arg(clap::Arg::with_name("rom-db")
+                            .long("rom-db")
+                            .value_name("DATFILE")
+                            .help("No-Intro DAT file to look the ROM's name up in"))
+                        .arg(clap::Arg::with_name("cheats")
+                            .long("cheats")
+                            .value_name("PATH")
+                            .help("A libretro .cht file, or a directory to find the ROM's in by CRC32, or by name with --rom-db"))
+                        .arg(clap::Arg::with_name("achievements")
+                            .long("achievements")
+                            .value_name("JSONFILE")
+                            .help("Achievements to watch memory for, announced as they unlock"))
+                        .arg(clap::Arg::with_name("serial")
+                            .long("serial")
+                            .value_name("SINK")
+                            .default_value("stdout")
+                            .help("Where bytes sent over the serial port go: stdout, none, tcp:ADDR, or a file"))
+                        .arg(clap::Arg::with_name("serial-log")
+                            .long("serial-log")
+                            .value_name("FILE")
+                            .help("Log every byte sent or received over the serial port, with its cycle and clock, to FILE"))
+                        .arg(clap::Arg::with_name("crash-dump")
+                            .long("crash-dump")
+                            .value_name("DIRECTORY")
+                            .help("If the game crashes, write its last instructions, a save state, the screen and the header to a new directory in DIRECTORY"))
+                        .arg(clap::Arg::with_name("no-keyboard")
+                            .long("no-keyboard")
+                            .help("Ignore the keyboard for GameBoy controls"))
+                        .arg(clap::Arg::with_name("no-gamepad")
+                            .long("no-gamepad")
+                            .help("Ignore connected gamepads for GameBoy controls"))
+                        .subcommand(clap::SubCommand::with_name("bench")
+                            .about("Runs a ROM headless as fast as possible and reports the speed")
+                            .arg(clap::Arg::with_name("ROMFILE")
+                                .help("GameBoy ROM to benchmark")
+                                .required(true)
+                                .index(1))
+                            .arg(clap::Arg::with_name("frames")
+                                .long("frames")
+                                .value_name("N")
+                                .default_value("3600")
+                                .help("Number of frames to emulate"))
+                            .arg(clap::Arg::with_name("json")
+                                .long("json")
+                                .value_name("FILE")
+                                .help("Also write the results to FILE as JSON")))
+                        .subcommand(clap::SubCommand::with_name("info")
+                            .about("Prints what a ROM's cartridge header says about it")
+                            .arg(clap::Arg::with_name("ROMFILE")
+                                .help("GameBoy ROM to inspect")
+                                .required(true)
+                                .index(1))
+                            .arg(clap::Arg::with_name("json")
+                                .long("json")
+                                .conflicts_with("rom-db")
+                                .help("Print the whole parsed header as JSON instead, for other tools to read"))
+                            .arg(clap::Arg::with_name("rom-db")
+                                .long("rom-db")
+                                .value_name("DATFILE")
+                                .help("No-Intro DAT file to look the ROM up in")))
+                        .subcommand(clap::SubCommand::with_name("diff")
+                            .about("Runs a ROM against a reference trace log and reports where they first diverge")
+                            .arg(clap::Arg::with_name("ROMFILE")
+                                .help("GameBoy ROM to run")
+                                .required(true)
+                                .index(1))
+                            .arg(clap::Arg::with_name("LOGFILE")
+                                .help("Reference log, one gameboy-doctor style line per instruction, optionally with [C000]:12 memory fields")
+                                .required(true)
+                                .index(2)))
+                        .subcommand(clap::SubCommand::with_name("disasm")
+                            .about("Prints a ROM bank's code as SM83 assembly")
+                            .arg(clap::Arg::with_name("ROMFILE")
+                                .help("GameBoy ROM to disassemble")
+                                .required(true)
+                                .index(1))
+                            .arg(clap::Arg::with_name("bank")
+                                .long("bank")
+                                .value_name("N")
+                                .default_value("0")
+                                .help("ROM bank to disassemble, bank 0 is at 0000-3fff and the rest at 4000-7fff"))
+                            .arg(clap::Arg::with_name("range")
+                                .long("range")
+                                .value_name("START..END")
+                                .help("Addresses to disassemble in hex, e.g. 0150..0200, defaults to the whole bank"))
+                            .arg(clap::Arg::with_name("sym")
+                                .long("sym")
+                                .value_name("SYMFILE")
+                                .help("RGBDS or no$gmb symbol file to name addresses with, defaults to the ROM's name with .sym if there is one")))
+                        .subcommand(clap::SubCommand::with_name("analyze")
+                            .about("Maps out a ROM: bank usage, free space, RST and interrupt vectors, and text")
+                            .arg(clap::Arg::with_name("ROMFILE")
+                                .help("GameBoy ROM to analyze")
+                                .required(true)
+                                .index(1))
+                            .arg(clap::Arg::with_name("min-string")
+                                .long("min-string")
+                                .value_name("LENGTH")
+                                .default_value("6")
+                                .help("Shortest run of printable ASCII to list as a string")))
+                        .subcommand(clap::SubCommand::with_name("batch")
+                            .about("Runs every ROM in a directory headless and reports the ones that crash, lock up or can't be loaded")
+                            .arg(clap::Arg::with_name("DIRECTORY")
+                                .help("Directory of .gb and .gbc ROMs")
+                                .required(true)
+                                .index(1))
+                            .arg(clap::Arg::with_name("frames")
+                                .long("frames")
+                                .value_name("N")
+                                .default_value("1800")
+                                .help("Number of frames to run each ROM for"))
+                            .arg(clap::Arg::with_name("threads")
+                                .long("threads")
+                                .value_name("N")
+                                .help("How many ROMs to run at once, defaults to one per CPU"))
+                            .arg(clap::Arg::with_name("markdown")
+                                .long("markdown")
+                                .value_name("FILE")
+                                .help("Also write a compatibility table to FILE as Markdown"))
+                            .arg(clap::Arg::with_name("json")
+                                .long("json")
+                                .value_name("FILE")
+                                .help("Also write the compatibility results to FILE as JSON")))
                         .setting(clap::AppSettings::ArgRequiredElseHelp)
+                        .setting(clap::AppSettings::SubcommandsNegateReqs)
                         .get_matches();
+
+    if let Some(bench_args) = args.subcommand_matches("bench") {
+        run_bench(bench_args);
+        return;
+    }
+
+    if let Some(info_args) = args.subcommand_matches("info") {
+        run_info(info_args);
+        return;
+    }
+
+    if let Some(diff_args) = args.subcommand_matches("diff") {
+        run_diff(diff_args);
+        return;
+    }
+
+    if let Some(disasm_args) = args.subcommand_matches("disasm") {
+        run_disasm(disasm_args);
+        return;
+    }
+
+    if let Some(analyze_args) = args.subcommand_matches("analyze") {
+        run_analyze(analyze_args);
+        return;
+    }
+
+    if let Some(batch_args) = args.subcommand_matches("batch") {
+        run_batch(batch_args);
+        return;
+    }
+
     let filename = args.value_of("ROMFILE").unwrap();
 
-    let cartridge = Cartridge::new(filename).unwrap_or_else(|err| {
+    if Path::new(filename).is_dir() {
+        if let Err(e) = gui::library::run(PathBuf::from(filename)) {
+            eprintln!("Library error: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    let mut cartridge = Cartridge::new(filename).unwrap_or_else(|err| {
         eprintln!("Problem loading cartridge \"{}\": {}", filename, err);
         process::exit(1);
     });
+    println!("{:#?}", cartridge.header);
+    println!("read_rom_size: {}", cartridge.rom_len());
+
+    if let Some(image) = args.value_of("camera-image") {
+        let sensor = camera::StaticImage::new(image).unwrap_or_else(|err| {
+            eprintln!("Problem loading camera image \"{}\": {}", image, err);
+            process::exit(1);
+        });
+        cartridge.set_camera_sensor(Box::new(sensor));
+    }
+
+    if let Some(device) = args.value_of("webcam") {
+        set_webcam(&mut cartridge, device);
+    }
+
+    let movie = args.value_of("movie").map(|path| {
+        let movie = Movie::load(Path::new(path)).unwrap_or_else(|err| {
+            eprintln!("Problem loading movie \"{}\": {}", path, err);
+            process::exit(1);
+        });
+        for warning in &movie.warnings {
+            eprintln!("Movie \"{}\" may desync, {}", path, warning);
+        }
+        movie
+    });
+
+    let battery_save = match args.value_of("save") {
+        Some(path) => PathBuf::from(path),
+        None => Path::new(filename).with_extension("sav"),
+    };
+    // movies start from their own SRAM or none at all, and mustn't touch the real save
+    if let Some(sram) = movie.as_ref().and_then(|movie| movie.sram.as_ref()) {
+        cartridge.load_battery_save(sram).unwrap_or_else(|err| {
+            eprintln!("Problem loading the movie's SRAM: {}", err);
+            process::exit(1);
+        });
+    } else if cartridge.has_battery() && battery_save.exists() && movie.is_none() {
+        let bytes = fs::read(&battery_save).unwrap_or_else(|err| {
+            eprintln!("Problem reading battery save \"{}\": {}", battery_save.display(), err);
+            process::exit(1);
+        });
+        cartridge.load_battery_save(&bytes).unwrap_or_else(|err| {
+            eprintln!("Problem loading battery save \"{}\": {}", battery_save.display(), err);
+            process::exit(1);
+        });
+    }
+
+    let game_name = match args.value_of("rom-db") {
+        Some(dat) => lookup_rom(filename, dat).map(|entry| entry.name),
+        None => None,
+    };
+
+    let cheats = load_cheats(&args, filename, game_name.as_deref());
+
+    let mut gameboy = GameBoy::new(cartridge);
+    gameboy.set_cheats(cheats::enabled_codes(&cheats));
+    gameboy.set_serial_sink(serial_sink(args.value_of("serial").unwrap()));
+    if let Some(log) = args.value_of("serial-log") {
+        log_serial(&mut gameboy, log);
+    }
+
+    gameboy.set_boot_mode(match args.value_of("boot").unwrap() {
+        "simulate" => BootMode::Simulate,
+        "strict" => BootMode::Strict,
+        _ => BootMode::Skip,
+    });
+
+    gameboy.set_model(match args.value_of("model").unwrap() {
+        "mgb" => Model::MGB,
+        "cgb" => Model::CGB,
+        _ => Model::DMG,
+    });
+
+    if args.is_present("start-at-entry-target") && !gameboy.start_at_entry_target() {
+        eprintln!("The entry point isn't a jump, starting at 0x100");
+    }
+
+    let ghosting = args.value_of("ghosting").unwrap().parse().unwrap_or_else(|err| {
+        eprintln!("Invalid ghosting: {}", err);
+        process::exit(1);
+    });
+    gameboy.set_ghosting(ghosting);
 
-    if let Err(e) = gui::run(cartridge) {
+    let rtc_speed: f64 = args.value_of("rtc-speed").unwrap().parse().unwrap_or_else(|err| {
+        eprintln!("Invalid rtc speed: {}", err);
+        process::exit(1);
+    });
+    if !rtc_speed.is_finite() || rtc_speed <= 0.0 {
+        eprintln!("Invalid rtc speed: it has to be a number more than 0");
+        process::exit(1);
+    }
+    if rtc_speed != 1.0 {
+        if !gameboy.header().has_rtc() {
+            eprintln!("The cartridge has no real time clock, --rtc-speed does nothing");
+        }
+        // counting from the real time now, so the clock carries on from where the game left it
+        let now = SystemClock.now();
+        gameboy.set_clock_source(ScaledClock::new(SystemClock, now, rtc_speed));
+    }
+
+    if let Some(path) = args.value_of("script") {
+        let script = fs::read_to_string(path).map_err(Error::from)
+            .and_then(|text| InputScript::parse(&text))
+            .unwrap_or_else(|err| {
+                eprintln!("Problem loading script \"{}\": {}", path, err);
+                process::exit(1);
+            });
+        script.queue(&mut gameboy);
+    }
+
+    if let Some(movie) = &movie {
+        movie.queue(&mut gameboy);
+    }
+
+    if args.is_present("cdl") {
+        gameboy.set_code_data_logging(true);
+    }
+
+    if let Some(directory) = args.value_of("dump-frames") {
+        gameboy.set_frame_dumping(Some(Path::new(directory))).unwrap_or_else(|err| {
+            eprintln!("Problem starting frame dumping: {}", err);
+            process::exit(1);
+        });
+    }
+
+    let turbo_interval = args.value_of("turbo-interval").unwrap().parse().unwrap_or_else(|err| {
+        eprintln!("Invalid turbo interval: {}", err);
+        process::exit(1);
+    });
+
+    let speed = args.value_of("speed").unwrap().parse().unwrap_or_else(|err| {
+        eprintln!("Invalid speed: {}", err);
+        process::exit(1);
+    });
+
+    let scale: u32 = args.value_of("scale").unwrap().parse().unwrap_or_else(|err| {
+        eprintln!("Invalid scale: {}", err);
+        process::exit(1);
+    });
+    if scale == 0 {
+        eprintln!("Invalid scale: it has to be at least 1");
+        process::exit(1);
+    }
+
+    let rumble = args.value_of("rumble").unwrap().parse().unwrap_or_else(|err| {
+        eprintln!("Invalid rumble: {}", err);
+        process::exit(1);
+    });
+
+    let achievements = args.value_of("achievements").map(|path| {
+        Achievements::load(Path::new(path)).unwrap_or_else(|err| {
+            eprintln!("Problem loading achievements \"{}\": {}", path, err);
+            process::exit(1);
+        })
+    });
+
+    let border_path = match args.value_of("border") {
+        Some(path) => PathBuf::from(path),
+        None => Path::new(filename).with_extension("border.png"),
+    };
+    // a missing default border is fine, most games don't have one
+    let border = if args.is_present("border") || border_path.exists() {
+        Some(Border::load(&border_path).unwrap_or_else(|err| {
+            eprintln!("Problem loading border \"{}\": {}", border_path.display(), err);
+            process::exit(1);
+        }))
+    } else {
+        None
+    };
+
+    let options = gui::Options {
+        remote: args.value_of("remote").map(String::from),
+        // a bare port only listens locally, anything further afield has to be asked for
+        serve: args.value_of("serve").map(|serve| match serve.parse::<u16>() {
+            Ok(port) => format!("127.0.0.1:{}", port),
+            Err(_) => serve.to_string(),
+        }),
+        turbo_interval,
+        input_sources: gui::input::Sources {
+            keyboard: !args.is_present("no-keyboard"),
+            gamepads: !args.is_present("no-gamepad"),
+        },
+        speed,
+        scale,
+        rumble,
+        battery_save: if movie.is_none() { Some(battery_save) } else { None },
+        code_data_log: args.value_of("cdl").map(PathBuf::from),
+        game_name,
+        achievements,
+        border,
+        rom: Some(PathBuf::from(filename)),
+        symbols: load_symbols(&args, filename),
+        crash_dump: args.value_of("crash-dump").map(PathBuf::from),
+        cheats,
+    };
+
+    if let Err(e) = gui::run(gameboy, options) {
         eprintln!("Game error: {}", e);
 
         process::exit(1);
     }
 }
+
+fn run_bench(args: &clap::ArgMatches) {
+    let filename = args.value_of("ROMFILE").unwrap();
+    let frames = value_t!(args, "frames", u64).unwrap_or_else(|e| e.exit());
+
+    let cartridge = Cartridge::new(filename).unwrap_or_else(|err| {
+        eprintln!("Problem loading cartridge \"{}\": {}", filename, err);
+        process::exit(1);
+    });
+
+    let report = bench::run(cartridge, frames).unwrap_or_else(|err| {
+        eprintln!("Game error: {}", err);
+        process::exit(1);
+    });
+
+    println!("{} frames in {:.3}s", report.frames, report.wall_time.as_secs_f64());
+    println!("{:.3} emulated seconds per wallclock second", report.speed());
+    println!("{:.0} instructions per second", report.instructions_per_second());
+
+    if let Some(json) = args.value_of("json") {
+        report.write_json(Path::new(json)).unwrap_or_else(|err| {
+            eprintln!("Problem writing benchmark report: {}", err);
+            process::exit(1);
+        });
+    }
+}
+
+fn run_info(args: &clap::ArgMatches) {
+    let filename = args.value_of("ROMFILE").unwrap();
+
+    let cartridge = Cartridge::new(filename).unwrap_or_else(|err| {
+        eprintln!("Problem loading cartridge \"{}\": {}", filename, err);
+        process::exit(1);
+    });
+    let header = &cartridge.header;
+
+    if args.is_present("json") {
+        match serde_json::to_string_pretty(header) {
+            Ok(json) => println!("{}", json),
+            Err(err) => {
+                eprintln!("Problem writing the header as JSON: {}", err);
+                process::exit(1);
+            },
+        }
+        return;
+    }
+
+    println!("title: {}", header.title);
+    println!("cartridge type: {}", header.cartridge_type);
+    println!("rom size: {} bytes ({} read)", header.rom_size, cartridge.rom_len());
+    println!("ram size: {} bytes", header.ram_size);
+    println!("licensee: {}", header.licensee_code);
+    println!("cgb: {}, sgb: {}", header.cgb_flag, header.sgb_flag);
+    let entry: Vec<String> = header.raw_entry_point.iter().map(|byte| format!("{:02x}", byte)).collect();
+    match header.entry_target() {
+        Some(target) => println!("entry point: {} (jumps to {:04x})", entry.join(" "), target),
+        None => println!("entry point: {} (not a jump)", entry.join(" ")),
+    }
+    println!("logo: {}", if header.logo_valid() { "ok" } else { "bad" });
+    println!("header checksum: {:02x} ({})", header.header_checksum,
+             if header.header_checksum_valid() { "ok" } else { "bad" });
+
+    if let Some(dat) = args.value_of("rom-db") {
+        match lookup_rom(filename, dat) {
+            Some(entry) => {
+                println!("database name: {}", entry.name);
+                println!("region: {}", entry.region.as_deref().unwrap_or("unknown"));
+                if entry.bad_dump {
+                    println!("known bad dump!");
+                }
+            },
+            None => println!("database name: not found, may be a bad dump or a hack"),
+        }
+    }
+}
+
+// where --serial sends the bytes the game shifts out: stdout, nowhere, a TCP connection or a file
+fn serial_sink(sink: &str) -> SerialSink {
+    if sink == "stdout" {
+        SerialSink::Stdout
+    } else if sink == "none" {
+        SerialSink::Discard
+    } else if let Some(addr) = sink.strip_prefix("tcp:") {
+        let stream = TcpStream::connect(addr).unwrap_or_else(|err| {
+            eprintln!("Problem connecting serial output to {}: {}", addr, err);
+            process::exit(1);
+        });
+        SerialSink::Writer(Box::new(stream))
+    } else {
+        let file = File::create(sink).unwrap_or_else(|err| {
+            eprintln!("Problem creating serial output file \"{}\": {}", sink, err);
+            process::exit(1);
+        });
+        SerialSink::Writer(Box::new(file))
+    }
+}
+
+// a line per byte over the serial port. a failed write stops the log, not the game
+fn log_serial(gameboy: &mut GameBoy, log: &str) {
+    let file = File::create(log).unwrap_or_else(|err| {
+        eprintln!("Problem creating serial log \"{}\": {}", log, err);
+        process::exit(1);
+    });
+    let mut file = Some(LineWriter::new(file));
+    let log = log.to_string();
+    gameboy.set_serial_logger(move |exchange| {
+        if let Some(writer) = &mut file {
+            if let Err(err) = writeln!(writer, "{}", exchange) {
+                eprintln!("Problem writing serial log \"{}\", stopping it: {}", log, err);
+                file = None;
+            }
+        }
+    });
+}
+
+// from --cheats, a .cht file or a directory to find the ROM's in
+fn load_cheats(args: &clap::ArgMatches, filename: &str, game_name: Option<&str>) -> Vec<Cheat> {
+    let path = match args.value_of("cheats") {
+        Some(path) => Path::new(path),
+        None => return Vec::new(),
+    };
+    let path = if path.is_dir() {
+        let rom = fs::read(filename).unwrap_or_else(|err| {
+            eprintln!("Problem reading ROM \"{}\": {}", filename, err);
+            process::exit(1);
+        });
+        match cheats::find(path, &rom, game_name) {
+            Some(found) => found,
+            None => {
+                eprintln!("No cheats for \"{}\" in \"{}\"", filename, path.display());
+                return Vec::new();
+            },
+        }
+    } else {
+        path.to_path_buf()
+    };
+
+    cheats::load(&path).unwrap_or_else(|err| {
+        eprintln!("Problem loading cheats \"{}\": {}", path.display(), err);
+        process::exit(1);
+    })
+}
+
+// the ROM's entry in the DAT, warning about bad dumps and any problems reading either file
+fn lookup_rom(filename: &str, dat: &str) -> Option<RomEntry> {
+    let database = RomDatabase::load(Path::new(dat)).map_err(|err| {
+        eprintln!("Problem loading ROM database \"{}\": {}", dat, err);
+    }).ok()?;
+    let rom = fs::read(filename).map_err(|err| {
+        eprintln!("Problem reading ROM \"{}\": {}", filename, err);
+    }).ok()?;
+
+    match database.lookup(&rom) {
+        Some(entry) => {
+            if entry.bad_dump {
+                eprintln!("\"{}\" is a known bad dump of {}", filename, entry.name);
+            }
+            Some(entry.clone())
+        },
+        None => {
+            eprintln!("\"{}\" (crc32 {:08x}) isn't in the ROM database", filename, romdb::crc32(&rom));
+            None
+        },
+    }
+}
+
+fn run_diff(args: &clap::ArgMatches) {
+    let filename = args.value_of("ROMFILE").unwrap();
+    let log_filename = args.value_of("LOGFILE").unwrap();
+
+    let cartridge = Cartridge::new(filename).unwrap_or_else(|err| {
+        eprintln!("Problem loading cartridge \"{}\": {}", filename, err);
+        process::exit(1);
+    });
+    let log = File::open(log_filename).unwrap_or_else(|err| {
+        eprintln!("Problem opening log \"{}\": {}", log_filename, err);
+        process::exit(1);
+    });
+
+    let mut gameboy = GameBoy::new(cartridge);
+    match diff::against_log(&mut gameboy, BufReader::new(log)) {
+        Ok(None) => println!("no divergence from {}", log_filename),
+        Ok(Some(divergence)) => {
+            print!("{}", divergence);
+            process::exit(2);
+        },
+        Err(err) => {
+            eprintln!("Problem comparing against log: {}", err);
+            process::exit(1);
+        },
+    }
+}
+
+#[cfg(feature = "webcam")]
+fn set_webcam(cartridge: &mut Cartridge, device: &str) {
+    let sensor = camera::Webcam::new(device).unwrap_or_else(|err| {
+        eprintln!("Problem opening webcam \"{}\": {}", device, err);
+        process::exit(1);
+    });
+    cartridge.set_camera_sensor(Box::new(sensor));
+}
+
+#[cfg(not(feature = "webcam"))]
+fn set_webcam(_: &mut Cartridge, _: &str) {
+    eprintln!("Webcam support requires building with the \"webcam\" feature");
+    process::exit(1);
+}
+
+fn run_disasm(args: &clap::ArgMatches) {
+    let filename = args.value_of("ROMFILE").unwrap();
+    let bank = value_t!(args, "bank", u16).unwrap_or_else(|e| e.exit());
+
+    let rom = fs::read(filename).unwrap_or_else(|err| {
+        eprintln!("Problem reading ROM \"{}\": {}", filename, err);
+        process::exit(1);
+    });
+
+    let addrs = match args.value_of("range") {
+        Some(range) => parse_range(range).unwrap_or_else(|| {
+            eprintln!("Invalid range \"{}\", expected hex addresses like 0150..0200", range);
+            process::exit(1);
+        }),
+        None if bank == 0 => 0x0000..0x4000,
+        None => 0x4000..0x8000,
+    };
+
+    let symbols = load_symbols(args, filename);
+
+    match disasm::listing(&rom, bank, addrs, &symbols) {
+        Ok(listing) => print!("{}", listing),
+        Err(err) => {
+            eprintln!("Problem disassembling: {}", err);
+            process::exit(1);
+        },
+    }
+}
+
+// the ROM's symbol file, from --sym or next to it
+fn load_symbols(args: &clap::ArgMatches, filename: &str) -> Symbols {
+    let sym_path = match args.value_of("sym") {
+        Some(path) => PathBuf::from(path),
+        None => Path::new(filename).with_extension("sym"),
+    };
+    // like borders, a missing default symbol file just means there are no names
+    if args.is_present("sym") || sym_path.exists() {
+        fs::read_to_string(&sym_path).map_err(Error::from)
+            .and_then(|text| Symbols::parse(&text))
+            .unwrap_or_else(|err| {
+                eprintln!("Problem loading symbols \"{}\": {}", sym_path.display(), err);
+                process::exit(1);
+            })
+    } else {
+        Symbols::new()
+    }
+}
+
+fn run_analyze(args: &clap::ArgMatches) {
+    let filename = args.value_of("ROMFILE").unwrap();
+    let min_string = value_t!(args, "min-string", usize).unwrap_or_else(|e| e.exit());
+
+    let rom = fs::read(filename).unwrap_or_else(|err| {
+        eprintln!("Problem reading ROM \"{}\": {}", filename, err);
+        process::exit(1);
+    });
+
+    print!("{}", analyze::analyze(&rom, min_string.max(1)));
+}
+
+fn run_batch(args: &clap::ArgMatches) {
+    let directory = Path::new(args.value_of("DIRECTORY").unwrap());
+    let frames = value_t!(args, "frames", u64).unwrap_or_else(|e| e.exit());
+    let threads = match args.value_of("threads") {
+        Some(_) => value_t!(args, "threads", usize).unwrap_or_else(|e| e.exit()),
+        None => std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+    };
+
+    let roms = batch::roms_in(directory).unwrap_or_else(|err| {
+        eprintln!("Problem reading \"{}\": {}", directory.display(), err);
+        process::exit(1);
+    });
+    if roms.is_empty() {
+        eprintln!("No .gb or .gbc ROMs in \"{}\"", directory.display());
+        process::exit(1);
+    }
+
+    let results = batch::run(&roms, frames, threads);
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for result in &results {
+        let name = result.rom.file_name().unwrap_or_default().to_string_lossy();
+        match &result.title {
+            Some(title) if !title.is_empty() => print!("{} ({})", name, title),
+            _ => print!("{}", name),
+        }
+        if result.outcome.is_ok() {
+            println!(": ok, {}", result.rating(frames).name());
+        } else {
+            println!(": {} after {} frames, {}", result.outcome, result.frames, result.rating(frames).name());
+        }
+        match counts.iter_mut().find(|(kind, _)| *kind == result.outcome.kind()) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((result.outcome.kind(), 1)),
+        }
+    }
+
+    let summary: Vec<String> = counts.iter().map(|(kind, count)| format!("{} {}", count, kind)).collect();
+    println!("{} ROMs: {}", results.len(), summary.join(", "));
+    println!("{}", batch::summary(&results, frames));
+
+    let reports = [
+        (args.value_of("markdown"), batch::markdown_report as fn(&[batch::RomResult], u64) -> String),
+        (args.value_of("json"), batch::json_report),
+    ];
+    for (path, report) in reports.iter() {
+        if let Some(path) = path {
+            fs::write(path, report(&results, frames)).unwrap_or_else(|err| {
+                eprintln!("Problem writing compatibility report \"{}\": {}", path, err);
+                process::exit(1);
+            });
+        }
+    }
+}
+
+// "start..end" in hex, end not included
+fn parse_range(range: &str) -> Option<Range<u16>> {
+    let parse = |hex: &str| u16::from_str_radix(hex.trim().trim_start_matches("0x").trim_start_matches('$'), 16).ok();
+    let mut parts = range.splitn(2, "..");
+    let start = parse(parts.next()?)?;
+    let end = parse(parts.next()?)?;
+    if start < end { Some(start..end) } else { None }
+}
@@ -0,0 +1,26 @@
+// tilt for cartridges with an accelerometer, like the MBC7 in Kirby Tilt 'n' Tumble.
+// where it comes from is up to the frontend: keys, an analog stick, or a phone's own sensors
+
+pub trait MotionSource: Send {
+    // how far the GameBoy's tipped in g, x to the right and y towards the player,
+    // about -1.0 to 1.0 for anything a player could manage
+    fn tilt(&mut self) -> (f32, f32);
+}
+
+// nothing plugged in, the GameBoy lies flat
+pub struct Level;
+impl MotionSource for Level {
+    fn tilt(&mut self) -> (f32, f32) {
+        (0.0, 0.0)
+    }
+}
+
+// the MBC7's accelerometer reads about 0x81D0 lying flat, moving about 0x70 per g
+pub const MBC7_LEVEL: u16 = 0x81D0;
+pub const MBC7_PER_G: f32 = 112.0;
+
+// a tilt as the MBC7's x and y registers would read it
+pub fn mbc7_reading((x, y): (f32, f32)) -> (u16, u16) {
+    let reading = |g: f32| (MBC7_LEVEL as f32 + g.clamp(-2.0, 2.0) * MBC7_PER_G) as u16;
+    (reading(x), reading(y))
+}
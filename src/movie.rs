@@ -0,0 +1,243 @@
+// input movies from other emulators, so runs made there (TASes, mostly) can be played back here.
+// VisualBoyAdvance's .vbm and BizHawk's .bk2 are read, both boiled down to the buttons held on
+// each frame from power on and queued on the GameBoy like a script's presses. movies that start
+// from a save state can't be played, that state's in the other emulator's own format
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::gameboy::GameBoy;
+use crate::gameboy::joypad::Button;
+
+const VBM_SIGNATURE: &[u8] = b"VBM\x1A";
+const VBM_HEADER_LENGTH: usize = 0x40;
+
+// start flags
+const VBM_FROM_SNAPSHOT: u8 = 1 << 0;
+const VBM_FROM_SRAM: u8 = 1 << 1;
+// system flags
+const VBM_GBA: u8 = 1 << 0;
+const VBM_GBC: u8 = 1 << 1;
+const VBM_SGB: u8 = 1 << 2;
+
+// the order of a VBA controller's bits
+const VBM_BUTTONS: [Button; 8] = [
+    Button::A, Button::B, Button::Select, Button::Start,
+    Button::Right, Button::Left, Button::Up, Button::Down,
+];
+
+const ZIP_LOCAL_HEADER: u32 = 0x0403_4B50;
+const ZIP_CENTRAL_HEADER: u32 = 0x0201_4B50;
+const ZIP_END_OF_DIRECTORY: u32 = 0x0605_4B50;
+
+#[derive(Debug, Default)]
+pub struct Movie {
+    // the buttons held on each frame, as a bit per button in the order of Button
+    frames: Vec<u8>,
+    // battery backed RAM the movie starts with. without it, it starts with none
+    pub sram: Option<Vec<u8>>,
+    pub rerecords: Option<u32>,
+    // things about the movie that'll probably make it desync here
+    pub warnings: Vec<String>,
+}
+
+impl Movie {
+    pub fn load(path: &Path) -> Result<Movie, Box<dyn Error>> {
+        let bytes = fs::read(path)?;
+        if bytes.starts_with(VBM_SIGNATURE) {
+            Movie::parse_vbm(&bytes)
+        } else if bytes.starts_with(&ZIP_LOCAL_HEADER.to_le_bytes()) {
+            Movie::parse_bk2(&bytes)
+        } else {
+            Err("not a .vbm or .bk2 movie".into())
+        }
+    }
+
+    pub fn parse_vbm(bytes: &[u8]) -> Result<Movie, Box<dyn Error>> {
+        if bytes.len() < VBM_HEADER_LENGTH || !bytes.starts_with(VBM_SIGNATURE) {
+            return Err("not a .vbm movie".into());
+        }
+        let frame_count = read_u32(bytes, 0x0C) as usize;
+        let rerecords = read_u32(bytes, 0x10);
+        let start_flags = bytes[0x14];
+        let controllers = bytes[0x15] & 0x0F;
+        let system_flags = bytes[0x16];
+        let start_offset = read_u32(bytes, 0x38) as usize;
+        let input_offset = read_u32(bytes, 0x3C) as usize;
+
+        if start_flags & VBM_FROM_SNAPSHOT != 0 {
+            return Err("the movie starts from a VisualBoyAdvance save state, only ones from power on can be played".into());
+        }
+        if system_flags & VBM_GBA != 0 {
+            return Err("it's a Game Boy Advance movie".into());
+        }
+        if controllers == 0 {
+            return Err("the movie doesn't have any controllers".into());
+        }
+
+        let mut warnings = Vec::new();
+        if system_flags & VBM_GBC != 0 {
+            warnings.push("it was recorded in Game Boy Color mode".to_string());
+        }
+        if system_flags & VBM_SGB != 0 {
+            warnings.push("it was recorded in Super Game Boy mode".to_string());
+        }
+
+        let sram = if start_flags & VBM_FROM_SRAM != 0 {
+            Some(bytes.get(start_offset..input_offset).ok_or("the movie's SRAM is cut short")?.to_vec())
+        } else {
+            None
+        };
+
+        // a u16 per controller per frame, and only the first one's played
+        let stride = controllers.count_ones() as usize * 2;
+        let input = bytes.get(input_offset..).ok_or("the movie's input is missing")?;
+        if input.len() < frame_count * stride {
+            return Err(format!("the movie says it's {} frames but has input for {}", frame_count, input.len() / stride).into());
+        }
+        let frames = input.chunks(stride).take(frame_count)
+            .map(|frame| {
+                VBM_BUTTONS.iter().enumerate()
+                    .filter(|(bit, _)| frame[0] & 1 << bit != 0)
+                    .fold(0, |held, (_, &button)| held | button_bit(button))
+            })
+            .collect();
+
+        Ok(Movie { frames, sram, rerecords: Some(rerecords), warnings })
+    }
+
+    // a .bk2 is a zip, with the input as text in "Input Log.txt": a LogKey line naming the
+    // buttons, then a line per frame with a . for each one not held
+    pub fn parse_bk2(bytes: &[u8]) -> Result<Movie, Box<dyn Error>> {
+        let header = String::from_utf8(zip_entry(bytes, "Header.txt")?.unwrap_or_default())?;
+        let header = |key: &str| header.lines()
+            .find_map(|line| line.strip_prefix(key).and_then(|rest| rest.strip_prefix(' ')))
+            .map(str::trim);
+
+        if header("StartsFromSavestate").is_some_and(|starts| starts.eq_ignore_ascii_case("true")) {
+            return Err("the movie starts from a BizHawk save state, only ones from power on can be played".into());
+        }
+        let mut warnings = Vec::new();
+        match header("Platform") {
+            Some("GB") | None => (),
+            Some(platform) => warnings.push(format!("it was recorded on the {} platform", platform)),
+        }
+
+        let sram = if header("StartsFromSaveRam").is_some_and(|starts| starts.eq_ignore_ascii_case("true")) {
+            Some(zip_entry(bytes, "SaveRam")?.ok_or("the movie starts from SRAM but doesn't have any")?)
+        } else {
+            None
+        };
+
+        let log = zip_entry(bytes, "Input Log.txt")?.ok_or("the movie doesn't have an Input Log.txt")?;
+        let log = String::from_utf8(log)?;
+        let keys: Vec<&str> = log.lines()
+            .find_map(|line| line.strip_prefix("LogKey:"))
+            .ok_or("the movie's input log doesn't have a LogKey")?
+            .split(['|', '#'])
+            .filter(|key| !key.is_empty())
+            .collect();
+
+        let mut frames = Vec::new();
+        for line in log.lines().filter(|line| line.starts_with('|')) {
+            let held: Vec<char> = line.chars().filter(|&c| c != '|').collect();
+            if held.len() != keys.len() {
+                return Err(format!("frame {} of the movie doesn't match its LogKey", frames.len()).into());
+            }
+            let mut buttons = 0;
+            for (key, state) in keys.iter().zip(held) {
+                if state == '.' || state == ' ' {
+                    continue;
+                }
+                match Button::from_name(&key.to_ascii_lowercase()) {
+                    Some(button) => buttons |= button_bit(button),
+                    None if *key == "Power" => warnings.push(format!("it resets the console on frame {}", frames.len())),
+                    None => (),
+                }
+            }
+            frames.push(buttons);
+        }
+
+        let rerecords = header("rerecordCount").and_then(|count| count.parse().ok());
+        Ok(Movie { frames, sram, rerecords, warnings })
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    // queues the whole movie on gameboy, counting frames from now, a press per stretch of
+    // frames each button's held for
+    pub fn queue(&self, gameboy: &mut GameBoy) {
+        for &button in &VBM_BUTTONS {
+            let bit = button_bit(button);
+            let mut held_since = None;
+            for (frame, &held) in self.frames.iter().chain(&[0]).enumerate() {
+                match (held & bit != 0, held_since) {
+                    (true, None) => held_since = Some(frame),
+                    (false, Some(start)) => {
+                        gameboy.press_after(start as u32, button, (frame - start) as u32);
+                        held_since = None;
+                    },
+                    _ => (),
+                }
+            }
+        }
+    }
+}
+
+fn button_bit(button: Button) -> u8 {
+    1 << button as u8
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+// the file called name in a zip archive, found through the central directory at the end.
+// only stored and deflated files, which is all BizHawk writes
+fn zip_entry(archive: &[u8], name: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+    let truncated = || "the movie's zip is cut short";
+    let end = (0..archive.len().saturating_sub(21)).rev()
+        .find(|&offset| read_u32(archive, offset) == ZIP_END_OF_DIRECTORY)
+        .ok_or("the movie's zip doesn't have a central directory")?;
+    let entries = read_u16(archive, end + 10);
+    let mut offset = read_u32(archive, end + 16) as usize;
+
+    for _ in 0..entries {
+        if archive.len() < offset + 46 || read_u32(archive, offset) != ZIP_CENTRAL_HEADER {
+            return Err(truncated().into());
+        }
+        let method = read_u16(archive, offset + 10);
+        let compressed_size = read_u32(archive, offset + 20) as usize;
+        let name_length = read_u16(archive, offset + 28) as usize;
+        let extra_length = read_u16(archive, offset + 30) as usize;
+        let comment_length = read_u16(archive, offset + 32) as usize;
+        let local_offset = read_u32(archive, offset + 42) as usize;
+        let entry_name = archive.get(offset + 46..offset + 46 + name_length).ok_or_else(truncated)?;
+        offset += 46 + name_length + extra_length + comment_length;
+        if entry_name != name.as_bytes() {
+            continue;
+        }
+
+        // the local header's name and extra field can differ from the central directory's
+        if archive.len() < local_offset + 30 || read_u32(archive, local_offset) != ZIP_LOCAL_HEADER {
+            return Err(truncated().into());
+        }
+        let start = local_offset + 30
+            + read_u16(archive, local_offset + 26) as usize
+            + read_u16(archive, local_offset + 28) as usize;
+        let data = archive.get(start..start + compressed_size).ok_or_else(truncated)?;
+        return match method {
+            0 => Ok(Some(data.to_vec())),
+            8 => Ok(Some(inflate::inflate_bytes(data)?)),
+            _ => Err(format!("{} in the movie is compressed in a way we can't read", name).into()),
+        };
+    }
+    Ok(None)
+}
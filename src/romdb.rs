@@ -0,0 +1,87 @@
+// looks roms up by CRC32 in a No-Intro style DAT file, for their proper name, region,
+// and whether they're a known bad dump. we don't ship a DAT, point us at one from datomatic
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RomEntry {
+    pub name: String,
+    // the first bracketed part of the name, e.g. "USA, Europe"
+    pub region: Option<String>,
+    pub bad_dump: bool,
+}
+
+pub struct RomDatabase {
+    entries: HashMap<u32, RomEntry>,
+}
+
+impl RomDatabase {
+    pub fn load(path: &Path) -> Result<RomDatabase, Box<dyn Error>> {
+        RomDatabase::parse(&fs::read_to_string(path)?)
+    }
+
+    // reads the <game> entries of a logiqx XML DAT, as No-Intro publishes them
+    pub fn parse(dat: &str) -> Result<RomDatabase, Box<dyn Error>> {
+        let mut entries = HashMap::new();
+
+        for game in dat.split("<game ").skip(1) {
+            let end = game.find("</game>").ok_or("unterminated <game> in DAT")?;
+            let game = &game[..end];
+            let name = attribute(game, "name").ok_or("<game> without a name in DAT")?;
+
+            for rom in game.split("<rom ").skip(1) {
+                let crc = match attribute(rom, "crc") {
+                    Some(crc) => u32::from_str_radix(&crc, 16)
+                        .map_err(|e| format!("bad crc {} for {}: {}", crc, name, e))?,
+                    None => continue,
+                };
+                let bad_dump = attribute(rom, "status").as_deref() == Some("baddump")
+                    || name.contains("[b]");
+
+                entries.insert(crc, RomEntry { name: name.clone(), region: region(&name), bad_dump });
+            }
+        }
+
+        if entries.is_empty() {
+            return Err("no roms with CRCs found in DAT".into());
+        }
+        Ok(RomDatabase { entries })
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<&RomEntry> {
+        self.entries.get(&crc32(rom))
+    }
+}
+
+// the value of name="..." in the first tag of xml, unescaped
+fn attribute(xml: &str, name: &str) -> Option<String> {
+    let tag = &xml[..xml.find('>')?];
+    let pattern = format!(" {}=\"", name);
+    let start = format!(" {}", tag).find(&pattern)? + pattern.len() - 1;
+    let value = &tag[start..];
+    let value = &value[..value.find('"')?];
+
+    Some(value.replace("&quot;", "\"").replace("&apos;", "'")
+        .replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&"))
+}
+
+fn region(name: &str) -> Option<String> {
+    let start = name.find(" (")? + 2;
+    let end = start + name[start..].find(')')?;
+    Some(name[start..end].to_string())
+}
+
+// the CRC-32 DATs list, as used by zip
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
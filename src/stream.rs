@@ -0,0 +1,196 @@
+// an async face on the emulator, for embedding it in async servers (streaming backends, chat
+// bots playing games, etc). the GameBoy runs on its own thread and its frames come out of a
+// futures Stream, while controls go in from whichever task likes
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use futures_core::Stream;
+
+use crate::gameboy::{GameBoy, CYCLES_PER_SECOND};
+use crate::gameboy::joypad::{Button, Controls};
+use crate::gameboy::lcd::CYCLES_PER_FRAME;
+
+// frames waiting to be polled. a real time stream drops the oldest past this,
+// an unpaced one stops emulating until there's room
+const QUEUE_LENGTH: usize = 4;
+
+pub struct Frame {
+    // from GameBoy::frame_count, so gaps show where frames were dropped
+    pub number: u64,
+    // RGBA, SCREEN_WIDTH x SCREEN_HEIGHT
+    pub pixels: Vec<u8>,
+}
+
+// what the stream yields. audio chunks will join frames here once there's sound
+pub enum Output {
+    Frame(Frame),
+    // the last item, the GameBoy can't go on
+    Error(String),
+}
+
+// what the emulator thread and the stream share
+struct Shared {
+    queue: VecDeque<Output>,
+    waker: Option<Waker>,
+    controls: Controls,
+    controls_changed: bool,
+    presses: Vec<(Button, u32)>,
+    stopping: bool,
+    finished: bool,
+}
+
+pub struct GameBoyStream {
+    shared: Arc<(Mutex<Shared>, Condvar)>,
+    worker: Option<JoinHandle<Box<GameBoy>>>,
+}
+
+impl GameBoyStream {
+    // paced streams run at real time, dropping frames nobody polls for in time.
+    // unpaced ones run as fast as they're polled, and never drop any
+    pub fn new(gameboy: GameBoy, paced: bool) -> GameBoyStream {
+        let shared = Arc::new((Mutex::new(Shared {
+            queue: VecDeque::with_capacity(QUEUE_LENGTH),
+            waker: None,
+            controls: Controls::default(),
+            controls_changed: false,
+            presses: Vec::new(),
+            stopping: false,
+            finished: false,
+        }), Condvar::new()));
+
+        // boxed, as debug builds copy a GameBoy around enough to overflow a thread's stack
+        let gameboy = Box::new(gameboy);
+        let worker_shared = shared.clone();
+        let worker = thread::Builder::new()
+            .name("gameboy stream".to_string())
+            .spawn(move || GameBoyStream::run(gameboy, paced, &worker_shared))
+            .expect("couldn't start the gameboy stream thread");
+
+        GameBoyStream { shared, worker: Some(worker) }
+    }
+
+    // held from the next frame until changed
+    pub fn set_controls(&self, controls: Controls) {
+        let mut shared = self.shared.0.lock().unwrap();
+        shared.controls = controls;
+        shared.controls_changed = true;
+    }
+
+    // press a button for some frames, on top of the held controls
+    pub fn press(&self, button: Button, frames: u32) {
+        self.shared.0.lock().unwrap().presses.push((button, frames));
+    }
+
+    // stops the emulator thread and hands the GameBoy back, e.g. to save its battery
+    pub fn stop(mut self) -> Option<GameBoy> {
+        self.signal_stop();
+        self.worker.take().and_then(|worker| worker.join().ok()).map(|gameboy| *gameboy)
+    }
+
+    fn signal_stop(&self) {
+        let (lock, wakeup) = &*self.shared;
+        lock.lock().unwrap().stopping = true;
+        wakeup.notify_all();
+    }
+
+    fn run(mut gameboy: Box<GameBoy>, paced: bool, shared: &(Mutex<Shared>, Condvar)) -> Box<GameBoy> {
+        let (lock, wakeup) = shared;
+        let frame_time = Duration::from_nanos(
+            CYCLES_PER_FRAME as u64 * 1_000_000_000 / CYCLES_PER_SECOND as u64);
+        let mut next_frame = Instant::now();
+
+        loop {
+            {
+                let mut state = lock.lock().unwrap();
+                if !paced {
+                    while state.queue.len() >= QUEUE_LENGTH && !state.stopping {
+                        state = wakeup.wait(state).unwrap();
+                    }
+                }
+                if state.stopping {
+                    break;
+                }
+                if state.controls_changed {
+                    gameboy.set_controls(state.controls);
+                    state.controls_changed = false;
+                }
+                for (button, frames) in state.presses.drain(..) {
+                    gameboy.press(button, frames);
+                }
+            }
+
+            let output = match gameboy.run_to_vblank() {
+                Ok(()) => Output::Frame(Frame {
+                    number: gameboy.frame_count(),
+                    pixels: gameboy.screenshot_rgba(),
+                }),
+                Err(err) => Output::Error(err.to_string()),
+            };
+            let failed = matches!(output, Output::Error(_));
+
+            {
+                let mut state = lock.lock().unwrap();
+                if state.queue.len() >= QUEUE_LENGTH {
+                    state.queue.pop_front();
+                }
+                state.queue.push_back(output);
+                state.finished = failed;
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+            }
+            if failed {
+                break;
+            }
+
+            if paced {
+                next_frame += frame_time;
+                let now = Instant::now();
+                if next_frame > now {
+                    thread::sleep(next_frame - now);
+                } else {
+                    // behind, so don't try to catch up
+                    next_frame = now;
+                }
+            }
+        }
+
+        gameboy
+    }
+}
+
+impl Stream for GameBoyStream {
+    type Item = Output;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Output>> {
+        let (lock, wakeup) = &*self.shared;
+        let mut state = lock.lock().unwrap();
+
+        match state.queue.pop_front() {
+            Some(output) => {
+                // an unpaced thread might be waiting for room
+                wakeup.notify_all();
+                Poll::Ready(Some(output))
+            },
+            None if state.finished || state.stopping => Poll::Ready(None),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            },
+        }
+    }
+}
+
+impl Drop for GameBoyStream {
+    fn drop(&mut self) {
+        self.signal_stop();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
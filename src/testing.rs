@@ -0,0 +1,132 @@
+// screenshot tests for crates built on the core: run a rom headless to a frame and compare
+// the screen with a reference PNG, like tests/golden.rs but against images rather than hashes.
+//
+//   assert_frame_eq!("tests/roms/game.gb", 300, "tests/frames/title.png");
+//   assert_frame_eq!("tests/roms/game.gb", 300, "tests/frames/title.png", 8);
+//
+// the last argument's how far each colour channel may be off, 0 if it's left out. with
+// GBOXIDE_BLESS set, missing or mismatched references are written from what was drawn instead
+
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::cartridge::Cartridge;
+use crate::gameboy::GameBoy;
+use crate::gameboy::dump;
+use crate::gameboy::lcd::{SCREEN_WIDTH, SCREEN_HEIGHT};
+
+#[macro_export]
+macro_rules! assert_frame_eq {
+    ($rom:expr, $frames:expr, $reference:expr) => {
+        $crate::assert_frame_eq!($rom, $frames, $reference, 0)
+    };
+    ($rom:expr, $frames:expr, $reference:expr, $tolerance:expr) => {
+        if let Err(err) = $crate::testing::check_frame(
+                ::std::path::Path::new($rom), $frames, ::std::path::Path::new($reference), $tolerance) {
+            panic!("assert_frame_eq failed: {}", err);
+        }
+    };
+}
+
+#[derive(Debug)]
+pub struct FrameMismatch {
+    pub reference: PathBuf,
+    // pixels off by more than the tolerance, and the first of them
+    pub pixels: usize,
+    pub first: (usize, usize),
+    // the biggest difference in any channel
+    pub worst: u8,
+    // where what was drawn has been saved, to look at next to the reference
+    pub actual: Option<PathBuf>,
+}
+
+impl fmt::Display for FrameMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} pixels differ from {}, the first at {},{}, by up to {}",
+               self.pixels, self.reference.display(), self.first.0, self.first.1, self.worst)?;
+        if let Some(actual) = &self.actual {
+            write!(f, ", frame saved to {}", actual.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for FrameMismatch {}
+
+// the RGBA screen after running a fresh GameBoy for frames frames
+pub fn run_frames(cartridge: Cartridge, frames: u64) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut gameboy = GameBoy::new(cartridge);
+    for _ in 0..frames {
+        gameboy.run_to_vblank()?;
+    }
+    Ok(screenshot(&gameboy))
+}
+
+// what's on gameboy's screen now, as RGBA
+pub fn screenshot(gameboy: &GameBoy) -> Vec<u8> {
+    let mut frame = vec![0x00; SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize * 4];
+    gameboy.draw_frame(&mut frame);
+    frame
+}
+
+// compares an RGBA screen with the reference PNG, where no channel may be more than tolerance off
+pub fn compare_frame(frame: &[u8], reference: &Path, tolerance: u8) -> Result<Option<FrameMismatch>, Box<dyn Error>> {
+    let (width, height, expected) = dump::load_png(reference)?;
+    if width != SCREEN_WIDTH as u32 || height != SCREEN_HEIGHT as u32 {
+        return Err(format!("{} is {}x{}, not {}x{}", reference.display(), width, height, SCREEN_WIDTH, SCREEN_HEIGHT).into());
+    }
+    if expected.len() != SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize * 4 {
+        return Err(format!("{} decoded to {} bytes, not the {} a {}x{} RGBA screen needs", reference.display(),
+                           expected.len(), SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize * 4,
+                           SCREEN_WIDTH, SCREEN_HEIGHT).into());
+    }
+
+    let mut mismatch: Option<FrameMismatch> = None;
+    for (index, (actual, expected)) in frame.chunks(4).zip(expected.chunks(4)).enumerate() {
+        let difference = actual.iter().zip(expected)
+            .map(|(&actual, &expected)| (actual as i16 - expected as i16).unsigned_abs() as u8)
+            .max()
+            .unwrap_or(0);
+        if difference <= tolerance {
+            continue;
+        }
+        let mismatch = mismatch.get_or_insert_with(|| FrameMismatch {
+            reference: reference.to_path_buf(),
+            pixels: 0,
+            first: (index % SCREEN_WIDTH as usize, index / SCREEN_WIDTH as usize),
+            worst: 0,
+            actual: None,
+        });
+        mismatch.pixels += 1;
+        mismatch.worst = mismatch.worst.max(difference);
+    }
+    Ok(mismatch)
+}
+
+// runs rom for frames frames and compares the screen with reference, what assert_frame_eq does
+pub fn check_frame(rom: &Path, frames: u64, reference: &Path, tolerance: u8) -> Result<(), Box<dyn Error>> {
+    let cartridge = Cartridge::new(&rom.to_string_lossy())?;
+    let frame = run_frames(cartridge, frames)?;
+    let bless = env::var_os("GBOXIDE_BLESS").is_some();
+
+    let mismatch = if reference.exists() { compare_frame(&frame, reference, tolerance)? } else { None };
+    if bless && (mismatch.is_some() || !reference.exists()) {
+        dump::save_png(reference, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, &frame)?;
+        return Ok(());
+    }
+    if !reference.exists() {
+        return Err(format!("{} doesn't exist, run with GBOXIDE_BLESS=1 to write it", reference.display()).into());
+    }
+
+    match mismatch {
+        Some(mut mismatch) => {
+            let name = reference.file_name().unwrap_or_default().to_string_lossy();
+            let actual = env::temp_dir().join(format!("gboxide-actual-{}", name));
+            mismatch.actual = dump::save_png(&actual, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, &frame).ok().map(|_| actual);
+            Err(mismatch.into())
+        },
+        None => Ok(()),
+    }
+}
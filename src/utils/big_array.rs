@@ -0,0 +1,44 @@
+// serde only handles arrays up to 32 long, this handles any length.
+// use with #[serde(with = "crate::utils::big_array")]
+
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::fmt;
+use core::marker::PhantomData;
+
+use serde::de::{Deserialize, Deserializer, Error, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeTuple, Serializer};
+
+pub fn serialize<S, T, const N: usize>(array: &[T; N], serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer, T: Serialize
+{
+    let mut tuple = serializer.serialize_tuple(N)?;
+    for item in array.iter() {
+        tuple.serialize_element(item)?;
+    }
+    tuple.end()
+}
+
+pub fn deserialize<'de, D, T, const N: usize>(deserializer: D) -> Result<[T; N], D::Error>
+    where D: Deserializer<'de>, T: Deserialize<'de>
+{
+    deserializer.deserialize_tuple(N, ArrayVisitor::<T, N>(PhantomData))
+}
+
+struct ArrayVisitor<T, const N: usize>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>, const N: usize> Visitor<'de> for ArrayVisitor<T, N> {
+    type Value = [T; N];
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an array of length {}", N)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<[T; N], A::Error> {
+        let mut items = Vec::with_capacity(N);
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        items.try_into().map_err(|items: Vec<T>| A::Error::invalid_length(items.len(), &self))
+    }
+}
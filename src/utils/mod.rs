@@ -1 +1,3 @@
 pub mod string;
+#[cfg(feature = "serialize")]
+pub mod big_array;
@@ -1,4 +1,4 @@
-use std::str;
+use core::str;
 
 pub fn str_from_u8_null_utf8(utf8_src: &[u8]) -> Result<&str, str::Utf8Error> {
     let null_range_end = utf8_src.iter()
@@ -0,0 +1,38 @@
+// still images loaded as the Pocket Camera's sensor
+
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use gboxide::camera::{CameraSensor, StaticImage, SENSOR_WIDTH, SENSOR_HEIGHT};
+
+// writes a grayscale PNG at the given bit depth, samples big endian as PNGs have them
+fn write_gray_png(name: &str, width: u32, height: u32, depth: png::BitDepth, data: &[u8]) -> PathBuf {
+    let path = env::temp_dir().join(format!("gboxide-camera-{}.png", name));
+    let w = BufWriter::new(File::create(&path).unwrap());
+
+    let mut png_encoder = png::Encoder::new(w, width, height);
+    png_encoder.set_color(png::ColorType::Grayscale);
+    png_encoder.set_depth(depth);
+    let mut writer = png_encoder.write_header().unwrap();
+    writer.write_image_data(data).unwrap();
+
+    path
+}
+
+#[test]
+fn sixteen_bit_png() {
+    // left half 0x8040, right half 0xFF00, which come out as their high bytes
+    let (width, height) = (4, 2);
+    let row = [0x80, 0x40, 0x80, 0x40, 0xFF, 0x00, 0xFF, 0x00];
+    let data: Vec<u8> = row.iter().cycle().take(row.len() * height).cloned().collect();
+    let path = write_gray_png("16bit", width, height as u32, png::BitDepth::Sixteen, &data);
+
+    let pixels = StaticImage::new(path.to_str().unwrap()).unwrap().capture();
+    assert_eq!(pixels.len(), SENSOR_WIDTH * SENSOR_HEIGHT);
+    for row in pixels.chunks(SENSOR_WIDTH) {
+        assert_eq!(row[0], 0x80);
+        assert_eq!(row[SENSOR_WIDTH - 1], 0xFF);
+    }
+}
@@ -0,0 +1,45 @@
+// the hand-assembled ROMs the tests and benches run, built on one blank cartridge
+
+// not every test uses every part of this
+#![allow(dead_code)]
+
+// a blank 32KiB ROM-only cartridge whose entry point jumps to 0x0150, for a program to go
+pub struct RomBuilder {
+    rom: Vec<u8>,
+}
+
+impl RomBuilder {
+    pub fn new(title: &[u8]) -> RomBuilder {
+        let mut rom = vec![0x00; 0x8000];
+        // entry point: NOP; JP 0x0150
+        rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+        rom[0x134..0x134 + title.len()].copy_from_slice(title);
+        rom[0x14A] = 0x01; // non-japanese
+        RomBuilder { rom }
+    }
+
+    pub fn cartridge_type(mut self, cartridge_type: u8) -> RomBuilder {
+        self.rom[0x147] = cartridge_type;
+        self
+    }
+
+    // the header's RAM size code, e.g. 0x02 for 8KiB
+    pub fn ram_size(mut self, ram_size: u8) -> RomBuilder {
+        self.rom[0x149] = ram_size;
+        self
+    }
+
+    pub fn bytes(mut self, address: usize, bytes: &[u8]) -> RomBuilder {
+        self.rom[address..address + bytes.len()].copy_from_slice(bytes);
+        self
+    }
+
+    // the code the entry point jumps to
+    pub fn program(self, program: &[u8]) -> RomBuilder {
+        self.bytes(0x150, program)
+    }
+
+    pub fn build(self) -> Vec<u8> {
+        self.rom
+    }
+}
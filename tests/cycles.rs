@@ -0,0 +1,138 @@
+// machine cycles for every opcode, run one at a time against a flat bus
+//
+// each instruction's time comes from its bus accesses plus a spin() for every internal cycle,
+// so a missing spin() shows up as an instruction running short. conditional jumps, calls and
+// returns are run both ways, with the flags all clear and then all set.
+// the counts are the usual ones, see https://gbdev.io/gb-opcodes/optables/
+
+use gboxide::gameboy::bus::{Bus, FlatMemory};
+use gboxide::gameboy::cpu::CPU;
+use gboxide::gameboy::registers::Flags;
+
+// unprefixed opcodes, with the not taken count for conditional ones (see TAKEN).
+// 0 for the opcodes that don't exist, and 0xCB, which is the prefix
+const CYCLES: [u8; 256] = [
+//  0  1  2  3  4  5  6  7  8  9  A  B  C  D  E  F
+    1, 3, 2, 2, 1, 1, 2, 1, 5, 2, 2, 2, 1, 1, 2, 1, // 0x
+    1, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1, // 1x
+    2, 3, 2, 2, 1, 1, 2, 1, 2, 2, 2, 2, 1, 1, 2, 1, // 2x
+    2, 3, 2, 2, 3, 3, 3, 1, 2, 2, 2, 2, 1, 1, 2, 1, // 3x
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 4x
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 5x
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 6x
+    2, 2, 2, 2, 2, 2, 1, 2, 1, 1, 1, 1, 1, 1, 2, 1, // 7x
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 8x
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 9x
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // Ax
+    1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // Bx
+    2, 3, 3, 4, 3, 4, 2, 4, 2, 4, 3, 0, 3, 6, 2, 4, // Cx
+    2, 3, 3, 0, 3, 4, 2, 4, 2, 4, 3, 0, 3, 0, 2, 4, // Dx
+    3, 3, 2, 0, 0, 4, 2, 4, 4, 1, 4, 0, 0, 0, 2, 4, // Ex
+    3, 3, 2, 1, 0, 4, 2, 4, 3, 2, 4, 1, 0, 0, 2, 4, // Fx
+];
+
+// conditional jumps, calls and returns, and their count when the condition holds
+const TAKEN: [(u8, u8); 16] = [
+    (0x20, 3), (0x28, 3), (0x30, 3), (0x38, 3), // JR
+    (0xC0, 5), (0xC8, 5), (0xD0, 5), (0xD8, 5), // RET
+    (0xC2, 4), (0xCA, 4), (0xD2, 4), (0xDA, 4), // JP
+    (0xC4, 6), (0xCC, 6), (0xD4, 6), (0xDC, 6), // CALL
+];
+
+// STOP's time depends on the joypad and the CGB speed switch, so it's left out
+const SKIPPED: [u8; 1] = [0x10];
+
+// 0xCB-prefixed opcodes: 2 for the fetches, with (HL) read and written back for 4, or only
+// read for BIT's 3
+const CB_CYCLES: [u8; 256] = [
+//  0  1  2  3  4  5  6  7  8  9  A  B  C  D  E  F
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // 0x
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // 1x
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // 2x
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // 3x
+    2, 2, 2, 2, 2, 2, 3, 2, 2, 2, 2, 2, 2, 2, 3, 2, // 4x
+    2, 2, 2, 2, 2, 2, 3, 2, 2, 2, 2, 2, 2, 2, 3, 2, // 5x
+    2, 2, 2, 2, 2, 2, 3, 2, 2, 2, 2, 2, 2, 2, 3, 2, // 6x
+    2, 2, 2, 2, 2, 2, 3, 2, 2, 2, 2, 2, 2, 2, 3, 2, // 7x
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // 8x
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // 9x
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // Ax
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // Bx
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // Cx
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // Dx
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // Ex
+    2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // Fx
+];
+
+// NZ, Z, NC or C, from bits 3 and 4 of a conditional opcode
+fn condition_holds(op: u8, flags: Flags) -> bool {
+    match (op >> 3) & 0b11 {
+        0 => !flags.contains(Flags::ZERO),
+        1 => flags.contains(Flags::ZERO),
+        2 => !flags.contains(Flags::CARRY),
+        _ => flags.contains(Flags::CARRY),
+    }
+}
+
+// the instruction's bytes at 0xC000, where run starts
+fn flat_memory(bytes: &[u8]) -> FlatMemory {
+    let mut memory = FlatMemory::new();
+    memory.ram[0xC000..0xC000 + bytes.len()].copy_from_slice(bytes);
+    memory
+}
+
+// runs the instruction at 0xC000 and returns how many machine cycles it took,
+// or None if the cpu didn't recognise it
+fn run<B: Bus>(mut bus: B, flags: Flags) -> Option<u128> {
+    let mut cpu = CPU::new();
+    {
+        let r = cpu.registers_mut();
+        r.f = flags;
+        r.pc = 0xC000;
+        r.sp = 0xD000;
+        r.h = 0xD1;
+        r.l = 0x00;
+    }
+
+    let start = bus.get_cycles();
+    cpu.step(&mut bus).ok()?;
+    Some((bus.get_cycles() - start) / 4)
+}
+
+#[test]
+fn opcode_cycles() {
+    let mut failures = Vec::new();
+
+    for op in 0..=255u8 {
+        if op == 0xCB || SKIPPED.contains(&op) {
+            continue;
+        }
+        for &flags in [Flags::empty(), Flags::all()].iter() {
+            let expected = match TAKEN.iter().find(|&&(taken_op, _)| taken_op == op) {
+                Some(&(_, taken)) if condition_holds(op, flags) => taken,
+                _ => CYCLES[op as usize],
+            };
+            match (run(flat_memory(&[op]), flags), expected) {
+                (None, 0) => (),
+                (None, _) => failures.push(format!("{:02x}: not recognised, expected {} cycles", op, expected)),
+                (Some(actual), 0) => failures.push(format!("{:02x}: expected to be unused, ran for {} cycles", op, actual)),
+                (Some(actual), expected) if actual != expected as u128 => {
+                    failures.push(format!("{:02x} with flags {:02x}: expected {} cycles, got {}",
+                                          op, flags.bits(), expected, actual));
+                },
+                _ => (),
+            }
+        }
+    }
+
+    for op in 0..=255u8 {
+        let expected = CB_CYCLES[op as usize];
+        match run(flat_memory(&[0xCB, op]), Flags::empty()) {
+            Some(actual) if actual == expected as u128 => (),
+            Some(actual) => failures.push(format!("cb{:02x}: expected {} cycles, got {}", op, expected, actual)),
+            None => failures.push(format!("cb{:02x}: not recognised, expected {} cycles", op, expected)),
+        }
+    }
+
+    assert!(failures.is_empty(), "opcode cycle mismatches:\n{}", failures.join("\n"));
+}
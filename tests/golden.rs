@@ -0,0 +1,262 @@
+// golden frame regression tests
+//
+// each line of tests/golden/manifest.txt is `<rom> <frames> <hash>`:
+// the rom is run headless for that many frames and an FNV-1a hash of the final
+// RGBA framebuffer is compared against the checked in hash.
+// roms are either files in tests/roms, or `builtin:<name>` for the roms built below.
+//
+// run with GBOXIDE_BLESS=1 to rewrite the manifest with the current hashes,
+// and check the frames written to the temp dir look right before committing.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use gboxide::cartridge::Cartridge;
+use gboxide::gameboy::GameBoy;
+use gboxide::gameboy::lcd::{SCREEN_WIDTH, SCREEN_HEIGHT};
+
+mod common;
+use common::RomBuilder;
+
+struct Golden {
+    rom: String,
+    frames: u64,
+    hash: u64,
+}
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+fn read_manifest() -> Vec<Golden> {
+    let manifest = fs::read_to_string(golden_dir().join("manifest.txt"))
+        .expect("couldn't read tests/golden/manifest.txt");
+
+    manifest.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            assert_eq!(fields.len(), 3, "bad manifest line: {}", line);
+            Golden {
+                rom: fields[0].to_string(),
+                frames: fields[1].parse().expect("bad frame count"),
+                hash: u64::from_str_radix(fields[2], 16).expect("bad hash"),
+            }
+        })
+        .collect()
+}
+
+fn write_manifest(goldens: &[Golden]) {
+    let mut manifest = String::from("# rom frames fnv1a-hash, see tests/golden.rs\n");
+    for golden in goldens {
+        manifest.push_str(&format!("{} {} {:016x}\n", golden.rom, golden.frames, golden.hash));
+    }
+    fs::write(golden_dir().join("manifest.txt"), manifest).expect("couldn't write manifest");
+}
+
+fn load_cartridge(rom: &str) -> Option<Cartridge> {
+    if rom.starts_with("builtin:") {
+        let rom_data = match &rom["builtin:".len()..] {
+            "scroll" => scroll_rom(),
+            // the same sprites either way
+            "sram-dma" => sprite_dma_rom(0xA000, 0xA0),
+            "echo-dma" => sprite_dma_rom(0xC000, 0xE0),
+            name => panic!("unknown builtin rom {}", name),
+        };
+        return Some(Cartridge::from_rom(rom_data, &[]).unwrap());
+    }
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("roms").join(rom);
+    if !path.exists() {
+        // roms we can't redistribute can be dropped into tests/roms locally
+        eprintln!("skipping {}, rom not found", rom);
+        return None;
+    }
+    Some(Cartridge::new(path.to_str().unwrap()).unwrap())
+}
+
+fn run_frames(cartridge: Cartridge, frames: u64) -> Vec<u8> {
+    let mut gameboy = GameBoy::new(cartridge);
+    for _ in 0..frames {
+        gameboy.run_to_vblank().unwrap();
+    }
+
+    let mut frame = vec![0x00; SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize * 4];
+    gameboy.draw_frame(&mut frame);
+    frame
+}
+
+// FNV-1a, so the hashes are stable across rust versions
+fn hash_frame(frame: &[u8]) -> u64 {
+    frame.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3)
+    })
+}
+
+fn save_frame(frame: &[u8], name: &str) -> PathBuf {
+    let file_name = format!("gboxide-golden-{}.png", name.replace(|c: char| !c.is_alphanumeric(), "_"));
+    let path = env::temp_dir().join(file_name);
+    let ref mut w = BufWriter::new(File::create(&path).unwrap());
+
+    let mut png_encoder = png::Encoder::new(w, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32);
+    png_encoder.set_color(png::ColorType::RGBA);
+    png_encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = png_encoder.write_header().unwrap();
+    writer.write_image_data(frame).unwrap();
+
+    path
+}
+
+#[test]
+fn golden_frames() {
+    let bless = env::var_os("GBOXIDE_BLESS").is_some();
+    let mut goldens = read_manifest();
+    let mut failures = Vec::new();
+
+    for golden in goldens.iter_mut() {
+        let cartridge = match load_cartridge(&golden.rom) {
+            Some(cartridge) => cartridge,
+            None => continue,
+        };
+
+        let frame = run_frames(cartridge, golden.frames);
+        let hash = hash_frame(&frame);
+        if hash == golden.hash {
+            continue;
+        }
+
+        let path = save_frame(&frame, &golden.rom);
+        if bless {
+            eprintln!("blessing {}: {:016x}, frame saved to {}", golden.rom, hash, path.display());
+            golden.hash = hash;
+        } else {
+            failures.push(format!("{} after {} frames: expected {:016x}, got {:016x}, frame saved to {}",
+                                  golden.rom, golden.frames, golden.hash, hash, path.display()));
+        }
+    }
+
+    if bless {
+        write_manifest(&goldens);
+    }
+
+    assert!(failures.is_empty(), "golden frame mismatches:\n{}", failures.join("\n"));
+}
+
+// copies the first 256 tiles' worth of itself into VRAM as tile data,
+// fills the bg map with every tile, then scrolls diagonally one pixel per frame
+fn scroll_rom() -> Vec<u8> {
+    let program = [
+        0xF3,             // DI
+        0x31, 0xFE, 0xFF, // LD SP,0xFFFE
+        0xAF,             // XOR A
+        0xE0, 0x40,       // LDH (0x40),A     ; LCD off
+        0x21, 0x00, 0x00, // LD HL,0x0000
+        0x11, 0x00, 0x80, // LD DE,0x8000
+        0x01, 0x00, 0x10, // LD BC,0x1000
+        0x2A,             // LD A,(HL+)       ; copy tile data
+        0x12,             // LD (DE),A
+        0x13,             // INC DE
+        0x0B,             // DEC BC
+        0x78,             // LD A,B
+        0xB1,             // OR C
+        0x20, 0xF8,       // JR NZ,-8
+        0x21, 0x00, 0x98, // LD HL,0x9800
+        0x01, 0x00, 0x04, // LD BC,0x0400
+        0x7D,             // LD A,L           ; fill bg map
+        0x22,             // LD (HL+),A
+        0x0B,             // DEC BC
+        0x78,             // LD A,B
+        0xB1,             // OR C
+        0x20, 0xF9,       // JR NZ,-7
+        0x3E, 0xE4,       // LD A,0xE4
+        0xE0, 0x47,       // LDH (0x47),A     ; bg palette
+        0x3E, 0x91,       // LD A,0x91
+        0xE0, 0x40,       // LDH (0x40),A     ; LCD on, tile data at 0x8000, bg on
+        0x3E, 0x01,       // LD A,0x01
+        0xE0, 0xFF,       // LDH (0xFF),A     ; enable vblank interrupt
+        0xFB,             // EI
+        0x76,             // HALT             ; wait for vblank
+        0x00,             // NOP
+        0xF0, 0x43,       // LDH A,(0x43)
+        0x3C,             // INC A
+        0xE0, 0x43,       // LDH (0x43),A     ; scroll x
+        0xF0, 0x42,       // LDH A,(0x42)
+        0x3C,             // INC A
+        0xE0, 0x42,       // LDH (0x42),A     ; scroll y
+        0x18, 0xF2,       // JR -14
+    ];
+
+    RomBuilder::new(b"SCROLL")
+        .bytes(0x40, &[0xD9]) // vblank interrupt: RETI
+        .program(&program)
+        .build()
+}
+
+// copies a table of four sprites from ROM into buffer, then has OAM DMA copy them from page,
+// with the routine in HRAM as a game would. cartridge RAM is enabled first, in case that's
+// where buffer is
+fn sprite_dma_rom(buffer: u16, page: u8) -> Vec<u8> {
+    let [buffer_low, buffer_high] = buffer.to_le_bytes();
+    let program = [
+        0xF3,             // DI
+        0x31, 0xFE, 0xFF, // LD SP,0xFFFE
+        0xAF,             // XOR A
+        0xE0, 0x40,       // LDH (0x40),A     ; LCD off
+        0x21, 0x10, 0x80, // LD HL,0x8010
+        0x06, 0x10,       // LD B,0x10
+        0x3E, 0xFF,       // LD A,0xFF
+        0x22,             // LD (HL+),A       ; tile 1 solid black
+        0x05,             // DEC B
+        0x20, 0xFC,       // JR NZ,-4
+        0x3E, 0x0A,       // LD A,0x0A
+        0xEA, 0x00, 0x00, // LD (0x0000),A    ; enable cartridge RAM
+        0x21, 0x00, 0x02, // LD HL,0x0200
+        0x11, buffer_low, buffer_high, // LD DE,buffer
+        0x06, 0xA0,       // LD B,0xA0
+        0x2A,             // LD A,(HL+)       ; copy the sprite table
+        0x12,             // LD (DE),A
+        0x13,             // INC DE
+        0x05,             // DEC B
+        0x20, 0xFA,       // JR NZ,-6
+        0x21, 0xA0, 0x02, // LD HL,0x02A0
+        0x0E, 0x80,       // LD C,0x80
+        0x06, 0x0A,       // LD B,0x0A
+        0x2A,             // LD A,(HL+)       ; copy the DMA routine to HRAM
+        0xE2,             // LD (C),A
+        0x0C,             // INC C
+        0x05,             // DEC B
+        0x20, 0xFA,       // JR NZ,-6
+        0x3E, 0xE4,       // LD A,0xE4
+        0xE0, 0x47,       // LDH (0x47),A     ; bg palette
+        0xE0, 0x48,       // LDH (0x48),A     ; sprite palette 0
+        0xCD, 0x80, 0xFF, // CALL 0xFF80
+        0x3E, 0x93,       // LD A,0x93
+        0xE0, 0x40,       // LDH (0x40),A     ; LCD on, tile data at 0x8000, sprites and bg on
+        0x18, 0xFE,       // JR -2
+    ];
+
+    // y, x, tile and attributes, in a diagonal. the rest of the table's zeros, which are off screen
+    let sprites: Vec<u8> = (0..4u8)
+        .flat_map(|sprite| [40 + 16 * sprite, 40 + 24 * sprite, 0x01, 0x00])
+        .collect();
+
+    let dma_routine = [
+        0x3E, page,       // LD A,page
+        0xE0, 0x46,       // LDH (0x46),A     ; start the DMA
+        0x3E, 0x28,       // LD A,0x28
+        0x3D,             // DEC A            ; wait out its 160 cycles
+        0x20, 0xFD,       // JR NZ,-3
+        0xC9,             // RET
+    ];
+
+    RomBuilder::new(b"SPRDMA")
+        .cartridge_type(0x03) // MBC1+RAM+BATTERY
+        .ram_size(0x02)       // 8KiB of RAM
+        .program(&program)
+        .bytes(0x200, &sprites)
+        .bytes(0x2A0, &dma_routine)
+        .build()
+}
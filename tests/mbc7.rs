@@ -0,0 +1,104 @@
+// the MBC7's accelerometer and EEPROM, driven the way Kirby Tilt 'n' Tumble does
+
+use gboxide::cartridge::Cartridge;
+use gboxide::motion::{self, MotionSource};
+
+mod common;
+use common::RomBuilder;
+
+struct Tipped(f32, f32);
+impl MotionSource for Tipped {
+    fn tilt(&mut self) -> (f32, f32) {
+        (self.0, self.1)
+    }
+}
+
+// a blank MBC7 cartridge with its registers enabled
+fn mbc7_cartridge() -> Cartridge {
+    let rom = RomBuilder::new(b"MBC7 ")
+        .cartridge_type(0x22) // MBC7+SENSOR+RUMBLE+RAM+BATTERY
+        .build();
+
+    let mut cartridge = Cartridge::from_rom(rom, &[]).unwrap();
+    cartridge.write(0x0000, 0x0A);
+    cartridge.write(0x4000, 0x40);
+    cartridge
+}
+
+fn read_tilt(cartridge: &mut Cartridge) -> (u16, u16) {
+    cartridge.write(0xA000, 0x55);
+    cartridge.write(0xA010, 0xAA);
+    let x = u16::from_le_bytes([cartridge.read(0xA020), cartridge.read(0xA030)]);
+    let y = u16::from_le_bytes([cartridge.read(0xA040), cartridge.read(0xA050)]);
+    (x, y)
+}
+
+const SELECT: u8 = 0x80;
+const CLOCK: u8 = 0x40;
+
+// clocks bits out to the EEPROM and returns what it put on data out after each one
+fn clock_bits(cartridge: &mut Cartridge, bits: &[bool]) -> Vec<bool> {
+    bits.iter().map(|&bit| {
+        let data_in = (bit as u8) << 1;
+        cartridge.write(0xA080, SELECT | data_in);
+        cartridge.write(0xA080, SELECT | CLOCK | data_in);
+        cartridge.read(0xA080) & 0x01 != 0
+    }).collect()
+}
+
+// a start bit, then value's bottom len bits from the top down
+fn command(cartridge: &mut Cartridge, value: u32, len: u8) -> Vec<bool> {
+    let mut bits = vec![true];
+    bits.extend((0..len).rev().map(|bit| value >> bit & 1 != 0));
+    let out = clock_bits(cartridge, &bits);
+    cartridge.write(0xA080, 0x00);
+    out
+}
+
+fn read_word(cartridge: &mut Cartridge, addr: u8) -> u16 {
+    let mut bits = vec![true, true, false];
+    bits.extend((0..8).rev().map(|bit| addr >> bit & 1 != 0));
+    clock_bits(cartridge, &bits);
+    let out = clock_bits(cartridge, &[false; 16]);
+    cartridge.write(0xA080, 0x00);
+    out.iter().fold(0, |word, &bit| word << 1 | bit as u16)
+}
+
+#[test]
+fn latches_tilt() {
+    let mut cartridge = mbc7_cartridge();
+    assert_eq!(read_tilt(&mut cartridge), (motion::MBC7_LEVEL, motion::MBC7_LEVEL));
+
+    cartridge.set_motion_source(Box::new(Tipped(0.5, -1.0)));
+    assert_eq!(read_tilt(&mut cartridge), motion::mbc7_reading((0.5, -1.0)));
+
+    // erased, but not latched again
+    cartridge.write(0xA000, 0x55);
+    assert_eq!(cartridge.read(0xA030), 0x80);
+    assert_eq!(cartridge.read(0xA020), 0x00);
+}
+
+#[test]
+fn eeprom_writes_once_enabled() {
+    let mut cartridge = mbc7_cartridge();
+    assert_eq!(read_word(&mut cartridge, 0x05), 0xFFFF);
+
+    // WRITE is ignored until EWEN
+    command(&mut cartridge, 0b01_0000_0101 << 16 | 0x1234, 2 + 8 + 16);
+    assert_eq!(read_word(&mut cartridge, 0x05), 0xFFFF);
+
+    command(&mut cartridge, 0b00_1100_0000, 2 + 8);
+    let out = command(&mut cartridge, 0b01_0000_0101 << 16 | 0x1234, 2 + 8 + 16);
+    assert_eq!(out.last(), Some(&true), "ready once the write's done");
+    assert_eq!(read_word(&mut cartridge, 0x05), 0x1234);
+    assert_eq!(read_word(&mut cartridge, 0x06), 0xFFFF);
+
+    // saved as little endian words
+    let save = cartridge.battery_save();
+    assert_eq!(save.len(), 256);
+    assert_eq!(&save[0x0A..0x0C], &[0x34, 0x12]);
+
+    // ERASE
+    command(&mut cartridge, 0b11_0000_0101, 2 + 8);
+    assert_eq!(read_word(&mut cartridge, 0x05), 0xFFFF);
+}
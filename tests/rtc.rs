@@ -0,0 +1,120 @@
+// the MBC3's real time clock, run against a manual clock source
+
+use gboxide::cartridge::Cartridge;
+use gboxide::clock::{ManualClock, ScaledClock};
+
+mod common;
+use common::RomBuilder;
+
+const DAY: u64 = 24 * 60 * 60;
+
+// a blank MBC3 + Timer + RAM + Battery cartridge with 8KiB of RAM and its clock on source
+fn clock_cartridge(source: &ManualClock) -> Cartridge {
+    let rom = RomBuilder::new(b"RTC")
+        .cartridge_type(0x10) // MBC3+TIMER+RAM+BATTERY
+        .ram_size(0x02)       // 8KiB of RAM
+        .build();
+
+    let mut cartridge = Cartridge::from_rom(rom, &[]).unwrap();
+    cartridge.set_clock_source(Box::new(source.clone()));
+    cartridge.write(0x0000, 0x0A); // enable RAM and the clock
+    cartridge
+}
+
+// latches the clock and reads seconds, minutes, hours, day counter low and high
+fn latch(cartridge: &mut Cartridge) -> [u8; 5] {
+    cartridge.write(0x6000, 0x00);
+    cartridge.write(0x6000, 0x01);
+    let mut registers = [0; 5];
+    for (register, value) in registers.iter_mut().enumerate() {
+        cartridge.write(0x4000, 0x08 + register as u8);
+        *value = cartridge.read(0xA000);
+    }
+    registers
+}
+
+fn write_register(cartridge: &mut Cartridge, register: u8, value: u8) {
+    cartridge.write(0x4000, 0x08 + register);
+    cartridge.write(0xA000, value);
+}
+
+#[test]
+fn counts_time() {
+    let source = ManualClock::new(1_000_000);
+    let mut cartridge = clock_cartridge(&source);
+    assert_eq!(latch(&mut cartridge), [0, 0, 0, 0, 0]);
+
+    source.advance(300 * DAY + 5 * 3600 + 4 * 60 + 3);
+    // nothing changes until it's latched again
+    cartridge.write(0x4000, 0x08);
+    assert_eq!(cartridge.read(0xA000), 0);
+    // day 300 is 0x12C, its top bit in the high register
+    assert_eq!(latch(&mut cartridge), [3, 4, 5, 0x2C, 0x01]);
+
+    // the day counter wraps at 512 and sets the carry bit, which stays until it's written
+    source.advance(212 * DAY);
+    assert_eq!(latch(&mut cartridge), [3, 4, 5, 0, 0x80]);
+    write_register(&mut cartridge, 4, 0x00);
+    assert_eq!(latch(&mut cartridge), [3, 4, 5, 0, 0x00]);
+}
+
+#[test]
+fn halts_and_sets() {
+    let source = ManualClock::new(0);
+    let mut cartridge = clock_cartridge(&source);
+
+    write_register(&mut cartridge, 4, 0x40);
+    write_register(&mut cartridge, 2, 23);
+    write_register(&mut cartridge, 1, 59);
+    source.advance(DAY);
+    assert_eq!(latch(&mut cartridge), [0, 59, 23, 0, 0x40]);
+
+    write_register(&mut cartridge, 4, 0x00);
+    source.advance(61);
+    assert_eq!(latch(&mut cartridge), [1, 0, 0, 1, 0x00]);
+}
+
+#[test]
+fn battery_save_keeps_counting() {
+    let source = ManualClock::new(1_000_000);
+    let mut cartridge = clock_cartridge(&source);
+    source.advance(DAY + 10);
+    latch(&mut cartridge);
+    let save = cartridge.battery_save();
+    // 8KiB of RAM and the 48 byte footer
+    assert_eq!(save.len(), 0x2000 + 48);
+
+    source.advance(DAY);
+    let mut loaded = clock_cartridge(&ManualClock::new(0));
+    loaded.set_clock_source(Box::new(source.clone()));
+    loaded.load_battery_save(&save).unwrap();
+    assert_eq!(latch(&mut loaded), [10, 0, 0, 2, 0x00]);
+}
+
+#[test]
+fn battery_save_keeps_sped_up_time() {
+    // an hour at 60 times the speed, then saved, and loaded a minute later at the usual speed
+    let source = ManualClock::new(1_000_000);
+    let mut cartridge = clock_cartridge(&source);
+    cartridge.set_clock_source(Box::new(ScaledClock::new(source.clone(), 1_000_000, 60.0)));
+    source.advance(3600);
+    assert_eq!(latch(&mut cartridge), [0, 0, 12, 2, 0x00]);
+    let save = cartridge.battery_save();
+
+    source.advance(60);
+    let mut loaded = clock_cartridge(&source);
+    loaded.load_battery_save(&save).unwrap();
+    assert_eq!(latch(&mut loaded), [0, 1, 12, 2, 0x00]);
+}
+
+#[test]
+fn switching_sources_keeps_the_time() {
+    let source = ManualClock::new(5_000);
+    let mut cartridge = clock_cartridge(&source);
+    source.advance(90);
+
+    let other = ManualClock::new(0);
+    cartridge.set_clock_source(Box::new(other.clone()));
+    other.advance(30);
+    assert_eq!(latch(&mut cartridge), [0, 2, 0, 0, 0x00]);
+}
@@ -0,0 +1,33 @@
+// GameBoy::run_cycles, which runs whole instructions until it's used up its budget
+
+use gboxide::cartridge::Cartridge;
+use gboxide::gameboy::GameBoy;
+
+mod common;
+use common::RomBuilder;
+
+// from the entry point: NOP (4 cycles) then JP 0x0150 (16), then CALL 0x0200 (24) over and over
+fn call_loop() -> GameBoy {
+    let rom = RomBuilder::new(b"CYCLES")
+        .program(&[0xCD, 0x00, 0x02]) // CALL 0x0200
+        .bytes(0x200, &[0xC3, 0x50, 0x01]) // JP 0x0150
+        .build();
+    GameBoy::new(Cartridge::from_rom(rom, &[]).unwrap())
+}
+
+#[test]
+fn stops_on_the_budget() {
+    let mut gameboy = call_loop();
+    assert_eq!(gameboy.run_cycles(0).unwrap().cycles, 0);
+    assert_eq!(gameboy.run_cycles(4).unwrap().cycles, 4);
+    assert_eq!(gameboy.run_cycles(16).unwrap().cycles, 16);
+}
+
+#[test]
+fn finishes_the_instruction_it_starts() {
+    let mut gameboy = call_loop();
+    // one cycle past the NOP starts the JP
+    assert_eq!(gameboy.run_cycles(5).unwrap().cycles, 20);
+    // and one into the CALL runs all 24 of it, the most a budget can be overshot by
+    assert_eq!(gameboy.run_cycles(1).unwrap().cycles, 24);
+}
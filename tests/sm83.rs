@@ -0,0 +1,168 @@
+// single instruction cpu tests, using the community SM83 json test vectors
+// (https://github.com/SingleStepTests/sm83)
+//
+// each file holds tests for one opcode, with the cpu and ram state before and after
+// the instruction and the bus activity for every machine cycle in between.
+// the vectors are too big to bundle, so the test's ignored by default. tools/fetch-sm83-tests.sh
+// downloads them to tests/sm83, or point GBOXIDE_SM83_TESTS at a v1 directory you already have,
+// then run it with --ignored
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use gboxide::gameboy::cpu::CPU;
+use gboxide::gameboy::bus::{Bus, BusActivity, FlatMemory};
+use gboxide::gameboy::registers::Flags;
+
+// a bus the vectors can be loaded into and checked against afterwards
+trait TestBus: Bus {
+    fn ram(&mut self) -> &mut [u8];
+    fn activity(&self) -> &[BusActivity];
+}
+
+impl TestBus for FlatMemory {
+    fn ram(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn activity(&self) -> &[BusActivity] {
+        &self.activity
+    }
+}
+
+fn test_dir() -> PathBuf {
+    match env::var_os("GBOXIDE_SM83_TESTS") {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("sm83"),
+    }
+}
+
+fn field(state: &Value, name: &str) -> u64 {
+    state[name].as_u64().unwrap_or_else(|| panic!("missing field {}", name))
+}
+
+fn set_state<B: TestBus>(cpu: &mut CPU, bus: &mut B, state: &Value) {
+    let r = cpu.registers_mut();
+    r.a = field(state, "a") as u8;
+    r.f = Flags::from_bits_truncate(field(state, "f") as u8);
+    r.b = field(state, "b") as u8;
+    r.c = field(state, "c") as u8;
+    r.d = field(state, "d") as u8;
+    r.e = field(state, "e") as u8;
+    r.h = field(state, "h") as u8;
+    r.l = field(state, "l") as u8;
+    r.sp = field(state, "sp") as u16;
+    r.pc = field(state, "pc") as u16;
+    cpu.set_interrupt_master_enable(field(state, "ime") != 0);
+
+    let ram = bus.ram();
+    for entry in state["ram"].as_array().unwrap() {
+        ram[entry[0].as_u64().unwrap() as usize] = entry[1].as_u64().unwrap() as u8;
+    }
+}
+
+// returns a description of each difference from the expected state
+fn check_state<B: TestBus>(cpu: &CPU, bus: &mut B, state: &Value) -> Vec<String> {
+    let r = cpu.registers();
+    let registers = [
+        ("a", r.a as u64), ("f", r.f.bits() as u64),
+        ("b", r.b as u64), ("c", r.c as u64),
+        ("d", r.d as u64), ("e", r.e as u64),
+        ("h", r.h as u64), ("l", r.l as u64),
+        ("sp", r.sp as u64), ("pc", r.pc as u64),
+        ("ime", cpu.interrupt_master_enable() as u64),
+    ];
+
+    let mut errors = Vec::new();
+    for &(name, actual) in registers.iter() {
+        let expected = field(state, name);
+        if actual != expected {
+            errors.push(format!("{}: expected {:#x}, got {:#x}", name, expected, actual));
+        }
+    }
+
+    let ram = bus.ram();
+    for entry in state["ram"].as_array().unwrap() {
+        let addr = entry[0].as_u64().unwrap() as usize;
+        let expected = entry[1].as_u64().unwrap() as u8;
+        if ram[addr] != expected {
+            errors.push(format!("ram[{:#06x}]: expected {:#04x}, got {:#04x}", addr, expected, ram[addr]));
+        }
+    }
+
+    errors
+}
+
+// cycles are [address, data, "rwm" style flags], or null for an internal cycle
+fn expected_activity(cycles: &Value) -> Vec<BusActivity> {
+    cycles.as_array().unwrap().iter()
+        .map(|cycle| {
+            let (addr, data, flags) = match cycle.as_array() {
+                Some(cycle) => (cycle[0].as_u64(), cycle[1].as_u64(), cycle[2].as_str().unwrap_or("")),
+                None => return BusActivity::Idle,
+            };
+            match (addr, data) {
+                (Some(addr), Some(data)) if flags.contains('r') => BusActivity::Read(addr as u16, data as u8),
+                (Some(addr), Some(data)) if flags.contains('w') => BusActivity::Write(addr as u16, data as u8),
+                _ => BusActivity::Idle,
+            }
+        })
+        .collect()
+}
+
+fn run_test<B: TestBus>(mut bus: B, test: &Value) -> Vec<String> {
+    let mut cpu = CPU::new();
+    set_state(&mut cpu, &mut bus, &test["initial"]);
+
+    if let Err(e) = cpu.step(&mut bus) {
+        return vec![e.to_string()];
+    }
+
+    let mut errors = check_state(&cpu, &mut bus, &test["final"]);
+
+    // internal cycles put arbitrary values on the bus, so only reads and writes are compared exactly
+    let expected = expected_activity(&test["cycles"]);
+    let actual = bus.activity();
+    let is_access = |activity: &&BusActivity| **activity != BusActivity::Idle;
+    let expected_accesses: Vec<&BusActivity> = expected.iter().filter(is_access).collect();
+    let actual_accesses: Vec<&BusActivity> = actual.iter().filter(is_access).collect();
+    if expected.len() != actual.len() || expected_accesses != actual_accesses {
+        errors.push(format!("bus activity: expected {:?}, got {:?}", expected, actual));
+    }
+
+    errors
+}
+
+#[test]
+#[ignore = "needs the SM83 test vectors, fetch them with tools/fetch-sm83-tests.sh"]
+fn sm83_single_instructions() {
+    let dir = test_dir();
+    assert!(dir.is_dir(), "no SM83 test vectors in {}, fetch them with tools/fetch-sm83-tests.sh \
+                           or set GBOXIDE_SM83_TESTS", dir.display());
+
+    let mut files: Vec<PathBuf> = fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+        .collect();
+    files.sort();
+
+    let mut failed_opcodes = Vec::new();
+    for path in files {
+        let tests: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap())
+            .unwrap_or_else(|e| panic!("couldn't parse {}: {}", path.display(), e));
+
+        // report the first failing test for each opcode, the rest are usually the same bug
+        for test in tests.as_array().unwrap() {
+            let errors = run_test(FlatMemory::new(), test);
+            if !errors.is_empty() {
+                failed_opcodes.push(format!("{}: {}", test["name"].as_str().unwrap_or("?"), errors.join(", ")));
+                break;
+            }
+        }
+    }
+
+    assert!(failed_opcodes.is_empty(), "{} opcodes failed:\n{}", failed_opcodes.len(), failed_opcodes.join("\n"));
+}
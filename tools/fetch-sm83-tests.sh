@@ -0,0 +1,20 @@
+#!/bin/sh
+# fetches the SingleStepTests SM83 vectors for tests/sm83.rs, into $GBOXIDE_SM83_TESTS if it's set
+# or tests/sm83 otherwise. then run them with:
+#   cargo test --no-default-features --features std --test sm83 -- --ignored
+set -e
+
+dest="${GBOXIDE_SM83_TESTS:-$(dirname "$0")/../tests/sm83}"
+checkout="$(mktemp -d)"
+trap 'rm -rf "$checkout"' EXIT
+
+git clone --depth 1 https://github.com/SingleStepTests/sm83 "$checkout"
+mkdir -p "$dest"
+for file in "$checkout"/v1/*.json "$checkout"/v1/*.json.gz; do
+    case "$file" in
+        *\*) ;;
+        *.gz) gunzip -c "$file" > "$dest/$(basename "$file" .gz)" ;;
+        *) cp "$file" "$dest/" ;;
+    esac
+done
+echo "SM83 test vectors are in $dest"